@@ -0,0 +1,88 @@
+# Prose for describe() and the notes of highlevel(), in the Fluent syntax
+# (https://projectfluent.org): one message per line, variables as { $name }.
+
+## Pieces
+
+bits-one = 1 bit
+bits = { $n } bits
+bit = bit { $bit }
+bit-lsb0 = bit { $bit } (LSB=0)
+kept-range = bits { $range }
+kept-range-lsb0 = bits { $range } (LSB=0)
+kept-wrapped = the top { $top } and bottom { $bottom } bits
+width-byte = byte
+width-halfword = halfword
+width-word = word
+address-absolute = address { $address }
+address-register = the address in { $ra }
+address-offset = { $d } bytes from the address in { $ra }
+signed = signed
+unsigned = unsigned
+
+## describe()
+
+describe-nothing = do nothing
+describe-copy = copy { $rs } into { $rd }
+describe-rlwinm = rotate { $rs } left by { $bits }, keep { $kept } and clear the rest, into { $ra }
+describe-rlwimi = rotate { $rs } left by { $bits } and insert { $kept } of the result into { $ra }, leaving its other bits unchanged
+describe-rlwnm = rotate { $rs } left by the low 5 bits of { $rb }, keep { $kept } and clear the rest, into { $ra }
+describe-extlwi = extract the { $n }-bit field starting at { $bit } of { $rs } into the high bits of { $ra }
+describe-extrwi = extract the { $n }-bit field starting at { $bit } of { $rs } into the low bits of { $ra }
+describe-rotlwi = rotate { $rs } left by { $bits } into { $ra }
+describe-rotrwi = rotate { $rs } right by { $bits } into { $ra }
+describe-rotlw = rotate { $rs } left by the low 5 bits of { $rb } into { $ra }
+describe-slwi = shift { $rs } left by { $bits } into { $ra }, filling with zeros
+describe-srwi = shift { $rs } right by { $bits } into { $ra }, filling with zeros
+describe-clrlwi = copy { $rs } into { $ra } with the high { $bits } cleared
+describe-clrrwi = copy { $rs } into { $ra } with the low { $bits } cleared
+describe-clrlslwi = clear the high { $cleared } of { $rs }, then shift it left by { $bits } into { $ra }
+describe-inslwi = insert the high { $bits } of { $rs } into { $ra } at { $bit }, leaving its other bits unchanged
+describe-insrwi = insert the low { $bits } of { $rs } into { $ra } at { $bit }, leaving its other bits unchanged
+describe-set = set { $rd } to { $value }
+describe-subtract = subtract { $value } from { $ra } into { $rd }
+describe-add = add { $value } to { $ra } into { $rd }
+describe-or = copy { $rs } into { $ra } with the bits of { $value } set
+describe-load = load the word at { $address } into { $rd }
+describe-load-zero = load the { $width } at { $address } into { $rd }, zero-extended
+describe-load-algebraic = load the halfword at { $address } into { $rd }, sign-extended
+describe-store = store { $value } at { $address }
+describe-store-low = the low { $width } of { $rs }
+describe-update = { $access }, then point { $ra } at it
+describe-compare = compare { $ra } with { $rb } as { $signedness } numbers, setting { $crf }
+describe-record = { $action }, then compare { $ra } with 0 as signed numbers, setting cr0
+describe-crop = set { $bt } to { $value }
+describe-branch = branch to { $target }
+describe-call = call { $target }, leaving the return address in lr
+describe-branch-if = branch to { $target } if { $condition }
+describe-decrement-branch-if = decrement ctr and branch to { $target } if { $condition }
+target-self = itself
+target-ahead = { $n } bytes ahead
+target-back = { $n } bytes back
+condition-set = { $bit } is set
+condition-clear = { $bit } is clear
+condition-ctr-nonzero = ctr is not zero
+condition-ctr-zero = ctr is zero
+condition-and = { $a } and { $b }
+cr-and = { $a } and { $b }
+cr-or = { $a } or { $b }
+cr-xor = { $a } xor { $b }
+cr-nand = not ({ $a } and { $b })
+cr-nor = not ({ $a } or { $b })
+cr-eqv = whether { $a } equals { $b }
+cr-andc = { $a } and not { $b }
+cr-orc = { $a } or not { $b }
+
+## highlevel() notes
+
+note-nothing = this does nothing
+note-mr = this is just `mr { $rd },{ $rs }`
+note-wrapped = keeps the top { $top } and bottom { $bottom } bits
+note-insert-a = insert a { $n }-bit field from { $rs } into { $ra } at { $bit }
+note-insert-an = insert an { $n }-bit field from { $rs } into { $ra } at { $bit }
+note-ra-zero = rA=0 reads as 0, not r0
+note-update-ra-zero = invalid form: update with rA=0
+note-update-ra-rd = invalid form: update with rA=rD
+note-record = the dot also sets cr0 from a signed compare of { $ra } with 0, so a `beq` after it branches if { $ra } is 0 and a `bne` if it isn't
+intent-extlwi = { $ra } = { $rs } bits { $range }, left-justified
+intent-inslwi = { $ra } bits { $dest } = { $rs } bits { $src }
+intent-insrwi = { $ra } bits { $dest } = low { $n } bits of { $rs }
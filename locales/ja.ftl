@@ -0,0 +1,87 @@
+# describe() と highlevel() の注記の日本語訳。書式は en.ftl と同じ Fluent です。
+
+## 部品
+
+bits-one = 1ビット
+bits = { $n }ビット
+bit = ビット{ $bit }
+bit-lsb0 = ビット{ $bit }（LSB=0）
+kept-range = ビット{ $range }
+kept-range-lsb0 = ビット{ $range }（LSB=0）
+kept-wrapped = 上位{ $top }ビットと下位{ $bottom }ビット
+width-byte = バイト
+width-halfword = ハーフワード
+width-word = ワード
+address-absolute = アドレス{ $address }
+address-register = { $ra }が指すアドレス
+address-offset = { $ra }が指すアドレスから{ $d }バイトの位置
+signed = 符号付き
+unsigned = 符号なし
+
+## describe()
+
+describe-nothing = 何もしない
+describe-copy = { $rs }を{ $rd }にコピーする
+describe-rlwinm = { $rs }を左に{ $bits }ローテートし、{ $kept }を残して他をクリアした値を{ $ra }に入れる
+describe-rlwimi = { $rs }を左に{ $bits }ローテートし、その{ $kept }を{ $ra }に挿入する（{ $ra }の他のビットはそのまま）
+describe-rlwnm = { $rs }を{ $rb }の下位5ビット分だけ左にローテートし、{ $kept }を残して他をクリアした値を{ $ra }に入れる
+describe-extlwi = { $rs }の{ $bit }から始まる{ $n }ビットのフィールドを取り出して{ $ra }の上位ビットに入れる
+describe-extrwi = { $rs }の{ $bit }から始まる{ $n }ビットのフィールドを取り出して{ $ra }の下位ビットに入れる
+describe-rotlwi = { $rs }を左に{ $bits }ローテートして{ $ra }に入れる
+describe-rotrwi = { $rs }を右に{ $bits }ローテートして{ $ra }に入れる
+describe-rotlw = { $rs }を{ $rb }の下位5ビット分だけ左にローテートして{ $ra }に入れる
+describe-slwi = { $rs }を左に{ $bits }シフトして{ $ra }に入れる（空いたビットは0）
+describe-srwi = { $rs }を右に{ $bits }シフトして{ $ra }に入れる（空いたビットは0）
+describe-clrlwi = { $rs }の上位{ $bits }をクリアして{ $ra }にコピーする
+describe-clrrwi = { $rs }の下位{ $bits }をクリアして{ $ra }にコピーする
+describe-clrlslwi = { $rs }の上位{ $cleared }をクリアし、左に{ $bits }シフトして{ $ra }に入れる
+describe-inslwi = { $rs }の上位{ $bits }を{ $ra }の{ $bit }に挿入する（{ $ra }の他のビットはそのまま）
+describe-insrwi = { $rs }の下位{ $bits }を{ $ra }の{ $bit }に挿入する（{ $ra }の他のビットはそのまま）
+describe-set = { $rd }を{ $value }にする
+describe-subtract = { $ra }から{ $value }を引いて{ $rd }に入れる
+describe-add = { $ra }に{ $value }を足して{ $rd }に入れる
+describe-or = { $rs }の{ $value }のビットを立てて{ $ra }に入れる
+describe-load = { $address }のワードを{ $rd }にロードする
+describe-load-zero = { $address }の{ $width }を{ $rd }にゼロ拡張してロードする
+describe-load-algebraic = { $address }のハーフワードを{ $rd }に符号拡張してロードする
+describe-store = { $value }を{ $address }にストアする
+describe-store-low = { $rs }の下位{ $width }
+describe-update = { $access }（その後{ $ra }をそのアドレスに更新する）
+describe-compare = { $ra }と{ $rb }を{ $signedness }の数として比較し、{ $crf }を設定する
+describe-record = { $action }（その後{ $ra }と0を符号付きの数として比較し、cr0を設定する）
+describe-crop = { $bt }を{ $value }にする
+describe-branch = { $target }へ分岐する
+describe-call = { $target }を呼び出し、戻りアドレスをlrに残す
+describe-branch-if = { $condition }なら{ $target }へ分岐する
+describe-decrement-branch-if = ctrを1減らし、{ $condition }なら{ $target }へ分岐する
+target-self = 自分自身
+target-ahead = { $n }バイト先
+target-back = { $n }バイト前
+condition-set = { $bit }がセットされている
+condition-clear = { $bit }がクリアされている
+condition-ctr-nonzero = ctrが0でない
+condition-ctr-zero = ctrが0である
+condition-and = { $a }かつ{ $b }
+cr-and = { $a }かつ{ $b }
+cr-or = { $a }または{ $b }
+cr-xor = { $a }と{ $b }の排他的論理和
+cr-nand = 「{ $a }かつ{ $b }」の否定
+cr-nor = 「{ $a }または{ $b }」の否定
+cr-eqv = { $a }と{ $b }が等しいかどうか
+cr-andc = { $a }かつ{ $b }でない
+cr-orc = { $a }または{ $b }でない
+
+## highlevel() の注記
+
+note-nothing = 何もしない
+note-mr = 実質的に `mr { $rd },{ $rs }`
+note-wrapped = 上位{ $top }ビットと下位{ $bottom }ビットを残す
+note-insert-a = { $rs }の{ $n }ビットのフィールドを{ $ra }の{ $bit }に挿入
+note-insert-an = { $rs }の{ $n }ビットのフィールドを{ $ra }の{ $bit }に挿入
+note-ra-zero = rA=0 は r0 ではなく 0 として読まれる
+note-update-ra-zero = 無効な形式: rA=0 での更新
+note-update-ra-rd = 無効な形式: rA=rD での更新
+note-record = ドット付きなので{ $ra }と0の符号付き比較の結果もcr0に入る。直後の`beq`は{ $ra }が0なら、`bne`は0でなければ分岐する
+intent-extlwi = { $ra } = { $rs }のビット{ $range }（左詰め）
+intent-inslwi = { $ra }のビット{ $dest } = { $rs }のビット{ $src }
+intent-insrwi = { $ra }のビット{ $dest } = { $rs }の下位{ $n }ビット
@@ -0,0 +1,543 @@
+//! GameCube Action Replay codes, decrypted: `XXXXXXXX YYYYYYYY` lines like
+//! Gecko's, as Dolphin keeps them in a game's `[ActionReplay]` section. The
+//! first word packs a subtype, type and size into its top 7 bits over the
+//! address's low 25:
+//!
+//! ```text
+//! 31 30 | 29 28 27 | 26 25 | 24 ... 0
+//! sub     type       size    address - 0x80000000
+//! ```
+//!
+//! Type 0 writes (subtype 0, the value repeated to fill), writes through a
+//! pointer (1) or adds (2); types 1 to 7 compare, and skip lines if the
+//! comparison fails. A first word of zero is a "zero code" that ends a
+//! block or the whole code.
+//!
+//! The encrypted `XXXX-XXXX-XXXXX` form the AR itself takes is not read or
+//! written: ppcheat doesn't implement the AR's seeded cipher, so an
+//! encrypted line is refused rather than misread. Dolphin's AR editor
+//! decrypts codes pasted into it.
+
+use core::fmt;
+
+use crate::disasm::{Listing, Options};
+use crate::gecko::{self, CodeListError, Entry, GeckoError, Line, ADDRESS_MASK, BASE_ADDRESS};
+use crate::Width;
+
+/// How much a command reads or writes.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Size {
+    Byte,
+    Half,
+    Word,
+    /// A word, added as a single-precision float
+    Float,
+}
+
+impl Size {
+    const ALL: [Size; 4] = [Self::Byte, Self::Half, Self::Word, Self::Float];
+
+    /// The value's bits the command uses.
+    fn mask(self) -> u32 {
+        match self {
+            Self::Byte => 0xFF,
+            Self::Half => 0xFFFF,
+            Self::Word | Self::Float => 0xFFFF_FFFF,
+        }
+    }
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Byte => "byte",
+            Self::Half => "half word",
+            Self::Word => "word",
+            Self::Float => "float",
+        })
+    }
+}
+
+/// What a conditional compares the value in memory with its own by.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Test {
+    Equal,
+    NotEqual,
+    LessSigned,
+    GreaterSigned,
+    LessUnsigned,
+    GreaterUnsigned,
+    /// Nonzero when ANDed
+    And,
+}
+
+impl Test {
+    const ALL: [Test; 7] = [
+        Self::Equal,
+        Self::NotEqual,
+        Self::LessSigned,
+        Self::GreaterSigned,
+        Self::LessUnsigned,
+        Self::GreaterUnsigned,
+        Self::And,
+    ];
+
+    /// The code's type field, 1 to 7.
+    fn number(self) -> u8 {
+        Self::ALL.iter().position(|&t| t == self).unwrap() as u8 + 1
+    }
+}
+
+impl fmt::Display for Test {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Equal => "==",
+            Self::NotEqual => "!=",
+            Self::LessSigned => "< (signed)",
+            Self::GreaterSigned => "> (signed)",
+            Self::LessUnsigned => "<",
+            Self::GreaterUnsigned => ">",
+            Self::And => "&",
+        })
+    }
+}
+
+/// One line of a decrypted code.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// `value` written `count` times from `addr` on, a `size` apart
+    Write {
+        size: Size,
+        addr: u32,
+        value: u32,
+        count: u32,
+    },
+    /// `value` written where the pointer at `addr` points
+    Pointer { size: Size, addr: u32, value: u32 },
+    /// `value` added to what's at `addr`
+    Add { size: Size, addr: u32, value: u32 },
+    /// Unless the `size` at `addr` passes `test` against `value`, `skip`
+    /// lines: 1, 2, up to the next `00000000 40000000`, or all the rest
+    Conditional {
+        test: Test,
+        size: Size,
+        addr: u32,
+        value: u32,
+        skip: u8,
+    },
+    /// A zero code, `00000000 YYYYYYYY`: `00000000` ends the code and
+    /// `40000000` a conditional's block
+    Zero(u32),
+    /// The master code, which says where the AR hooks the game
+    Master(Line),
+}
+
+impl Command {
+    /// The command `line` reads as.
+    pub fn parse(line: Line) -> Command {
+        let Line(a, b) = line;
+        if a == 0 {
+            return Command::Zero(b);
+        }
+        let subtype = (a >> 30) as u8;
+        let size = Size::ALL[(a >> 25 & 3) as usize];
+        let addr = BASE_ADDRESS | (a & ADDRESS_MASK);
+        match (a >> 27 & 7, subtype) {
+            (0, 0) => {
+                let (value, count) = match size {
+                    Size::Byte => (b & 0xFF, (b >> 8) + 1),
+                    Size::Half => (b & 0xFFFF, (b >> 16) + 1),
+                    Size::Word | Size::Float => (b, 1),
+                };
+                Command::Write {
+                    size,
+                    addr,
+                    value,
+                    count,
+                }
+            }
+            (0, 1) => Command::Pointer {
+                size,
+                addr,
+                value: b,
+            },
+            (0, 2) => Command::Add {
+                size,
+                addr,
+                value: b,
+            },
+            (0, _) => Command::Master(line),
+            (test, skip) => Command::Conditional {
+                test: Test::ALL[test as usize - 1],
+                size,
+                addr,
+                value: b,
+                skip,
+            },
+        }
+    }
+
+    /// The line that says this, or why no line can: an address outside
+    /// 0x80000000..0x82000000, or a fill too long for its count.
+    pub fn line(&self) -> Result<Line, GeckoError> {
+        let address = |subtype: u8, ty: u8, size: Size, addr: u32| {
+            let size = Size::ALL.iter().position(|&s| s == size).unwrap() as u8;
+            gecko::address_word(subtype << 6 | ty << 3 | size << 1, addr)
+        };
+        Ok(match *self {
+            Command::Write {
+                size,
+                addr,
+                value,
+                count,
+            } => {
+                let (shift, max) = match size {
+                    Size::Byte => (8, 1 << 24),
+                    Size::Half => (16, 1 << 16),
+                    Size::Word | Size::Float => (0, 1),
+                };
+                if count == 0 || count > max {
+                    return Err(GeckoError::Fill(count));
+                }
+                let fill = (count - 1) << shift;
+                Line(address(0, 0, size, addr)?, fill | (value & size.mask()))
+            }
+            Command::Pointer { size, addr, value } => Line(address(1, 0, size, addr)?, value),
+            Command::Add { size, addr, value } => Line(address(2, 0, size, addr)?, value),
+            Command::Conditional {
+                test,
+                size,
+                addr,
+                value,
+                skip,
+            } => Line(address(skip, test.number(), size, addr)?, value),
+            Command::Zero(value) => Line(0, value),
+            Command::Master(line) => line,
+        })
+    }
+}
+
+/// `write word 38600001 at 80003100`
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Command::Write {
+                size,
+                addr,
+                value,
+                count: 1,
+            } => write!(f, "write {} {:x} at {:08x}", size, value, addr),
+            Command::Write {
+                size,
+                addr,
+                value,
+                count,
+            } => write!(
+                f,
+                "fill {} {}s from {:08x} with {:x}",
+                count, size, addr, value
+            ),
+            Command::Pointer { size, addr, value } => write!(
+                f,
+                "write {} {:x} through the pointer at {:08x}",
+                size, value, addr
+            ),
+            Command::Add { size, addr, value } => {
+                write!(f, "add {:x} to the {} at {:08x}", value, size, addr)
+            }
+            Command::Conditional {
+                test,
+                size,
+                addr,
+                value,
+                skip,
+            } => write!(
+                f,
+                "unless the {} at {:08x} {} {:x}, skip {}",
+                size,
+                addr,
+                test,
+                value,
+                match skip {
+                    0 => "the next line",
+                    1 => "the next 2 lines",
+                    2 => "to the next 00000000 40000000",
+                    _ => "the rest of the code",
+                }
+            ),
+            Command::Zero(0) => write!(f, "end of the code"),
+            Command::Zero(0x4000_0000) => write!(f, "end of the conditional's block"),
+            Command::Zero(value) => write!(f, "zero code {:08X}", value),
+            Command::Master(_) => write!(f, "master code"),
+        }
+    }
+}
+
+/// The commands of a list of decrypted codes. Lines are read as for
+/// [`gecko::parse_list`]; encrypted ones are refused.
+pub fn parse_list(text: &str) -> Result<Vec<Command>, CodeListError> {
+    if let Some(line) = text.lines().position(encrypted) {
+        return Err(CodeListError {
+            line: line + 1,
+            message: "an encrypted code; ppcheat doesn't implement the AR's cipher, \
+                      so decrypt it by pasting it into Dolphin's AR editor"
+                .to_string(),
+        });
+    }
+    let lines = gecko::code_lines(text)?;
+    Ok(lines.into_iter().map(|(_, l)| Command::parse(l)).collect())
+}
+
+/// Whether `line` is a code in the AR's encrypted `XXXX-XXXX-XXXXX` form.
+fn encrypted(line: &str) -> bool {
+    let groups: Vec<_> = line.trim().split('-').map(str::len).collect();
+    groups == [4, 4, 5]
+        && line
+            .trim()
+            .chars()
+            .all(|c| c == '-' || c.is_ascii_digit() || c.is_ascii_uppercase())
+}
+
+/// `bytes` written at `addr` as 32-bit writes, one per word.
+pub fn word_writes(bytes: &[u8], addr: u32) -> Result<Vec<Line>, GeckoError> {
+    gecko::words(bytes, addr)?
+        .into_iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let command = Command::Write {
+                size: Size::Word,
+                addr: addr.wrapping_add(4 * i as u32),
+                value,
+                count: 1,
+            };
+            command.line()
+        })
+        .collect()
+}
+
+/// `commands` listed as [`gecko::listing`] lists a Gecko code: runs of
+/// word writes disassembled at their addresses, and the rest as comments
+/// saying what they do.
+pub fn listing(commands: &[Command], options: &Options) -> Listing {
+    let entries = commands.iter().map(|command| match *command {
+        Command::Write {
+            size: Size::Word,
+            addr,
+            value,
+            count: 1,
+        } => Entry::Write(addr, value),
+        _ => match command.line() {
+            Ok(line) => Entry::Comment(format!("{}  {}", line, command)),
+            Err(_) => Entry::Comment(command.to_string()),
+        },
+    });
+    gecko::code_listing(entries, options)
+}
+
+/// A code list converted to the other format: its lines, and the lines
+/// of each code that has no equivalent there, with why.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Conversion {
+    pub lines: Vec<Line>,
+    pub skipped: Vec<(Vec<Line>, String)>,
+}
+
+impl Conversion {
+    /// Adds the lines converted from `original`, or notes why there are
+    /// none.
+    fn push(&mut self, converted: Result<Vec<Line>, String>, original: Vec<Line>) {
+        match converted {
+            Ok(lines) => self.lines.extend(lines),
+            Err(why) => self.skipped.push((original, why)),
+        }
+    }
+}
+
+/// `commands` as Gecko codes: writes as 04 codes, and fills as 00 and 02
+/// codes, split where they're longer than one holds. The zero code ending
+/// the list is dropped, as Gecko lists need none.
+pub fn to_gecko(commands: &[Command]) -> Conversion {
+    let mut out = Conversion::default();
+    for command in commands {
+        let converted = match *command {
+            Command::Write {
+                size: Size::Word | Size::Float,
+                addr,
+                value,
+                ..
+            } => Ok(gecko::Code::Write { addr, value }.lines()),
+            Command::Write {
+                size,
+                addr,
+                value,
+                count,
+            } => {
+                let width = match size {
+                    Size::Byte => Width::Byte,
+                    _ => Width::Half,
+                };
+                let lines = (0..count)
+                    .step_by(0x1_0000)
+                    .map(|done| {
+                        let fill = gecko::Code::Fill {
+                            width,
+                            addr: addr.wrapping_add(done * width.bytes()),
+                            value,
+                            count: (count - done).min(0x1_0000),
+                        };
+                        fill.lines()
+                    })
+                    .collect::<Result<Vec<_>, _>>();
+                Ok(lines.map(|lines| lines.concat()))
+            }
+            Command::Zero(0) => Ok(Ok(Vec::new())),
+            Command::Pointer { .. } => Err("Gecko writes through pointers with 48 and 14 codes"),
+            Command::Add { .. } => Err("Gecko adds through its registers, with 80 and 86 codes"),
+            Command::Conditional { .. } => {
+                Err("Gecko conditionals run up to an endif, not a line count")
+            }
+            Command::Zero(_) => Err("Gecko has no equivalent of this zero code"),
+            Command::Master(_) => Err("Gecko needs no master code"),
+        };
+        let converted = converted
+            .map_err(str::to_string)
+            .and_then(|lines| lines.map_err(|e| e.to_string()));
+        out.push(converted, command.line().into_iter().collect());
+    }
+    out
+}
+
+/// `codes` as AR commands: 04 codes as 32-bit writes, and 00 and 02 codes
+/// as fills.
+pub fn from_gecko(codes: &[gecko::Code]) -> Conversion {
+    let mut out = Conversion::default();
+    for code in codes {
+        let command = match *code {
+            gecko::Code::Write { addr, value } => Ok(Command::Write {
+                size: Size::Word,
+                addr,
+                value,
+                count: 1,
+            }),
+            gecko::Code::Fill {
+                width,
+                addr,
+                value,
+                count,
+            } => Ok(Command::Write {
+                size: match width {
+                    Width::Byte => Size::Byte,
+                    Width::Half => Size::Half,
+                    Width::Word => Size::Word,
+                },
+                addr,
+                value,
+                count,
+            }),
+            gecko::Code::InsertAsm { .. } => {
+                Err("the AR has no code type that runs instructions".to_string())
+            }
+            gecko::Code::Other(ref lines) => Err(format!(
+                "the AR has no equivalent of {:02X} codes",
+                lines[0].0 >> 24
+            )),
+        };
+        let converted = command.and_then(|command| {
+            command
+                .line()
+                .map(|line| vec![line])
+                .map_err(|e| e.to_string())
+        });
+        out.push(converted, code.lines().unwrap_or_default());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action_replay() {
+        let commands = parse_list(
+            "Infinite health\n\
+             04003100 38600001\n\
+             00003104 00000160\n\
+             0A003108 00000005\n\
+             84003120 3F800000\n\
+             00000000 40000000\n",
+        )
+        .unwrap();
+        assert_eq!(
+            commands[1],
+            Command::Write {
+                size: Size::Byte,
+                addr: 0x8000_3104,
+                value: 0x60,
+                count: 2
+            }
+        );
+        for (command, line) in commands
+            .iter()
+            .zip(["04003100 38600001", "00003104 00000160"])
+        {
+            assert_eq!(command.line().unwrap().to_string(), line);
+        }
+        assert_eq!(
+            listing(&commands, &Options::default()).text,
+            "# 04 writes, 80003100..80003104\n\
+             80003100: 38 60 00 01  li r3,1\n\
+             \n\
+             # 00003104 00000160  fill 2 bytes from 80003104 with 60\n\
+             \n\
+             # 0A003108 00000005  unless the half word at 80003108 == 5, skip the next line\n\
+             \n\
+             # 84003120 3F800000  add 3f800000 to the word at 80003120\n\
+             \n\
+             # 00000000 40000000  end of the conditional's block\n"
+        );
+        assert_eq!(
+            word_writes(&[0x38, 0x60, 0x00, 0x01], 0x8100_0000).unwrap(),
+            [Line(0x0500_0000, 0x3860_0001)]
+        );
+        assert_eq!(parse_list("\n0J4E-8QVB-XH3K2\n").unwrap_err().line, 2);
+    }
+
+    #[test]
+    fn test_convert() {
+        let commands = parse_list(
+            "04003100 38600001\n\
+             02003104 00010001\n\
+             0A003108 00000005\n\
+             00000000 00000000\n",
+        )
+        .unwrap();
+        let gecko = to_gecko(&commands);
+        assert_eq!(
+            gecko.lines,
+            [
+                Line(0x0400_3100, 0x3860_0001),
+                Line(0x0200_3104, 0x0001_0001)
+            ]
+        );
+        assert_eq!(gecko.skipped.len(), 1);
+        assert_eq!(gecko.skipped[0].0, [Line(0x0A00_3108, 5)]);
+
+        let codes = gecko::parse_list(
+            "04003100 38600001\n\
+             00003104 00020060\n\
+             C2003200 00000001\n\
+             38600001 00000000\n",
+        )
+        .unwrap();
+        let ar = from_gecko(&codes);
+        assert_eq!(
+            ar.lines,
+            [
+                Line(0x0400_3100, 0x3860_0001),
+                Line(0x0000_3104, 0x0000_0260)
+            ]
+        );
+        assert_eq!(ar.skipped[0].0.len(), 2);
+    }
+}
@@ -0,0 +1,324 @@
+//! Assembles a program into the bytes it stands for: big-endian instruction
+//! words and the values of `.byte`, `.short` and `.long`, with `.align` and
+//! `.space` as zeros. Labels are laid out first, so branches can go
+//! forward to them as well as back.
+//!
+//! [`Format`] writes the bytes out for whatever takes them next: a C or
+//! Rust array to paste into a loader, hex words for a code manager,
+//! S-records or Intel HEX for a flash tool, or the raw binary.
+
+use core::fmt;
+
+use crate::encoding::EncodeError;
+use crate::formats::{ihex, srec};
+use crate::parser::{Directive, Program, Statement};
+use crate::relocate::{labels, resolve, RelocateError};
+use crate::Width;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AsmError {
+    /// A relocation that can't be resolved at this base
+    Relocate(RelocateError),
+    /// An instruction with a field that doesn't fit
+    Encode { line: usize, error: EncodeError },
+    /// A data value too wide for its directive, e.g. `.byte 256`
+    DataRange {
+        line: usize,
+        value: i64,
+        width: Width,
+    },
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Relocate(e) => write!(f, "{}", e),
+            Self::Encode { line, error } => write!(f, "line {}: {}", line, error),
+            Self::DataRange { line, value, width } => write!(
+                f,
+                "line {}: {} doesn't fit in {} byte{}",
+                line,
+                value,
+                width.bytes(),
+                if *width == Width::Byte { "" } else { "s" }
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+impl From<RelocateError> for AsmError {
+    fn from(e: RelocateError) -> Self {
+        Self::Relocate(e)
+    }
+}
+
+/// `value` as `width` big-endian bytes, signed or unsigned.
+fn data(value: i64, width: Width) -> Option<Vec<u8>> {
+    let bits = 8 * width.bytes();
+    let fits = value >= -(1 << (bits - 1)) && value < (1 << bits);
+    let bytes = (value as u32).to_be_bytes();
+    match fits {
+        true => Some(bytes[4 - width.bytes() as usize..].to_vec()),
+        false => None,
+    }
+}
+
+/// The bytes of `program` placed at `base`, which `@ha`/`@l` references to
+/// its labels and branches to constants are resolved against. Sections aren't kept apart: everything
+/// is laid out in source order.
+pub fn assemble(program: &Program, base: u32) -> Result<Vec<u8>, AsmError> {
+    let labels = labels(program, base);
+    let mut out = Vec::new();
+
+    for (addr, line) in program.layout(base, false) {
+        match &line.statement {
+            Statement::Instruction(op) => {
+                let word = resolve(program, &labels, line, addr, op)?
+                    .encode()
+                    .map_err(|error| AsmError::Encode {
+                        line: line.number,
+                        error,
+                    })?;
+                out.extend(word.to_be_bytes());
+            }
+            Statement::Directive(Directive::Data { width, values }) => {
+                for &value in values {
+                    let bytes = data(value, *width).ok_or(AsmError::DataRange {
+                        line: line.number,
+                        value,
+                        width: *width,
+                    })?;
+                    out.extend(bytes);
+                }
+            }
+            Statement::Directive(directive) => {
+                out.resize(out.len() + directive.size(addr) as usize, 0);
+            }
+            Statement::Label(_) => {}
+        }
+    }
+
+    Ok(out)
+}
+
+/// A shape to write assembled bytes in.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Format {
+    /// `unsigned char patch[] = { 0x3c, 0x60, ... };`
+    C,
+    /// `const PATCH: [u32; N] = [0x3C608000, ...];`, or `[u8; N]` if the
+    /// bytes aren't whole words
+    Rust,
+    /// A word per line, `3C608000`, and any trailing bytes on their own
+    Hex,
+    /// Motorola S-records, S3 data records at the base address
+    Srec,
+    /// Intel HEX, with 32-bit linear addresses
+    IntelHex,
+    /// The bytes themselves
+    Binary,
+}
+
+impl Format {
+    pub const NAMES: [&'static str; 6] = ["c", "rust", "hex", "srec", "ihex", "bin"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "c" => Some(Self::C),
+            "rust" => Some(Self::Rust),
+            "hex" => Some(Self::Hex),
+            "srec" => Some(Self::Srec),
+            "ihex" => Some(Self::IntelHex),
+            "bin" => Some(Self::Binary),
+            _ => None,
+        }
+    }
+
+    /// `bytes` assembled at `base` in this format; `name` names the C
+    /// array, upper-cased the Rust constant, and goes in the S0 header.
+    pub fn write(self, bytes: &[u8], name: &str, base: u32) -> Vec<u8> {
+        match self {
+            Self::C => c_array(bytes, name).into_bytes(),
+            Self::Rust => rust_array(bytes, name).into_bytes(),
+            Self::Hex => hex_words(bytes).into_bytes(),
+            Self::Srec => srec::write(bytes, base, name).into_bytes(),
+            Self::IntelHex => ihex::write(bytes, base).into_bytes(),
+            Self::Binary => bytes.to_vec(),
+        }
+    }
+}
+
+/// `items` as the indented, comma-terminated lines of an array literal,
+/// `per_line` to a line.
+fn array_body(items: &[String], per_line: usize) -> String {
+    items
+        .chunks(per_line)
+        .map(|line| format!("    {},\n", line.join(", ")))
+        .collect()
+}
+
+fn c_array(bytes: &[u8], name: &str) -> String {
+    let items: Vec<_> = bytes.iter().map(|b| format!("0x{:02x}", b)).collect();
+    format!(
+        "unsigned char {}[] = {{\n{}}};\n",
+        name,
+        array_body(&items, 8)
+    )
+}
+
+fn rust_array(bytes: &[u8], name: &str) -> String {
+    let name = name.to_ascii_uppercase();
+    match bytes.len() % 4 {
+        0 => {
+            let words: Vec<_> = bytes
+                .chunks(4)
+                .map(|w| format!("0x{:08X}", u32::from_be_bytes([w[0], w[1], w[2], w[3]])))
+                .collect();
+            format!(
+                "const {}: [u32; {}] = [\n{}];\n",
+                name,
+                words.len(),
+                array_body(&words, 4)
+            )
+        }
+        _ => {
+            let items: Vec<_> = bytes.iter().map(|b| format!("0x{:02x}", b)).collect();
+            format!(
+                "const {}: [u8; {}] = [\n{}];\n",
+                name,
+                items.len(),
+                array_body(&items, 8)
+            )
+        }
+    }
+}
+
+fn hex_words(bytes: &[u8]) -> String {
+    bytes
+        .chunks(4)
+        .map(|w| {
+            let digits: String = w.iter().map(|b| format!("{:02X}", b)).collect();
+            digits + "\n"
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_program;
+
+    #[test]
+    fn test_assemble() {
+        let program = parse_program(
+            "lis r3,table@ha\naddi r3,r3,table@l\n.byte 1, -1\n.align 2\n\
+             table:\n.long 0x80001234\n.short -2\n",
+        )
+        .unwrap();
+        assert_eq!(
+            assemble(&program, 0x8000_3100).unwrap(),
+            [
+                0x3c, 0x60, 0x80, 0x00, 0x38, 0x63, 0x31, 0x0c, 0x01, 0xff, 0x00, 0x00, 0x80, 0x00,
+                0x12, 0x34, 0xff, 0xfe
+            ]
+        );
+        let program = parse_program("li r3,1\n.byte 256\n").unwrap();
+        assert_eq!(
+            assemble(&program, 0).unwrap_err().to_string(),
+            "line 2: 256 doesn't fit in 1 byte"
+        );
+    }
+
+    #[test]
+    fn test_formats() {
+        let bytes = [0x3c, 0x60, 0x80, 0x00, 0x38, 0x63, 0x31, 0x0c];
+        let write = |format: Format, bytes: &[u8]| {
+            String::from_utf8(format.write(bytes, "patch", 0)).unwrap()
+        };
+        assert_eq!(
+            write(Format::C, &bytes),
+            "unsigned char patch[] = {\n    0x3c, 0x60, 0x80, 0x00, 0x38, 0x63, 0x31, 0x0c,\n};\n"
+        );
+        assert_eq!(
+            write(Format::Rust, &bytes),
+            "const PATCH: [u32; 2] = [\n    0x3C608000, 0x3863310C,\n];\n"
+        );
+        assert_eq!(
+            write(Format::Rust, &bytes[..6]),
+            "const PATCH: [u8; 6] = [\n    0x3c, 0x60, 0x80, 0x00, 0x38, 0x63,\n];\n"
+        );
+        assert_eq!(write(Format::Hex, &bytes[..6]), "3C608000\n3863\n");
+        assert_eq!(Format::Binary.write(&bytes, "patch", 0), bytes);
+    }
+
+    #[test]
+    fn test_branches() {
+        let program = parse_program(
+            "loop:
+lwzu r4,4(r3)
+stw r4,0(r5)
+bdnz loop
+b done
+li r3,0
+done:
+",
+        )
+        .unwrap();
+        let words: Vec<_> = assemble(&program, 0x8000_3100)
+            .unwrap()
+            .chunks(4)
+            .map(|w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]))
+            .collect();
+        assert_eq!(
+            words,
+            [
+                0x8483_0004,
+                0x9085_0000,
+                0x4200_FFF8,
+                0x4800_0008,
+                0x3860_0000
+            ]
+        );
+        let program = parse_program(
+            ".set far, 0x82000000
+bl far
+",
+        )
+        .unwrap();
+        assert_eq!(
+            assemble(&program, 0x8000_0004).unwrap(),
+            [0x49, 0xff, 0xff, 0xfd]
+        );
+        assert_eq!(
+            assemble(&program, 0x8000_0000).unwrap_err().to_string(),
+            "line 2: `far` is 0x2000000 bytes away, out of the branch's reach"
+        );
+
+        // absolute targets, taken from where the branch is assembled
+        let program = parse_program(
+            "bl 0x80001000
+beq cr7,0x80003000
+",
+        )
+        .unwrap();
+        assert_eq!(
+            assemble(&program, 0x8000_3000).unwrap(),
+            [0x4b, 0xff, 0xe0, 0x01, 0x41, 0x9e, 0xff, 0xfc]
+        );
+        assert_eq!(
+            assemble(&program, 0x8001_3000).unwrap_err().to_string(),
+            "line 2: `0x80003000` is 0x10004 bytes away, out of the branch's reach"
+        );
+        let program = parse_program(
+            "b 0x80003102
+",
+        )
+        .unwrap();
+        assert_eq!(
+            assemble(&program, 0x8000_3000).unwrap_err().to_string(),
+            "line 1: `0x80003102` is 258 bytes away, not a whole number of instructions"
+        );
+    }
+}
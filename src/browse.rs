@@ -0,0 +1,184 @@
+//! Navigation state for browsing an addressed listing: a cursor, jump
+//! history and named bookmarks. Front ends (the line-mode `browse` command)
+//! drive it and render around [`Browser::window`].
+
+use std::collections::BTreeMap;
+
+use crate::gecko::relative_target;
+use crate::image::Image;
+use crate::Opcode;
+
+#[derive(Debug, Clone)]
+pub struct Browser {
+    listing: Vec<(u32, Opcode)>,
+    cursor: usize,
+    history: Vec<usize>,
+    bookmarks: BTreeMap<String, u32>,
+}
+
+impl Browser {
+    pub fn new(listing: Vec<(u32, Opcode)>) -> Self {
+        Browser {
+            listing,
+            cursor: 0,
+            history: Vec::new(),
+            bookmarks: BTreeMap::new(),
+        }
+    }
+
+    /// The words of `image`'s code sections that decode, starting at its
+    /// entry point if that's one of them.
+    pub fn from_image(image: &Image) -> Self {
+        let listing = image
+            .sections
+            .iter()
+            .filter(|s| s.executable)
+            .flat_map(|s| {
+                s.data
+                    .chunks_exact(4)
+                    .enumerate()
+                    .filter_map(move |(i, w)| {
+                        let word = u32::from_be_bytes([w[0], w[1], w[2], w[3]]);
+                        let op = Opcode::decode(word).ok()?;
+                        Some((s.addr.wrapping_add(4 * i as u32), op))
+                    })
+            })
+            .collect();
+        let mut browser = Browser::new(listing);
+        if let Some(idx) = browser.position(image.entry) {
+            browser.cursor = idx;
+        }
+        browser
+    }
+
+    fn position(&self, addr: u32) -> Option<usize> {
+        self.listing.iter().position(|&(a, _)| a == addr)
+    }
+
+    pub fn current(&self) -> Option<(u32, &Opcode)> {
+        self.listing.get(self.cursor).map(|(addr, op)| (*addr, op))
+    }
+
+    /// Moves the cursor by `delta` lines, clamped to the listing.
+    pub fn step(&mut self, delta: isize) {
+        let last = self.listing.len().saturating_sub(1) as isize;
+        self.cursor = (self.cursor as isize + delta).max(0).min(last) as usize;
+    }
+
+    /// Jumps to the instruction at `addr`, remembering where we came from.
+    pub fn jump(&mut self, addr: u32) -> bool {
+        match self.position(addr) {
+            Some(idx) => {
+                self.history.push(self.cursor);
+                self.cursor = idx;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Jumps to where the `b` or `bc` at the cursor branches, if it's
+    /// relative and the target is in the listing.
+    pub fn follow(&mut self) -> bool {
+        let target = self
+            .current()
+            .and_then(|(addr, op)| relative_target(op.encode().ok()?, addr));
+        match target {
+            Some(target) => self.jump(target),
+            None => false,
+        }
+    }
+
+    /// Returns to the position before the last jump.
+    pub fn back(&mut self) -> bool {
+        match self.history.pop() {
+            Some(idx) => {
+                self.cursor = idx;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn bookmark(&mut self, name: &str) {
+        if let Some((addr, _)) = self.current() {
+            self.bookmarks.insert(name.to_string(), addr);
+        }
+    }
+
+    pub fn goto_bookmark(&mut self, name: &str) -> bool {
+        match self.bookmarks.get(name) {
+            Some(&addr) => self.jump(addr),
+            None => false,
+        }
+    }
+
+    pub fn bookmarks(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.bookmarks
+            .iter()
+            .map(|(name, &addr)| (name.as_str(), addr))
+    }
+
+    /// Up to `radius` lines either side of the cursor, flagging the current one.
+    pub fn window(&self, radius: usize) -> impl Iterator<Item = (bool, u32, &Opcode)> + '_ {
+        let start = self.cursor.saturating_sub(radius);
+        let end = (self.cursor + radius + 1).min(self.listing.len());
+        (start..end).map(move |i| {
+            let (addr, op) = &self.listing[i];
+            (i == self.cursor, *addr, op)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_program;
+    use crate::profile::layout;
+
+    #[test]
+    fn test_navigation() {
+        let program = parse_program("li r3,0\nli r4,1\nli r5,2\nli r6,3\n").unwrap();
+        let listing = layout(&program, 0x8000_0000);
+        let mut browser = Browser::new(listing.into_iter().map(|(a, op)| (a, *op)).collect());
+
+        browser.bookmark("start");
+        assert!(browser.jump(0x8000_000c));
+        browser.step(-1);
+        assert_eq!(browser.current().unwrap().0, 0x8000_0008);
+        assert!(browser.goto_bookmark("start"));
+        assert!(browser.back());
+        assert_eq!(browser.current().unwrap().0, 0x8000_0008);
+        assert!(browser.back());
+        assert_eq!(browser.current().unwrap().0, 0x8000_0000);
+        assert!(!browser.back());
+        assert!(!browser.jump(0x1234));
+    }
+
+    #[test]
+    fn test_follow() {
+        // beq +8; li r3,0; li r3,1; b -8, with a word in the middle that
+        // doesn't decode
+        let words: [u32; 5] = [
+            0x4182_0008,
+            0x3860_0000,
+            0x3860_0001,
+            0x0000_0000,
+            0x4BFF_FFF8,
+        ];
+        let bytes: Vec<_> = words.iter().flat_map(|w| w.to_be_bytes()).collect();
+        let mut browser = Browser::from_image(&Image::raw(&bytes, 0x8000_3100));
+
+        assert!(browser.follow());
+        assert_eq!(browser.current().unwrap().0, 0x8000_3108);
+        // li isn't a branch
+        assert!(!browser.follow());
+        browser.step(1);
+        assert_eq!(browser.current().unwrap().0, 0x8000_3110);
+        assert!(browser.follow());
+        assert_eq!(browser.current().unwrap().0, 0x8000_3108);
+        assert!(browser.back());
+        assert!(browser.back());
+        assert_eq!(browser.current().unwrap().0, 0x8000_3100);
+    }
+}
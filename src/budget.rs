@@ -0,0 +1,255 @@
+//! Size and alignment budgets for payloads, e.g. "must fit in 0x40 bytes at
+//! a code cave". When a payload is over budget, points at the instructions
+//! that could be folded together.
+
+use core::fmt;
+
+use crate::eval::{mask, mask_bounds};
+use crate::parser::{Program, Statement};
+use crate::{Opcode, Register};
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Budget {
+    /// Maximum payload size in bytes
+    pub max_size: Option<u32>,
+    /// Required alignment of both the load address and the payload size
+    pub align: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    TooLarge {
+        size: u32,
+        max: u32,
+    },
+    MisalignedBase {
+        base: u32,
+        align: u32,
+    },
+    /// The size needs `padding` more bytes to reach a multiple of the alignment.
+    UnalignedSize {
+        size: u32,
+        padding: u32,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge { size, max } => write!(
+                f,
+                "payload is {:#x} bytes, {:#x} over the {:#x} byte budget",
+                size,
+                size - max,
+                max
+            ),
+            Self::MisalignedBase { base, align } => {
+                write!(f, "load address {:08x} is not {}-byte aligned", base, align)
+            }
+            Self::UnalignedSize { size, padding } => write!(
+                f,
+                "payload size {:#x} needs {} bytes of padding",
+                size, padding
+            ),
+        }
+    }
+}
+
+/// A way to make the payload smaller.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    /// 1-based source line the change starts at
+    pub line: usize,
+    pub saves: u32,
+    pub hint: String,
+}
+
+/// Bytes `program` occupies when placed at `base`.
+pub fn size(program: &Program, base: u32) -> u32 {
+    program.lines.iter().fold(0, |size, line| {
+        size + match &line.statement {
+            Statement::Instruction(_) => 4,
+            Statement::Directive(d) => d.size(base.wrapping_add(size)),
+            Statement::Label(_) => 0,
+        }
+    })
+}
+
+/// `rlwinm rA,rS,a,..` followed by `rlwinm rA,rA,b,..` is a single rotate
+/// by `a+b` under the rotated first mask ANDed with the second, whenever that
+/// is still one contiguous mask. Record forms, which set cr0 on the way,
+/// are left alone.
+pub(crate) fn merge_rotates(first: &Opcode, second: &Opcode) -> Option<Opcode> {
+    match (first.canonicalize(), second.canonicalize()) {
+        (
+            Opcode::Rlwinm {
+                ra: a,
+                rs,
+                sh: sh1,
+                mb: mb1,
+                me: me1,
+                rc: false,
+            },
+            Opcode::Rlwinm {
+                ra,
+                rs: through,
+                sh: sh2,
+                mb: mb2,
+                me: me2,
+                rc: false,
+            },
+        ) if through == a && ra == a => {
+            let m = mask(mb1, me1).rotate_left(sh2.into()) & mask(mb2, me2);
+            if m == 0 {
+                return Some(Opcode::Li { rd: ra, simm: 0 });
+            }
+            let (mb, me) = mask_bounds(m)?;
+            Some(Opcode::Rlwinm {
+                ra,
+                rs,
+                sh: (sh1 + sh2) % 32,
+                mb,
+                me,
+                rc: false,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// `lis rX,0` followed by `addi rX,rX,v` is `li rX,v`; `addi rX,rX,0`
+/// after a `lis` does nothing.
+pub(crate) fn shorten_constant(first: &Opcode, second: &Opcode) -> Option<String> {
+    match (first.canonicalize(), second.canonicalize()) {
+        (
+            Opcode::Addis {
+                rd,
+                ra: Register(0),
+                simm: hi,
+            },
+            Opcode::Addi {
+                rd: d2,
+                ra,
+                simm: lo,
+            },
+        ) if ra == rd && d2 == rd => match (hi, lo) {
+            (0, lo) => Some(format!("use {}", Opcode::Li { rd, simm: lo })),
+            (_, 0) => Some("the addi adds 0; drop it".to_string()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn suggestions(program: &Program) -> Vec<Suggestion> {
+    let ops: Vec<_> = program
+        .lines
+        .iter()
+        .filter_map(|line| match line.statement {
+            Statement::Instruction(op) => Some((line.number, op)),
+            _ => None,
+        })
+        .collect();
+
+    let mut found = Vec::new();
+    for pair in ops.windows(2) {
+        let ((line, first), (_, second)) = (pair[0], pair[1]);
+        let hint = merge_rotates(&first, &second)
+            .map(|op| format!("merge the two rotates into {}", op))
+            .or_else(|| shorten_constant(&first, &second));
+        if let Some(hint) = hint {
+            found.push(Suggestion {
+                line,
+                saves: 4,
+                hint,
+            });
+        }
+    }
+
+    for line in &program.lines {
+        if let Statement::Directive(d) = &line.statement {
+            let bytes = d.size(0);
+            if bytes > 0 {
+                found.push(Suggestion {
+                    line: line.number,
+                    saves: bytes,
+                    hint: "data emitted inside the payload; move it or materialize it in code"
+                        .to_string(),
+                });
+            }
+        }
+    }
+
+    found.sort_by_key(|s| s.line);
+    found
+}
+
+/// Problems with `program` at `base` under `budget`, and, if it's too big,
+/// where to look for savings.
+pub fn check(program: &Program, base: u32, budget: &Budget) -> (Vec<Problem>, Vec<Suggestion>) {
+    let size = size(program, base);
+    let mut problems = Vec::new();
+
+    if let Some(align) = budget.align.filter(|&a| a > 1) {
+        if !base.is_multiple_of(align) {
+            problems.push(Problem::MisalignedBase { base, align });
+        }
+        if !size.is_multiple_of(align) {
+            problems.push(Problem::UnalignedSize {
+                size,
+                padding: align - size % align,
+            });
+        }
+    }
+    if let Some(max) = budget.max_size.filter(|&max| size > max) {
+        problems.push(Problem::TooLarge { size, max });
+        return (problems, suggestions(program));
+    }
+
+    (problems, Vec::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_program;
+
+    #[test]
+    fn test_budget() {
+        let program = parse_program(
+            "srwi r3,r4,8\nclrlwi r3,r3,24\nlis r5,0\naddi r5,r5,0x40\nstw r3,0(r5)\n",
+        )
+        .unwrap();
+        let budget = Budget {
+            max_size: Some(0x10),
+            align: Some(8),
+        };
+
+        let (problems, suggestions) = check(&program, 0x8000_1004, &budget);
+        assert_eq!(
+            problems,
+            vec![
+                Problem::MisalignedBase {
+                    base: 0x8000_1004,
+                    align: 8
+                },
+                Problem::UnalignedSize {
+                    size: 20,
+                    padding: 4
+                },
+                Problem::TooLarge { size: 20, max: 16 },
+            ]
+        );
+        let hints: Vec<_> = suggestions
+            .iter()
+            .map(|s| (s.line, s.hint.as_str()))
+            .collect();
+        assert_eq!(
+            hints,
+            vec![
+                (1, "merge the two rotates into rlwinm r3,r4,24,24,31"),
+                (3, "use li r5,64"),
+            ]
+        );
+    }
+}
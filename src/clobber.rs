@@ -0,0 +1,225 @@
+//! Clobber safety for injected code: which registers a payload may change
+//! without saving them first depends on the hook framework, so the set of
+//! free registers comes from a convention profile.
+//!
+//! Profiles are a small TOML subset:
+//!
+//! ```toml
+//! name = "gecko-c2"
+//! # free to use at the hook site
+//! scratch = ["r0", "r11", "r12"]
+//! ```
+
+use core::fmt;
+
+use crate::parser::{parse_register, Program, Statement};
+use crate::{Opcode, Register};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Convention {
+    pub name: String,
+    /// Registers the payload may overwrite without restoring them
+    pub scratch: Vec<Register>,
+}
+
+impl Default for Convention {
+    /// The EABI volatile registers, r0 and r3-r12. Only right for hooks that
+    /// replace a function call.
+    fn default() -> Self {
+        let mut scratch = vec![Register(0)];
+        scratch.extend((3..=12).map(Register));
+        Convention {
+            name: "eabi-call".to_string(),
+            scratch,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConventionError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ConventionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ConventionError {}
+
+fn parse_string(text: &str) -> Option<&str> {
+    text.strip_prefix('"')?.strip_suffix('"')
+}
+
+impl Convention {
+    pub fn parse(inp: &str) -> Result<Self, ConventionError> {
+        let mut name = None;
+        let mut scratch = None;
+
+        for (idx, raw) in inp.lines().enumerate() {
+            let line = idx + 1;
+            let err = |message: String| ConventionError { line, message };
+            let text = raw.split('#').next().unwrap_or("").trim();
+            if text.is_empty() {
+                continue;
+            }
+
+            let (key, value) = text
+                .split_once('=')
+                .ok_or_else(|| err("expected `key = value`".to_string()))?;
+            let value = value.trim();
+            match key.trim() {
+                "name" => {
+                    let value = parse_string(value)
+                        .ok_or_else(|| err("`name` must be a string".to_string()))?;
+                    name = Some(value.to_string());
+                }
+                "scratch" => {
+                    let items = value
+                        .strip_prefix('[')
+                        .and_then(|v| v.strip_suffix(']'))
+                        .ok_or_else(|| err("`scratch` must be an array".to_string()))?;
+                    let regs = items
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|item| !item.is_empty())
+                        .map(|item| match parse_string(item).map(parse_register) {
+                            Some(Ok(("", reg))) => Ok(reg),
+                            _ => Err(err(format!("invalid register {}", item))),
+                        })
+                        .collect::<Result<_, _>>()?;
+                    scratch = Some(regs);
+                }
+                key => return Err(err(format!("unknown key `{}`", key))),
+            }
+        }
+
+        Ok(Convention {
+            name: name.unwrap_or_else(|| "custom".to_string()),
+            scratch: scratch.ok_or(ConventionError {
+                line: 0,
+                message: "missing `scratch`".to_string(),
+            })?,
+        })
+    }
+
+    pub fn is_scratch(&self, reg: Register) -> bool {
+        self.scratch.contains(&reg)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Clobber {
+    /// A non-scratch register is written without being saved to the stack
+    /// first and reloaded by its last write.
+    Unsaved { line: usize, reg: Register },
+    /// The stack pointer doesn't end where it started.
+    Stack { delta: i32 },
+}
+
+impl fmt::Display for Clobber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsaved { line, reg } => write!(
+                f,
+                "line {}: {} is not scratch under this convention and is never restored",
+                line, reg
+            ),
+            Self::Stack { delta } => write!(f, "stack pointer is off by {} on exit", delta),
+        }
+    }
+}
+
+fn stack_adjustment(op: &Opcode) -> Option<i32> {
+    match op.canonicalize() {
+        Opcode::Addi { rd, ra, simm } if rd == Register::SP && ra == Register::SP => {
+            Some(simm.into())
+        }
+        Opcode::Store {
+            update: true,
+            ra,
+            d,
+            ..
+        } if ra == Register::SP => Some(d.into()),
+        _ => None,
+    }
+}
+
+/// Registers `program` changes that `convention` doesn't allow it to.
+pub fn check(program: &Program, convention: &Convention) -> Vec<Clobber> {
+    let ops: Vec<_> = program
+        .lines
+        .iter()
+        .filter_map(|line| match line.statement {
+            Statement::Instruction(op) => Some((line.number, op.canonicalize())),
+            _ => None,
+        })
+        .collect();
+
+    let mut clobbers = Vec::new();
+    let mut saved = Vec::new();
+    let mut delta = 0;
+
+    for &(line, op) in &ops {
+        match op {
+            Opcode::Store { rs, ra, .. } if ra == Register::SP => saved.push(rs),
+            _ => {}
+        }
+        delta += stack_adjustment(&op).unwrap_or(0);
+
+        for reg in op.writes() {
+            if reg == Register::SP || convention.is_scratch(reg) {
+                continue;
+            }
+            let restored = saved.contains(&reg) && {
+                let last = ops
+                    .iter()
+                    .rev()
+                    .find(|(_, op)| op.writes().contains(&reg))
+                    .map(|&(_, op)| op);
+                matches!(last, Some(Opcode::Load { rd, ra, .. }) if rd == reg && ra == Register::SP)
+            };
+            let reported = clobbers
+                .iter()
+                .any(|c| matches!(c, Clobber::Unsaved { reg: r, .. } if *r == reg));
+            if !restored && !reported {
+                clobbers.push(Clobber::Unsaved { line, reg });
+            }
+        }
+    }
+
+    if delta != 0 {
+        clobbers.push(Clobber::Stack { delta });
+    }
+    clobbers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_program;
+
+    #[test]
+    fn test_clobbers() {
+        let program = parse_program(
+            "stwu sp,-16(sp)\nstw r31,12(sp)\nli r31,1\nli r11,2\nli r3,0\n\
+             lwz r31,12(sp)\naddi sp,sp,16\n",
+        )
+        .unwrap();
+
+        assert_eq!(check(&program, &Convention::default()), vec![]);
+
+        let gecko =
+            Convention::parse("name = \"gecko-c2\"\nscratch = [\"r11\", \"r12\"]\n").unwrap();
+        assert_eq!(
+            check(&program, &gecko),
+            vec![Clobber::Unsaved {
+                line: 5,
+                reg: Register(3)
+            }]
+        );
+        assert!(Convention::parse("scratch = [\"r40\"]").is_err());
+    }
+}
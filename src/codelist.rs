@@ -0,0 +1,133 @@
+//! Code lists in the text form Ocarina, Gecko OS and most code managers
+//! read and write: the game's ID and title, then each code under its name,
+//! any notes on it after its lines, and a blank line between codes.
+//!
+//! ```text
+//! GALE01
+//! Super Smash Bros. Melee
+//!
+//! Infinite lives [ppcheat]
+//! 04003100 38600001
+//! Player 1 only
+//! ```
+//!
+//! The lines are kept as they are, so a list can hold Gecko or decrypted
+//! Action Replay codes.
+
+use core::fmt;
+
+use crate::gecko::{is_code, CodeListError, Line};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CodeList {
+    /// `GALE01`: game code, region and publisher
+    pub game_id: String,
+    pub title: String,
+    pub codes: Vec<NamedCode>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedCode {
+    pub name: String,
+    pub lines: Vec<Line>,
+    /// The lines after the code that aren't code
+    pub notes: Vec<String>,
+}
+
+impl CodeList {
+    pub fn parse(text: &str) -> Result<Self, CodeListError> {
+        let lines: Vec<_> = text.lines().map(str::trim_end).enumerate().collect();
+        let error = |line: usize, message: String| CodeListError {
+            line: line + 1,
+            message,
+        };
+        let mut list = CodeList::default();
+        let start = match lines.iter().position(|(_, l)| !l.is_empty()) {
+            Some(start) => start,
+            None => return Ok(list),
+        };
+        list.game_id = lines[start].1.trim().to_string();
+        let mut rest = &lines[start + 1..];
+        if let Some(&(_, title)) = rest.first() {
+            list.title = title.trim().to_string();
+            rest = &rest[1..];
+        }
+
+        let blocks = rest.split(|(_, l)| l.trim().is_empty());
+        for (&(number, name), body) in blocks.filter_map(|b| b.split_first()) {
+            if is_code(name) {
+                return Err(error(number, "a code without a name".to_string()));
+            }
+            let mut code = NamedCode {
+                name: name.trim().to_string(),
+                lines: Vec::new(),
+                notes: Vec::new(),
+            };
+            for &(number, text) in body {
+                match is_code(text) {
+                    true => code.lines.push(text.parse().map_err(|e| error(number, e))?),
+                    false => code.notes.push(text.to_string()),
+                }
+            }
+            list.codes.push(code);
+        }
+        Ok(list)
+    }
+
+    /// The code called `name`.
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut NamedCode> {
+        self.codes.iter_mut().find(|c| c.name == name)
+    }
+}
+
+impl fmt::Display for CodeList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.game_id)?;
+        writeln!(f, "{}", self.title)?;
+        for code in &self.codes {
+            writeln!(f, "\n{}", code.name)?;
+            for line in &code.lines {
+                writeln!(f, "{}", line)?;
+            }
+            for note in &code.notes {
+                writeln!(f, "{}", note)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_list() {
+        let text = "GALE01\n\
+                    Super Smash Bros. Melee\n\
+                    \n\
+                    Infinite lives [ppcheat]\n\
+                    04003100 38600001\n\
+                    Player 1 only\n\
+                    \n\
+                    \n\
+                    No intro\n\
+                    c2003200 00000001\n\
+                    38600001 00000000\n";
+        let list = CodeList::parse(text).unwrap();
+        assert_eq!(list.game_id, "GALE01");
+        assert_eq!(list.codes.len(), 2);
+        assert_eq!(list.codes[0].notes, ["Player 1 only"]);
+        assert_eq!(list.codes[1].lines[0], Line(0xC200_3200, 1));
+        assert_eq!(
+            list.to_string(),
+            text.replace("\n\n\n", "\n\n").replace("c2", "C2")
+        );
+        assert_eq!(
+            CodeList::parse("GALE01\n\n04003100 38600001\n")
+                .unwrap_err()
+                .line,
+            3
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! Explains how the results of two rotates differ, bit range by bit range,
+//! for the pairs that are easy to mix up: `extlwi` and `extrwi`, `inslwi`
+//! and `insrwi`, or two `rlwinm` a bit apart in MB.
+
+use crate::{eval, BitOrder, Opcode, Register};
+
+/// Where one bit of an instruction's result comes from.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Origin {
+    /// Bit `bit` of `rs`, after rotating by `rb` for rlwnm
+    Source {
+        rs: Register,
+        rb: Option<Register>,
+        bit: u8,
+    },
+    /// Cleared by the mask
+    Zero,
+    /// The destination's own bit, which rlwimi leaves alone
+    Unchanged,
+}
+
+impl Origin {
+    /// Whether `self` at result bit i and `next` at i+1 read as one range.
+    fn continues(self, next: Origin) -> bool {
+        match (self, next) {
+            (Self::Source { rs, rb, bit }, Self::Source { .. }) => {
+                // a wrap from bit 31 to 0 starts a new range
+                bit < 31
+                    && next
+                        == Self::Source {
+                            rs,
+                            rb,
+                            bit: bit + 1,
+                        }
+            }
+            (a, b) => a == b,
+        }
+    }
+}
+
+/// The register an instruction writes and where each of its 32 bits comes
+/// from, for rlwinm, rlwimi, rlwnm and their pseudo-ops.
+pub fn origins(op: &Opcode) -> Option<(Register, [Origin; 32])> {
+    let (ra, rs, rb, sh, mb, me, insert) = match op.canonicalize() {
+        Opcode::Rlwinm {
+            ra, rs, sh, mb, me, ..
+        } => (ra, rs, None, sh, mb, me, false),
+        Opcode::Rlwimi {
+            ra, rs, sh, mb, me, ..
+        } => (ra, rs, None, sh, mb, me, true),
+        Opcode::Rlwnm {
+            ra, rs, rb, mb, me, ..
+        } => (ra, rs, Some(rb), 0, mb, me, false),
+        _ => return None,
+    };
+    let mask = eval::mask(mb, me);
+    let mut bits = [Origin::Zero; 32];
+    for (i, bit) in bits.iter_mut().enumerate() {
+        *bit = match (mask & (0x8000_0000 >> i) != 0, insert) {
+            (true, _) => Origin::Source {
+                rs,
+                rb,
+                bit: ((i + usize::from(sh)) % 32) as u8,
+            },
+            (false, true) => Origin::Unchanged,
+            (false, false) => Origin::Zero,
+        };
+    }
+    Some((ra, bits))
+}
+
+/// Result bits `first..=last`, where `left` and `right` both continue the
+/// range they start at `first`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Span {
+    pub first: u8,
+    pub last: u8,
+    pub left: Origin,
+    pub right: Origin,
+}
+
+impl Span {
+    pub fn same(&self) -> bool {
+        self.left == self.right
+    }
+}
+
+/// The result bits of `left` and `right` split into ranges that each come
+/// from one place in either instruction.
+pub fn compare(left: &Opcode, right: &Opcode) -> Result<Vec<Span>, String> {
+    let origins = |op: &Opcode| {
+        origins(op)
+            .map(|(_, bits)| bits)
+            .ok_or_else(|| format!("`{}` isn't a rotate, so has no bits to compare", op))
+    };
+    let (a, b) = (origins(left)?, origins(right)?);
+
+    let mut spans: Vec<Span> = Vec::new();
+    for i in 0..32 {
+        match spans.last_mut() {
+            Some(span) if a[i - 1].continues(a[i]) && b[i - 1].continues(b[i]) => {
+                span.last = i as u8
+            }
+            _ => spans.push(Span {
+                first: i as u8,
+                last: i as u8,
+                left: a[i],
+                right: b[i],
+            }),
+        }
+    }
+    Ok(spans)
+}
+
+/// `bits 4..11`, or `bit 4` for a range of one. [`explain`] says which
+/// numbering once, rather than on every range.
+fn bits(first: u8, last: u8) -> String {
+    match first == last {
+        true => format!("bit {}", BitOrder::current().number(first)),
+        false => format!("bits {}", BitOrder::range(first, last)),
+    }
+}
+
+/// Where `span`'s bits come from in one instruction, starting at `origin`.
+fn origin_name(origin: Origin, span: &Span, dest: Register) -> String {
+    match origin {
+        Origin::Source { rs, rb, bit } => {
+            let last = bit + (span.last - span.first);
+            match rb {
+                None => format!("{} of {}", bits(bit, last), rs),
+                Some(rb) => format!("{} of {} rotated left by {}", bits(bit, last), rs, rb),
+            }
+        }
+        Origin::Zero => "0".to_string(),
+        Origin::Unchanged => format!("unchanged {}", dest),
+    }
+}
+
+/// A few lines saying what `left` and `right` have in common and, range by
+/// range, where their results differ.
+pub fn explain(left: &Opcode, right: &Opcode) -> Result<String, String> {
+    let spans = compare(left, right)?;
+    let dest = |op: &Opcode| origins(op).map(|(ra, _)| ra).unwrap();
+    let (ra, rb) = (dest(left), dest(right));
+
+    let mut out = vec![match BitOrder::current() {
+        BitOrder::Msb0 => format!("{} vs {}:", left, right),
+        BitOrder::Lsb0 => format!("{} vs {} (bits numbered LSB=0):", left, right),
+    }];
+    if ra != rb {
+        out.push(format!(
+            "  (they write different registers, {} and {})",
+            ra, rb
+        ));
+    }
+    if spans.iter().all(Span::same) {
+        out.push("  same result in every bit".to_string());
+    }
+    for span in &spans {
+        let left = origin_name(span.left, span, ra);
+        out.push(match span.same() && ra == rb {
+            true => format!("  {}: {} in both", bits(span.first, span.last), left),
+            false => format!(
+                "  {}: {} vs {}",
+                bits(span.first, span.last),
+                left,
+                origin_name(span.right, span, rb)
+            ),
+        });
+    }
+    Ok(out.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_opcode;
+
+    #[test]
+    fn test_explain() {
+        let op = |asm| parse_opcode(asm).expect("parse failed").1;
+        assert_eq!(
+            explain(&op("extlwi r3,r4,8,8"), &op("extrwi r3,r4,8,8")).unwrap(),
+            "extlwi r3,r4,8,8 vs extrwi r3,r4,8,8:\n  \
+             bits 0..7: bits 8..15 of r4 vs 0\n  \
+             bits 8..23: 0 in both\n  \
+             bits 24..31: 0 vs bits 8..15 of r4"
+        );
+        assert_eq!(
+            explain(&op("slwi r3,r4,8"), &op("rlwinm r3,r4,8,0,23")).unwrap(),
+            "slwi r3,r4,8 vs rlwinm r3,r4,8,0,23:\n  \
+             same result in every bit\n  \
+             bits 0..23: bits 8..31 of r4 in both\n  \
+             bits 24..31: 0 in both"
+        );
+        assert!(explain(&op("li r3,1"), &op("slwi r3,r4,8")).is_err());
+    }
+}
@@ -0,0 +1,647 @@
+//! Plain-English descriptions of instructions, for reading code with someone
+//! who doesn't think in rotates and masks yet.
+
+use crate::locale::message;
+use crate::{wrapped, BitOrder, CrLogic, Opcode, Register, Width};
+
+fn width_name(width: Width) -> String {
+    match width {
+        Width::Byte => message("width-byte", &[]),
+        Width::Half => message("width-halfword", &[]),
+        Width::Word => message("width-word", &[]),
+    }
+}
+
+/// `rA+d` as prose, where rA=0 means no base register at all.
+fn address(ra: Register, d: i16) -> String {
+    match (ra.0, d) {
+        (0, d) => message(
+            "address-absolute",
+            &[("address", &format!("{:#x}", d as i32 as u32))],
+        ),
+        (_, 0) => message("address-register", &[("ra", &ra)]),
+        (_, d) => message("address-offset", &[("d", &d), ("ra", &ra)]),
+    }
+}
+
+/// `bits 4..19`, or for a mask that wraps around, `the top 8 and bottom 8 bits`.
+pub(crate) fn kept(mb: u8, me: u8) -> String {
+    match wrapped(mb, me) {
+        Some((top, bottom)) => message("kept-wrapped", &[("top", &top), ("bottom", &bottom)]),
+        None => match BitOrder::current() {
+            BitOrder::Msb0 => message("kept-range", &[("range", &format!("{}..{}", mb, me))]),
+            BitOrder::Lsb0 => message("kept-range-lsb0", &[("range", &BitOrder::range(mb, me))]),
+        },
+    }
+}
+
+fn bits(n: u8) -> String {
+    match n {
+        1 => message("bits-one", &[]),
+        n => message("bits", &[("n", &n)]),
+    }
+}
+
+/// "load ..., then point rA at it" for the update forms.
+fn update(update: bool, access: String, ra: Register) -> String {
+    match update {
+        true => message("describe-update", &[("access", &access), ("ra", &ra)]),
+        false => access,
+    }
+}
+
+impl Opcode {
+    /// What the instruction does, as a sentence fragment, e.g. "extract the
+    /// 8-bit field starting at bit 12 of r7 into the low bits of r0". Raw
+    /// rotates are described by the pseudo-op they amount to, if any, and
+    /// record forms by what they do without the dot, then the compare. The
+    /// language is [`crate::locale::Locale::current`].
+    pub fn describe(&self) -> String {
+        if let (Some(ra), Some(plain)) = (self.record(), self.with_record(false)) {
+            let action = plain.describe();
+            return message("describe-record", &[("action", &action), ("ra", &ra)]);
+        }
+        match self.copies() {
+            Some((rd, rs)) if rd == rs => return message("describe-nothing", &[]),
+            Some((rd, rs)) => return message("describe-copy", &[("rs", &rs), ("rd", &rd)]),
+            None => {}
+        }
+        match *self {
+            Self::Rlwinm {
+                ra, rs, sh, mb, me, ..
+            } => match self.simplify() {
+                Some((simple, _)) => simple.describe(),
+                None => message(
+                    "describe-rlwinm",
+                    &[
+                        ("rs", &rs),
+                        ("bits", &bits(sh)),
+                        ("kept", &kept(mb, me)),
+                        ("ra", &ra),
+                    ],
+                ),
+            },
+            Self::Rlwimi {
+                ra, rs, sh, mb, me, ..
+            } => match self.simplify() {
+                Some((simple, _)) => simple.describe(),
+                None => message(
+                    "describe-rlwimi",
+                    &[
+                        ("rs", &rs),
+                        ("bits", &bits(sh)),
+                        ("kept", &kept(mb, me)),
+                        ("ra", &ra),
+                    ],
+                ),
+            },
+            Self::Rlwnm {
+                ra, rs, rb, mb, me, ..
+            } => match self.simplify() {
+                Some((simple, _)) => simple.describe(),
+                None => message(
+                    "describe-rlwnm",
+                    &[
+                        ("rs", &rs),
+                        ("rb", &rb),
+                        ("kept", &kept(mb, me)),
+                        ("ra", &ra),
+                    ],
+                ),
+            },
+            Self::Extlwi { ra, rs, n, b } | Self::Extrwi { ra, rs, n, b } => {
+                let id = match self {
+                    Self::Extlwi { .. } => "describe-extlwi",
+                    _ => "describe-extrwi",
+                };
+                message(
+                    id,
+                    &[
+                        ("n", &n),
+                        ("bit", &BitOrder::bit(b)),
+                        ("rs", &rs),
+                        ("ra", &ra),
+                    ],
+                )
+            }
+            Self::Rotlwi { ra, rs, n }
+            | Self::Rotrwi { ra, rs, n }
+            | Self::Slwi { ra, rs, n }
+            | Self::Srwi { ra, rs, n }
+            | Self::Clrlwi { ra, rs, n }
+            | Self::Clrrwi { ra, rs, n } => {
+                let id = match self {
+                    Self::Rotlwi { .. } => "describe-rotlwi",
+                    Self::Rotrwi { .. } => "describe-rotrwi",
+                    Self::Slwi { .. } => "describe-slwi",
+                    Self::Srwi { .. } => "describe-srwi",
+                    Self::Clrlwi { .. } => "describe-clrlwi",
+                    _ => "describe-clrrwi",
+                };
+                message(id, &[("rs", &rs), ("bits", &bits(n)), ("ra", &ra)])
+            }
+            Self::Rotlw { ra, rs, rb } => {
+                message("describe-rotlw", &[("rs", &rs), ("rb", &rb), ("ra", &ra)])
+            }
+            Self::Clrlslwi { ra, rs, b, n } => message(
+                "describe-clrlslwi",
+                &[
+                    ("cleared", &bits(b)),
+                    ("rs", &rs),
+                    ("bits", &bits(n)),
+                    ("ra", &ra),
+                ],
+            ),
+            Self::Inslwi { ra, rs, n, b } | Self::Insrwi { ra, rs, n, b } => {
+                let id = match self {
+                    Self::Inslwi { .. } => "describe-inslwi",
+                    _ => "describe-insrwi",
+                };
+                message(
+                    id,
+                    &[
+                        ("bits", &bits(n)),
+                        ("rs", &rs),
+                        ("ra", &ra),
+                        ("bit", &BitOrder::bit(b)),
+                    ],
+                )
+            }
+            Self::Addi { rd, ra, simm } if ra.0 == 0 => {
+                message("describe-set", &[("rd", &rd), ("value", &simm)])
+            }
+            Self::Addi { rd, ra, simm } if simm < 0 => message(
+                "describe-subtract",
+                &[("value", &-(simm as i32)), ("ra", &ra), ("rd", &rd)],
+            ),
+            Self::Addi { rd, ra, simm } => message(
+                "describe-add",
+                &[("value", &simm), ("ra", &ra), ("rd", &rd)],
+            ),
+            Self::Addis { rd, ra, simm } if ra.0 == 0 => message(
+                "describe-set",
+                &[
+                    ("rd", &rd),
+                    ("value", &format!("{:#x}", (simm as u16 as u32) << 16)),
+                ],
+            ),
+            Self::Addis { rd, ra, simm } => message(
+                "describe-add",
+                &[
+                    ("value", &format!("{:#x}", (simm as u16 as u32) << 16)),
+                    ("ra", &ra),
+                    ("rd", &rd),
+                ],
+            ),
+            Self::Ori { ra, rs, uimm } => message(
+                "describe-or",
+                &[("rs", &rs), ("ra", &ra), ("value", &format!("{:#x}", uimm))],
+            ),
+            Self::Oris { ra, rs, uimm } => message(
+                "describe-or",
+                &[
+                    ("rs", &rs),
+                    ("ra", &ra),
+                    ("value", &format!("{:#x}", u32::from(uimm) << 16)),
+                ],
+            ),
+            Self::Li { rd, simm } => message("describe-set", &[("rd", &rd), ("value", &simm)]),
+            Self::Lis { rd, simm } => message(
+                "describe-set",
+                &[
+                    ("rd", &rd),
+                    ("value", &format!("{:#x}", (simm as u16 as u32) << 16)),
+                ],
+            ),
+            Self::Load {
+                width,
+                update: u,
+                rd,
+                d,
+                ra,
+            } => {
+                let load = match width {
+                    Width::Word => message(
+                        "describe-load",
+                        &[("address", &address(ra, d)), ("rd", &rd)],
+                    ),
+                    _ => message(
+                        "describe-load-zero",
+                        &[
+                            ("width", &width_name(width)),
+                            ("address", &address(ra, d)),
+                            ("rd", &rd),
+                        ],
+                    ),
+                };
+                update(u, load, ra)
+            }
+            Self::Lha {
+                update: u,
+                rd,
+                d,
+                ra,
+            } => {
+                let load = message(
+                    "describe-load-algebraic",
+                    &[("address", &address(ra, d)), ("rd", &rd)],
+                );
+                update(u, load, ra)
+            }
+            Self::Store {
+                width,
+                update: u,
+                rs,
+                d,
+                ra,
+            } => {
+                let value = match width {
+                    Width::Word => rs.to_string(),
+                    _ => message(
+                        "describe-store-low",
+                        &[("width", &width_name(width)), ("rs", &rs)],
+                    ),
+                };
+                let store = message(
+                    "describe-store",
+                    &[("value", &value), ("address", &address(ra, d))],
+                );
+                update(u, store, ra)
+            }
+            Self::Cmp {
+                logical,
+                crf,
+                ra,
+                rb,
+            } => compare(logical, crf, ra, &rb),
+            Self::Cmpi {
+                logical: false,
+                crf,
+                ra,
+                imm,
+            } => compare(false, crf, ra, &imm),
+            Self::Cmpi { crf, ra, imm, .. } => compare(true, crf, ra, &(imm as u16)),
+            Self::B { disp, link } => message(
+                if link {
+                    "describe-call"
+                } else {
+                    "describe-branch"
+                },
+                &[("target", &target(disp))],
+            ),
+            Self::Bc { bo, bi, disp } => {
+                let target = target(disp.into());
+                let ctr = match (bo & 4, bo & 2) {
+                    (0, 0) => Some(message("condition-ctr-nonzero", &[])),
+                    (0, _) => Some(message("condition-ctr-zero", &[])),
+                    _ => None,
+                };
+                let bit = format!("{:#}", bi);
+                let bit = match (bo & 16, bo & 8) {
+                    (0, 0) => Some(message("condition-clear", &[("bit", &bit)])),
+                    (0, _) => Some(message("condition-set", &[("bit", &bit)])),
+                    _ => None,
+                };
+                let condition = match (ctr.clone(), bit) {
+                    (Some(a), Some(b)) => Some(message("condition-and", &[("a", &a), ("b", &b)])),
+                    (a, b) => a.or(b),
+                };
+                match (ctr, condition) {
+                    (_, None) => message("describe-branch", &[("target", &target)]),
+                    (None, Some(condition)) => message(
+                        "describe-branch-if",
+                        &[("target", &target), ("condition", &condition)],
+                    ),
+                    (Some(_), Some(condition)) => message(
+                        "describe-decrement-branch-if",
+                        &[("target", &target), ("condition", &condition)],
+                    ),
+                }
+            }
+            Self::CrOp { op, bt, ba, bb } => {
+                let (a, b) = (format!("{:#}", ba), format!("{:#}", bb));
+                let id = match op {
+                    CrLogic::And => "cr-and",
+                    CrLogic::Or => "cr-or",
+                    CrLogic::Xor => "cr-xor",
+                    CrLogic::Nand => "cr-nand",
+                    CrLogic::Nor => "cr-nor",
+                    CrLogic::Eqv => "cr-eqv",
+                    CrLogic::Andc => "cr-andc",
+                    CrLogic::Orc => "cr-orc",
+                };
+                let value = message(id, &[("a", &a), ("b", &b)]);
+                message(
+                    "describe-crop",
+                    &[("bt", &format!("{:#}", bt)), ("value", &value)],
+                )
+            }
+        }
+    }
+}
+
+/// A branch target as prose: "8 bytes ahead".
+fn target(disp: i32) -> String {
+    match disp {
+        0 => message("target-self", &[]),
+        d if d > 0 => message("target-ahead", &[("n", &d)]),
+        d => message("target-back", &[("n", &-d)]),
+    }
+}
+
+fn compare(
+    logical: bool,
+    crf: crate::CrField,
+    ra: Register,
+    rb: &dyn core::fmt::Display,
+) -> String {
+    let signedness = message(if logical { "unsigned" } else { "signed" }, &[]);
+    message(
+        "describe-compare",
+        &[
+            ("ra", &ra),
+            ("rb", rb),
+            ("signedness", &signedness),
+            ("crf", &crf),
+        ],
+    )
+}
+
+/// "rS is rotated left SH bits", and the rest of the manual's rotate
+/// wording from there to the mask.
+fn rotate_manual(by: &str, then: &str) -> String {
+    format!(
+        "The contents of rS are rotated left {}. A mask is generated having \
+         1-bits from bit MB through bit ME and 0-bits elsewhere. {}",
+        by, then
+    )
+}
+
+/// The manual's EA sentence, and for update forms where EA ends up.
+fn update_manual(update: bool, access: String) -> String {
+    match update {
+        false => format!("The effective address is the sum (rA|0) + d. {}", access),
+        true => format!(
+            "The effective address is the sum (rA) + d. {} EA is placed into rA.",
+            access
+        ),
+    }
+}
+
+impl Opcode {
+    /// The instruction's name in the PowerPC manuals, e.g. "Rotate Left Word
+    /// Immediate then AND with Mask". Pseudo-ops are named by the real
+    /// instruction they assemble to.
+    pub fn manual_name(&self) -> String {
+        let width = |width| match width {
+            Width::Byte => "Byte",
+            Width::Half => "Half Word",
+            Width::Word => "Word",
+        };
+        let update = |update| if update { " with Update" } else { "" };
+        match self.canonicalize() {
+            Self::Rlwinm { .. } => "Rotate Left Word Immediate then AND with Mask".to_string(),
+            Self::Rlwimi { .. } => "Rotate Left Word Immediate then Mask Insert".to_string(),
+            Self::Rlwnm { .. } => "Rotate Left Word then AND with Mask".to_string(),
+            Self::Addi { .. } => "Add Immediate".to_string(),
+            Self::Addis { .. } => "Add Immediate Shifted".to_string(),
+            Self::Ori { .. } => "OR Immediate".to_string(),
+            Self::Oris { .. } => "OR Immediate Shifted".to_string(),
+            Self::Load {
+                width: w,
+                update: u,
+                ..
+            } => format!("Load {} and Zero{}", width(w), update(u)),
+            Self::Lha { update: u, .. } => format!("Load Half Word Algebraic{}", update(u)),
+            Self::Store {
+                width: w,
+                update: u,
+                ..
+            } => format!("Store {}{}", width(w), update(u)),
+            Self::Cmp { logical, .. } => match logical {
+                false => "Compare".to_string(),
+                true => "Compare Logical".to_string(),
+            },
+            Self::Cmpi { logical, .. } => match logical {
+                false => "Compare Immediate".to_string(),
+                true => "Compare Logical Immediate".to_string(),
+            },
+            Self::CrOp { op, .. } => {
+                let op = match op {
+                    CrLogic::And => "AND",
+                    CrLogic::Or => "OR",
+                    CrLogic::Xor => "XOR",
+                    CrLogic::Nand => "NAND",
+                    CrLogic::Nor => "NOR",
+                    CrLogic::Eqv => "Equivalent",
+                    CrLogic::Andc => "AND with Complement",
+                    CrLogic::Orc => "OR with Complement",
+                };
+                format!("Condition Register {}", op)
+            }
+            Self::B { link: false, .. } => "Branch".to_string(),
+            Self::B { link: true, .. } => "Branch and Link".to_string(),
+            Self::Bc { .. } => "Branch Conditional".to_string(),
+            // canonicalize() leaves only real instructions
+            op => unreachable!("{} is a pseudo-op", op),
+        }
+    }
+
+    /// What the instruction does in the manuals' words, with fields by their
+    /// encoding names (rS, SH, MB...) rather than values.
+    pub fn manual(&self) -> String {
+        let width = |width| match width {
+            Width::Byte => ("byte", "eight"),
+            Width::Half => ("halfword", "16"),
+            Width::Word => ("word", "32"),
+        };
+        match self.canonicalize() {
+            Self::Rlwinm { .. } => rotate_manual(
+                "SH bits",
+                "The rotated data is ANDed with the generated mask and the result is \
+                 placed into rA.",
+            ),
+            Self::Rlwimi { .. } => rotate_manual(
+                "SH bits",
+                "The rotated data is inserted into rA under control of the generated mask.",
+            ),
+            Self::Rlwnm { .. } => rotate_manual(
+                "the number of bits specified by the low-order five bits of rB",
+                "The rotated data is ANDed with the generated mask and the result is \
+                 placed into rA.",
+            ),
+            Self::Addi { .. } => "The sum (rA|0) + SIMM is placed into rD.".to_string(),
+            Self::Addis { .. } => {
+                "The sum (rA|0) + (SIMM || 0x0000) is placed into rD.".to_string()
+            }
+            Self::Ori { .. } => {
+                "The contents of rS are ORed with 0x0000 || UIMM and the result is placed \
+                 into rA."
+                    .to_string()
+            }
+            Self::Oris { .. } => {
+                "The contents of rS are ORed with UIMM || 0x0000 and the result is placed \
+                 into rA."
+                    .to_string()
+            }
+            Self::Load {
+                width: Width::Word,
+                update,
+                ..
+            } => update_manual(
+                update,
+                "The word in memory addressed by EA is loaded into rD.".to_string(),
+            ),
+            Self::Load {
+                width: w, update, ..
+            } => {
+                let (unit, bits) = width(w);
+                update_manual(
+                    update,
+                    format!(
+                        "The {} in memory addressed by EA is loaded into the low-order {} \
+                         bits of rD. The remaining bits in rD are cleared.",
+                        unit, bits
+                    ),
+                )
+            }
+            Self::Lha { update, .. } => update_manual(
+                update,
+                "The halfword in memory addressed by EA is loaded into the low-order 16 bits \
+                 of rD. The remaining bits in rD are filled with a copy of the most-significant \
+                 bit of the loaded halfword."
+                    .to_string(),
+            ),
+            Self::Store {
+                width: Width::Word,
+                update,
+                ..
+            } => update_manual(
+                update,
+                "The contents of rS are stored into the word in memory addressed by EA."
+                    .to_string(),
+            ),
+            Self::Store {
+                width: w, update, ..
+            } => {
+                let (unit, bits) = width(w);
+                update_manual(
+                    update,
+                    format!(
+                        "The contents of the low-order {} bits of rS are stored into the {} \
+                         in memory addressed by EA.",
+                        bits, unit
+                    ),
+                )
+            }
+            Self::Cmp { logical, .. } => format!(
+                "The contents of rA are compared with the contents of rB, treating the \
+                 operands as {} integers. The result of the comparison is placed into CR \
+                 field crfD.",
+                if logical { "unsigned" } else { "signed" }
+            ),
+            Self::Cmpi { logical: false, .. } => {
+                "The contents of rA are compared with the sign-extended value of the SIMM \
+                 field, treating the operands as signed integers. The result of the \
+                 comparison is placed into CR field crfD."
+                    .to_string()
+            }
+            Self::Cmpi { .. } => {
+                "The contents of rA are compared with 0x0000 || UIMM, treating the operands \
+                 as unsigned integers. The result of the comparison is placed into CR field \
+                 crfD."
+                    .to_string()
+            }
+            Self::CrOp { op, .. } => {
+                let combine = match op {
+                    CrLogic::And => "ANDed with the bit",
+                    CrLogic::Or => "ORed with the bit",
+                    CrLogic::Xor => "XORed with the bit",
+                    CrLogic::Nand => "ANDed with the bit",
+                    CrLogic::Nor => "ORed with the bit",
+                    CrLogic::Eqv => "XORed with the bit",
+                    CrLogic::Andc => "ANDed with the complement of the bit",
+                    CrLogic::Orc => "ORed with the complement of the bit",
+                };
+                let result = match op {
+                    CrLogic::Nand | CrLogic::Nor | CrLogic::Eqv => "complemented result",
+                    _ => "result",
+                };
+                format!(
+                    "The bit in the condition register specified by crbA is {} in the \
+                     condition register specified by crbB, and the {} is placed into the \
+                     condition register bit specified by crbD.",
+                    combine, result
+                )
+            }
+            Self::B { link, .. } => format!(
+                "The branch target address is the sum of LI || 0b00 sign-extended and the \
+                 address of this instruction.{}",
+                if link {
+                    " The effective address of the instruction following the branch \
+                     instruction is placed into the link register."
+                } else {
+                    ""
+                }
+            ),
+            Self::Bc { .. } => "The BI field specifies the bit in the condition register to be \
+                 used as the condition of the branch. The BO field is encoded as described \
+                 in the manuals: it decrements ctr and tests it, tests the CR bit for true or \
+                 false, or does neither. If the condition is met, the branch target address \
+                 is the sum of BD || 0b00 sign-extended and the address of this instruction."
+                .to_string(),
+            // canonicalize() leaves only real instructions
+            op => unreachable!("{} is a pseudo-op", op),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_opcode;
+
+    #[test]
+    fn test_describe() {
+        let describe = |asm| parse_opcode(asm).expect("parse failed").1.describe();
+        assert_eq!(
+            describe("extrwi r0,r7,8,12"),
+            "extract the 8-bit field starting at bit 12 of r7 into the low bits of r0"
+        );
+        assert_eq!(
+            describe("rlwinm r0,r7,20,24,31"),
+            describe("extrwi r0,r7,8,12")
+        );
+        assert_eq!(
+            describe("rlwinm r3,r4,8,4,19"),
+            "rotate r4 left by 8 bits, keep bits 4..19 and clear the rest, into r3"
+        );
+        assert_eq!(
+            describe("lhau r3,-2(r4)"),
+            "load the halfword at -2 bytes from the address in r4 into r3, sign-extended, \
+             then point r4 at it"
+        );
+        assert_eq!(
+            describe("rlwinm r3,r4,8,24,7"),
+            "rotate r4 left by 8 bits, keep the top 8 and bottom 8 bits and clear the rest, into r3"
+        );
+        assert_eq!(describe("cror eq,gt,eq"), "set cr0.eq to cr0.gt or cr0.eq");
+    }
+
+    #[test]
+    fn test_manual() {
+        let op = |asm| parse_opcode(asm).expect("parse failed").1;
+        assert_eq!(
+            op("extrwi r0,r7,8,12").manual_name(),
+            "Rotate Left Word Immediate then AND with Mask"
+        );
+        assert_eq!(
+            op("lhzu r3,2(r4)").manual_name(),
+            "Load Half Word and Zero with Update"
+        );
+        assert_eq!(
+            op("stb r0,8(r3)").manual(),
+            "The effective address is the sum (rA|0) + d. The contents of the low-order eight \
+             bits of rS are stored into the byte in memory addressed by EA."
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! Aligns two versions of the same function, e.g. from different game
+//! revisions, instruction by instruction. Used when porting codes between
+//! versions and when matching decomp output against the original.
+
+use crate::disasm::{self, Endian, Options};
+use crate::image::{Image, Section};
+use crate::parser::{self, Directive, Line, Program, Statement};
+use crate::signature;
+use crate::symbols::SymbolTable;
+use crate::Opcode;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Change {
+    /// Identical instruction
+    Same,
+    /// Different spelling of the same instruction, e.g. `slwi` and `rlwinm`
+    Equivalent,
+    /// Same operation, different operands (often just a moved address)
+    Operands,
+    /// Different operation
+    Replaced,
+    /// Only in the left listing
+    Removed,
+    /// Only in the right listing
+    Added,
+}
+
+impl Change {
+    /// Gutter marker for the split view.
+    pub fn marker(self) -> char {
+        match self {
+            Self::Same => ' ',
+            Self::Equivalent => '~',
+            Self::Operands => '*',
+            Self::Replaced => '!',
+            Self::Removed => '<',
+            Self::Added => '>',
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffRow<'a> {
+    pub left: Option<&'a Line>,
+    pub right: Option<&'a Line>,
+    pub change: Change,
+}
+
+/// The instructions of `program`, canonical, and the data it emits among
+/// them (the words of a listing that don't decode), as `None`.
+fn instructions(program: &Program) -> Vec<(&Line, Option<Opcode>)> {
+    program
+        .lines
+        .iter()
+        .filter_map(|line| match line.statement {
+            Statement::Instruction(op) => Some((line, Some(op.canonicalize()))),
+            Statement::Directive(Directive::Data { .. }) => Some((line, None)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether two lines are aligned: instructions of the same mnemonic, or
+/// the same data.
+fn aligned(left: (&Line, Option<Opcode>), right: (&Line, Option<Opcode>)) -> bool {
+    match (left.1, right.1) {
+        (Some(l), Some(r)) => l.mnemonic() == r.mnemonic(),
+        (None, None) => left.0.statement == right.0.statement,
+        _ => false,
+    }
+}
+
+fn classify(left: (&Line, Option<Opcode>), right: (&Line, Option<Opcode>)) -> Change {
+    let ((l, lop), (r, rop)) = (left, right);
+    if lop != rop || l.reloc != r.reloc {
+        return Change::Operands;
+    }
+    if l.statement == r.statement {
+        Change::Same
+    } else {
+        Change::Equivalent
+    }
+}
+
+/// Emits a run of unmatched lines, pairing removals with additions.
+fn pair_up<'a>(
+    rows: &mut Vec<DiffRow<'a>>,
+    removed: &mut Vec<&'a Line>,
+    added: &mut Vec<&'a Line>,
+) {
+    for k in 0..removed.len().max(added.len()) {
+        let (left, right) = (removed.get(k).copied(), added.get(k).copied());
+        let change = match (left, right) {
+            (Some(_), Some(_)) => Change::Replaced,
+            (Some(_), None) => Change::Removed,
+            _ => Change::Added,
+        };
+        rows.push(DiffRow {
+            left,
+            right,
+            change,
+        });
+    }
+    removed.clear();
+    added.clear();
+}
+
+/// The function `name` of one build, `image` with its `symbols`,
+/// disassembled and read back for [`diff`]: the symbol's size in bytes, or
+/// without one, up to its first `blr`.
+pub fn function(image: &Image, symbols: &SymbolTable, name: &str) -> Result<Program, String> {
+    let symbol = symbols
+        .iter()
+        .find(|s| s.name == name && !s.data)
+        .ok_or_else(|| format!("no function `{}` in the symbols", name))?;
+    let size = match symbol.size {
+        Some(size) if size > 0 => size,
+        _ => {
+            let (_, end) = signature::function(image, symbol.addr)
+                .ok_or_else(|| format!("`{}` at {:08x} isn't loaded", name, symbol.addr))?;
+            end - symbol.addr
+        }
+    };
+    let data = image
+        .read(symbol.addr, size)
+        .ok_or_else(|| format!("`{}` at {:08x} isn't all loaded", name, symbol.addr))?;
+    let section = Section {
+        addr: symbol.addr,
+        data: data.to_vec(),
+        executable: true,
+    };
+    let options = Options {
+        symbols: symbols.clone(),
+        ..Options::default()
+    };
+    let listing = disasm::listing(&section, Endian::Big, &options);
+    parser::parse_program(&listing.text).map_err(|e| format!("`{}`: {}", name, e))
+}
+
+/// Rows pairing up `left` and `right`. Instructions are aligned on their
+/// canonical mnemonic (longest common subsequence), so a port where only
+/// addresses and offsets moved lines up row for row.
+pub fn diff<'a>(left: &'a Program, right: &'a Program) -> Vec<DiffRow<'a>> {
+    let (a, b) = (instructions(left), instructions(right));
+
+    // lcs[i][j]: length of the common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if aligned(a[i], b[j]) {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut rows = Vec::new();
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && aligned(a[i], b[j]) {
+            pair_up(&mut rows, &mut removed, &mut added);
+            rows.push(DiffRow {
+                left: Some(a[i].0),
+                right: Some(b[j].0),
+                change: classify(a[i], b[j]),
+            });
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            removed.push(a[i].0);
+            i += 1;
+        } else {
+            added.push(b[j].0);
+            j += 1;
+        }
+    }
+    pair_up(&mut rows, &mut removed, &mut added);
+
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_program;
+
+    #[test]
+    fn test_diff() {
+        let left = parse_program(
+            "lis r3,0x8045\nlwz r4,0x1234(r3)\nslwi r4,r4,2\nli r5,0\nstw r4,8(r3)\n",
+        )
+        .unwrap();
+        let right = parse_program(
+            "lis r3,0x8046\nlwz r4,0x1234(r3)\nrlwinm r4,r4,2,0,29\nstw r4,8(r3)\nPAD = 1\n",
+        )
+        .unwrap();
+
+        let changes: Vec<_> = diff(&left, &right).iter().map(|r| r.change).collect();
+        assert_eq!(
+            changes,
+            vec![
+                Change::Operands,
+                Change::Same,
+                Change::Equivalent,
+                Change::Removed,
+                Change::Same,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_function() {
+        // li r3,0; blr; then in the other build the function moved and
+        // gained an instruction: li r3,0; li r4,1; blr
+        let words =
+            |words: &[u32]| -> Vec<u8> { words.iter().flat_map(|w| w.to_be_bytes()).collect() };
+        let old = Image::raw(&words(&[0x3860_0000, 0x4E80_0020]), 0x8000_3100);
+        let new = Image::raw(
+            &words(&[0x6000_0000, 0x3860_0000, 0x3880_0001, 0x4E80_0020]),
+            0x8000_3200,
+        );
+        let old_map = SymbolTable::parse("main = .text:0x80003100; // type:function\n");
+        let new_map = SymbolTable::parse("main = .text:0x80003204; // type:function size:0xC\n");
+
+        let left = function(&old, &old_map, "main").unwrap();
+        let right = function(&new, &new_map, "main").unwrap();
+        assert_eq!(right.lines[0].address, Some(0x8000_3204));
+        let changes: Vec<_> = diff(&left, &right).iter().map(|r| r.change).collect();
+        assert_eq!(changes, vec![Change::Same, Change::Added, Change::Same]);
+
+        assert!(function(&old, &old_map, "exit").is_err());
+    }
+}
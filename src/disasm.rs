@@ -0,0 +1,761 @@
+//! Disassembly of raw memory: byte buffers, raw binary files, and hex dumps
+//! pasted from a memory viewer like Dolphin's. A word that doesn't decode
+//! is kept as `.long`, with a guess at what it is, so a listing always
+//! assembles back to the same bytes.
+//!
+//! A dump is hex digits, with or without spaces between bytes or words. A
+//! line may start with its address followed by `:`, and anything after the
+//! hex (an ASCII column, say) is ignored.
+
+use core::fmt;
+use std::io;
+use std::path::Path;
+
+use crate::encoding::DecodeError;
+use crate::image::{Image, Section};
+use crate::symbols::SymbolTable;
+use crate::{Opcode, Register};
+
+/// Byte order of an instruction stream.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Endian {
+    /// GameCube, Wii and most embedded PowerPC
+    Big,
+    /// ppc64le and other little-endian modes
+    Little,
+}
+
+/// Primary opcodes no 32-bit PowerPC instruction uses, leaving GameCube's
+/// paired singles (4, 56, 57, 60, 61) assigned.
+const UNASSIGNED: &[u32] = &[0, 1, 5, 6, 9, 22, 30, 58, 62];
+
+impl Endian {
+    pub fn word(self, bytes: [u8; 4]) -> u32 {
+        match self {
+            Self::Big => u32::from_be_bytes(bytes),
+            Self::Little => u32::from_le_bytes(bytes),
+        }
+    }
+
+    /// Guesses the byte order from how many words read as plausible
+    /// instructions each way: ones ppcheat decodes count double, others
+    /// count if their primary opcode is assigned. Ties go to big-endian.
+    pub fn detect(bytes: &[u8]) -> Self {
+        let score = |endian: Self| -> usize {
+            bytes
+                .chunks_exact(4)
+                .map(|w| endian.word([w[0], w[1], w[2], w[3]]))
+                .map(|word| match Opcode::decode(word) {
+                    Ok(_) => 2,
+                    Err(_) if !UNASSIGNED.contains(&(word >> 26)) => 1,
+                    Err(_) => 0,
+                })
+                .sum()
+        };
+        match score(Self::Little) > score(Self::Big) {
+            true => Self::Little,
+            false => Self::Big,
+        }
+    }
+}
+
+/// Each word of `bytes` decoded, with its address counting up from `base`.
+/// A trailing partial word is ignored.
+pub fn disassemble(
+    bytes: &[u8],
+    base: u32,
+    endian: Endian,
+) -> impl Iterator<Item = (u32, Result<Opcode, DecodeError>)> + '_ {
+    bytes.chunks_exact(4).enumerate().map(move |(i, w)| {
+        let word = endian.word([w[0], w[1], w[2], w[3]]);
+        (base.wrapping_add(4 * i as u32), Opcode::decode(word))
+    })
+}
+
+/// What an instruction ppcheat doesn't decode probably is, from its
+/// opcode fields alone: enough to tell code from data at a glance.
+pub fn guess(word: u32) -> Option<&'static str> {
+    let (primary, extended) = (word >> 26, (word >> 1) & 0x3FF);
+    let spr = ((word >> 16) & 0x1F) | ((word >> 6) & 0x3E0);
+    Some(match (primary, extended) {
+        _ if word == 0x6000_0000 => "nop",
+        _ if word == 0x4E80_0020 => "blr",
+        _ if word == 0x4E80_0021 => "blrl",
+        _ if word == 0x4E80_0420 => "bctr",
+        _ if word == 0x4E80_0421 => "bctrl",
+        (3, _) => "twi",
+        (4, _) => "a paired-single instruction",
+        (7, _) => "mulli",
+        (8, _) => "subfic",
+        (12, _) => "addic",
+        (13, _) => "addic.",
+        (17, _) => "sc",
+        (19, 16) => "bclr",
+        (19, 150) => "isync",
+        (19, 528) => "bcctr",
+        (24, _) => "ori",
+        (25, _) => "oris",
+        (26, _) => "xori",
+        (27, _) => "xoris",
+        (28, _) => "andi.",
+        (29, _) => "andis.",
+        (31, 8) => "subfc",
+        (31, 10) => "addc",
+        (31, 19) => "mfcr",
+        (31, 23) => "lwzx",
+        (31, 24) => "slw",
+        (31, 26) => "cntlzw",
+        (31, 28) => "and",
+        (31, 40) => "subf",
+        (31, 83) => "mfmsr",
+        (31, 86) => "dcbf",
+        (31, 104) => "neg",
+        (31, 136) => "subfe",
+        (31, 138) => "adde",
+        (31, 144) => "mtcrf",
+        (31, 146) => "mtmsr",
+        (31, 151) => "stwx",
+        (31, 202) => "addze",
+        (31, 235) => "mullw",
+        (31, 266) => "add",
+        (31, 316) => "xor",
+        (31, 339) if spr == 8 => "mflr",
+        (31, 339) if spr == 9 => "mfctr",
+        (31, 339) => "mfspr",
+        (31, 444) if (word >> 21) & 0x1F == (word >> 11) & 0x1F => "mr",
+        (31, 444) => "or",
+        (31, 459) => "divwu",
+        (31, 467) if spr == 8 => "mtlr",
+        (31, 467) if spr == 9 => "mtctr",
+        (31, 467) => "mtspr",
+        (31, 491) => "divw",
+        (31, 536) => "srw",
+        (31, 598) => "sync",
+        (31, 792) => "sraw",
+        (31, 824) => "srawi",
+        (31, 922) => "extsh",
+        (31, 954) => "extsb",
+        (31, 982) => "icbi",
+        (46, _) => "lmw",
+        (47, _) => "stmw",
+        (48, _) => "lfs",
+        (49, _) => "lfsu",
+        (50, _) => "lfd",
+        (51, _) => "lfdu",
+        (52, _) => "stfs",
+        (53, _) => "stfsu",
+        (54, _) => "stfd",
+        (55, _) => "stfdu",
+        (56, _) => "psq_l",
+        (57, _) => "psq_lu",
+        (59, _) => "a single-precision float instruction",
+        (60, _) => "psq_st",
+        (61, _) => "psq_stu",
+        (63, _) => "a float instruction",
+        _ => return None,
+    })
+}
+
+/// The comment for a word kept as `.long`: the guess at what it is, or
+/// why it didn't decode when that says more.
+pub fn best_guess(error: &DecodeError) -> String {
+    match error {
+        DecodeError::Unsupported(word) => match guess(*word) {
+            Some(name) => format!("looks like {}", name),
+            None => "not an instruction ppcheat knows".to_string(),
+        },
+        error => error.to_string(),
+    }
+}
+
+/// `word` as a comment names it: disassembled, or in brackets what it
+/// likely is.
+pub fn instruction(word: u32) -> String {
+    match Opcode::decode(word) {
+        Ok(op) => op.to_string(),
+        Err(e) => format!("({})", best_guess(&e)),
+    }
+}
+
+/// How a listing is laid out.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum Style {
+    /// ppcheat's own, which the parser reads back
+    #[default]
+    Ppcheat,
+    /// `objdump -d`'s, to diff against binutils; see [`crate::objdump`]
+    Objdump,
+}
+
+/// How to lay out a listing and what to annotate it with.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Options {
+    pub style: Style,
+    /// Names for the addresses instructions refer to; see [`listing`]
+    pub symbols: SymbolTable,
+    /// Write what [`sweep`] takes for data as directives, not instructions.
+    /// objdump's style lists everything as instructions, as objdump does.
+    pub find_data: bool,
+}
+
+/// A listing, and how many of its words didn't decode or were data.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Listing {
+    pub text: String,
+    pub words: usize,
+    pub unknown: usize,
+    pub data: usize,
+}
+
+impl Listing {
+    pub fn extend(&mut self, other: Listing) {
+        self.text += &other.text;
+        self.words += other.words;
+        self.unknown += other.unknown;
+        self.data += other.data;
+    }
+}
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// What a word of a section probably is, by [`sweep`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Content {
+    Code,
+    /// An address inside the section, as in a jump table
+    Pointer,
+    /// A single-precision constant, as in a float pool
+    Float,
+    /// Anything else that isn't an instruction
+    Data,
+}
+
+/// Whether `word` reads as a float a compiler would keep in a pool: a
+/// normal number of everyday size, written in few digits. Branches, whose
+/// opcode covers 131072.0 up, never do.
+fn plausible_float(word: u32) -> bool {
+    let value = f32::from_bits(word).abs();
+    let digits = format!("{:e}", value);
+    let mantissa = digits.split('e').next().unwrap_or("");
+    word >> 26 != 18
+        && value.is_normal()
+        && (1e-4..=1e7).contains(&value)
+        && mantissa.chars().filter(char::is_ascii_digit).count() <= 8
+}
+
+/// Each whole word of `section` taken as code or data. Two or more words
+/// in a row that point into the section are a jump table, and two or more
+/// plausible floats a float pool, even if they'd decode. A word with an
+/// unassigned primary opcode is data, as are two or more in a row that
+/// neither decode nor look like anything [`guess`] knows, and an
+/// instruction with data either side.
+pub fn sweep(section: &Section, endian: Endian) -> Vec<Content> {
+    let words: Vec<_> = section
+        .data
+        .chunks_exact(4)
+        .map(|w| endian.word([w[0], w[1], w[2], w[3]]))
+        .collect();
+    let end = section.addr.wrapping_add(section.data.len() as u32);
+    let pointer = |w: u32| w != 0 && w.is_multiple_of(4) && (section.addr..end).contains(&w);
+    let unassigned = |w: u32| UNASSIGNED.contains(&(w >> 26));
+    let unknown = |w: u32| Opcode::decode(w).is_err() && guess(w).is_none();
+
+    let mut contents = vec![Content::Code; words.len()];
+    // runs of two or more words that `test` picks out
+    let mut runs = |test: &dyn Fn(u32) -> bool, content: Content| {
+        let mut i = 0;
+        while i < words.len() {
+            let run = words[i..].iter().take_while(|&&w| test(w)).count();
+            if run >= 2 {
+                for c in &mut contents[i..i + run] {
+                    if *c == Content::Code {
+                        *c = content;
+                    }
+                }
+            }
+            i += run.max(1);
+        }
+    };
+    runs(&|w| pointer(w), Content::Pointer);
+    runs(&|w| plausible_float(w), Content::Float);
+    runs(&|w| unknown(w), Content::Data);
+
+    for (i, &w) in words.iter().enumerate() {
+        if contents[i] != Content::Code {
+            continue;
+        }
+        if plausible_float(w) && unknown(w) {
+            contents[i] = Content::Float;
+        } else if unassigned(w) {
+            contents[i] = Content::Data;
+        }
+    }
+    for i in 1..words.len().saturating_sub(1) {
+        let data = |c: Content| c != Content::Code;
+        if contents[i] == Content::Code && data(contents[i - 1]) && data(contents[i + 1]) {
+            contents[i] = Content::Data;
+        }
+    }
+    contents
+}
+
+/// The section as a listing the parser reads back: a line per word with its
+/// address and bytes, as objdump writes them. A word that doesn't decode
+/// is kept as `.long`, its address, bytes and [`best_guess`] in a comment,
+/// and a trailing partial word becomes `.byte`. Bytes are shown in memory
+/// order whatever the `endian`.
+///
+/// An instruction that refers to an address the `options`' symbols can
+/// name gets the name in a comment: a branch's target, a small data access
+/// off r13 or r2, or the second half of a `lis` pair, `lis r3,0x8040` then
+/// `addi r3,r3,-32768` or `lwz r4,16(r3)`. With [`Options::find_data`],
+/// words [`sweep`] takes for data are written as `.long` pointers,
+/// `.float`s or `.byte`s.
+pub fn listing(section: &Section, endian: Endian, options: &Options) -> Listing {
+    let symbols = &options.symbols;
+    let contents = match options.find_data {
+        true => sweep(section, endian),
+        false => Vec::new(),
+    };
+    let mut listing = Listing::default();
+    let mut references = References::default();
+    let out = &mut listing.text;
+    for (i, (word, (addr, op))) in section
+        .data
+        .chunks_exact(4)
+        .zip(disassemble(&section.data, section.addr, endian))
+        .enumerate()
+    {
+        let bytes = format!(
+            "{:02x} {:02x} {:02x} {:02x}",
+            word[0], word[1], word[2], word[3]
+        );
+        // in memory order, so it assembles back to the same bytes
+        let value = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        let content = match (contents.get(i), endian) {
+            (None, _) => Content::Code,
+            // a value would assemble big-endian
+            (Some(Content::Pointer | Content::Float), Endian::Little) => Content::Data,
+            (Some(&content), _) => content,
+        };
+        listing.words += 1;
+        if content != Content::Code {
+            references = References::default();
+            listing.data += 1;
+        }
+        *out += &match (content, op) {
+            (Content::Code, Ok(op)) => match references.next(&op, addr, symbols) {
+                Some(name) => format!("{:08x}: {}  {}  # {}\n", addr, bytes, op, name),
+                None => format!("{:08x}: {}  {}\n", addr, bytes, op),
+            },
+            (Content::Code, Err(e)) => {
+                references = References::default();
+                listing.unknown += 1;
+                format!(
+                    ".long 0x{:08X}  # {:08x}: {}  {}\n",
+                    value,
+                    addr,
+                    bytes,
+                    best_guess(&e)
+                )
+            }
+            (Content::Pointer, _) => format!(
+                ".long 0x{:08X}  # {:08x}: {}  {}\n",
+                value,
+                addr,
+                bytes,
+                symbols.name(value).as_deref().unwrap_or("pointer")
+            ),
+            (Content::Float, _) => format!(
+                ".float {:?}  # {:08x}: {}\n",
+                f32::from_bits(value),
+                addr,
+                bytes
+            ),
+            (Content::Data, _) => {
+                let bytes: Vec<_> = word.iter().map(|b| format!("{:#04x}", b)).collect();
+                format!(".byte {}  # {:08x}: data\n", bytes.join(", "), addr)
+            }
+        };
+    }
+    let tail = section.data.chunks_exact(4).remainder();
+    if !tail.is_empty() {
+        let bytes: Vec<_> = tail.iter().map(|b| format!("{:#04x}", b)).collect();
+        let addr = section
+            .addr
+            .wrapping_add((section.data.len() - tail.len()) as u32);
+        *out += &format!(
+            ".byte {}  # {:08x}: {} trailing byte{}\n",
+            bytes.join(", "),
+            addr,
+            tail.len(),
+            if tail.len() == 1 { "" } else { "s" }
+        );
+    }
+    listing
+}
+
+/// What a `lis` has left in each register, to follow into the instruction
+/// that completes the address.
+#[derive(Default)]
+struct References {
+    high: [Option<u32>; 32],
+}
+
+impl References {
+    /// The name of what `op` at `addr` refers to, if it's a branch or
+    /// completes an address, noting what it leaves in which register.
+    fn next(&mut self, op: &Opcode, addr: u32, symbols: &SymbolTable) -> Option<String> {
+        let base = |high: &[Option<u32>; 32], ra: Register| {
+            symbols.sda_base(ra).or(high[usize::from(ra.0)])
+        };
+        let op = op.canonicalize();
+        let target = match op {
+            Opcode::B { disp, .. } => symbols.code_name(addr.wrapping_add(disp as u32)),
+            Opcode::Bc { disp, .. } => symbols.code_name(addr.wrapping_add(disp as u32)),
+            Opcode::Addi { ra, simm, .. } if ra.0 != 0 => {
+                base(&self.high, ra).and_then(|b| symbols.name(b.wrapping_add(simm as u32)))
+            }
+            Opcode::Ori { rs, uimm, .. } => {
+                self.high[usize::from(rs.0)].and_then(|b| symbols.name(b | u32::from(uimm)))
+            }
+            Opcode::Load { d, ra, .. }
+            | Opcode::Lha { d, ra, .. }
+            | Opcode::Store { d, ra, .. }
+                if ra.0 != 0 =>
+            {
+                base(&self.high, ra).and_then(|b| symbols.name(b.wrapping_add(d as u32)))
+            }
+            _ => None,
+        };
+        match op {
+            // control can arrive after from elsewhere, and a call clobbers
+            Opcode::B { .. } | Opcode::Bc { .. } => *self = Self::default(),
+            Opcode::Addis {
+                rd,
+                ra: Register(0),
+                simm,
+            } => self.high[usize::from(rd.0)] = Some((simm as u32) << 16),
+            Opcode::Addi { rd, .. } | Opcode::Addis { rd, .. } => {
+                self.high[usize::from(rd.0)] = None
+            }
+            Opcode::Ori { ra, .. } | Opcode::Oris { ra, .. } => self.high[usize::from(ra.0)] = None,
+            Opcode::Load { rd, ra, update, .. } | Opcode::Lha { rd, ra, update, .. } => {
+                self.high[usize::from(rd.0)] = None;
+                if update {
+                    self.high[usize::from(ra.0)] = None;
+                }
+            }
+            Opcode::Store {
+                ra, update: true, ..
+            } => self.high[usize::from(ra.0)] = None,
+            Opcode::Rlwinm { ra, .. } | Opcode::Rlwimi { ra, .. } | Opcode::Rlwnm { ra, .. } => {
+                self.high[usize::from(ra.0)] = None
+            }
+            _ => {}
+        }
+        target
+    }
+}
+
+/// [`listing`] of the raw binary at `path` loaded at `base`, in the byte
+/// order [`Endian::detect`] guesses if `endian` is `None`. objdump calls a
+/// raw binary's one section `.data`, so that style does too.
+pub fn listing_file(
+    path: &Path,
+    base: u32,
+    endian: Option<Endian>,
+    options: &Options,
+) -> io::Result<Listing> {
+    let image = Image::raw(&std::fs::read(path)?, base);
+    let section = &image.sections[0];
+    let endian = endian.unwrap_or_else(|| Endian::detect(&section.data));
+    Ok(match options.style {
+        Style::Ppcheat => listing(section, endian, options),
+        Style::Objdump => crate::objdump::section(".data", section, endian, &options.symbols),
+    })
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HexDump {
+    /// The address of the first byte, if the dump had addresses
+    pub base: Option<u32>,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HexDumpError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for HexDumpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for HexDumpError {}
+
+fn hex(token: &str) -> Option<&str> {
+    let digits = token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+        .unwrap_or(token);
+    match !digits.is_empty() && digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        true => Some(digits),
+        false => None,
+    }
+}
+
+impl HexDump {
+    pub fn parse(inp: &str) -> Result<Self, HexDumpError> {
+        let mut dump = HexDump::default();
+
+        for (idx, raw) in inp.lines().enumerate() {
+            let error = |message: String| HexDumpError {
+                line: idx + 1,
+                message,
+            };
+            let mut tokens = raw.split_whitespace().peekable();
+            if let Some(addr) = tokens.peek().and_then(|t| t.strip_suffix(':')) {
+                let addr = hex(addr)
+                    .and_then(|a| u32::from_str_radix(a, 16).ok())
+                    .ok_or_else(|| error(format!("invalid address `{}:`", addr)))?;
+                let expected = dump
+                    .base
+                    .map(|base| base.wrapping_add(dump.bytes.len() as u32));
+                match expected {
+                    None if dump.bytes.is_empty() => dump.base = Some(addr),
+                    None => return Err(error("only some lines have addresses".to_string())),
+                    Some(expected) if expected != addr => {
+                        return Err(error(format!(
+                            "starts at {:08x}, but the line before ends at {:08x}",
+                            addr, expected
+                        )))
+                    }
+                    Some(_) => {}
+                }
+                tokens.next();
+            } else if dump.base.is_some() && tokens.peek().is_some() {
+                return Err(error("only some lines have addresses".to_string()));
+            }
+
+            let digits: String = tokens.map_while(hex).collect();
+            if !digits.len().is_multiple_of(2) {
+                return Err(error("odd number of hex digits".to_string()));
+            }
+            dump.bytes.extend(
+                (0..digits.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap()),
+            );
+        }
+
+        Ok(dump)
+    }
+
+    /// [`disassemble`] from the dump's own address, or `base` if it had none.
+    pub fn disassemble(
+        &self,
+        base: u32,
+        endian: Endian,
+    ) -> impl Iterator<Item = (u32, Result<Opcode, DecodeError>)> + '_ {
+        disassemble(&self.bytes, self.base.unwrap_or(base), endian)
+    }
+}
+
+/// 16 bytes a line, as words, with the bytes as ASCII after them (`.` for
+/// those that aren't printable), and the address of each line first if the
+/// dump has one; the form [`HexDump::parse`] reads back.
+impl fmt::Display for HexDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, line) in self.bytes.chunks(16).enumerate() {
+            if let Some(base) = self.base {
+                write!(f, "{:08x}: ", base.wrapping_add(16 * i as u32))?;
+            }
+            let mut hex = String::new();
+            for (j, byte) in line.iter().enumerate() {
+                if j > 0 && j % 4 == 0 {
+                    hex.push(' ');
+                }
+                hex += &format!("{:02x}", byte);
+            }
+            let ascii: String = line
+                .iter()
+                .map(|&b| match b.is_ascii_graphic() || b == b' ' {
+                    true => b as char,
+                    false => '.',
+                })
+                .collect();
+            writeln!(f, "{:<35}  |{}|", hex, ascii)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex_dump() {
+        let dump = HexDump::parse("80003100: 5400843e 3860ffff  T..>8`..\n80003108: 7c 08 02 a6\n")
+            .unwrap();
+        assert_eq!(dump.base, Some(0x8000_3100));
+        let lines: Vec<_> = dump
+            .disassemble(0, Endian::Big)
+            .map(|(addr, op)| match op {
+                Ok(op) => format!("{:08x} {}", addr, op),
+                Err(e) => format!("{:08x} {}", addr, e),
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "80003100 srwi r0,r0,16",
+                "80003104 li r3,-1",
+                "80003108 7C0802A6 (primary opcode 31) isn't an instruction ppcheat explains"
+            ]
+        );
+        assert_eq!(
+            HexDump::parse("5400843e3860FFFF").unwrap(),
+            HexDump {
+                base: None,
+                bytes: vec![0x54, 0x00, 0x84, 0x3e, 0x38, 0x60, 0xff, 0xff]
+            }
+        );
+        assert_eq!(
+            HexDump::parse("80003100: 5400843e\n80003108: 3860ffff")
+                .unwrap_err()
+                .to_string(),
+            "line 2: starts at 80003108, but the line before ends at 80003104"
+        );
+
+        let dump = HexDump {
+            base: Some(0),
+            bytes: b"Hello, world!\n\0\0\x7f".to_vec(),
+        };
+        assert_eq!(
+            dump.to_string(),
+            "00000000: 48656c6c 6f2c2077 6f726c64 210a0000  |Hello, world!...|\n\
+             00000010: 7f                                   |.|\n"
+        );
+        assert_eq!(HexDump::parse(&dump.to_string()), Ok(dump));
+    }
+
+    #[test]
+    fn test_listing() {
+        let bytes = [0x54, 0x00, 0x84, 0x3e, 0x7c, 0x08, 0x02, 0xa6, 0x12, 0x34];
+        let section = &Image::raw(&bytes, 0x8000_3100).sections[0];
+        let listing = listing(section, Endian::Big, &Options::default());
+        assert_eq!(
+            listing.text,
+            "80003100: 54 00 84 3e  srwi r0,r0,16\n\
+             .long 0x7C0802A6  # 80003104: 7c 08 02 a6  looks like mflr\n\
+             .byte 0x12, 0x34  # 80003108: 2 trailing bytes\n"
+        );
+        assert_eq!((listing.words, listing.unknown), (2, 1));
+        let program = crate::parser::parse_program(&listing.text).unwrap();
+        assert_eq!(program.lines[0].address, Some(0x8000_3100));
+        assert_eq!(crate::asm::assemble(&program, 0x8000_3100).unwrap(), bytes);
+
+        // lis r3,0x8040; addi r3,r3,16; lwz r4,-32764(r13)
+        let bytes = [
+            0x3c, 0x60, 0x80, 0x40, 0x38, 0x63, 0x00, 0x10, 0x80, 0x8d, 0x80, 0x04,
+        ];
+        let options = Options {
+            symbols: SymbolTable::parse(
+                "gameState = 0x80400000; // type:object size:0x20\n_SDA_BASE_ = 0x80408000;\n",
+            ),
+            ..Options::default()
+        };
+        let section = &Image::raw(&bytes, 0x8000_3100).sections[0];
+        assert_eq!(
+            super::listing(section, Endian::Big, &options).text,
+            "80003100: 3c 60 80 40  lis r3,0x8040\n\
+             80003104: 38 63 00 10  addi r3,r3,16  # gameState+0x10\n\
+             80003108: 80 8d 80 04  lwz r4,-32764(r13)  # gameState+0x4\n"
+        );
+        // lis r3,0x8040; ori r3,r3,0x10
+        let bytes = [0x3c, 0x60, 0x80, 0x40, 0x60, 0x63, 0x00, 0x10];
+        let section = &Image::raw(&bytes, 0x8000_3100).sections[0];
+        assert!(super::listing(section, Endian::Big, &options)
+            .text
+            .ends_with("ori r3,r3,0x10  # gameState+0x10\n"));
+        assert_eq!(
+            best_guess(&DecodeError::Unsupported(0x0000_0001)),
+            "not an instruction ppcheat knows"
+        );
+    }
+
+    #[test]
+    fn test_sweep() {
+        // li r3,1; a jump table; 1.0 and 0.5; a zero word; li r3,1
+        let words: [u32; 7] = [
+            0x3860_0001,
+            0x8000_3100,
+            0x8000_3118,
+            0x3F80_0000,
+            0x3F00_0000,
+            0x0000_0000,
+            0x3860_0001,
+        ];
+        let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_be_bytes()).collect();
+        let section = &Image::raw(&bytes, 0x8000_3100).sections[0];
+        assert_eq!(
+            sweep(section, Endian::Big),
+            [
+                Content::Code,
+                Content::Pointer,
+                Content::Pointer,
+                Content::Float,
+                Content::Float,
+                Content::Data,
+                Content::Code
+            ]
+        );
+        let options = Options {
+            find_data: true,
+            ..Options::default()
+        };
+        let listing = listing(section, Endian::Big, &options);
+        assert_eq!(
+            listing.text,
+            "80003100: 38 60 00 01  li r3,1\n\
+             .long 0x80003100  # 80003104: 80 00 31 00  pointer\n\
+             .long 0x80003118  # 80003108: 80 00 31 18  pointer\n\
+             .float 1.0  # 8000310c: 3f 80 00 00\n\
+             .float 0.5  # 80003110: 3f 00 00 00\n\
+             .byte 0x00, 0x00, 0x00, 0x00  # 80003114: data\n\
+             80003118: 38 60 00 01  li r3,1\n"
+        );
+        assert_eq!(listing.data, 5);
+        let program = crate::parser::parse_program(&listing.text).unwrap();
+        assert_eq!(crate::asm::assemble(&program, 0x8000_3100).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_endian() {
+        // mflr r0; stw r0,4(r1); stwu r1,-16(r1); li r3,0
+        let big = [
+            0x7c, 0x08, 0x02, 0xa6, 0x90, 0x01, 0x00, 0x04, 0x94, 0x21, 0xff, 0xf0, 0x38, 0x60,
+            0x00, 0x00,
+        ];
+        let little: Vec<_> = big
+            .chunks(4)
+            .flat_map(|w| w.iter().rev().copied())
+            .collect();
+        assert_eq!(Endian::detect(&big), Endian::Big);
+        assert_eq!(Endian::detect(&little), Endian::Little);
+        let ops: Vec<_> = disassemble(&little, 0, Endian::Little)
+            .filter_map(|(_, op)| op.ok())
+            .map(|op| op.to_string())
+            .collect();
+        assert_eq!(ops, ["stw r0,4(r1)", "stwu r1,-16(r1)", "li r3,0"]);
+    }
+}
@@ -0,0 +1,642 @@
+//! Machine encodings: each instruction's 32 bits as the named fields of the
+//! PowerPC manuals, from OPCD in bits 0..5 (IBM numbering) onward, and
+//! decoding words back into instructions.
+
+use core::fmt;
+
+use crate::{CrBit, CrField, CrLogic, Opcode, Register, Width};
+
+/// One field of an encoded instruction.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Field {
+    /// The manuals' name, e.g. `OPCD`, `RS` or `SIMM`
+    pub name: &'static str,
+    /// In bits; the widths of an instruction's fields add up to 32
+    pub width: u8,
+    pub value: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EncodeError {
+    /// A field's value doesn't fit in its width, e.g. r40 in a 5-bit RA.
+    OutOfRange {
+        field: &'static str,
+        value: u32,
+        width: u8,
+    },
+    /// A pseudo-op's operands don't amount to a rotate, e.g. `extlwi` of 0
+    /// bits; `why` is the rule they break.
+    BadOperands(Opcode, &'static str),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfRange {
+                field,
+                value,
+                width,
+            } => write!(f, "{}={} doesn't fit in {} bits", field, value, width),
+            Self::BadOperands(op, why) => write!(f, "`{}` needs {}", op, why),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    /// A real instruction ppcheat doesn't explain, e.g. `mflr`.
+    Unsupported(u32),
+    /// Reserved bits are set, a field has a value the 32-bit architecture
+    /// forbids, or the fields break [`Opcode::check`]'s rules, like
+    /// `lwzu r5,8(r0)`; `why` says which.
+    Invalid(u32, &'static str),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Unsupported(word) => write!(
+                f,
+                "{:08X} (primary opcode {}) isn't an instruction ppcheat explains",
+                word,
+                word >> 26
+            ),
+            Self::Invalid(word, why) => write!(f, "{:08X} is invalid: {}", word, why),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+const fn field(name: &'static str, width: u8, value: u32) -> Field {
+    Field { name, width, value }
+}
+
+fn reg(name: &'static str, r: Register) -> Field {
+    field(name, 5, r.0.into())
+}
+
+/// OPCD, two registers and a 16-bit immediate: addi, addis, loads and stores.
+fn d_form(opcd: u32, rd: Field, ra: Field, imm: (&'static str, i16)) -> Vec<Field> {
+    vec![
+        field("OPCD", 6, opcd),
+        rd,
+        ra,
+        field(imm.0, 16, imm.1 as u16 as u32),
+    ]
+}
+
+impl Opcode {
+    /// The operand rules of rotates: SH, MB and ME are bit numbers, and
+    /// the manuals' simplified mnemonics have rules of their own, which
+    /// [`canonicalize`](Self::canonicalize) relies on to keep the meaning.
+    /// And those of the update forms of loads and stores, which the manuals
+    /// call invalid with rA = 0, or for a load, rA = rD.
+    /// The parser checks every instruction it reads; anything built by hand
+    /// should be checked before it's explained.
+    pub fn check(&self) -> Result<(), EncodeError> {
+        let why = match *self {
+            Self::Rlwinm { sh, mb, me, .. } | Self::Rlwimi { sh, mb, me, .. } => {
+                match sh < 32 && mb < 32 && me < 32 {
+                    true => return Ok(()),
+                    false => "sh, mb and me < 32",
+                }
+            }
+            Self::Rlwnm { mb, me, .. } => match mb < 32 && me < 32 {
+                true => return Ok(()),
+                false => "mb and me < 32",
+            },
+            Self::Extlwi { n, b, .. }
+            | Self::Extrwi { n, b, .. }
+            | Self::Inslwi { n, b, .. }
+            | Self::Insrwi { n, b, .. } => match n > 0 && u16::from(b) + u16::from(n) <= 32 {
+                true => return Ok(()),
+                false => "n > 0 and b + n <= 32",
+            },
+            Self::Rotlwi { n, .. }
+            | Self::Rotrwi { n, .. }
+            | Self::Slwi { n, .. }
+            | Self::Srwi { n, .. }
+            | Self::Clrlwi { n, .. }
+            | Self::Clrrwi { n, .. } => match n < 32 {
+                true => return Ok(()),
+                false => "n < 32",
+            },
+            Self::Clrlslwi { b, n, .. } => match n <= b && b < 32 {
+                true => return Ok(()),
+                false => "n <= b < 32",
+            },
+            Self::Load {
+                update: true,
+                rd,
+                ra,
+                ..
+            }
+            | Self::Lha {
+                update: true,
+                rd,
+                ra,
+                ..
+            } => match ra.0 != 0 && ra != rd {
+                true => return Ok(()),
+                false => "rA != 0 and rA != rD, as an update load",
+            },
+            Self::Store {
+                update: true, ra, ..
+            } => match ra.0 != 0 {
+                true => return Ok(()),
+                false => "rA != 0, as an update store",
+            },
+            _ => return Ok(()),
+        };
+        Err(EncodeError::BadOperands(*self, why))
+    }
+
+    /// The fields of the instruction's encoding, of its real form for a
+    /// pseudo-op.
+    pub fn fields(&self) -> Result<Vec<Field>, EncodeError> {
+        self.check()?;
+        let rotate = |opcd, rs, ra, sh: Field, mb: u8, me: u8, rc: bool| {
+            vec![
+                field("OPCD", 6, opcd),
+                reg("RS", rs),
+                reg("RA", ra),
+                sh,
+                field("MB", 5, mb.into()),
+                field("ME", 5, me.into()),
+                field("Rc", 1, rc.into()),
+            ]
+        };
+        let fields = match self.canonicalize() {
+            Self::Rlwinm {
+                ra,
+                rs,
+                sh,
+                mb,
+                me,
+                rc,
+            } => rotate(21, rs, ra, field("SH", 5, sh.into()), mb, me, rc),
+            Self::Rlwimi {
+                ra,
+                rs,
+                sh,
+                mb,
+                me,
+                rc,
+            } => rotate(20, rs, ra, field("SH", 5, sh.into()), mb, me, rc),
+            Self::Rlwnm {
+                ra,
+                rs,
+                rb,
+                mb,
+                me,
+                rc,
+            } => rotate(23, rs, ra, reg("RB", rb), mb, me, rc),
+            Self::Addi { rd, ra, simm } => d_form(14, reg("RD", rd), reg("RA", ra), ("SIMM", simm)),
+            Self::Addis { rd, ra, simm } => {
+                d_form(15, reg("RD", rd), reg("RA", ra), ("SIMM", simm))
+            }
+            Self::Ori { ra, rs, uimm } => {
+                d_form(24, reg("RS", rs), reg("RA", ra), ("UIMM", uimm as i16))
+            }
+            Self::Oris { ra, rs, uimm } => {
+                d_form(25, reg("RS", rs), reg("RA", ra), ("UIMM", uimm as i16))
+            }
+            Self::Load {
+                width,
+                update,
+                rd,
+                d,
+                ra,
+            } => {
+                let opcd = match width {
+                    Width::Byte => 34,
+                    Width::Half => 40,
+                    Width::Word => 32,
+                };
+                d_form(
+                    opcd + u32::from(update),
+                    reg("RD", rd),
+                    reg("RA", ra),
+                    ("d", d),
+                )
+            }
+            Self::Lha { update, rd, d, ra } => d_form(
+                42 + u32::from(update),
+                reg("RD", rd),
+                reg("RA", ra),
+                ("d", d),
+            ),
+            Self::Store {
+                width,
+                update,
+                rs,
+                d,
+                ra,
+            } => {
+                let opcd = match width {
+                    Width::Byte => 38,
+                    Width::Half => 44,
+                    Width::Word => 36,
+                };
+                d_form(
+                    opcd + u32::from(update),
+                    reg("RS", rs),
+                    reg("RA", ra),
+                    ("d", d),
+                )
+            }
+            Self::Cmp {
+                logical,
+                crf,
+                ra,
+                rb,
+            } => vec![
+                field("OPCD", 6, 31),
+                field("crfD", 3, crf.0.into()),
+                field("0", 1, 0),
+                field("L", 1, 0),
+                reg("RA", ra),
+                reg("RB", rb),
+                field("XO", 10, if logical { 32 } else { 0 }),
+                field("0", 1, 0),
+            ],
+            Self::Cmpi {
+                logical,
+                crf,
+                ra,
+                imm,
+            } => vec![
+                field("OPCD", 6, if logical { 10 } else { 11 }),
+                field("crfD", 3, crf.0.into()),
+                field("0", 1, 0),
+                field("L", 1, 0),
+                reg("RA", ra),
+                field(if logical { "UIMM" } else { "SIMM" }, 16, imm as u16 as u32),
+            ],
+            Self::CrOp { op, bt, ba, bb } => {
+                let xo = match op {
+                    CrLogic::And => 257,
+                    CrLogic::Or => 449,
+                    CrLogic::Xor => 193,
+                    CrLogic::Nand => 225,
+                    CrLogic::Nor => 33,
+                    CrLogic::Eqv => 289,
+                    CrLogic::Andc => 129,
+                    CrLogic::Orc => 417,
+                };
+                vec![
+                    field("OPCD", 6, 19),
+                    field("crbD", 5, bt.0.into()),
+                    field("crbA", 5, ba.0.into()),
+                    field("crbB", 5, bb.0.into()),
+                    field("XO", 10, xo),
+                    field("0", 1, 0),
+                ]
+            }
+            Self::B { disp, link } => {
+                if disp % 4 != 0 || !(-(1 << 25)..1 << 25).contains(&disp) {
+                    let why = "a multiple of 4 within ±32MB";
+                    return Err(EncodeError::BadOperands(*self, why));
+                }
+                vec![
+                    field("OPCD", 6, 18),
+                    field("LI", 24, (disp as u32 >> 2) & 0xFF_FFFF),
+                    field("AA", 1, 0),
+                    field("LK", 1, link.into()),
+                ]
+            }
+            Self::Bc { bo, bi, disp } => {
+                if disp % 4 != 0 {
+                    let why = "a multiple of 4 within ±32KB";
+                    return Err(EncodeError::BadOperands(*self, why));
+                }
+                vec![
+                    field("OPCD", 6, 16),
+                    field("BO", 5, bo.into()),
+                    field("BI", 5, bi.0.into()),
+                    field("BD", 14, (disp as u16 as u32) >> 2),
+                    field("AA", 1, 0),
+                    field("LK", 1, 0),
+                ]
+            }
+            // canonicalize() leaves only real instructions
+            op => unreachable!("{} is a pseudo-op", op),
+        };
+        match fields.iter().find(|f| u64::from(f.value) >> f.width != 0) {
+            Some(f) => Err(EncodeError::OutOfRange {
+                field: f.name,
+                value: f.value,
+                width: f.width,
+            }),
+            None => Ok(fields),
+        }
+    }
+
+    /// The instruction a machine word encodes, as the clearest pseudo-op
+    /// for it (`srwi` rather than `rlwinm`, `li` rather than `addi` from 0).
+    pub fn decode(word: u32) -> Result<Self, DecodeError> {
+        // IBM-numbered bits first..=last
+        let bits = |first: u32, last: u32| (word >> (31 - last)) & ((1 << (last - first + 1)) - 1);
+        let reg = |first| Register(bits(first, first + 4) as u8);
+        let (rd, ra, rb) = (reg(6), reg(11), reg(16));
+        let (sh, mb, me) = (bits(16, 20) as u8, bits(21, 25) as u8, bits(26, 30) as u8);
+        let simm = bits(16, 31) as u16 as i16;
+        let rc = bits(31, 31) == 1;
+
+        let load = |width, update| Self::Load {
+            width,
+            update,
+            rd,
+            d: simm,
+            ra,
+        };
+        let store = |width, update| Self::Store {
+            width,
+            update,
+            rs: rd,
+            d: simm,
+            ra,
+        };
+        let compare = || match (bits(9, 9), bits(10, 10)) {
+            (0, 0) => Ok((CrField(bits(6, 8) as u8), ra)),
+            (_, 0) => Err(DecodeError::Invalid(word, "reserved bit 9 is set")),
+            _ => Err(DecodeError::Invalid(word, "L=1 compares 64-bit registers")),
+        };
+        let op = match word >> 26 {
+            20 => Self::Rlwimi {
+                ra,
+                rs: rd,
+                sh,
+                mb,
+                me,
+                rc,
+            },
+            21 => Self::Rlwinm {
+                ra,
+                rs: rd,
+                sh,
+                mb,
+                me,
+                rc,
+            },
+            23 => Self::Rlwnm {
+                ra,
+                rs: rd,
+                rb,
+                mb,
+                me,
+                rc,
+            },
+            14 if ra.0 == 0 => Self::Li { rd, simm },
+            14 => Self::Addi { rd, ra, simm },
+            15 if ra.0 == 0 => Self::Lis { rd, simm },
+            15 => Self::Addis { rd, ra, simm },
+            24 => Self::Ori {
+                ra,
+                rs: rd,
+                uimm: simm as u16,
+            },
+            25 => Self::Oris {
+                ra,
+                rs: rd,
+                uimm: simm as u16,
+            },
+            32 | 33 => load(Width::Word, word >> 26 == 33),
+            34 | 35 => load(Width::Byte, word >> 26 == 35),
+            40 | 41 => load(Width::Half, word >> 26 == 41),
+            42 | 43 => Self::Lha {
+                update: word >> 26 == 43,
+                rd,
+                d: simm,
+                ra,
+            },
+            36 | 37 => store(Width::Word, word >> 26 == 37),
+            38 | 39 => store(Width::Byte, word >> 26 == 39),
+            44 | 45 => store(Width::Half, word >> 26 == 45),
+            10 | 11 => {
+                let (crf, ra) = compare()?;
+                Self::Cmpi {
+                    logical: word >> 26 == 10,
+                    crf,
+                    ra,
+                    imm: simm,
+                }
+            }
+            31 if matches!(bits(21, 30), 0 | 32) => {
+                let (crf, ra) = compare()?;
+                if rc {
+                    return Err(DecodeError::Invalid(word, "reserved bit 31 is set"));
+                }
+                Self::Cmp {
+                    logical: bits(21, 30) == 32,
+                    crf,
+                    ra,
+                    rb,
+                }
+            }
+            19 => {
+                let op = match bits(21, 30) {
+                    257 => CrLogic::And,
+                    449 => CrLogic::Or,
+                    193 => CrLogic::Xor,
+                    225 => CrLogic::Nand,
+                    33 => CrLogic::Nor,
+                    289 => CrLogic::Eqv,
+                    129 => CrLogic::Andc,
+                    417 => CrLogic::Orc,
+                    _ => return Err(DecodeError::Unsupported(word)),
+                };
+                if rc {
+                    return Err(DecodeError::Invalid(word, "reserved bit 31 is set"));
+                }
+                let bit = |first| CrBit(bits(first, first + 4) as u8);
+                Self::CrOp {
+                    op,
+                    bt: bit(6),
+                    ba: bit(11),
+                    bb: bit(16),
+                }
+            }
+            // absolute and bcl branches aren't modelled
+            18 if bits(30, 30) == 1 => return Err(DecodeError::Unsupported(word)),
+            18 => Self::B {
+                disp: ((bits(6, 29) << 8) as i32) >> 6,
+                link: rc,
+            },
+            16 if bits(30, 31) != 0 => return Err(DecodeError::Unsupported(word)),
+            16 => Self::Bc {
+                bo: bits(6, 10) as u8,
+                bi: CrBit(bits(11, 15) as u8),
+                disp: (bits(16, 29) << 2) as u16 as i16,
+            },
+            _ => return Err(DecodeError::Unsupported(word)),
+        };
+        // fields that fit but break the rules, like `lwzu r5,8(r0)`
+        if let Err(EncodeError::BadOperands(_, why)) = op.check() {
+            return Err(DecodeError::Invalid(word, why));
+        }
+        Ok(op.simplify().map_or(op, |(simple, _)| simple))
+    }
+
+    /// The instruction's 32-bit machine word; `to_be_bytes()` gives it in
+    /// memory order.
+    pub fn encode(&self) -> Result<u32, EncodeError> {
+        let fields = self.fields()?;
+        Ok(fields
+            .iter()
+            .fold(0, |word, f| (word << f.width) | f.value as u64) as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_opcode;
+
+    use super::{DecodeError, EncodeError};
+    use crate::{Opcode, Register, Width};
+
+    #[test]
+    fn test_encode() {
+        let encode = |asm| parse_opcode(asm).expect("parse failed").1.encode().unwrap();
+        assert_eq!(encode("rlwinm r0,r3,16,0,15"), 0x5460_801E);
+        assert_eq!(encode("extrwi r3,r4,8,16"), 0x5483_C63E);
+        assert_eq!(encode("addi r1,r1,-16"), 0x3821_FFF0);
+        assert_eq!(encode("stwu r1,-32(r1)"), 0x9421_FFE0);
+        assert_eq!(encode("cmplwi r0,5"), 0x2800_0005);
+        assert_eq!(encode("ori r3,r3,0x10"), 0x6063_0010);
+        assert_eq!(encode("oris r4,r3,0x8040"), 0x6464_8040);
+        assert_eq!(encode("cmpw cr7,r3,r4"), 0x7F83_2000);
+        assert_eq!(encode("cror eq,gt,eq"), 0x4C41_1382);
+        assert_eq!(encode("srwi r3,r4,0"), 0x5483_003E);
+        assert_eq!(encode("rlwinm. r3,r3,0,24,31"), 0x5463_063F);
+        assert_eq!(encode("clrlwi. r3,r3,24"), 0x5463_063F);
+
+        let (ra, rs) = (Register(3), Register(4));
+        let extlwi = Opcode::Extlwi { ra, rs, n: 0, b: 4 };
+        assert_eq!(
+            extlwi.encode().unwrap_err().to_string(),
+            "`extlwi r3,r4,0,4` needs n > 0 and b + n <= 32"
+        );
+        let rotate = Opcode::Rlwinm {
+            ra,
+            rs,
+            sh: 40,
+            mb: 0,
+            me: 31,
+            rc: false,
+        };
+        assert_eq!(
+            rotate.check().unwrap_err().to_string(),
+            "`rlwinm r3,r4,40,0,31` needs sh, mb and me < 32"
+        );
+        for asm in &[
+            "lwzu r5,8(r0)",
+            "lwzu r5,8(r5)",
+            "lhau r3,2(r3)",
+            "stwu r1,-32(r0)",
+        ] {
+            assert!(parse_opcode(asm).is_err(), "{} parsed", asm);
+        }
+        let update = Opcode::Load {
+            width: Width::Word,
+            update: true,
+            rd: Register(5),
+            ra: Register(0),
+            d: 8,
+        };
+        assert_eq!(
+            update.encode().unwrap_err().to_string(),
+            "`lwzu r5,8(r0)` needs rA != 0 and rA != rD, as an update load"
+        );
+        // built by hand and never checked, a pseudo-op breaking the rules
+        // still canonicalizes, to the rotate its fields wrap to
+        let slwi = Opcode::Slwi { ra, rs, n: 40 };
+        assert!(slwi.check().is_err());
+        assert_eq!(
+            slwi.canonicalize(),
+            Opcode::Rlwinm {
+                ra,
+                rs,
+                sh: 8,
+                mb: 0,
+                me: 23,
+                rc: false
+            }
+        );
+        let wide = Opcode::Rlwinm {
+            ra: Register(3),
+            rs: Register(40),
+            sh: 0,
+            mb: 0,
+            me: 31,
+            rc: false,
+        };
+        assert_eq!(
+            wide.encode(),
+            Err(EncodeError::OutOfRange {
+                field: "RS",
+                value: 40,
+                width: 5
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        let decode = |word| Opcode::decode(word).unwrap().to_string();
+        assert_eq!(decode(0x5400_843E), "srwi r0,r0,16");
+        assert_eq!(decode(0x5460_801E), "slwi r0,r3,16");
+        assert_eq!(decode(0x5483_4136), "rlwinm r3,r4,8,4,27");
+        assert_eq!(decode(0x3860_FFFF), "li r3,-1");
+        assert_eq!(decode(0x6000_0000), "ori r0,r0,0x0");
+        assert_eq!(decode(0x9421_FFE0), "stwu r1,-32(r1)");
+        assert_eq!(decode(0x2800_0005), "cmplwi cr0,r0,5");
+        assert_eq!(decode(0x4C41_1382), "cror eq,gt,eq");
+        assert_eq!(decode(0x4800_0010), "b .+16");
+        assert_eq!(decode(0x4BFF_FFF1), "bl .-16");
+        assert_eq!(decode(0x419E_FFF8), "beq cr7,.-8");
+        assert_eq!(decode(0x4200_FFFC), "bdnz .-4");
+        for asm in &[
+            "inslwi r3,r4,8,8",
+            "lhau r3,2(r4)",
+            "cmpw cr7,r3,r4",
+            "crandc 4,5,6",
+            "bne cr1,.+32",
+            "bc 20,0,.-4",
+            "rlwimi. r3,r4,8,16,23",
+            "rlwnm. r3,r4,r5,0,31",
+        ] {
+            let op = parse_opcode(asm).expect("parse failed").1;
+            let word = op.encode().unwrap();
+            assert_eq!(Opcode::decode(word).unwrap().encode().unwrap(), word);
+        }
+        assert_eq!(
+            Opcode::decode(0x7C08_02A6),
+            Err(DecodeError::Unsupported(0x7C08_02A6))
+        );
+        // record forms aren't simplified
+        assert_eq!(decode(0x5463_063F), "rlwinm. r3,r3,0,24,31");
+        assert_eq!(
+            Opcode::decode(0x7C20_2000).unwrap_err().to_string(),
+            "7C202000 is invalid: L=1 compares 64-bit registers"
+        );
+        // update forms with rA = 0, or a load into its own base
+        assert_eq!(
+            Opcode::decode(0x84A0_0008),
+            Err(DecodeError::Invalid(
+                0x84A0_0008,
+                "rA != 0 and rA != rD, as an update load"
+            ))
+        );
+        for &word in &[0x84A5_0008, 0xAC63_0002, 0x9420_FFE0] {
+            assert!(
+                matches!(Opcode::decode(word), Err(DecodeError::Invalid(..))),
+                "{:08X} decoded",
+                word
+            );
+        }
+    }
+}
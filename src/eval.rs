@@ -0,0 +1,393 @@
+use core::cmp::Ordering;
+use core::fmt;
+use std::collections::BTreeMap;
+
+use crate::{CrBit, CrField, Opcode, Register, Width};
+
+/// Sparse big-endian memory. Bytes that were never written read as 0.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Memory {
+    bytes: BTreeMap<u32, u8>,
+}
+
+impl Memory {
+    pub fn read(&self, addr: u32, width: Width) -> u32 {
+        (0..width.bytes()).fold(0, |acc, i| {
+            let byte = self.bytes.get(&addr.wrapping_add(i)).copied().unwrap_or(0);
+            (acc << 8) | byte as u32
+        })
+    }
+
+    /// Every byte that has been written, in address order.
+    pub fn bytes(&self) -> impl Iterator<Item = (u32, u8)> + '_ {
+        self.bytes.iter().map(|(&addr, &byte)| (addr, byte))
+    }
+
+    pub fn write(&mut self, addr: u32, width: Width, value: u32) {
+        let n = width.bytes();
+        for i in 0..n {
+            let byte = (value >> (8 * (n - 1 - i))) as u8;
+            self.bytes.insert(addr.wrapping_add(i), byte);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Cpu {
+    pub gpr: [u32; 32],
+    /// Condition register, cr0 in the top nibble
+    pub cr: u32,
+    pub mem: Memory,
+}
+
+impl Cpu {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reg(&self, r: Register) -> u32 {
+        self.gpr[r.0 as usize]
+    }
+
+    pub fn set_reg(&mut self, r: Register, value: u32) {
+        self.gpr[r.0 as usize] = value;
+    }
+
+    pub fn cr_bit(&self, bit: CrBit) -> bool {
+        self.cr & (0x8000_0000 >> bit.0) != 0
+    }
+
+    pub fn set_cr_bit(&mut self, bit: CrBit, value: bool) {
+        let m = 0x8000_0000 >> bit.0;
+        self.cr = if value { self.cr | m } else { self.cr & !m };
+    }
+
+    /// Sets `crf` to LT, GT or EQ. XER isn't modelled, so SO is always clear.
+    pub fn set_cr_field(&mut self, crf: CrField, ordering: Ordering) {
+        let bits = match ordering {
+            Ordering::Less => 0b1000,
+            Ordering::Greater => 0b0100,
+            Ordering::Equal => 0b0010,
+        };
+        let shift = 28 - 4 * u32::from(crf.0);
+        self.cr = (self.cr & !(0xF << shift)) | (bits << shift);
+    }
+
+    /// The `(rA|0)` operand of addi, addis and the D-form loads/stores:
+    /// r0 in the rA position reads as the literal value 0.
+    pub fn reg_or_zero(&self, r: Register) -> u32 {
+        if r.0 == 0 {
+            0
+        } else {
+            self.reg(r)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    /// The instruction is an invalid form (e.g. an update load with rA=0).
+    InvalidForm(Opcode, &'static str),
+    /// A branch: evaluation is straight-line, with no pc to move.
+    Branch(Opcode),
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidForm(op, why) => write!(f, "invalid instruction form {:?}: {}", op, why),
+            Self::Branch(op) => write!(f, "can't follow the branch `{}`", op),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// MASK(mb..me) in IBM bit numbering, wrapping around when mb > me.
+pub(crate) fn mask(mb: u8, me: u8) -> u32 {
+    let begin = u32::MAX >> (mb & 31);
+    let end = u32::MAX << (31 - (me & 31));
+    if mb <= me {
+        begin & end
+    } else {
+        begin | end
+    }
+}
+
+/// `(mb, me)` with `MASK(mb..me) == m`, if `m` is a single (possibly
+/// wrapping) run of ones.
+pub(crate) fn mask_bounds(m: u32) -> Option<(u8, u8)> {
+    (0..32u8)
+        .flat_map(|mb| (0..32u8).map(move |me| (mb, me)))
+        .find(|&(mb, me)| mask(mb, me) == m)
+}
+
+fn update_ea(cpu: &Cpu, op: &Opcode, ra: Register, d: i16, update: bool) -> Result<u32, EvalError> {
+    let base = if update {
+        if ra.0 == 0 {
+            return Err(EvalError::InvalidForm(*op, "update with rA=0"));
+        }
+        cpu.reg(ra)
+    } else {
+        cpu.reg_or_zero(ra)
+    };
+    Ok(base.wrapping_add(d as i32 as u32))
+}
+
+fn nonzero(r: Register) -> Option<Register> {
+    if r.0 == 0 {
+        None
+    } else {
+        Some(r)
+    }
+}
+
+impl Opcode {
+    /// GPRs whose values the instruction reads. An `(rA|0)` operand naming r0
+    /// is not a read.
+    pub fn reads(&self) -> Vec<Register> {
+        match self.canonicalize() {
+            Self::Rlwinm { rs, .. } => vec![rs],
+            Self::Rlwimi { ra, rs, .. } => vec![rs, ra],
+            Self::Rlwnm { rs, rb, .. } => vec![rs, rb],
+            Self::Addi { ra, .. } | Self::Addis { ra, .. } => nonzero(ra).into_iter().collect(),
+            Self::Ori { rs, .. } | Self::Oris { rs, .. } => vec![rs],
+            Self::Load { ra, .. } | Self::Lha { ra, .. } => nonzero(ra).into_iter().collect(),
+            Self::Store { rs, ra, .. } => {
+                let mut regs = vec![rs];
+                regs.extend(nonzero(ra).filter(|&ra| ra != rs));
+                regs
+            }
+            Self::Cmp { ra, rb, .. } => vec![ra, rb],
+            Self::Cmpi { ra, .. } => vec![ra],
+            Self::CrOp { .. } | Self::B { .. } | Self::Bc { .. } => vec![],
+            op => unreachable!("{:?} did not canonicalize", op),
+        }
+    }
+
+    /// GPRs the instruction writes.
+    pub fn writes(&self) -> Vec<Register> {
+        match self.canonicalize() {
+            Self::Rlwinm { ra, .. } | Self::Rlwimi { ra, .. } | Self::Rlwnm { ra, .. } => vec![ra],
+            Self::Addi { rd, .. } | Self::Addis { rd, .. } => vec![rd],
+            Self::Ori { ra, .. } | Self::Oris { ra, .. } => vec![ra],
+            Self::Load { update, rd, ra, .. } | Self::Lha { update, rd, ra, .. } => {
+                if update {
+                    vec![rd, ra]
+                } else {
+                    vec![rd]
+                }
+            }
+            Self::Store { update, ra, .. } => {
+                if update {
+                    vec![ra]
+                } else {
+                    vec![]
+                }
+            }
+            Self::Cmp { .. } | Self::Cmpi { .. } | Self::CrOp { .. } => vec![],
+            Self::B { .. } | Self::Bc { .. } => vec![],
+            op => unreachable!("{:?} did not canonicalize", op),
+        }
+    }
+
+    pub fn eval(&self, cpu: &mut Cpu) -> Result<(), EvalError> {
+        match self.canonicalize() {
+            Self::Rlwinm {
+                ra, rs, sh, mb, me, ..
+            } => {
+                let rot = cpu.reg(rs).rotate_left(sh as u32 & 31);
+                cpu.set_reg(ra, rot & mask(mb, me));
+            }
+            Self::Rlwimi {
+                ra, rs, sh, mb, me, ..
+            } => {
+                let m = mask(mb, me);
+                let rot = cpu.reg(rs).rotate_left(sh as u32 & 31);
+                cpu.set_reg(ra, (rot & m) | (cpu.reg(ra) & !m));
+            }
+            Self::Rlwnm {
+                ra, rs, rb, mb, me, ..
+            } => {
+                let rot = cpu.reg(rs).rotate_left(cpu.reg(rb) & 31);
+                cpu.set_reg(ra, rot & mask(mb, me));
+            }
+            Self::Addi { rd, ra, simm } => {
+                let value = cpu.reg_or_zero(ra).wrapping_add(simm as i32 as u32);
+                cpu.set_reg(rd, value);
+            }
+            Self::Addis { rd, ra, simm } => {
+                let value = cpu.reg_or_zero(ra).wrapping_add((simm as u16 as u32) << 16);
+                cpu.set_reg(rd, value);
+            }
+            Self::Ori { ra, rs, uimm } => cpu.set_reg(ra, cpu.reg(rs) | u32::from(uimm)),
+            Self::Oris { ra, rs, uimm } => cpu.set_reg(ra, cpu.reg(rs) | u32::from(uimm) << 16),
+            Self::Load {
+                width,
+                update,
+                rd,
+                d,
+                ra,
+            } => {
+                if update && ra == rd {
+                    return Err(EvalError::InvalidForm(*self, "update with rA=rD"));
+                }
+                let ea = update_ea(cpu, self, ra, d, update)?;
+                let value = cpu.mem.read(ea, width);
+                cpu.set_reg(rd, value);
+                if update {
+                    cpu.set_reg(ra, ea);
+                }
+            }
+            Self::Lha { update, rd, d, ra } => {
+                if update && ra == rd {
+                    return Err(EvalError::InvalidForm(*self, "update with rA=rD"));
+                }
+                let ea = update_ea(cpu, self, ra, d, update)?;
+                let value = cpu.mem.read(ea, Width::Half) as u16 as i16 as i32 as u32;
+                cpu.set_reg(rd, value);
+                if update {
+                    cpu.set_reg(ra, ea);
+                }
+            }
+            Self::Store {
+                width,
+                update,
+                rs,
+                d,
+                ra,
+            } => {
+                let ea = update_ea(cpu, self, ra, d, update)?;
+                let value = cpu.reg(rs);
+                cpu.mem.write(ea, width, value);
+                if update {
+                    cpu.set_reg(ra, ea);
+                }
+            }
+            Self::Cmp {
+                logical,
+                crf,
+                ra,
+                rb,
+            } => {
+                let (a, b) = (cpu.reg(ra), cpu.reg(rb));
+                let ordering = if logical {
+                    a.cmp(&b)
+                } else {
+                    (a as i32).cmp(&(b as i32))
+                };
+                cpu.set_cr_field(crf, ordering);
+            }
+            Self::Cmpi {
+                logical,
+                crf,
+                ra,
+                imm,
+            } => {
+                let a = cpu.reg(ra);
+                let ordering = if logical {
+                    a.cmp(&u32::from(imm as u16))
+                } else {
+                    (a as i32).cmp(&i32::from(imm))
+                };
+                cpu.set_cr_field(crf, ordering);
+            }
+            Self::CrOp { op, bt, ba, bb } => {
+                let value = op.apply(cpu.cr_bit(ba), cpu.cr_bit(bb));
+                cpu.set_cr_bit(bt, value);
+            }
+            Self::B { .. } | Self::Bc { .. } => return Err(EvalError::Branch(*self)),
+            op => unreachable!("{:?} did not canonicalize", op),
+        }
+        if let Some(ra) = self.record() {
+            cpu.set_cr_field(CrField(0), (cpu.reg(ra) as i32).cmp(&0));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_opcode;
+
+    fn run(cpu: &mut Cpu, asm: &str) -> Result<(), EvalError> {
+        let (_, op) = parse_opcode(asm).expect("parse failed");
+        op.eval(cpu)
+    }
+
+    #[test]
+    fn test_mask() {
+        assert_eq!(mask(0, 15), 0xFFFF_0000);
+        assert_eq!(mask(16, 31), 0x0000_FFFF);
+        assert_eq!(mask(24, 7), 0xFF00_00FF);
+    }
+
+    #[test]
+    fn test_ra_zero_is_literal_zero() {
+        let mut cpu = Cpu::new();
+        cpu.gpr[0] = 0x1000;
+        cpu.gpr[4] = 0x1000;
+
+        run(&mut cpu, "addi r3,r0,16").unwrap();
+        assert_eq!(cpu.gpr[3], 16);
+
+        run(&mut cpu, "addi r3,r4,16").unwrap();
+        assert_eq!(cpu.gpr[3], 0x1010);
+
+        // ori has no (rA|0): r0 is r0
+        run(&mut cpu, "oris r3,r0,0x8040").unwrap();
+        run(&mut cpu, "ori r3,r3,0x10").unwrap();
+        assert_eq!(cpu.gpr[3], 0x8040_1010);
+
+        cpu.mem.write(0x20, Width::Word, 0xDEAD_BEEF);
+        run(&mut cpu, "lwz r5,0x20(r0)").unwrap();
+        assert_eq!(cpu.gpr[5], 0xDEAD_BEEF);
+
+        // the parser refuses it; built by hand, so does eval
+        assert!(parse_opcode("lwzu r5,0x20(r0)").is_err());
+        let update = Opcode::Load {
+            width: Width::Word,
+            update: true,
+            rd: Register(5),
+            d: 0x20,
+            ra: Register(0),
+        };
+        assert!(update.eval(&mut cpu).is_err());
+    }
+
+    #[test]
+    fn test_condition_register() {
+        let mut cpu = Cpu::new();
+        cpu.gpr[3] = 0xFFFF_FFFF;
+
+        run(&mut cpu, "cmpwi r3,0").unwrap();
+        run(&mut cpu, "cmplwi cr7,r3,0").unwrap();
+        assert_eq!(cpu.cr, 0x8000_0004);
+
+        run(&mut cpu, "cror 4*cr1+eq,lt,4*cr7+gt").unwrap();
+        assert!(cpu.cr_bit(CrBit(6)));
+    }
+
+    #[test]
+    fn test_rotates() {
+        let mut cpu = Cpu::new();
+        cpu.gpr[7] = 0x1234_5678;
+        run(&mut cpu, "rlwinm r0,r7,16,0,15").unwrap();
+        assert_eq!(cpu.gpr[0], 0x5678_0000);
+
+        cpu.gpr[3] = 0xFFFF_FFFF;
+        run(&mut cpu, "inslwi r3,r7,8,8").unwrap();
+        assert_eq!(cpu.gpr[3], 0xFF12_FFFF);
+
+        // record forms compare the result with 0 into cr0
+        run(&mut cpu, "rlwinm. r4,r7,0,0,0").unwrap();
+        assert_eq!((cpu.gpr[4], cpu.cr), (0, 0x2000_0000));
+        run(&mut cpu, "rlwimi. r3,r7,0,0,0").unwrap();
+        assert_eq!((cpu.gpr[3], cpu.cr), (0x7F12_FFFF, 0x4000_0000));
+        cpu.gpr[5] = 4;
+        run(&mut cpu, "rlwnm. r6,r3,r5,0,31").unwrap();
+        assert_eq!((cpu.gpr[6], cpu.cr), (0xF12F_FFF7, 0x8000_0000));
+    }
+}
@@ -0,0 +1,587 @@
+//! Explanations as data: what [`Opcode::explain`] builds and
+//! [`Opcode::highlevel`] prints, for front ends (a web UI, editor hovers)
+//! that want to render the pieces themselves.
+
+use core::fmt;
+
+use crate::locale::message;
+use crate::{here, mask_name, wrapped, BitOrder, CrBit, CrField, CrLogic, Opcode, Register};
+
+/// An operand as written in the assembly.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Operand {
+    Reg(Register),
+    CrField(CrField),
+    CrBit(CrBit),
+    Imm(i32),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Shl,
+    And,
+    Or,
+    Xor,
+    Eq,
+    Ne,
+    /// Logical `&&`
+    LogicalAnd,
+}
+
+impl BinOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Shl => "<<",
+            Self::And => "&",
+            Self::Or => "|",
+            Self::Xor => "^",
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::LogicalAnd => "&&",
+        }
+    }
+}
+
+/// C-like expression. Grouping is explicit, so it prints exactly as built.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Reg(Register),
+    CrField(CrField),
+    /// A CR bit, printed by name: `cr1.eq`
+    CrBit(CrBit),
+    /// A special register: `pc`, `lr` or `ctr`
+    Name(&'static str),
+    /// An address relative to the instruction: `.+8`
+    Here(i32),
+    /// A symbol's address
+    Label(String),
+    Int(i32),
+    /// An unsigned constant, printed in hex
+    Hex(u32),
+    /// The mask of `mb..me`, printed as `MASK(mb..me)=0x...`
+    Mask {
+        mb: u8,
+        me: u8,
+    },
+    Binary(Box<Expr>, BinOp, Box<Expr>),
+    /// `(e)`
+    Group(Box<Expr>),
+    /// Bitwise `~e`
+    Complement(Box<Expr>),
+    /// Logical `!e`
+    Not(Box<Expr>),
+    /// `f(args...)`, e.g. `rotl32` or `compare`
+    Call(&'static str, Vec<Expr>),
+    /// `(type)e`
+    Cast(&'static str, Box<Expr>),
+    /// `*(type *)(e)`
+    Deref(&'static str, Box<Expr>),
+    /// `c ? a : b`
+    Select(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn binary(a: Expr, op: BinOp, b: Expr) -> Self {
+        Self::Binary(Box::new(a), op, Box::new(b))
+    }
+
+    fn group(self) -> Self {
+        Self::Group(Box::new(self))
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reg(r) => write!(f, "{}", r),
+            Self::CrField(crf) => write!(f, "{}", crf),
+            Self::CrBit(bit) => write!(f, "{:#}", bit),
+            Self::Name(name) => f.write_str(name),
+            Self::Here(disp) => f.write_str(&here(*disp)),
+            Self::Label(label) => f.write_str(label),
+            Self::Int(v) => write!(f, "{}", v),
+            Self::Hex(v) => write!(f, "{:#x}", v),
+            Self::Mask { mb, me } => f.write_str(&mask_name(*mb, *me)),
+            Self::Binary(a, op, b) => write!(f, "{} {} {}", a, op.symbol(), b),
+            Self::Group(e) => write!(f, "({})", e),
+            Self::Complement(e) => write!(f, "~{}", e),
+            Self::Not(e) => write!(f, "!{}", e),
+            Self::Call(name, args) => {
+                let args: Vec<_> = args.iter().map(Expr::to_string).collect();
+                write!(f, "{}({})", name, args.join(", "))
+            }
+            Self::Cast(ty, e) => write!(f, "({}){}", ty, e),
+            Self::Deref(ty, e) => write!(f, "*({} *)({})", ty, e),
+            Self::Select(c, a, b) => write!(f, "{} ? {} : {}", c, a, b),
+        }
+    }
+}
+
+/// `target = value`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assign {
+    pub target: Expr,
+    pub value: Expr,
+}
+
+impl fmt::Display for Assign {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = {}", self.target, self.value)
+    }
+}
+
+/// What an instruction does: printed as its assignments separated by `; `,
+/// each note following in parentheses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explanation {
+    /// As written in the assembly, pseudo-op operands included
+    pub operands: Vec<Operand>,
+    /// The AND mask of a rotate, see [`Opcode::mask_value`]
+    pub mask: Option<u32>,
+    /// In order: a load with update assigns rD, then rA
+    pub effects: Vec<Assign>,
+    /// Caveats, and what a pseudo-op is for
+    pub notes: Vec<String>,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let effects: Vec<_> = self.effects.iter().map(Assign::to_string).collect();
+        f.write_str(&effects.join("; "))?;
+        for note in &self.notes {
+            write!(f, "  ({})", note)?;
+        }
+        Ok(())
+    }
+}
+
+fn ra_zero_note() -> String {
+    message("note-ra-zero", &[])
+}
+
+/// An `(rA|0) + d` effective address. `rA=0` is the literal value 0 here,
+/// not the contents of r0.
+fn effective_address(ra: Register, d: i16) -> Expr {
+    match (ra.0, d) {
+        (0, d) => Expr::Hex(d as i32 as u32),
+        (_, 0) => Expr::Reg(ra),
+        (_, d) if d < 0 => Expr::binary(Expr::Reg(ra), BinOp::Sub, Expr::Int(-i32::from(d))),
+        (_, d) => Expr::binary(Expr::Reg(ra), BinOp::Add, Expr::Int(d.into())),
+    }
+}
+
+fn invalid_update(ra: Register, rd: Register, is_load: bool) -> Option<&'static str> {
+    if ra.0 == 0 {
+        Some("note-update-ra-zero")
+    } else if is_load && ra == rd {
+        Some("note-update-ra-rd")
+    } else {
+        None
+    }
+}
+
+/// A D-form access `access`, and for the update forms rA = EA.
+fn d_form(
+    access: Assign,
+    update: bool,
+    rd: Register,
+    d: i16,
+    ra: Register,
+    is_load: bool,
+) -> (Vec<Assign>, Vec<String>) {
+    match (update, invalid_update(ra, rd, is_load)) {
+        (false, _) if ra.0 == 0 => (vec![access], vec![ra_zero_note()]),
+        (false, _) => (vec![access], Vec::new()),
+        (true, Some(note)) => (vec![access], vec![message(note, &[])]),
+        (true, None) => {
+            let ea = Assign {
+                target: Expr::Reg(ra),
+                value: effective_address(ra, d),
+            };
+            (vec![access, ea], Vec::new())
+        }
+    }
+}
+
+/// `rlwinm`-style notes: what a wrapped mask keeps.
+fn wrap_note(mb: u8, me: u8) -> Vec<String> {
+    match wrapped(mb, me) {
+        Some((top, bottom)) => vec![message(
+            "note-wrapped",
+            &[("top", &top), ("bottom", &bottom)],
+        )],
+        None => Vec::new(),
+    }
+}
+
+/// When a `bc` with these BO and BI fields branches, after decrementing
+/// ctr if it does; `None` if it always does.
+pub(crate) fn branch_condition(bo: u8, bi: CrBit) -> Option<Expr> {
+    let ctr = match (bo & 4, bo & 2) {
+        (0, 0) => Some(Expr::binary(Expr::Name("ctr"), BinOp::Ne, Expr::Int(0))),
+        (0, _) => Some(Expr::binary(Expr::Name("ctr"), BinOp::Eq, Expr::Int(0))),
+        _ => None,
+    };
+    let bit = match (bo & 16, bo & 8) {
+        (0, 0) => Some(Expr::Not(Box::new(Expr::CrBit(bi)))),
+        (0, _) => Some(Expr::CrBit(bi)),
+        _ => None,
+    };
+    match (ctr, bit) {
+        (Some(ctr), Some(bit)) => Some(Expr::binary(ctr, BinOp::LogicalAnd, bit)),
+        (ctr, bit) => ctr.or(bit),
+    }
+}
+
+impl Opcode {
+    /// What a branch does with its target written as `target`: `lr` for
+    /// `bl`, ctr for the decrementing forms, then `pc`.
+    pub(crate) fn branch_effects(&self, target: Expr) -> Vec<Assign> {
+        let pc = Expr::Name("pc");
+        match *self {
+            Self::B { link, .. } => {
+                let lr = Assign {
+                    target: Expr::Name("lr"),
+                    value: Expr::Here(4),
+                };
+                let jump = Assign {
+                    target: pc,
+                    value: target,
+                };
+                match link {
+                    true => vec![lr, jump],
+                    false => vec![jump],
+                }
+            }
+            Self::Bc { bo, bi, .. } => {
+                let mut effects = Vec::new();
+                if bo & 4 == 0 {
+                    effects.push(Assign {
+                        target: Expr::Name("ctr"),
+                        value: Expr::binary(Expr::Name("ctr"), BinOp::Sub, Expr::Int(1)),
+                    });
+                }
+                let value = match branch_condition(bo, bi) {
+                    Some(c) => Expr::Select(Box::new(c), Box::new(target), Box::new(Expr::Here(4))),
+                    None => target,
+                };
+                effects.push(Assign { target: pc, value });
+                effects
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// The operands as written, in order.
+    pub fn operands(&self) -> Vec<Operand> {
+        use Operand::{CrBit as B, CrField as F, Imm, Reg as R};
+        match *self {
+            Self::Rlwinm {
+                ra, rs, sh, mb, me, ..
+            }
+            | Self::Rlwimi {
+                ra, rs, sh, mb, me, ..
+            } => vec![R(ra), R(rs), Imm(sh.into()), Imm(mb.into()), Imm(me.into())],
+            Self::Rlwnm {
+                ra, rs, rb, mb, me, ..
+            } => {
+                vec![R(ra), R(rs), R(rb), Imm(mb.into()), Imm(me.into())]
+            }
+            Self::Addi { rd, ra, simm } | Self::Addis { rd, ra, simm } => {
+                vec![R(rd), R(ra), Imm(simm.into())]
+            }
+            Self::Ori { ra, rs, uimm } | Self::Oris { ra, rs, uimm } => {
+                vec![R(ra), R(rs), Imm(uimm.into())]
+            }
+            Self::Load { rd, d, ra, .. } | Self::Lha { rd, d, ra, .. } => {
+                vec![R(rd), Imm(d.into()), R(ra)]
+            }
+            Self::Store { rs, d, ra, .. } => vec![R(rs), Imm(d.into()), R(ra)],
+            Self::Cmp { crf, ra, rb, .. } => vec![F(crf), R(ra), R(rb)],
+            Self::Cmpi {
+                logical: false,
+                crf,
+                ra,
+                imm,
+            } => vec![F(crf), R(ra), Imm(imm.into())],
+            Self::Cmpi { crf, ra, imm, .. } => vec![F(crf), R(ra), Imm((imm as u16).into())],
+            Self::CrOp { bt, ba, bb, .. } => vec![B(bt), B(ba), B(bb)],
+            Self::B { disp, .. } => vec![Imm(disp)],
+            Self::Bc { bo, bi, disp } => match self.mnemonic() {
+                "bc" => vec![Imm(bo.into()), Imm(bi.0.into()), Imm(disp.into())],
+                "bdnz" | "bdz" => vec![Imm(disp.into())],
+                _ if bi.field().0 == 0 => vec![Imm(disp.into())],
+                _ => vec![F(bi.field()), Imm(disp.into())],
+            },
+            Self::Extlwi { ra, rs, n, b }
+            | Self::Extrwi { ra, rs, n, b }
+            | Self::Inslwi { ra, rs, n, b }
+            | Self::Insrwi { ra, rs, n, b } => vec![R(ra), R(rs), Imm(n.into()), Imm(b.into())],
+            Self::Clrlslwi { ra, rs, b, n } => vec![R(ra), R(rs), Imm(b.into()), Imm(n.into())],
+            Self::Rotlwi { ra, rs, n }
+            | Self::Rotrwi { ra, rs, n }
+            | Self::Slwi { ra, rs, n }
+            | Self::Srwi { ra, rs, n }
+            | Self::Clrlwi { ra, rs, n }
+            | Self::Clrrwi { ra, rs, n } => vec![R(ra), R(rs), Imm(n.into())],
+            Self::Rotlw { ra, rs, rb } => vec![R(ra), R(rs), R(rb)],
+            Self::Li { rd, simm } => vec![R(rd), Imm(simm.into())],
+            Self::Lis { rd, simm } => vec![R(rd), Imm((simm as u16).into())],
+        }
+    }
+
+    /// What [`Opcode::highlevel`] prints, before it's printed. An
+    /// instruction built with operands [`Opcode::check`] refuses is
+    /// explained as the rotate its fields wrap to, noting why it's wrong. A
+    /// record form goes on to compare its result into cr0.
+    pub fn explain(&self) -> Explanation {
+        let (mut effects, mut notes) = self.effects();
+        if let Some(ra) = self.record() {
+            effects.push(Assign {
+                target: Expr::CrField(CrField(0)),
+                value: Expr::Call("compare", vec![Expr::Reg(ra), Expr::Int(0)]),
+            });
+            notes.push(message("note-record", &[("ra", &ra)]));
+        }
+        notes.extend(self.check().err().map(|e| e.to_string()));
+        Explanation {
+            operands: self.operands(),
+            mask: self.mask_value(),
+            effects,
+            notes,
+        }
+    }
+
+    fn effects(&self) -> (Vec<Assign>, Vec<String>) {
+        let assign = |target, value| vec![Assign { target, value }];
+        let reg = Expr::Reg;
+        if let Some((rd, rs)) = self.copies() {
+            let note = match rd == rs {
+                true => message("note-nothing", &[]),
+                false => message("note-mr", &[("rd", &rd), ("rs", &rs)]),
+            };
+            return (assign(reg(rd), reg(rs)), vec![note]);
+        }
+        match *self {
+            Self::Rlwinm {
+                ra, rs, sh, mb, me, ..
+            } => {
+                let rotated = Expr::binary(reg(rs), BinOp::Shl, Expr::Int(sh.into()));
+                let value = Expr::binary(rotated.group(), BinOp::And, Expr::Mask { mb, me });
+                (assign(reg(ra), value), wrap_note(mb, me))
+            }
+            Self::Rlwimi {
+                ra, rs, sh, mb, me, ..
+            } => {
+                let mask = Expr::Mask { mb, me };
+                let kept = Expr::binary(
+                    reg(ra),
+                    BinOp::And,
+                    Expr::Complement(Box::new(mask.clone())),
+                );
+                let rotated = Expr::Call("rotl32", vec![reg(rs), Expr::Int(sh.into())]);
+                let inserted = Expr::binary(rotated, BinOp::And, mask);
+                let value = Expr::binary(kept.group(), BinOp::Or, inserted.group());
+                let notes = match self.simplify() {
+                    Some((Self::Inslwi { n, b, .. }, _)) | Some((Self::Insrwi { n, b, .. }, _)) => {
+                        let id = match n {
+                            8 | 11 | 18 => "note-insert-an",
+                            _ => "note-insert-a",
+                        };
+                        vec![message(
+                            id,
+                            &[
+                                ("n", &n),
+                                ("rs", &rs),
+                                ("ra", &ra),
+                                ("bit", &BitOrder::bit(b)),
+                            ],
+                        )]
+                    }
+                    _ => wrap_note(mb, me),
+                };
+                (assign(reg(ra), value), notes)
+            }
+            Self::Rlwnm {
+                ra, rs, rb, mb, me, ..
+            } => {
+                let rotated = Expr::binary(reg(rs), BinOp::Shl, reg(rb));
+                let value = Expr::binary(rotated.group(), BinOp::And, Expr::Mask { mb, me });
+                (assign(reg(ra), value), wrap_note(mb, me))
+            }
+            Self::Addi { rd, ra, simm } if ra.0 == 0 => (
+                assign(reg(rd), Expr::Int(simm.into())),
+                vec![ra_zero_note()],
+            ),
+            Self::Addi { rd, ra, simm } if simm < 0 => (
+                assign(
+                    reg(rd),
+                    Expr::binary(reg(ra), BinOp::Sub, Expr::Int(-i32::from(simm))),
+                ),
+                Vec::new(),
+            ),
+            Self::Addi { rd, ra, simm } => (
+                assign(
+                    reg(rd),
+                    Expr::binary(reg(ra), BinOp::Add, Expr::Int(simm.into())),
+                ),
+                Vec::new(),
+            ),
+            Self::Addis { rd, ra, simm } => {
+                let value = Expr::Hex((simm as u16 as u32) << 16);
+                match ra.0 {
+                    0 => (assign(reg(rd), value), vec![ra_zero_note()]),
+                    _ => (
+                        assign(reg(rd), Expr::binary(reg(ra), BinOp::Add, value)),
+                        Vec::new(),
+                    ),
+                }
+            }
+            Self::Ori { ra, rs, uimm } => (
+                assign(
+                    reg(ra),
+                    Expr::binary(reg(rs), BinOp::Or, Expr::Hex(uimm.into())),
+                ),
+                Vec::new(),
+            ),
+            Self::Oris { ra, rs, uimm } => (
+                assign(
+                    reg(ra),
+                    Expr::binary(reg(rs), BinOp::Or, Expr::Hex(u32::from(uimm) << 16)),
+                ),
+                Vec::new(),
+            ),
+            Self::Li { rd, simm } => (assign(reg(rd), Expr::Int(simm.into())), Vec::new()),
+            Self::Lis { rd, simm } => (
+                assign(reg(rd), Expr::Hex((simm as u16 as u32) << 16)),
+                Vec::new(),
+            ),
+            Self::Load {
+                width,
+                update,
+                rd,
+                d,
+                ra,
+            } => {
+                let load = Assign {
+                    target: reg(rd),
+                    value: Expr::Deref(width.c_type(), Box::new(effective_address(ra, d))),
+                };
+                d_form(load, update, rd, d, ra, true)
+            }
+            Self::Lha { update, rd, d, ra } => {
+                let half = Expr::Deref("s16", Box::new(effective_address(ra, d)));
+                let load = Assign {
+                    target: reg(rd),
+                    value: Expr::Cast("s32", Box::new(half)),
+                };
+                d_form(load, update, rd, d, ra, true)
+            }
+            Self::Store {
+                width,
+                update,
+                rs,
+                d,
+                ra,
+            } => {
+                let store = Assign {
+                    target: Expr::Deref(width.c_type(), Box::new(effective_address(ra, d))),
+                    value: reg(rs),
+                };
+                d_form(store, update, rs, d, ra, false)
+            }
+            Self::Cmp {
+                logical,
+                crf,
+                ra,
+                rb,
+            } => {
+                let operand = |r| match logical {
+                    true => Expr::Cast("u32", Box::new(reg(r))),
+                    false => reg(r),
+                };
+                let compare = Expr::Call("compare", vec![operand(ra), operand(rb)]);
+                (assign(Expr::CrField(crf), compare), Vec::new())
+            }
+            Self::Cmpi {
+                logical,
+                crf,
+                ra,
+                imm,
+            } => {
+                let args = match logical {
+                    false => vec![reg(ra), Expr::Int(imm.into())],
+                    true => vec![
+                        Expr::Cast("u32", Box::new(reg(ra))),
+                        Expr::Int((imm as u16).into()),
+                    ],
+                };
+                (
+                    assign(Expr::CrField(crf), Expr::Call("compare", args)),
+                    Vec::new(),
+                )
+            }
+            Self::CrOp { op, bt, ba, bb } => {
+                let (a, b) = (Expr::CrBit(ba), Expr::CrBit(bb));
+                let not = |e| Expr::Not(Box::new(e));
+                let value = match op {
+                    CrLogic::And => Expr::binary(a, BinOp::And, b),
+                    CrLogic::Or => Expr::binary(a, BinOp::Or, b),
+                    CrLogic::Xor => Expr::binary(a, BinOp::Xor, b),
+                    CrLogic::Nand => not(Expr::binary(a, BinOp::And, b).group()),
+                    CrLogic::Nor => not(Expr::binary(a, BinOp::Or, b).group()),
+                    CrLogic::Eqv => Expr::binary(a, BinOp::Eq, b),
+                    CrLogic::Andc => Expr::binary(a, BinOp::And, not(b)),
+                    CrLogic::Orc => Expr::binary(a, BinOp::Or, not(b)),
+                };
+                (assign(Expr::CrBit(bt), value), Vec::new())
+            }
+            Self::B { disp, .. } => (self.branch_effects(Expr::Here(disp)), Vec::new()),
+            Self::Bc { disp, .. } => (self.branch_effects(Expr::Here(disp.into())), Vec::new()),
+            _ => {
+                let (effects, mut notes) = self.canonicalize().effects();
+                notes.extend(self.intent());
+                (effects, notes)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_opcode;
+
+    #[test]
+    fn test_explain() {
+        let explain = |asm| parse_opcode(asm).expect("parse failed").1.explain();
+        let e = explain("lwzu r3,4(r4)");
+        assert_eq!(
+            e.operands,
+            vec![
+                Operand::Reg(Register(3)),
+                Operand::Imm(4),
+                Operand::Reg(Register(4))
+            ]
+        );
+        assert_eq!(e.effects.len(), 2);
+        assert_eq!(e.effects[1].target, Expr::Reg(Register(4)));
+
+        let e = explain("clrlwi r3,r4,16");
+        assert_eq!(e.mask, Some(0x0000_FFFF));
+        assert_eq!(
+            e.effects[0].value,
+            Expr::binary(
+                Expr::binary(Expr::Reg(Register(4)), BinOp::Shl, Expr::Int(0)).group(),
+                BinOp::And,
+                Expr::Mask { mb: 16, me: 31 }
+            )
+        );
+        assert_eq!(e.notes.len(), 1);
+        assert_eq!(e.to_string(), format!("{}  ({})", e.effects[0], e.notes[0]));
+    }
+}
@@ -0,0 +1,339 @@
+//! Integer literals and the constant expressions allowed in immediate
+//! operands, e.g. `rlwinm r3,r4,32-5,5,31`. Everything is folded at parse time.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+use nom::{
+    branch::alt,
+    bytes::complete::{is_a, tag},
+    character::complete::{
+        alpha1, alphanumeric1, digit1, hex_digit1, multispace0, none_of, oct_digit1,
+    },
+    combinator::{map, map_opt, map_res, opt, recognize},
+    error::{ErrorKind, FromExternalError, ParseError},
+    multi::many0,
+    sequence::{delimited, pair, preceded, tuple},
+    IResult,
+};
+
+/// nom error keeping whichever alternative got furthest into the input, so a
+/// bad last operand isn't reported as an unknown mnemonic by the final `alt`
+/// branch.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Failure<'a> {
+    pub input: &'a str,
+    pub kind: ErrorKind,
+}
+
+impl<'a> ParseError<&'a str> for Failure<'a> {
+    fn from_error_kind(input: &'a str, kind: ErrorKind) -> Self {
+        Failure { input, kind }
+    }
+
+    fn append(_: &'a str, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    fn or(self, other: Self) -> Self {
+        if other.input.len() < self.input.len() {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+impl<'a, E> FromExternalError<&'a str, E> for Failure<'a> {
+    fn from_external_error(input: &'a str, kind: ErrorKind, _: E) -> Self {
+        Failure { input, kind }
+    }
+}
+
+pub(crate) type PResult<'a, T> = IResult<&'a str, T, Failure<'a>>;
+
+/// `'A'`, or one of the escapes `'\n'`, `'\t'`, `'\0'`, `'\\'`, `'\''`.
+fn parse_char_literal(inp: &str) -> PResult<'_, i64> {
+    map(
+        delimited(
+            tag("'"),
+            alt((
+                map(tag("\\n"), |_| '\n'),
+                map(tag("\\t"), |_| '\t'),
+                map(tag("\\0"), |_| '\0'),
+                map(tag("\\\\"), |_| '\\'),
+                map(tag("\\'"), |_| '\''),
+                none_of("\\'"),
+            )),
+            tag("'"),
+        ),
+        |c| c as i64,
+    )(inp)
+}
+
+/// Signed integer literal: `16`, `0x10`, `0b1011`, `0o17`, `'A'`, `-8`,
+/// `-0x20`. Operand parsers narrow this to the width of their field.
+fn parse_integer(inp: &str) -> PResult<'_, i64> {
+    map(
+        tuple((
+            opt(tag("-")),
+            alt((
+                map_res(preceded(tag("0x"), hex_digit1), |x: &str| {
+                    i64::from_str_radix(x, 16)
+                }),
+                map_res(preceded(tag("0b"), is_a("01")), |x: &str| {
+                    i64::from_str_radix(x, 2)
+                }),
+                map_res(preceded(tag("0o"), oct_digit1), |x: &str| {
+                    i64::from_str_radix(x, 8)
+                }),
+                parse_char_literal,
+                map_res(digit1, |x: &str| x.parse::<i64>()),
+            )),
+        )),
+        |(neg, v)| if neg.is_some() { -v } else { v },
+    )(inp)
+}
+
+/// Constants defined with `.set`/`.equ`, visible to later operands.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Symbols {
+    values: BTreeMap<String, i64>,
+}
+
+/// Names the assembler predefines for condition register operands, so that
+/// `4*cr1+eq` is an ordinary expression.
+const CR_NAMES: &[(&str, i64)] = &[
+    ("cr0", 0),
+    ("cr1", 1),
+    ("cr2", 2),
+    ("cr3", 3),
+    ("cr4", 4),
+    ("cr5", 5),
+    ("cr6", 6),
+    ("cr7", 7),
+    ("lt", 0),
+    ("gt", 1),
+    ("eq", 2),
+    ("so", 3),
+    ("un", 3),
+];
+
+impl Symbols {
+    pub fn get(&self, name: &str) -> Option<i64> {
+        self.values.get(name).copied().or_else(|| {
+            CR_NAMES
+                .iter()
+                .find(|&&(cr, _)| cr == name)
+                .map(|&(_, value)| value)
+        })
+    }
+
+    /// Whether `name` was defined, as opposed to predefined.
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.values.contains_key(name)
+    }
+
+    pub fn define(&mut self, name: &str, value: i64) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, i64)> {
+        self.values
+            .iter()
+            .map(|(name, &value)| (name.as_str(), value))
+    }
+}
+
+/// Symbol name: `[A-Za-z_.][A-Za-z0-9_.$]*`.
+pub(crate) fn parse_identifier(inp: &str) -> PResult<'_, &str> {
+    recognize(pair(
+        alt((alpha1, tag("_"), tag("."))),
+        many0(alt((alphanumeric1, tag("_"), tag("."), tag("$")))),
+    ))(inp)
+}
+
+type Level = for<'a> fn(&'a str, &Symbols) -> PResult<'a, i64>;
+type Operator = (&'static str, fn(i64, i64) -> Option<i64>);
+
+/// One left-associative precedence level: `next (op next)*`.
+fn binary<'a>(inp: &'a str, syms: &Symbols, next: Level, ops: &[Operator]) -> PResult<'a, i64> {
+    let (mut inp, mut acc) = next(inp, syms)?;
+
+    'operators: loop {
+        let (rest, _) = multispace0(inp)?;
+        for (symbol, apply) in ops {
+            if let Ok((rest, _)) = tag::<_, _, Failure<'_>>(*symbol)(rest) {
+                // `|` and `&` aren't the first half of `||` and `&&`
+                if (*symbol == "|" || *symbol == "&") && rest.starts_with(symbol) {
+                    continue;
+                }
+                let (rest, _) = multispace0(rest)?;
+                let (rest, rhs) = next(rest, syms)?;
+                acc = apply(acc, rhs).ok_or_else(|| {
+                    nom::Err::Error(Failure::from_error_kind(inp, ErrorKind::Verify))
+                })?;
+                inp = rest;
+                continue 'operators;
+            }
+        }
+        return Ok((inp, acc));
+    }
+}
+
+fn parse_primary<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, i64> {
+    alt((
+        delimited(
+            tuple((tag("("), multispace0)),
+            |i| parse_expr(i, syms),
+            tuple((multispace0, tag(")"))),
+        ),
+        parse_integer,
+        map_opt(parse_identifier, |name| syms.get(name)),
+    ))(inp)
+}
+
+fn parse_unary<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, i64> {
+    let unary = |i| parse_unary(i, syms);
+    alt((
+        map(preceded(tuple((tag("-"), multispace0)), unary), |v| {
+            v.wrapping_neg()
+        }),
+        map(preceded(tuple((tag("~"), multispace0)), unary), |v| !v),
+        preceded(tuple((tag("+"), multispace0)), unary),
+        |i| parse_primary(i, syms),
+    ))(inp)
+}
+
+fn parse_product<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, i64> {
+    binary(
+        inp,
+        syms,
+        parse_unary,
+        &[
+            ("*", |a, b| Some(a.wrapping_mul(b))),
+            ("/", i64::checked_div),
+            ("%", i64::checked_rem),
+        ],
+    )
+}
+
+fn parse_sum<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, i64> {
+    binary(
+        inp,
+        syms,
+        parse_product,
+        &[
+            ("+", |a, b| Some(a.wrapping_add(b))),
+            ("-", |a, b| Some(a.wrapping_sub(b))),
+        ],
+    )
+}
+
+fn parse_shift<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, i64> {
+    binary(
+        inp,
+        syms,
+        parse_sum,
+        &[
+            ("<<", |a, b| {
+                u32::try_from(b).ok().and_then(|b| a.checked_shl(b))
+            }),
+            (">>", |a, b| {
+                u32::try_from(b).ok().and_then(|b| a.checked_shr(b))
+            }),
+        ],
+    )
+}
+
+fn parse_bitand<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, i64> {
+    binary(inp, syms, parse_shift, &[("&", |a, b| Some(a & b))])
+}
+
+fn parse_bitxor<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, i64> {
+    binary(inp, syms, parse_bitand, &[("^", |a, b| Some(a ^ b))])
+}
+
+/// Constant expression with C precedence, the operators
+/// `| ^ & << >> + - * / %` and unary `- ~ +`, and names from `syms`.
+pub(crate) fn parse_expr<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, i64> {
+    binary(inp, syms, parse_bitxor, &[("|", |a, b| Some(a | b))])
+}
+
+fn parse_comparison<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, i64> {
+    binary(
+        inp,
+        syms,
+        parse_expr,
+        &[
+            ("==", |a, b| Some((a == b) as i64)),
+            ("!=", |a, b| Some((a != b) as i64)),
+            ("<=", |a, b| Some((a <= b) as i64)),
+            (">=", |a, b| Some((a >= b) as i64)),
+            ("<", |a, b| Some((a < b) as i64)),
+            (">", |a, b| Some((a > b) as i64)),
+        ],
+    )
+}
+
+fn parse_conjunction<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, i64> {
+    binary(
+        inp,
+        syms,
+        parse_comparison,
+        &[("&&", |a, b| Some((a != 0 && b != 0) as i64))],
+    )
+}
+
+/// `.if` condition: an expression, optionally compared (`== != < <= > >=`)
+/// and combined with `&&` and `||`. Comparisons are 1 or 0.
+pub(crate) fn parse_condition<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, i64> {
+    binary(
+        inp,
+        syms,
+        parse_conjunction,
+        &[("||", |a, b| Some((a != 0 || b != 0) as i64))],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(inp: &str) -> PResult<'_, i64> {
+        parse_expr(inp, &Symbols::default())
+    }
+
+    #[test]
+    fn test_literal_radixes() {
+        assert_eq!(parse_integer("0b10110000"), Ok(("", 0xB0)));
+        assert_eq!(parse_integer("0o17"), Ok(("", 15)));
+        assert_eq!(parse_integer("'A'"), Ok(("", 65)));
+        assert_eq!(parse_integer("'\\n'"), Ok(("", 10)));
+    }
+
+    #[test]
+    fn test_expressions() {
+        assert_eq!(eval("32-5"), Ok(("", 27)));
+        assert_eq!(eval("1 + 2 * 3"), Ok(("", 7)));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(("", 9)));
+        assert_eq!(eval("1 << 4 | 1"), Ok(("", 17)));
+        assert_eq!(eval("~0 & 0xff"), Ok(("", 255)));
+        assert_eq!(eval("-(4)"), Ok(("", -4)));
+        assert_eq!(eval("8(r1)"), Ok(("(r1)", 8)));
+        assert!(eval("1/0").is_err());
+
+        let cond = |inp| parse_condition(inp, &Symbols::default());
+        assert_eq!(cond("1 + 1 == 2 && 3 > 4 || 1 | 2 != 3"), Ok(("", 0)));
+        assert_eq!(cond("2 <= 2 || 0"), Ok(("", 1)));
+        assert_eq!(cond("1 << 2 < 5"), Ok(("", 1)));
+    }
+
+    #[test]
+    fn test_symbols() {
+        let mut syms = Symbols::default();
+        syms.define("WIDTH", 5);
+        assert_eq!(parse_expr("(31-WIDTH)", &syms), Ok(("", 26)));
+        assert!(eval("WIDTH").is_err());
+    }
+}
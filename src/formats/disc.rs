@@ -0,0 +1,161 @@
+//! GameCube disc images (`.iso`, `.gcm`), read for their main DOL: the
+//! disc header gives the game's ID and title, and at 0x420 the DOL's
+//! offset, where it's stored whole. Only the header and the DOL are read,
+//! not the whole 1.4GB.
+//!
+//! Wii discs are recognized but not read: their partitions are encrypted
+//! with a key ppcheat doesn't ship, and compressed formats (nkit, RVZ,
+//! WBFS) need their own decoders.
+
+use core::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
+
+use super::dol::{self, Dol};
+use crate::image::{be32, ImageError};
+
+const GAMECUBE_MAGIC: u32 = 0xC233_9F3D;
+const WII_MAGIC: u32 = 0x5D1C_9EA3;
+const HEADER_SIZE: usize = 0x440;
+
+#[derive(Debug)]
+pub enum DiscError {
+    Io(io::Error),
+    /// The header's DOL doesn't parse
+    Image(ImageError),
+    /// Neither magic number is there
+    NotADisc,
+    Wii,
+}
+
+impl fmt::Display for DiscError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Image(e) => write!(f, "main DOL: {}", e),
+            Self::NotADisc => write!(f, "not a GameCube or Wii disc image"),
+            Self::Wii => write!(
+                f,
+                "Wii discs are encrypted, which ppcheat can't read; \
+                 extract main.dol with Dolphin and disassemble that"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DiscError {}
+
+impl From<io::Error> for DiscError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<ImageError> for DiscError {
+    fn from(e: ImageError) -> Self {
+        Self::Image(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Disc {
+    /// `GALE01`: game code, region and publisher
+    pub game_id: String,
+    pub title: String,
+    pub dol_offset: u32,
+    pub dol: Dol,
+}
+
+/// Whether `disc` starts with a GameCube or Wii disc header. Reads its
+/// first bytes and goes back to the start.
+pub fn detect(disc: &mut (impl Read + Seek)) -> io::Result<bool> {
+    let mut header = [0; 0x20];
+    let read = disc.read(&mut header)?;
+    disc.seek(SeekFrom::Start(0))?;
+    Ok(read == header.len()
+        && (be32(&header, 0x1C) == Ok(GAMECUBE_MAGIC) || be32(&header, 0x18) == Ok(WII_MAGIC)))
+}
+
+impl Disc {
+    pub fn read(disc: &mut (impl Read + Seek)) -> Result<Self, DiscError> {
+        let mut header = vec![0; HEADER_SIZE];
+        disc.seek(SeekFrom::Start(0))?;
+        disc.read_exact(&mut header)?;
+        if be32(&header, 0x18)? == WII_MAGIC {
+            return Err(DiscError::Wii);
+        }
+        if be32(&header, 0x1C)? != GAMECUBE_MAGIC {
+            return Err(DiscError::NotADisc);
+        }
+        let text = |range: std::ops::Range<usize>| {
+            let field = &header[range];
+            let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+            String::from_utf8_lossy(&field[..end]).into_owned()
+        };
+
+        let dol_offset = be32(&header, 0x420)?;
+        let mut bytes = vec![0; 0x100];
+        disc.seek(SeekFrom::Start(dol_offset.into()))?;
+        disc.read_exact(&mut bytes)?;
+        bytes.resize(dol::file_size(&bytes)? as usize, 0);
+        disc.read_exact(&mut bytes[0x100..])?;
+
+        Ok(Disc {
+            game_id: text(0..6),
+            title: text(0x20..0x400),
+            dol_offset,
+            dol: Dol::parse(&bytes)?,
+        })
+    }
+}
+
+/// `GALE01 Super Smash Bros. Melee (main DOL at 0x1e800)`
+impl fmt::Display for Disc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} (main DOL at {:#x})",
+            self.game_id, self.title, self.dol_offset
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::Options;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_disc() {
+        let mut disc = vec![0u8; 0x2000];
+        disc[..6].copy_from_slice(b"GPPE01");
+        disc[0x1C..0x20].copy_from_slice(&GAMECUBE_MAGIC.to_be_bytes());
+        disc[0x20..0x27].copy_from_slice(b"ppcheat");
+        disc[0x420..0x424].copy_from_slice(&0x1000u32.to_be_bytes());
+        // text0: 8 bytes at 0x80003100
+        for &(at, value) in &[(0x00, 0x100u32), (0x48, 0x8000_3100), (0x90, 8)] {
+            disc[0x1000 + at..0x1000 + at + 4].copy_from_slice(&value.to_be_bytes());
+        }
+        // li r3,1; blr
+        disc[0x1100..0x1108].copy_from_slice(&[0x38, 0x60, 0x00, 0x01, 0x4e, 0x80, 0x00, 0x20]);
+
+        let mut cursor = Cursor::new(disc);
+        assert!(detect(&mut cursor).unwrap());
+        let read = Disc::read(&mut cursor).unwrap();
+        assert_eq!(read.to_string(), "GPPE01 ppcheat (main DOL at 0x1000)");
+        assert_eq!(
+            read.dol
+                .function_listing(0x8000_3100, Some(4), &Options::default())
+                .unwrap()
+                .text,
+            "# text0 80003100..80003104\n80003100: 38 60 00 01  li r3,1\n"
+        );
+
+        let mut wii = cursor.into_inner();
+        wii[0x18..0x1C].copy_from_slice(&WII_MAGIC.to_be_bytes());
+        assert!(matches!(
+            Disc::read(&mut Cursor::new(wii)),
+            Err(DiscError::Wii)
+        ));
+    }
+}
@@ -0,0 +1,343 @@
+//! DOL, the GameCube and Wii executable: a 0x100-byte header giving the
+//! file offset, load address and size of up to 7 text and 11 data
+//! segments, then the BSS range and the entry point. Everything is
+//! big-endian.
+
+use core::fmt;
+
+use crate::disasm::{Endian, Listing, Options};
+use crate::image::{be32, slice, Image, ImageError, Section};
+
+const TEXT_SEGMENTS: usize = 7;
+const DATA_SEGMENTS: usize = 11;
+const HEADER_SIZE: u32 = 0x100;
+
+/// One loaded segment and where it came from in the file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    /// `text0`..`text6` or `data0`..`data10`, slot numbers as in the header
+    pub name: String,
+    pub offset: u32,
+    pub addr: u32,
+    pub data: Vec<u8>,
+}
+
+impl Segment {
+    pub fn size(&self) -> u32 {
+        self.data.len() as u32
+    }
+
+    pub fn contains(&self, addr: u32) -> bool {
+        addr.wrapping_sub(self.addr) < self.size()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dol {
+    /// The non-empty text segments, in slot order
+    pub text: Vec<Segment>,
+    /// The non-empty data segments, in slot order
+    pub data: Vec<Segment>,
+    pub bss_addr: u32,
+    pub bss_size: u32,
+    pub entry: u32,
+}
+
+impl Dol {
+    pub fn parse(bytes: &[u8]) -> Result<Self, ImageError> {
+        if bytes.len() < HEADER_SIZE as usize {
+            return Err(ImageError::UnknownFormat);
+        }
+        let segment = |slot: usize, name: String| -> Result<Option<Segment>, ImageError> {
+            let offset = be32(bytes, 4 * slot)?;
+            let addr = be32(bytes, 0x48 + 4 * slot)?;
+            let size = be32(bytes, 0x90 + 4 * slot)?;
+            match (size, offset) {
+                (0, _) => Ok(None),
+                // a segment can't start inside the header
+                (_, offset) if offset < HEADER_SIZE => Err(ImageError::UnknownFormat),
+                _ => Ok(Some(Segment {
+                    name,
+                    offset,
+                    addr,
+                    data: slice(bytes, offset, size)?,
+                })),
+            }
+        };
+
+        let mut dol = Dol {
+            text: Vec::new(),
+            data: Vec::new(),
+            bss_addr: be32(bytes, 0xD8)?,
+            bss_size: be32(bytes, 0xDC)?,
+            entry: be32(bytes, 0xE0)?,
+        };
+        for i in 0..TEXT_SEGMENTS {
+            dol.text.extend(segment(i, format!("text{}", i))?);
+        }
+        for i in 0..DATA_SEGMENTS {
+            dol.data
+                .extend(segment(TEXT_SEGMENTS + i, format!("data{}", i))?);
+        }
+        Ok(dol)
+    }
+
+    /// Text segments, then data segments.
+    pub fn segments(&self) -> impl Iterator<Item = &Segment> {
+        self.text.iter().chain(&self.data)
+    }
+
+    /// The segment `addr` is loaded from, if any.
+    pub fn segment_at(&self, addr: u32) -> Option<&Segment> {
+        self.segments().find(|s| s.contains(addr))
+    }
+
+    /// Where in the file the byte loaded at `addr` is.
+    pub fn file_offset(&self, addr: u32) -> Option<u32> {
+        let segment = self.segment_at(addr)?;
+        Some(segment.offset + (addr - segment.addr))
+    }
+
+    /// The address the file byte at `offset` is loaded at.
+    pub fn address(&self, offset: u32) -> Option<u32> {
+        self.segments()
+            .find(|s| offset.wrapping_sub(s.offset) < s.size())
+            .map(|s| s.addr + (offset - s.offset))
+    }
+
+    pub fn image(&self) -> Image {
+        let section = |segment: &Segment, executable| Section {
+            addr: segment.addr,
+            data: segment.data.clone(),
+            executable,
+        };
+        Image {
+            entry: self.entry,
+            sections: self
+                .text
+                .iter()
+                .map(|s| section(s, true))
+                .chain(self.data.iter().map(|s| section(s, false)))
+                .collect(),
+        }
+    }
+
+    /// Every text segment disassembled at its load address, each under a
+    /// comment naming it.
+    pub fn listing(&self, options: &Options) -> Listing {
+        super::listing(
+            self.text.iter().map(|segment| {
+                let section = Section {
+                    addr: segment.addr,
+                    data: segment.data.clone(),
+                    executable: true,
+                };
+                (segment.name.clone(), segment.to_string(), section)
+            }),
+            Endian::Big,
+            options,
+        )
+    }
+}
+
+/// The `blr` that ends most functions.
+const BLR: u32 = 0x4E80_0020;
+
+impl Dol {
+    /// The code from `addr` to the end of its function: `size` bytes, or
+    /// without a size up to the first `blr`, under a comment with its
+    /// range. Either way it stops at the end of the text segment.
+    pub fn function_listing(
+        &self,
+        addr: u32,
+        size: Option<u32>,
+        options: &Options,
+    ) -> Option<Listing> {
+        let segment = self.text.iter().find(|s| s.contains(addr))?;
+        let start = (addr - segment.addr) as usize;
+        let rest = &segment.data[start..];
+        let len = match size {
+            Some(size) => rest.len().min(size as usize),
+            None => rest
+                .chunks_exact(4)
+                .position(|w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]) == BLR)
+                .map_or(rest.len(), |i| 4 * i + 4),
+        };
+        let section = Section {
+            addr,
+            data: rest[..len].to_vec(),
+            executable: true,
+        };
+        let header = format!(
+            "{} {:08x}..{:08x}",
+            segment.name,
+            addr,
+            addr.wrapping_add(len as u32)
+        );
+        let name = segment.name.clone();
+        Some(super::listing(
+            Some((name, header, section)),
+            Endian::Big,
+            options,
+        ))
+    }
+}
+
+/// A word of a DOL a patch changed, or the bytes after the last whole word.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub addr: u32,
+    pub offset: u32,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+}
+
+impl Dol {
+    /// Writes `bytes` into `file`, the DOL this was parsed from, where the
+    /// bytes from `addr` on are loaded from, and gives the words that
+    /// changed. Nothing is written unless all of it is loaded from the file;
+    /// otherwise the error is the first address that isn't, like one in the
+    /// BSS.
+    pub fn patch(&self, file: &mut [u8], addr: u32, bytes: &[u8]) -> Result<Vec<Change>, u32> {
+        let offsets = (0..bytes.len() as u32)
+            .map(|i| {
+                let at = addr.wrapping_add(i);
+                self.file_offset(at)
+                    .filter(|&offset| (offset as usize) < file.len())
+                    .ok_or(at)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut changes = Vec::new();
+        for (i, new) in bytes.chunks(4).enumerate() {
+            let offsets = &offsets[4 * i..4 * i + new.len()];
+            let old: Vec<_> = offsets.iter().map(|&o| file[o as usize]).collect();
+            if old == new {
+                continue;
+            }
+            for (&offset, &byte) in offsets.iter().zip(new) {
+                file[offset as usize] = byte;
+            }
+            changes.push(Change {
+                addr: addr.wrapping_add(4 * i as u32),
+                offset: offsets[0],
+                old,
+                new: new.to_vec(),
+            });
+        }
+        Ok(changes)
+    }
+}
+
+/// How long the DOL whose header is `header` is: the end of its furthest
+/// segment.
+pub fn file_size(header: &[u8]) -> Result<u32, ImageError> {
+    let mut end = HEADER_SIZE;
+    for slot in 0..TEXT_SEGMENTS + DATA_SEGMENTS {
+        let (offset, size) = (be32(header, 4 * slot)?, be32(header, 0x90 + 4 * slot)?);
+        if size != 0 {
+            end = end.max(offset.checked_add(size).ok_or(ImageError::Truncated)?);
+        }
+    }
+    Ok(end)
+}
+
+/// `text0 80003100..80005f40 (0x2e40 bytes at file offset 0x100)`
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:08x}..{:08x} ({:#x} bytes at file offset {:#x})",
+            self.name,
+            self.addr,
+            self.addr.wrapping_add(self.size()),
+            self.size(),
+            self.offset
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(bytes: &mut [u8], at: usize, value: u32) {
+        bytes[at..at + 4].copy_from_slice(&value.to_be_bytes());
+    }
+
+    #[test]
+    fn test_dol() {
+        let mut bytes = vec![0u8; 0x100];
+        // text1 and data0
+        for &(at, value) in &[
+            (0x04, 0x100),
+            (0x4C, 0x8000_3100),
+            (0x94, 8),
+            (0x1C, 0x108),
+            (0x64, 0x8040_0000),
+            (0xAC, 4),
+            (0xD8, 0x8040_0020),
+            (0xDC, 0x100),
+            (0xE0, 0x8000_3100),
+        ] {
+            set(&mut bytes, at, value);
+        }
+        // srwi r0,r0,16; blr; .long 1
+        bytes.extend([0x54, 0x00, 0x84, 0x3e, 0x4e, 0x80, 0x00, 0x20, 0, 0, 0, 1]);
+
+        let dol = Dol::parse(&bytes).unwrap();
+        assert_eq!(dol.text[0].name, "text1");
+        assert_eq!(
+            dol.data[0].to_string(),
+            "data0 80400000..80400004 (0x4 bytes at file offset 0x108)"
+        );
+        assert_eq!(dol.file_offset(0x8000_3104), Some(0x104));
+        assert_eq!(dol.address(0x108), Some(0x8040_0000));
+        assert_eq!(dol.file_offset(0x8040_0004), None);
+        assert_eq!(dol.image().sections.len(), 2);
+        assert_eq!(
+            dol.listing(&Options::default()).text,
+            "# text1 80003100..80003108 (0x8 bytes at file offset 0x100)\n\
+             80003100: 54 00 84 3e  srwi r0,r0,16\n\
+             .long 0x4E800020  # 80003104: 4e 80 00 20  looks like blr\n"
+        );
+
+        assert_eq!(
+            dol.function_listing(0x8000_3100, None, &Options::default())
+                .unwrap()
+                .text
+                .lines()
+                .next(),
+            Some("# text1 80003100..80003108")
+        );
+        assert_eq!(file_size(&bytes), Ok(0x10C));
+
+        // srwi r0,r0,16 again, then li r3,1
+        let mut patched = bytes.clone();
+        let changes = dol
+            .patch(
+                &mut patched,
+                0x8000_3100,
+                &[0x54, 0x00, 0x84, 0x3e, 0x38, 0x60, 0, 1],
+            )
+            .unwrap();
+        assert_eq!(
+            changes,
+            [Change {
+                addr: 0x8000_3104,
+                offset: 0x104,
+                old: vec![0x4e, 0x80, 0x00, 0x20],
+                new: vec![0x38, 0x60, 0, 1]
+            }]
+        );
+        assert_eq!(patched[0x104..0x108], [0x38, 0x60, 0, 1]);
+        assert_eq!(
+            dol.patch(&mut patched, 0x8040_0002, &[1, 2, 3]),
+            Err(0x8040_0004)
+        );
+        assert_eq!(patched[0x10A..], [0, 1]);
+
+        set(&mut bytes, 0x04, 0x80);
+        assert_eq!(Dol::parse(&bytes), Err(ImageError::UnknownFormat));
+    }
+}
@@ -0,0 +1,514 @@
+//! ELF, what devkitPPC links to before `elf2dol` and what Linux and
+//! embedded PowerPC toolchains ship: 32- or 64-bit, either byte order. Its
+//! section headers give the loaded sections and the symbol table, whose
+//! functions become labels in the listing.
+//!
+//! 64-bit big-endian files use the ELFv1 ABI unless their flags say
+//! otherwise, where a function symbol (and the entry point) is the address
+//! of a descriptor in `.opd` whose first doubleword is the code address.
+
+use core::fmt;
+use std::convert::{TryFrom, TryInto};
+
+use crate::disasm::{Endian, Listing, Options};
+use crate::image::{Image, ImageError, Section};
+use crate::symbols::{self, SymbolTable};
+
+const EM_PPC: u16 = 20;
+const EM_PPC64: u16 = 21;
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_NOBITS: u32 = 8;
+const SHT_DYNSYM: u32 = 11;
+const SHF_ALLOC: u64 = 2;
+const SHF_EXECINSTR: u64 = 4;
+
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+const STT_SECTION: u8 = 3;
+const STT_FILE: u8 = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElfSection {
+    pub name: String,
+    pub addr: u64,
+    pub offset: u64,
+    /// The file's bytes for it, empty for `.bss`-like sections
+    pub data: Vec<u8>,
+    /// Its size in memory, which for `SHT_NOBITS` the data doesn't show
+    pub size: u64,
+    pub executable: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SymbolKind {
+    Function,
+    Object,
+    /// `STT_NOTYPE`, what labels in hand-written assembly get
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    /// The code address for a function, even under ELFv1
+    pub addr: u64,
+    pub size: u64,
+    pub kind: SymbolKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Elf {
+    pub is_64: bool,
+    pub endian: Endian,
+    pub entry: u64,
+    /// The allocated sections, in header order
+    pub sections: Vec<ElfSection>,
+    /// The defined symbols of `.symtab`, or of `.dynsym` for a stripped
+    /// file, sorted by address
+    pub symbols: Vec<Symbol>,
+}
+
+/// Field reads in the file's class and byte order.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    is_64: bool,
+    endian: Endian,
+}
+
+impl Reader<'_> {
+    fn field<const N: usize>(&self, at: u64) -> Result<[u8; N], ImageError> {
+        let at = usize::try_from(at).map_err(|_| ImageError::Truncated)?;
+        let mut field: [u8; N] = self
+            .bytes
+            .get(at..at.checked_add(N).ok_or(ImageError::Truncated)?)
+            .ok_or(ImageError::Truncated)?
+            .try_into()
+            .unwrap();
+        if self.endian == Endian::Little {
+            field.reverse();
+        }
+        Ok(field)
+    }
+
+    fn u8(&self, at: u64) -> Result<u8, ImageError> {
+        Ok(self.field::<1>(at)?[0])
+    }
+
+    fn u16(&self, at: u64) -> Result<u16, ImageError> {
+        self.field(at).map(u16::from_be_bytes)
+    }
+
+    fn u32(&self, at: u64) -> Result<u32, ImageError> {
+        self.field(at).map(u32::from_be_bytes)
+    }
+
+    fn u64(&self, at: u64) -> Result<u64, ImageError> {
+        self.field(at).map(u64::from_be_bytes)
+    }
+
+    /// An address or offset: 4 bytes in ELF32, 8 in ELF64.
+    fn addr(&self, at: u64) -> Result<u64, ImageError> {
+        match self.is_64 {
+            true => self.u64(at),
+            false => self.u32(at).map(u64::from),
+        }
+    }
+
+    fn bytes(&self, offset: u64, size: u64) -> Result<&[u8], ImageError> {
+        let start = usize::try_from(offset).map_err(|_| ImageError::Truncated)?;
+        let size = usize::try_from(size).map_err(|_| ImageError::Truncated)?;
+        self.bytes
+            .get(start..start.checked_add(size).ok_or(ImageError::Truncated)?)
+            .ok_or(ImageError::Truncated)
+    }
+
+    /// The NUL-terminated string at `offset` into the string table at
+    /// `table`.
+    fn string(&self, table: u64, offset: u32) -> Result<String, ImageError> {
+        let start =
+            usize::try_from(table + u64::from(offset)).map_err(|_| ImageError::Truncated)?;
+        let rest = self.bytes.get(start..).ok_or(ImageError::Truncated)?;
+        let end = rest
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(ImageError::Truncated)?;
+        Ok(String::from_utf8_lossy(&rest[..end]).into_owned())
+    }
+}
+
+/// One section header, before its name and data are looked up.
+struct Header {
+    name: u32,
+    ty: u32,
+    flags: u64,
+    addr: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+}
+
+impl Elf {
+    pub fn parse(bytes: &[u8]) -> Result<Self, ImageError> {
+        if !bytes.starts_with(b"\x7fELF") {
+            return Err(ImageError::UnknownFormat);
+        }
+        let is_64 = match bytes.get(4) {
+            Some(1) => false,
+            Some(2) => true,
+            _ => return Err(ImageError::BadHeader("class isn't 32- or 64-bit")),
+        };
+        let endian = match bytes.get(5) {
+            Some(1) => Endian::Little,
+            Some(2) => Endian::Big,
+            _ => return Err(ImageError::BadHeader("byte order isn't little or big")),
+        };
+        let r = Reader {
+            bytes,
+            is_64,
+            endian,
+        };
+        if ![EM_PPC, EM_PPC64].contains(&r.u16(0x12)?) {
+            return Err(ImageError::BadHeader("machine isn't PowerPC"));
+        }
+
+        // e_shoff, e_flags, e_shentsize, e_shnum and e_shstrndx
+        let (shoff, flags, sizes) = match is_64 {
+            true => (r.u64(0x28)?, r.u32(0x30)?, 0x3A),
+            false => (r.u32(0x20)?.into(), r.u32(0x24)?, 0x2E),
+        };
+        let (shentsize, shnum, shstrndx) = (r.u16(sizes)?, r.u16(sizes + 2)?, r.u16(sizes + 4)?);
+        let mut headers = Vec::with_capacity(usize::from(shnum));
+        for i in 0..u64::from(shnum) {
+            let sh = shoff + i * u64::from(shentsize);
+            let w = if is_64 { 8 } else { 4 };
+            headers.push(Header {
+                name: r.u32(sh)?,
+                ty: r.u32(sh + 4)?,
+                flags: r.addr(sh + 8)?,
+                addr: r.addr(sh + 8 + w)?,
+                offset: r.addr(sh + 8 + 2 * w)?,
+                size: r.addr(sh + 8 + 3 * w)?,
+                link: r.u32(sh + 8 + 4 * w)?,
+            });
+        }
+        let names = headers.get(usize::from(shstrndx)).map(|h| h.offset);
+        let name = |h: &Header| match names {
+            Some(table) => r.string(table, h.name),
+            None => Ok(String::new()),
+        };
+
+        let mut sections = Vec::new();
+        for h in headers.iter().filter(|h| h.flags & SHF_ALLOC != 0) {
+            sections.push(ElfSection {
+                name: name(h)?,
+                addr: h.addr,
+                offset: h.offset,
+                data: match h.ty {
+                    SHT_NOBITS => Vec::new(),
+                    _ => r.bytes(h.offset, h.size)?.to_vec(),
+                },
+                size: h.size,
+                executable: h.flags & SHF_EXECINSTR != 0,
+            });
+        }
+
+        let mut elf = Elf {
+            is_64,
+            endian,
+            entry: r.addr(0x18)?,
+            sections,
+            symbols: Vec::new(),
+        };
+
+        // ELFv1 is the default for big-endian ppc64: flags bits 0-1 are 1
+        // for v1 and 2 for v2, and 0 (unspecified) means v1
+        let descriptors = is_64 && endian == Endian::Big && flags & 3 != 2;
+        if descriptors {
+            elf.entry = elf.descriptor(elf.entry).unwrap_or(elf.entry);
+        }
+
+        let table = headers
+            .iter()
+            .find(|h| h.ty == SHT_SYMTAB)
+            .or_else(|| headers.iter().find(|h| h.ty == SHT_DYNSYM));
+        if let Some(table) = table {
+            let strings = headers
+                .get(table.link as usize)
+                .ok_or(ImageError::BadHeader(
+                    "symbol table links to no string table",
+                ))?
+                .offset;
+            let size = if is_64 { 24 } else { 16 };
+            for i in 1..table.size / size {
+                let sym = table.offset + i * size;
+                let (info, shndx, value, len) = match is_64 {
+                    true => (
+                        r.u8(sym + 4)?,
+                        r.u16(sym + 6)?,
+                        r.u64(sym + 8)?,
+                        r.u64(sym + 16)?,
+                    ),
+                    false => (
+                        r.u8(sym + 12)?,
+                        r.u16(sym + 14)?,
+                        r.u32(sym + 4)?.into(),
+                        r.u32(sym + 8)?.into(),
+                    ),
+                };
+                let name = r.string(strings, r.u32(sym)?)?;
+                // undefined, absolute and common symbols don't label code
+                if name.is_empty() || shndx == 0 || shndx >= 0xFF00 {
+                    continue;
+                }
+                let (kind, addr) = match info & 0xF {
+                    STT_SECTION | STT_FILE => continue,
+                    STT_FUNC if descriptors => {
+                        (SymbolKind::Function, elf.descriptor(value).unwrap_or(value))
+                    }
+                    STT_FUNC => (SymbolKind::Function, value),
+                    STT_OBJECT => (SymbolKind::Object, value),
+                    _ => (SymbolKind::Other, value),
+                };
+                elf.symbols.push(Symbol {
+                    name,
+                    addr,
+                    size: len,
+                    kind,
+                });
+            }
+        }
+        elf.symbols.sort_by_key(|s| s.addr);
+        Ok(elf)
+    }
+
+    /// The code address an ELFv1 function descriptor at `addr` points to.
+    fn descriptor(&self, addr: u64) -> Option<u64> {
+        let opd = self.sections.iter().find(|s| s.name == ".opd")?;
+        let at = usize::try_from(addr.checked_sub(opd.addr)?).ok()?;
+        let mut word: [u8; 8] = opd.data.get(at..at + 8)?.try_into().ok()?;
+        if self.endian == Endian::Little {
+            word.reverse();
+        }
+        Some(u64::from_be_bytes(word))
+    }
+
+    /// The loaded sections with file data. ppcheat's addresses are 32 bits,
+    /// so a section loaded above 4GB (a 64-bit kernel, say) is an error.
+    pub fn image(&self) -> Result<Image, ImageError> {
+        let addr = |addr: u64| {
+            u32::try_from(addr).map_err(|_| {
+                ImageError::BadHeader("a section is loaded above 4GB, past ppcheat's addresses")
+            })
+        };
+        let mut sections = Vec::new();
+        for s in self.sections.iter().filter(|s| !s.data.is_empty()) {
+            sections.push(Section {
+                addr: addr(s.addr)?,
+                data: s.data.clone(),
+                executable: s.executable,
+            });
+        }
+        Ok(Image {
+            entry: addr(self.entry)?,
+            sections,
+        })
+    }
+
+    /// The symbols below 4GB, objects as data.
+    pub fn symbol_table(&self) -> SymbolTable {
+        let mut table = SymbolTable::default();
+        for s in &self.symbols {
+            if let Ok(addr) = u32::try_from(s.addr) {
+                table.insert(symbols::Symbol {
+                    addr,
+                    size: u32::try_from(s.size).ok(),
+                    name: s.name.clone(),
+                    data: s.kind == SymbolKind::Object,
+                });
+            }
+        }
+        table
+    }
+
+    /// Every executable section disassembled at its load address, each
+    /// under a comment naming it, with a label for each of its functions
+    /// and untyped symbols, and the `options`' symbols besides its own.
+    pub fn listing(&self, options: &Options) -> Result<Listing, ImageError> {
+        let image = self.image()?;
+        let sections = self
+            .sections
+            .iter()
+            .filter(|s| !s.data.is_empty())
+            .zip(image.sections)
+            .filter(|(s, _)| s.executable)
+            .map(|(s, section)| (s.name.clone(), s.to_string(), section));
+        let mut symbols = self.symbol_table();
+        symbols.extend(options.symbols.clone());
+        let options = Options {
+            symbols,
+            ..options.clone()
+        };
+        Ok(super::listing(sections, self.endian, &options))
+    }
+}
+
+/// `.text 80004000..80004100 (0x100 bytes at file offset 0x100)`
+impl fmt::Display for ElfSection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:08x}..{:08x} ({:#x} bytes at file offset {:#x})",
+            self.name,
+            self.addr,
+            self.addr.wrapping_add(self.size),
+            self.size,
+            self.offset
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A minimal executable: `.text` at 0x10000100 holding `main` and
+    /// `helper`, a symbol table and the section names, in `endian` and
+    /// 32 or 64 bits.
+    fn elf(is_64: bool, endian: Endian) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let put = |bytes: &mut Vec<u8>, value: u64, size: usize| {
+            let be = value.to_be_bytes();
+            let mut field = be[8 - size..].to_vec();
+            if endian == Endian::Little {
+                field.reverse();
+            }
+            bytes.extend(field);
+        };
+        let w = if is_64 { 8 } else { 4 };
+        let (ehsize, shentsize, symsize) = if is_64 { (64, 64, 24) } else { (52, 40, 16) };
+
+        // text: li r3,1; addi r3,r3,1; li r3,2; b .-8
+        let text: Vec<u8> = [0x3860_0001u32, 0x3863_0001, 0x3860_0002, 0x4BFF_FFF8]
+            .iter()
+            .flat_map(|&word| {
+                let mut word = word.to_be_bytes();
+                if endian == Endian::Little {
+                    word.reverse();
+                }
+                word
+            })
+            .collect();
+        let names = b"\0.text\0.symtab\0.strtab\0.shstrtab\0";
+        let strings = b"\0main\0helper\0data\0";
+        let text_at = ehsize;
+        let symtab_at = text_at + 16;
+        let strtab_at = symtab_at + 4 * symsize;
+        let shstrtab_at = strtab_at + strings.len() as u64;
+        let shoff = shstrtab_at + names.len() as u64;
+
+        bytes.extend(b"\x7fELF");
+        bytes.extend([
+            if is_64 { 2 } else { 1 },
+            if endian == Endian::Big { 2 } else { 1 },
+            1,
+        ]);
+        bytes.resize(16, 0);
+        put(&mut bytes, 2, 2);
+        put(&mut bytes, if is_64 { 21 } else { 20 }, 2);
+        put(&mut bytes, 1, 4);
+        put(&mut bytes, 0x1000_0100, w);
+        put(&mut bytes, 0, w);
+        put(&mut bytes, shoff, w);
+        put(&mut bytes, if is_64 { 2 } else { 0 }, 4);
+        for &half in &[ehsize, 0, 0, shentsize, 5, 4] {
+            put(&mut bytes, half, 2);
+        }
+        bytes.extend(text);
+
+        // null, main (func), helper (notype), data (object)
+        for &(name, value, info) in &[
+            (0, 0, 0),
+            (1, 0x1000_0100, 0x12),
+            (6, 0x1000_0108, 0x10),
+            (13, 0x1000_0104, 0x11),
+        ] {
+            put(&mut bytes, name, 4);
+            if is_64 {
+                bytes.extend([info as u8, 0]);
+                put(&mut bytes, if name == 0 { 0 } else { 1 }, 2);
+                put(&mut bytes, value, 8);
+                put(&mut bytes, 8, 8);
+            } else {
+                put(&mut bytes, value, 4);
+                put(&mut bytes, 8, 4);
+                bytes.extend([info as u8, 0]);
+                put(&mut bytes, if name == 0 { 0 } else { 1 }, 2);
+            }
+        }
+        bytes.extend(&strings[..]);
+        bytes.extend(&names[..]);
+
+        // null, .text, .symtab, .strtab, .shstrtab
+        for &(name, ty, flags, addr, offset, size, link) in &[
+            (0, 0, 0, 0, 0, 0, 0),
+            (1, 1, 6, 0x1000_0100, text_at, 16, 0),
+            (7, 2, 0, 0, symtab_at, 4 * symsize, 3),
+            (15, 3, 0, 0, strtab_at, strings.len() as u64, 0),
+            (23, 3, 0, 0, shstrtab_at, names.len() as u64, 0),
+        ] {
+            put(&mut bytes, name, 4);
+            put(&mut bytes, ty, 4);
+            put(&mut bytes, flags, w);
+            put(&mut bytes, addr, w);
+            put(&mut bytes, offset, w);
+            put(&mut bytes, size, w);
+            put(&mut bytes, link, 4);
+            put(&mut bytes, 0, 4);
+            put(&mut bytes, 4, w);
+            put(&mut bytes, 0, w);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_elf() {
+        let listing = "# .text 10000100..10000110 (0x10 bytes at file offset 0x34)\n\
+                       main:\n\
+                       10000100: 38 60 00 01  li r3,1\n\
+                       10000104: 38 63 00 01  addi r3,r3,1\n\
+                       helper:\n\
+                       10000108: 38 60 00 02  li r3,2\n\
+                       1000010c: 4b ff ff f8  b .-8  # main+0x4\n";
+        let elf32 = Elf::parse(&elf(false, Endian::Big)).unwrap();
+        assert_eq!(elf32.symbols.len(), 3);
+        assert_eq!(elf32.listing(&Options::default()).unwrap().text, listing);
+        assert_eq!(
+            Image::parse(&elf(false, Endian::Big)).unwrap().entry,
+            0x1000_0100
+        );
+
+        // ppc64le, ELFv2: the same code, words byte-swapped in the file
+        let elf64 = Elf::parse(&elf(true, Endian::Little)).unwrap();
+        assert_eq!(elf64.endian, Endian::Little);
+        assert_eq!(
+            elf64.listing(&Options::default()).unwrap().text,
+            listing
+                .replace(
+                    "0x10 bytes at file offset 0x34",
+                    "0x10 bytes at file offset 0x40"
+                )
+                .replace("38 60 00 01", "01 00 60 38")
+                .replace("38 63 00 01", "01 00 63 38")
+                .replace("38 60 00 02", "02 00 60 38")
+                .replace("4b ff ff f8", "f8 ff ff 4b")
+        );
+
+        let mut bytes = elf(false, Endian::Big);
+        bytes[0x13] = 3;
+        assert_eq!(
+            Elf::parse(&bytes).unwrap_err().to_string(),
+            "bad header: machine isn't PowerPC"
+        );
+    }
+}
@@ -0,0 +1,404 @@
+//! GCT, the binary code list Gecko OS, Nintendont and USB loaders read
+//! off the SD card (`codes/GALE01.gct`): `00D0C0DE 00D0C0DE`, each line of
+//! code as two big-endian words, then `F0000000 00000000`.
+//!
+//! A GCT has no names, so its codes are told apart by how they're built:
+//! one that sets `ba` or `po` or opens a conditional carries on until an
+//! E0 (or enough E2s) ends it, and one that sets a Gecko register carries
+//! on into the code that uses it.
+
+use core::fmt;
+
+use crate::codelist::{CodeList, NamedCode};
+use crate::gecko::{self, extra_lines, CodeType, Line};
+use crate::memory_map::{self, Console};
+use crate::tidy;
+
+/// The first 8 bytes of a GCT.
+pub const MAGIC: [u8; 8] = [0x00, 0xD0, 0xC0, 0xDE, 0x00, 0xD0, 0xC0, 0xDE];
+/// The line ending a GCT's codes.
+pub const END: Line = Line(0xF000_0000, 0);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GctError {
+    /// The file doesn't start with `00D0C0DE 00D0C0DE`
+    Magic,
+    /// The file isn't whole lines: this many bytes are left over
+    Partial(usize),
+    /// The code at line `line` needs more lines than there are
+    Truncated { line: usize, code: u32 },
+    /// No `F0000000 00000000` ends the codes
+    NoEnd,
+    /// Lines after the end
+    Trailing(usize),
+}
+
+impl fmt::Display for GctError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Magic => write!(f, "not a GCT: no 00D0C0DE 00D0C0DE header"),
+            Self::Partial(n) => write!(f, "{} byte(s) left over after the last line", n),
+            Self::Truncated { line, code } => write!(
+                f,
+                "the {:02X} code at line {} needs more lines than there are",
+                code, line
+            ),
+            Self::NoEnd => write!(f, "no F0000000 00000000 ends the codes"),
+            Self::Trailing(n) => write!(f, "{} line(s) after the F0000000 00000000 end", n),
+        }
+    }
+}
+
+impl std::error::Error for GctError {}
+
+/// A GCT's codes, each its lines.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Gct {
+    pub codes: Vec<Vec<Line>>,
+}
+
+impl Gct {
+    pub fn parse(bytes: &[u8]) -> Result<Self, GctError> {
+        let body = match bytes.strip_prefix(&MAGIC[..]) {
+            Some(body) => body,
+            None => return Err(GctError::Magic),
+        };
+        let chunks = body.chunks_exact(8);
+        if !chunks.remainder().is_empty() {
+            return Err(GctError::Partial(chunks.remainder().len()));
+        }
+        let word = |b: &[u8]| u32::from_be_bytes([b[0], b[1], b[2], b[3]]);
+        let lines: Vec<_> = chunks.map(|l| Line(word(&l[..4]), word(&l[4..]))).collect();
+
+        // the end is the first F0 line that starts a code, not one in a
+        // code's payload
+        let mut at = 0;
+        while let Some(&line) = lines.get(at) {
+            if line.0 >> 24 == 0xF0 {
+                return match lines.len() - at - 1 {
+                    0 => Gct::from_lines(&lines[..at]),
+                    n => Err(GctError::Trailing(n)),
+                };
+            }
+            at += 1 + extra_lines(line);
+        }
+        Err(GctError::NoEnd)
+    }
+
+    /// `lines` split into codes, without an end line.
+    pub fn from_lines(lines: &[Line]) -> Result<Self, GctError> {
+        let mut codes = Vec::new();
+        let mut code: Vec<Line> = Vec::new();
+        let (mut open, mut based) = (0, false);
+        let mut rest = lines;
+        while let Some(&first) = rest.first() {
+            let count = 1 + extra_lines(first);
+            let this = rest.get(..count).ok_or(GctError::Truncated {
+                line: lines.len() - rest.len() + 1,
+                code: first.0 >> 24,
+            })?;
+            rest = &rest[count..];
+            code.extend(this);
+
+            let parsed = CodeType::parse(this);
+            let carry = matches!(
+                parsed,
+                CodeType::SetRegister { .. }
+                    | CodeType::LoadRegister { .. }
+                    | CodeType::Operation { .. }
+                    | CodeType::RegisterOperation { .. }
+            );
+            match parsed {
+                CodeType::FullTerminator { .. } => {
+                    open = 0;
+                    based = false;
+                }
+                CodeType::Endif { count, .. } => open -= open.min(usize::from(count)),
+                CodeType::If { endif, .. }
+                | CodeType::RegisterIf { endif, .. }
+                | CodeType::CounterIf { endif, .. }
+                | CodeType::AddressRangeIf { endif, .. } => {
+                    open = open - (endif && open > 0) as usize + 1
+                }
+                CodeType::Search { .. } => open += 1,
+                CodeType::LoadAddress { .. }
+                | CodeType::SetAddress { .. }
+                | CodeType::CodeAddress { .. } => based = true,
+                _ => {}
+            }
+            if open == 0 && !based && !carry {
+                codes.push(std::mem::take(&mut code));
+            }
+        }
+        if !code.is_empty() {
+            codes.push(code);
+        }
+        Ok(Gct { codes })
+    }
+
+    /// Every line of every code, without the end line.
+    pub fn lines(&self) -> impl Iterator<Item = Line> + '_ {
+        self.codes.iter().flatten().copied()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        for Line(a, b) in self.lines().chain(Some(END)) {
+            out.extend(a.to_be_bytes());
+            out.extend(b.to_be_bytes());
+        }
+        out
+    }
+
+    /// Drops the codes that are the same as one before, giving how many.
+    pub fn dedupe(&mut self) -> usize {
+        let before = self.codes.len();
+        let mut seen: Vec<Vec<Line>> = Vec::new();
+        self.codes.retain(|code| match seen.contains(code) {
+            true => false,
+            false => {
+                seen.push(code.clone());
+                true
+            }
+        });
+        before - self.codes.len()
+    }
+
+    /// `gcts`' codes in turn, without duplicates, and how many were dropped.
+    pub fn merge(gcts: &[Gct]) -> (Gct, usize) {
+        let mut merged = Gct {
+            codes: gcts.iter().flat_map(|g| g.codes.clone()).collect(),
+        };
+        let dropped = merged.dedupe();
+        (merged, dropped)
+    }
+
+    /// The codes as a code list for the game `game_id`, named by number.
+    pub fn to_list(&self, game_id: &str) -> CodeList {
+        CodeList {
+            game_id: game_id.to_string(),
+            title: String::new(),
+            codes: self
+                .codes
+                .iter()
+                .enumerate()
+                .map(|(i, lines)| NamedCode {
+                    name: format!("Code {}", i + 1),
+                    lines: lines.clone(),
+                    notes: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Something found checking a code, at its line numbered from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub line: usize,
+    /// Whether the code can't be right, rather than just likely wrong
+    pub error: bool,
+    pub message: String,
+}
+
+/// What became of a code built into a GCT.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Included,
+    /// Left out for an error
+    Skipped,
+    /// Left out as the same as the code of this name
+    Duplicate(String),
+}
+
+/// A code of a list built into a GCT, and what was found checking it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub lines: usize,
+    pub outcome: Outcome,
+    pub findings: Vec<Finding>,
+}
+
+/// A GCT built from a code list, and its manifest: what each code of the
+/// list became.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Build {
+    pub title: String,
+    pub gct: Gct,
+    pub entries: Vec<Entry>,
+}
+
+/// What's wrong with `lines` as a code: the checks `cheat tidy` makes,
+/// and where it writes on `console`.
+fn findings(lines: &[Line], console: Console) -> Vec<Finding> {
+    let mut found: Vec<_> = tidy::check_code(lines)
+        .into_iter()
+        .map(|(line, issue)| Finding {
+            line,
+            error: issue.is_error(),
+            message: issue.to_string(),
+        })
+        .collect();
+    // a code the checks above find truncated doesn't split into types
+    if let Ok(codes) = gecko::types(lines) {
+        for (at, problem) in memory_map::check_codes(&codes, console) {
+            found.push(Finding {
+                line: lines.iter().position(|&l| l == at).map_or(1, |i| i + 1),
+                error: problem.is_error(),
+                message: problem.to_string(),
+            });
+        }
+    }
+    found.sort_by_key(|f| f.line);
+    found
+}
+
+/// A GCT of `codes`, each checked for `console`, leaving out those with
+/// errors and those the same as one before.
+pub fn build(title: &str, codes: &[NamedCode], console: Console) -> Build {
+    let mut gct = Gct::default();
+    let mut entries: Vec<Entry> = Vec::new();
+    for code in codes {
+        let findings = findings(&code.lines, console);
+        let same = entries
+            .iter()
+            .zip(codes)
+            .find(|(e, c)| e.outcome == Outcome::Included && c.lines == code.lines);
+        let outcome = match same {
+            _ if findings.iter().any(|f| f.error) => Outcome::Skipped,
+            Some((entry, _)) => Outcome::Duplicate(entry.name.clone()),
+            None => Outcome::Included,
+        };
+        if outcome == Outcome::Included {
+            gct.codes.push(code.lines.clone());
+        }
+        entries.push(Entry {
+            name: code.name.clone(),
+            lines: code.lines.len(),
+            outcome,
+            findings,
+        });
+    }
+    Build {
+        title: title.to_string(),
+        gct,
+        entries,
+    }
+}
+
+impl Build {
+    /// Whether any code was left out for an error.
+    pub fn skipped_any(&self) -> bool {
+        self.entries.iter().any(|e| e.outcome == Outcome::Skipped)
+    }
+}
+
+/// The manifest: a line for the GCT, then one per code with what became of
+/// it, and what was found checking it under that.
+impl fmt::Display for Build {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let included = self
+            .entries
+            .iter()
+            .filter(|e| e.outcome == Outcome::Included);
+        writeln!(
+            f,
+            "{}{}{} of {} code(s), {} line(s), {} bytes",
+            self.title,
+            if self.title.is_empty() { "" } else { ": " },
+            included.count(),
+            self.entries.len(),
+            self.gct.lines().count(),
+            self.gct.to_bytes().len()
+        )?;
+        for entry in &self.entries {
+            let outcome = match entry.outcome {
+                Outcome::Included => "included ",
+                Outcome::Skipped => "skipped  ",
+                Outcome::Duplicate(_) => "duplicate",
+            };
+            write!(f, "  {}  {} ({} line(s))", outcome, entry.name, entry.lines)?;
+            match &entry.outcome {
+                Outcome::Duplicate(of) => writeln!(f, ", the same as {}", of)?,
+                _ => writeln!(f)?,
+            }
+            for finding in &entry.findings {
+                writeln!(
+                    f,
+                    "             line {}: {}: {}",
+                    finding.line,
+                    if finding.error { "error" } else { "warning" },
+                    finding.message
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gct() {
+        let lives = vec![Line(0x0400_3100, 0x3860_0001)];
+        // an if, its write and the terminator, then a C2 whose payload
+        // has an F0 line in it
+        let jump = vec![
+            Line(0x2000_3100, 1),
+            Line(0x0400_3104, 2),
+            Line(0xE000_0000, 0x8000_8000),
+        ];
+        let asm = vec![Line(0xC200_3200, 1), Line(0xF000_0000, 0)];
+        let gct = Gct {
+            codes: vec![lives.clone(), jump.clone(), asm.clone()],
+        };
+        let bytes = gct.to_bytes();
+        assert_eq!(&bytes[..8], MAGIC);
+        assert_eq!(&bytes[bytes.len() - 8..], [0xF0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(Gct::parse(&bytes), Ok(gct.clone()));
+
+        let other = Gct {
+            codes: vec![jump.clone(), vec![Line(0x0400_3300, 0)]],
+        };
+        let (merged, dropped) = Gct::merge(&[gct, other]);
+        assert_eq!(dropped, 1);
+        assert_eq!(merged.codes.len(), 4);
+        assert_eq!(merged.to_list("GALE01").codes[3].name, "Code 4");
+
+        assert_eq!(Gct::parse(&bytes[..bytes.len() - 8]), Err(GctError::NoEnd));
+        assert_eq!(Gct::parse(&bytes[8..]), Err(GctError::Magic));
+        assert_eq!(
+            Gct::parse(&[&bytes[..], &bytes[8..16]].concat()),
+            Err(GctError::Trailing(1))
+        );
+
+        let named = |name: &str, lines: &[Line]| NamedCode {
+            name: name.to_string(),
+            lines: lines.to_vec(),
+            notes: Vec::new(),
+        };
+        let codes = [
+            named("Infinite lives", &lives),
+            named("Moon jump", &jump[..2]),
+            named("Lives again", &lives),
+            named("Broken", &[Line(0x0590_0000, 0)]),
+        ];
+        let built = build("GALE01", &codes, Console::GameCube);
+        assert_eq!(built.gct.codes, [lives.clone(), jump[..2].to_vec()]);
+        assert_eq!(
+            built.to_string(),
+            "GALE01: 2 of 4 code(s), 3 line(s), 40 bytes\n\
+             \x20 included   Infinite lives (1 line(s))\n\
+             \x20 included   Moon jump (2 line(s))\n\
+             \x20            line 2: warning: 1 conditional(s) not ended; end the code with \
+             E0000000 80008000\n\
+             \x20 duplicate  Lives again (1 line(s)), the same as Infinite lives\n\
+             \x20 skipped    Broken (1 line(s))\n\
+             \x20            line 1: error: 81900000 isn't RAM\n"
+        );
+        assert!(built.skipped_any());
+    }
+}
@@ -0,0 +1,153 @@
+//! Intel HEX: a line per record, `:` then hex bytes giving the data
+//! length, a 16-bit address, the record type, the data and a checksum
+//! that makes all the bytes sum to zero. Type 00 is data, 01 the end of
+//! the file, 02 and 04 set the upper address bits (segment or linear),
+//! and 03 and 05 give the start address.
+
+use super::{hex_bytes, merge, RecordError};
+use crate::image::Image;
+
+/// Data bytes per record written.
+const RECORD_DATA: u32 = 16;
+
+/// Whether `bytes` look like Intel HEX, from how they start.
+pub fn detect(bytes: &[u8]) -> bool {
+    let start = bytes.iter().position(|b| !b.is_ascii_whitespace());
+    matches!(start.map(|i| &bytes[i..]), Some([b':', ..]))
+}
+
+/// The records' data as an image. Its entry is the start address if
+/// there was one, and the first data byte's otherwise.
+pub fn parse(text: &str) -> Result<Image, RecordError> {
+    let mut records = Vec::new();
+    let mut entry = None;
+    let mut upper = 0u32;
+    let mut ended = false;
+
+    let mut lines = 0;
+    for (idx, raw) in text.lines().enumerate() {
+        lines = idx + 1;
+        let line = raw.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |message: &str| RecordError {
+            line: idx + 1,
+            message: message.to_string(),
+        };
+        if ended {
+            return Err(error("record after the end-of-file record"));
+        }
+        let digits = line
+            .strip_prefix(':')
+            .ok_or_else(|| error("not an Intel HEX record: should start with `:`"))?;
+        let bytes = hex_bytes(digits).ok_or_else(|| error("odd or non-hex digits"))?;
+        if bytes.len() < 5 || bytes.len() != usize::from(bytes[0]) + 5 {
+            return Err(error("length doesn't match the record"));
+        }
+        let sum = bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+        if sum != 0 {
+            let checksum = bytes[bytes.len() - 1].wrapping_sub(sum);
+            return Err(error(&format!("checksum should be {:02X}", checksum)));
+        }
+
+        let offset = u32::from(u16::from_be_bytes([bytes[1], bytes[2]]));
+        let data = &bytes[4..bytes.len() - 1];
+        let value = || data.iter().fold(0u32, |v, &b| (v << 8) | u32::from(b));
+        match (bytes[3], data.len()) {
+            (0x00, _) => records.push((upper.wrapping_add(offset), data.to_vec())),
+            (0x01, _) => ended = true,
+            (0x02, 2) => upper = value() << 4,
+            (0x04, 2) => upper = value() << 16,
+            (0x03, 4) => entry = Some((value() >> 16 << 4).wrapping_add(value() & 0xFFFF)),
+            (0x05, 4) => entry = Some(value()),
+            (0x02..=0x05, _) => return Err(error("wrong data length for its type")),
+            (ty, _) => return Err(error(&format!("unknown record type {:02X}", ty))),
+        }
+    }
+    if !ended {
+        return Err(RecordError {
+            line: lines,
+            message: "no end-of-file (01) record; is the file cut short?".to_string(),
+        });
+    }
+
+    let sections = merge(records);
+    Ok(Image {
+        entry: entry
+            .or_else(|| sections.first().map(|s| s.addr))
+            .unwrap_or(0),
+        sections,
+    })
+}
+
+fn record(ty: u8, offset: u16, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8];
+    bytes.extend(offset.to_be_bytes());
+    bytes.push(ty);
+    bytes.extend(data);
+    let sum = bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+    bytes.push(sum.wrapping_neg());
+    let digits: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    format!(":{}\n", digits)
+}
+
+/// `bytes` loaded at `base` as Intel HEX: a linear address (04) record
+/// for each 64KB the data touches, data records that stay within one,
+/// a start address (05) of `base` and the end-of-file record.
+pub fn write(bytes: &[u8], base: u32) -> String {
+    let mut out = String::new();
+    let mut upper = None;
+    let mut at = 0;
+    while at < bytes.len() {
+        let addr = base.wrapping_add(at as u32);
+        if upper != Some(addr >> 16) {
+            upper = Some(addr >> 16);
+            out += &record(0x04, 0, &((addr >> 16) as u16).to_be_bytes());
+        }
+        // stop at the next 64KB boundary, or the record's start would wrap
+        let room = 0x1_0000 - (addr & 0xFFFF);
+        let len = (bytes.len() - at).min(RECORD_DATA.min(room) as usize);
+        out += &record(0x00, addr as u16, &bytes[at..at + len]);
+        at += len;
+    }
+    out += &record(0x05, 0, &base.to_be_bytes());
+    out + &record(0x01, 0, &[])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ihex() {
+        let bytes: Vec<u8> = (0..40).collect();
+        let written = write(&bytes, 0x8000_FFF0);
+        assert_eq!(
+            written.lines().take(3).collect::<Vec<_>>(),
+            [
+                ":0200000480007A",
+                ":10FFF000000102030405060708090A0B0C0D0E0F89",
+                ":02000004800179",
+            ]
+        );
+        let image = parse(&written).unwrap();
+        assert_eq!(image.entry, 0x8000_FFF0);
+        assert_eq!(image.sections.len(), 1);
+        assert_eq!(image.sections[0].data, bytes);
+
+        assert!(detect(b"\n:10010000214601360121470136007EFE09D2190140\n"));
+        assert_eq!(
+            parse(":10010000214601360121470136007EFE09D2190141\n")
+                .unwrap_err()
+                .to_string(),
+            "line 1: checksum should be 40"
+        );
+        assert_eq!(
+            parse(":10010000214601360121470136007EFE09D2190140\n")
+                .unwrap_err()
+                .to_string(),
+            "line 1: no end-of-file (01) record; is the file cut short?"
+        );
+    }
+}
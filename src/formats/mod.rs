@@ -0,0 +1,144 @@
+//! The file formats GameCube and Wii code ships in, or that Dolphin dumps
+//! it to, each parsed with its own headers and names kept, and loadable as
+//! an [`Image`](crate::image::Image). S-records and Intel HEX, what
+//! embedded PowerPC flash tools exchange, are here too.
+
+use core::fmt;
+
+use nom::combinator::all_consuming;
+
+use crate::disasm::{self, Endian, Listing, Options, Style};
+use crate::expr::parse_identifier;
+use crate::image::{Image, Section};
+use crate::objdump;
+
+pub mod disc;
+pub mod dol;
+pub mod elf;
+pub mod gct;
+pub mod ihex;
+pub mod ram;
+pub mod rel;
+pub mod srec;
+
+/// A line of a record-per-line format (S-records, Intel HEX) that doesn't
+/// parse or doesn't check out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+/// The bytes a record's hex digits stand for.
+pub(crate) fn hex_bytes(digits: &str) -> Option<Vec<u8>> {
+    if !digits.len().is_multiple_of(2) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Records' data as sections, each record joining the one before if it
+/// carries straight on from it. Flash images are taken as all code.
+pub(crate) fn merge(records: impl IntoIterator<Item = (u32, Vec<u8>)>) -> Vec<Section> {
+    let mut sections: Vec<Section> = Vec::new();
+    for (addr, data) in records {
+        match sections.last_mut() {
+            Some(s) if s.addr.wrapping_add(s.data.len() as u32) == addr => s.data.extend(data),
+            _ => sections.push(Section {
+                addr,
+                data,
+                executable: true,
+            }),
+        }
+    }
+    sections
+}
+
+/// The executable sections of `image` disassembled, each under a comment
+/// with its address range, for formats whose sections have no names.
+pub fn image_listing(image: &Image, options: &Options) -> Listing {
+    let sections = image.sections.iter().filter(|s| s.executable).map(|s| {
+        let end = s.addr.wrapping_add(s.data.len() as u32);
+        let header = format!("{:08x}..{:08x}", s.addr, end);
+        (header.clone(), header, s.clone())
+    });
+    listing(sections, Endian::Big, options)
+}
+
+/// Sections disassembled one after another, given as `(name, header,
+/// section)`. In ppcheat's style each is under a `# header` comment; see
+/// [`disasm::listing`]. Each code symbol inside a section, at a word
+/// boundary, is written as `name:` before its address (or as a comment, if
+/// the parser couldn't read the name back). objdump's style names the
+/// section instead, and gives the symbols as objdump does.
+pub(crate) fn listing(
+    sections: impl IntoIterator<Item = (String, String, Section)>,
+    endian: Endian,
+    options: &Options,
+) -> Listing {
+    let labels = options.symbols.labels();
+    let mut out = Listing::default();
+    for (i, (name, header, section)) in sections.into_iter().enumerate() {
+        if options.style == Style::Objdump {
+            out.extend(objdump::section(&name, &section, endian, &options.symbols));
+            continue;
+        }
+        if i > 0 {
+            out.text.push('\n');
+        }
+        out.text += &format!("# {}\n", header);
+
+        let mut here: Vec<_> = labels
+            .iter()
+            .map(|&(addr, name)| (addr.wrapping_sub(section.addr), name))
+            .filter(|&(offset, _)| offset % 4 == 0 && (offset as usize) < section.data.len())
+            .collect();
+        here.sort();
+        here.dedup();
+        let mut start = 0;
+        for (offset, name) in here {
+            let offset = offset as usize;
+            out.extend(piece(&section, start, offset, endian, options));
+            start = offset;
+            out.text += &match all_consuming(parse_identifier)(name) {
+                Ok(_) => format!("{}:\n", name),
+                Err(_) => format!("# {}:\n", name),
+            };
+        }
+        out.extend(piece(&section, start, section.data.len(), endian, options));
+    }
+    out
+}
+
+/// [`disasm::listing`] of `section`'s bytes `start..end`.
+fn piece(
+    section: &Section,
+    start: usize,
+    end: usize,
+    endian: Endian,
+    options: &Options,
+) -> Listing {
+    match start < end {
+        true => disasm::listing(
+            &Section {
+                addr: section.addr.wrapping_add(start as u32),
+                data: section.data[start..end].to_vec(),
+                executable: section.executable,
+            },
+            endian,
+            options,
+        ),
+        false => Listing::default(),
+    }
+}
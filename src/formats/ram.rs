@@ -0,0 +1,165 @@
+//! Dolphin's RAM dumps, `mem1.raw` and (on Wii) `mem2.raw`: each bank's
+//! physical memory from its first byte, nothing else. Games see MEM1 at
+//! 0x80000000 and MEM2 at 0x90000000 through the cached mapping, and at
+//! 0xC0000000 and 0xD0000000 uncached; any of those, or the physical
+//! address, reads the same byte.
+
+use crate::disasm::{Endian, Listing, Options};
+use crate::image::{Image, ImageError, Section};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Bank {
+    /// The 24MB of 1T-SRAM both consoles have
+    Mem1,
+    /// The Wii's 64MB of GDDR3
+    Mem2,
+}
+
+impl Bank {
+    /// The bank's cached virtual address.
+    pub fn base(self) -> u32 {
+        match self {
+            Self::Mem1 => 0x8000_0000,
+            Self::Mem2 => 0x9000_0000,
+        }
+    }
+
+    pub fn size(self) -> u32 {
+        match self {
+            Self::Mem1 => 0x0180_0000,
+            Self::Mem2 => 0x0400_0000,
+        }
+    }
+
+    /// `mem1`, as Dolphin names the dump.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Mem1 => "mem1",
+            Self::Mem2 => "mem2",
+        }
+    }
+}
+
+/// `addr` in the cached mapping: the physical, cached and uncached
+/// addresses of a byte all map to the cached one.
+pub fn cached(addr: u32) -> u32 {
+    (addr & 0x1FFF_FFFF) | 0x8000_0000
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RamDump {
+    pub mem1: Option<Vec<u8>>,
+    pub mem2: Option<Vec<u8>>,
+}
+
+impl RamDump {
+    /// The dumps of either bank or both. One shorter than its bank is
+    /// allowed (the rest just isn't loaded); one longer isn't that bank's.
+    pub fn new(mem1: Option<Vec<u8>>, mem2: Option<Vec<u8>>) -> Result<Self, ImageError> {
+        if mem1
+            .as_ref()
+            .is_some_and(|m| m.len() > Bank::Mem1.size() as usize)
+        {
+            return Err(ImageError::BadHeader(
+                "mem1 dump is longer than MEM1's 24MB",
+            ));
+        }
+        if mem2
+            .as_ref()
+            .is_some_and(|m| m.len() > Bank::Mem2.size() as usize)
+        {
+            return Err(ImageError::BadHeader(
+                "mem2 dump is longer than MEM2's 64MB",
+            ));
+        }
+        Ok(RamDump { mem1, mem2 })
+    }
+
+    fn banks(&self) -> impl Iterator<Item = (Bank, &Vec<u8>)> {
+        let mem1 = self.mem1.as_ref().map(|m| (Bank::Mem1, m));
+        let mem2 = self.mem2.as_ref().map(|m| (Bank::Mem2, m));
+        mem1.into_iter().chain(mem2)
+    }
+
+    /// Each loaded bank at its cached address, all of it taken as code.
+    pub fn image(&self) -> Image {
+        Image {
+            entry: Bank::Mem1.base(),
+            sections: self
+                .banks()
+                .map(|(bank, data)| Section {
+                    addr: bank.base(),
+                    data: data.clone(),
+                    executable: true,
+                })
+                .collect(),
+        }
+    }
+
+    /// `size` bytes at `addr`, in any mapping, and the bank they're in.
+    pub fn read(&self, addr: u32, size: u32) -> Option<(Bank, &[u8])> {
+        let addr = cached(addr);
+        self.banks().find_map(|(bank, data)| {
+            let start = addr.checked_sub(bank.base())? as usize;
+            let end = start.checked_add(size as usize)?;
+            data.get(start..end).map(|bytes| (bank, bytes))
+        })
+    }
+
+    /// `size` bytes at `addr` disassembled, under a comment naming the bank.
+    /// Addresses are shown in the cached mapping.
+    pub fn listing(&self, addr: u32, size: u32, options: &Options) -> Option<Listing> {
+        let (bank, bytes) = self.read(addr, size)?;
+        let addr = cached(addr);
+        let section = Section {
+            addr,
+            data: bytes.to_vec(),
+            executable: true,
+        };
+        let header = format!(
+            "{} {:08x}..{:08x}",
+            bank.name(),
+            addr,
+            addr.wrapping_add(size)
+        );
+        let name = bank.name().to_string();
+        Some(super::listing(
+            Some((name, header, section)),
+            Endian::Big,
+            options,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ram_dump() {
+        let mut mem1 = vec![0; 0x4000];
+        mem1[0x3100..0x3108].copy_from_slice(&[0x38, 0x60, 0x00, 0x01, 0x54, 0x00, 0x84, 0x3e]);
+        let dump = RamDump::new(Some(mem1), Some(vec![0xAB; 0x10])).unwrap();
+
+        assert_eq!(
+            dump.listing(0xC000_3100, 8, &Options::default())
+                .unwrap()
+                .text,
+            "# mem1 80003100..80003108\n\
+             80003100: 38 60 00 01  li r3,1\n\
+             80003104: 54 00 84 3e  srwi r0,r0,16\n"
+        );
+        assert_eq!(
+            dump.read(0x1000_0008, 2),
+            Some((Bank::Mem2, &[0xAB, 0xAB][..]))
+        );
+        assert_eq!(dump.read(0x8000_3ffe, 4), None);
+        assert_eq!(dump.image().read(0x9000_0000, 1), Some(&[0xAB][..]));
+        assert_eq!(
+            RamDump::new(None, Some(vec![0; 0x0400_0001]))
+                .unwrap_err()
+                .to_string(),
+            "bad header: mem2 dump is longer than MEM2's 64MB"
+        );
+    }
+}
@@ -0,0 +1,464 @@
+//! REL, the relocatable modules GameCube and Wii games load at runtime
+//! (with `OSLink`) for most of their game logic. A module is a header, a
+//! section table, the section contents, and per imported module a list of
+//! relocations to apply once it's been placed. Module 0 is the main DOL,
+//! whose addresses are absolute.
+//!
+//! Relocations are a packed stream: each entry moves a cursor forward by
+//! its `offset` within the current section, and `R_DOLPHIN_SECTION` picks
+//! the section.
+
+use core::fmt;
+
+use crate::disasm::{Endian, Listing, Options};
+use crate::image::{be16, be32, slice, Image, ImageError, Section};
+
+/// How a relocation patches the word or half it points at.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RelocKind {
+    /// `R_PPC_ADDR32`: the whole word
+    Addr32,
+    /// `R_PPC_ADDR24`: an absolute branch's LI field
+    Addr24,
+    /// `R_PPC_ADDR16`: a half word
+    Addr16,
+    /// `R_PPC_ADDR16_LO`: `@l`
+    Lo,
+    /// `R_PPC_ADDR16_HI`: `@h`
+    Hi,
+    /// `R_PPC_ADDR16_HA`: `@ha`
+    Ha,
+    /// `R_PPC_ADDR14`: an absolute conditional branch's BD field
+    Addr14,
+    /// `R_PPC_REL24`: `b` and `bl`
+    Rel24,
+    /// `R_PPC_REL14`: `bc`
+    Rel14,
+    /// `R_DOLPHIN_NOP`: only moves the cursor, for gaps over 0xFFFF bytes
+    Nop,
+    /// `R_DOLPHIN_SECTION`: continue in another section, at its start
+    Section,
+    /// `R_DOLPHIN_END`: the end of an import's list
+    End,
+}
+
+impl RelocKind {
+    fn from_type(ty: u8) -> Option<Self> {
+        Some(match ty {
+            1 => Self::Addr32,
+            2 => Self::Addr24,
+            3 => Self::Addr16,
+            4 => Self::Lo,
+            5 => Self::Hi,
+            6 => Self::Ha,
+            7..=9 => Self::Addr14,
+            10 => Self::Rel24,
+            11..=13 => Self::Rel14,
+            0 | 201 => Self::Nop,
+            202 => Self::Section,
+            203 => Self::End,
+            _ => return None,
+        })
+    }
+}
+
+/// One relocation from the stream, with the cursor already applied.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Relocation {
+    pub kind: RelocKind,
+    /// The section being patched, and where in it
+    pub section: u8,
+    pub offset: u32,
+    /// The section of the imported module the target is in; meaningless
+    /// for module 0
+    pub target_section: u8,
+    /// Offset from that section, or for module 0 the address itself
+    pub addend: u32,
+}
+
+/// The relocations against one module.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Import {
+    pub module: u32,
+    pub relocations: Vec<Relocation>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelSection {
+    pub index: usize,
+    /// File offset, 0 for BSS
+    pub offset: u32,
+    pub size: u32,
+    pub executable: bool,
+    /// Empty for BSS
+    pub data: Vec<u8>,
+}
+
+impl RelSection {
+    pub fn is_bss(&self) -> bool {
+        self.offset == 0 && self.size != 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rel {
+    pub id: u32,
+    pub version: u32,
+    /// Every slot of the section table, empty ones included, so indices
+    /// match what relocations say
+    pub sections: Vec<RelSection>,
+    pub imports: Vec<Import>,
+    pub bss_size: u32,
+    /// Alignment of the BSS, 32 before version 2
+    pub bss_align: u32,
+    /// `_prolog`, `_epilog` and `_unresolved` as (section, offset)
+    pub prolog: (u8, u32),
+    pub epilog: (u8, u32),
+    pub unresolved: (u8, u32),
+    /// The file's length, which BSS is placed after
+    pub file_size: u32,
+}
+
+/// The module placed at a base address, relocations applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Linked {
+    pub image: Image,
+    /// Where each section of the table ended up, `None` for empty slots
+    pub addresses: Vec<Option<u32>>,
+    /// Relocations against modules other than this one and the DOL, whose
+    /// addresses aren't known
+    pub unresolved: Vec<Import>,
+}
+
+fn u8_at(bytes: &[u8], at: usize) -> Result<u8, ImageError> {
+    bytes.get(at).copied().ok_or(ImageError::Truncated)
+}
+
+fn relocations(bytes: &[u8], mut at: usize) -> Result<Vec<Relocation>, ImageError> {
+    let mut relocations = Vec::new();
+    let (mut section, mut offset) = (0, 0u32);
+    loop {
+        let ty = u8_at(bytes, at + 2)?;
+        let kind = RelocKind::from_type(ty).ok_or(ImageError::BadHeader(
+            "unknown relocation type (only R_PPC_* types the games use are supported)",
+        ))?;
+        offset = offset.wrapping_add(u32::from(be16(bytes, at)?));
+        let target_section = u8_at(bytes, at + 3)?;
+        match kind {
+            RelocKind::End => return Ok(relocations),
+            RelocKind::Section => {
+                section = target_section;
+                offset = 0;
+            }
+            RelocKind::Nop => {}
+            kind => relocations.push(Relocation {
+                kind,
+                section,
+                offset,
+                target_section,
+                addend: be32(bytes, at + 4)?,
+            }),
+        }
+        at += 8;
+    }
+}
+
+impl Rel {
+    pub fn parse(bytes: &[u8]) -> Result<Self, ImageError> {
+        let version = be32(bytes, 0x1C)?;
+        if !(1..=3).contains(&version) {
+            return Err(ImageError::BadHeader("version isn't 1, 2 or 3"));
+        }
+        let count = be32(bytes, 0x0C)? as usize;
+        let table = be32(bytes, 0x10)? as usize;
+        if count > 0x100 {
+            return Err(ImageError::BadHeader("more than 256 sections"));
+        }
+
+        let mut sections = Vec::with_capacity(count);
+        for index in 0..count {
+            let entry = table + 8 * index;
+            let (offset, size) = (be32(bytes, entry)?, be32(bytes, entry + 4)?);
+            let start = offset & !1;
+            sections.push(RelSection {
+                index,
+                offset: start,
+                size,
+                executable: offset & 1 == 1,
+                data: match start {
+                    0 => Vec::new(),
+                    start => slice(bytes, start, size)?,
+                },
+            });
+        }
+
+        let (imports_at, imports_size) = (be32(bytes, 0x28)? as usize, be32(bytes, 0x2C)? as usize);
+        let mut imports = Vec::new();
+        for entry in (imports_at..imports_at + imports_size).step_by(8) {
+            imports.push(Import {
+                module: be32(bytes, entry)?,
+                relocations: relocations(bytes, be32(bytes, entry + 4)? as usize)?,
+            });
+        }
+
+        Ok(Rel {
+            id: be32(bytes, 0)?,
+            version,
+            sections,
+            imports,
+            bss_size: be32(bytes, 0x20)?,
+            bss_align: match version {
+                1 => 32,
+                _ => be32(bytes, 0x44)?.max(1),
+            },
+            prolog: (u8_at(bytes, 0x30)?, be32(bytes, 0x34)?),
+            epilog: (u8_at(bytes, 0x31)?, be32(bytes, 0x38)?),
+            unresolved: (u8_at(bytes, 0x32)?, be32(bytes, 0x3C)?),
+            file_size: bytes.len() as u32,
+        })
+    }
+
+    /// Section addresses for the module loaded at `base`: each section
+    /// stays at its file offset, and BSS goes after the end of the file.
+    pub fn addresses(&self, base: u32) -> Vec<Option<u32>> {
+        let end = base.wrapping_add(self.file_size);
+        let bss = end.wrapping_add(self.bss_align - 1) / self.bss_align * self.bss_align;
+        self.sections
+            .iter()
+            .map(|s| match (s.offset, s.size) {
+                (_, 0) => None,
+                (0, _) => Some(bss),
+                (offset, _) => Some(base.wrapping_add(offset)),
+            })
+            .collect()
+    }
+
+    /// The module loaded at `base`, with its relocations against itself and
+    /// the DOL applied.
+    pub fn link(&self, base: u32) -> Result<Linked, ImageError> {
+        let addresses = self.addresses(base);
+        let mut data: Vec<Vec<u8>> = self.sections.iter().map(|s| s.data.clone()).collect();
+        let mut unresolved = Vec::new();
+
+        for import in &self.imports {
+            if import.module != 0 && import.module != self.id {
+                unresolved.push(import.clone());
+                continue;
+            }
+            for r in &import.relocations {
+                let section = addresses.get(r.section as usize).copied().flatten();
+                let place = section.unwrap_or(0).wrapping_add(r.offset);
+                let error = |why| ImageError::Relocation { addr: place, why };
+                let target = match import.module {
+                    0 => r.addend,
+                    _ => addresses
+                        .get(r.target_section as usize)
+                        .copied()
+                        .flatten()
+                        .ok_or_else(|| error("target section is empty"))?
+                        .wrapping_add(r.addend),
+                };
+                let bytes = data
+                    .get_mut(r.section as usize)
+                    .filter(|d| !d.is_empty())
+                    .ok_or_else(|| error("patches a section with no data"))?;
+                apply(bytes, r.offset as usize, r.kind, target, place).map_err(error)?;
+            }
+        }
+
+        let sections = self
+            .sections
+            .iter()
+            .zip(&addresses)
+            .zip(data)
+            .filter(|((s, _), _)| !s.is_bss())
+            .filter_map(|((s, addr), data)| {
+                Some(Section {
+                    addr: (*addr)?,
+                    data,
+                    executable: s.executable,
+                })
+            })
+            .collect();
+        let entry = addresses
+            .get(self.prolog.0 as usize)
+            .copied()
+            .flatten()
+            .map_or(0, |addr| addr.wrapping_add(self.prolog.1));
+        Ok(Linked {
+            image: Image { entry, sections },
+            addresses,
+            unresolved,
+        })
+    }
+}
+
+/// Patches `bytes` at `at` so it refers to `target`, as seen from `place`.
+fn apply(
+    bytes: &mut [u8],
+    at: usize,
+    kind: RelocKind,
+    target: u32,
+    place: u32,
+) -> Result<(), &'static str> {
+    let half = |bytes: &mut [u8], value: u32| -> Result<(), &'static str> {
+        let field = bytes
+            .get_mut(at..at + 2)
+            .ok_or("past the end of its section")?;
+        field.copy_from_slice(&(value as u16).to_be_bytes());
+        Ok(())
+    };
+    let word = |bytes: &mut [u8], keep: u32, value: u32| -> Result<(), &'static str> {
+        let field = bytes
+            .get_mut(at..at + 4)
+            .ok_or("past the end of its section")?;
+        let old = u32::from_be_bytes([field[0], field[1], field[2], field[3]]);
+        field.copy_from_slice(&((old & keep) | (value & !keep)).to_be_bytes());
+        Ok(())
+    };
+    let disp = i64::from(target) - i64::from(place);
+    match kind {
+        RelocKind::Addr32 => word(bytes, 0, target),
+        RelocKind::Addr24 => word(bytes, 0xFC00_0003, target),
+        RelocKind::Addr16 | RelocKind::Lo => half(bytes, target),
+        RelocKind::Hi => half(bytes, target >> 16),
+        RelocKind::Ha => half(bytes, target.wrapping_add(0x8000) >> 16),
+        RelocKind::Addr14 => word(bytes, 0xFFFF_0003, target),
+        RelocKind::Rel24 if !(-(1 << 25)..1 << 25).contains(&disp) => {
+            Err("branch target out of ±32MB reach")
+        }
+        RelocKind::Rel24 => word(bytes, 0xFC00_0003, disp as u32),
+        RelocKind::Rel14 if !(-(1 << 15)..1 << 15).contains(&disp) => {
+            Err("branch target out of ±32KB reach")
+        }
+        RelocKind::Rel14 => word(bytes, 0xFFFF_0003, disp as u32),
+        RelocKind::Nop | RelocKind::Section | RelocKind::End => Ok(()),
+    }
+}
+
+impl Linked {
+    /// The executable sections disassembled at their linked addresses.
+    pub fn listing(&self, options: &Options) -> Listing {
+        let sections = self.image.sections.iter().filter(|s| s.executable);
+        super::listing(
+            sections.map(|s| {
+                let index = self.addresses.iter().position(|&a| a == Some(s.addr));
+                let name = format!("section {}", index.unwrap_or(0));
+                let header = format!(
+                    "{} {:08x}..{:08x}",
+                    name,
+                    s.addr,
+                    s.addr.wrapping_add(s.data.len() as u32)
+                );
+                (name, header, s.clone())
+            }),
+            Endian::Big,
+            options,
+        )
+    }
+}
+
+impl fmt::Display for Import {
+    /// `module 3: 12 relocations`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "module {}: {} relocation{}",
+            self.module,
+            self.relocations.len(),
+            if self.relocations.len() == 1 { "" } else { "s" }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Opcode;
+
+    fn put(bytes: &mut Vec<u8>, at: usize, value: u32) {
+        if bytes.len() < at + 4 {
+            bytes.resize(at + 4, 0);
+        }
+        bytes[at..at + 4].copy_from_slice(&value.to_be_bytes());
+    }
+
+    /// A relocation entry: cursor offset, type, section, addend.
+    fn reloc(bytes: &mut Vec<u8>, offset: u16, ty: u8, section: u8, addend: u32) {
+        bytes.extend(offset.to_be_bytes());
+        bytes.extend([ty, section]);
+        bytes.extend(addend.to_be_bytes());
+    }
+
+    #[test]
+    fn test_rel() {
+        let mut bytes = Vec::new();
+        // module 5, version 3, three sections with the table at 0x4c
+        for &(at, value) in &[
+            (0x00, 5),
+            (0x0C, 3),
+            (0x10, 0x4C),
+            (0x1C, 3),
+            (0x20, 0x10),
+            (0x28, 0x70),
+            (0x2C, 0x10),
+            (0x44, 32),
+        ] {
+            put(&mut bytes, at, value);
+        }
+        bytes[0x30] = 1;
+        // null section, text at 0x64, BSS
+        for &(at, value) in &[(0x54, 0x64 | 1), (0x58, 12), (0x5C, 0), (0x60, 0x10)] {
+            put(&mut bytes, at, value);
+        }
+        // lis r3,0; addi r3,r3,0; bl .
+        for &(at, value) in &[
+            (0x64, 0x3C60_0000),
+            (0x68, 0x3863_0000),
+            (0x6C, 0x4800_0001),
+        ] {
+            put(&mut bytes, at, value);
+        }
+        // imports: this module at 0x80, the DOL at 0xa0
+        for &(at, value) in &[(0x70, 5), (0x74, 0x80), (0x78, 0), (0x7C, 0xA0)] {
+            put(&mut bytes, at, value);
+        }
+        reloc(&mut bytes, 0, 202, 1, 0);
+        reloc(&mut bytes, 2, 6, 2, 4);
+        reloc(&mut bytes, 4, 4, 2, 4);
+        reloc(&mut bytes, 0, 203, 0, 0);
+        reloc(&mut bytes, 0, 202, 1, 0);
+        reloc(&mut bytes, 8, 10, 0, 0x8000_3100);
+        reloc(&mut bytes, 0, 203, 0, 0);
+
+        let rel = Rel::parse(&bytes).unwrap();
+        assert_eq!(rel.imports[0].to_string(), "module 5: 2 relocations");
+        assert_eq!(rel.imports[1].relocations[0].offset, 8);
+
+        // BSS lands at the first 32-byte boundary after the file
+        let linked = rel.link(0x8050_0000).unwrap();
+        assert_eq!(
+            linked.addresses,
+            [None, Some(0x8050_0064), Some(0x8050_00C0)]
+        );
+        assert_eq!(linked.image.entry, 0x8050_0064);
+        let ops: Vec<_> = linked.image.sections[0]
+            .data
+            .chunks(4)
+            .map(|w| {
+                let word = u32::from_be_bytes([w[0], w[1], w[2], w[3]]);
+                Opcode::decode(word).unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(ops, ["lis r3,0x8050", "addi r3,r3,196", "bl .-5230444"]);
+        assert!(linked
+            .listing(&Options::default())
+            .text
+            .starts_with("# section 1 80500064..80500070\n"));
+
+        assert_eq!(
+            rel.link(0x8400_0000).unwrap_err().to_string(),
+            "relocation at 8400006c: branch target out of ±32MB reach"
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! Motorola S-records: a line per record, `S`, the type digit, then hex
+//! bytes giving the count of bytes left, the address, the data and a
+//! checksum (the ones' complement of the low byte of their sum). S1, S2
+//! and S3 carry data at 16-, 24- and 32-bit addresses; S7, S8 and S9 give
+//! the start address; S5 and S6 count the data records; S0 is a header.
+
+use super::{hex_bytes, merge, RecordError};
+use crate::image::Image;
+
+/// Data bytes per record written.
+const RECORD_DATA: usize = 16;
+
+/// Whether `bytes` look like S-records, from how they start.
+pub fn detect(bytes: &[u8]) -> bool {
+    let start = bytes.iter().position(|b| !b.is_ascii_whitespace());
+    matches!(start.map(|i| &bytes[i..]), Some([b'S', b'0'..=b'9', ..]))
+}
+
+/// The records' data as an image. Its entry is the start address if
+/// there was one, and the first data byte's otherwise.
+pub fn parse(text: &str) -> Result<Image, RecordError> {
+    let mut records = Vec::new();
+    let mut entry = None;
+
+    for (idx, raw) in text.lines().enumerate() {
+        let line = raw.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |message: &str| RecordError {
+            line: idx + 1,
+            message: message.to_string(),
+        };
+        let ty = match line.as_bytes() {
+            [b'S', ty @ b'0'..=b'9', ..] => ty - b'0',
+            _ => return Err(error("not an S-record: should start with S0 to S9")),
+        };
+        let bytes = hex_bytes(&line[2..]).ok_or_else(|| error("odd or non-hex digits"))?;
+        let (&count, rest) = bytes
+            .split_first()
+            .ok_or_else(|| error("record has no count"))?;
+        if usize::from(count) != rest.len() {
+            return Err(error(&format!(
+                "count says {} bytes, but {} follow",
+                count,
+                rest.len()
+            )));
+        }
+        let sum = bytes[..bytes.len() - 1]
+            .iter()
+            .fold(0u8, |sum, &b| sum.wrapping_add(b));
+        if !sum != *rest.last().ok_or_else(|| error("record has no checksum"))? {
+            return Err(error(&format!("checksum should be {:02X}", !sum)));
+        }
+
+        let width = match ty {
+            0 | 1 | 5 | 9 => 2,
+            2 | 6 | 8 => 3,
+            3 | 7 => 4,
+            _ => return Err(error("S4 records are reserved")),
+        };
+        if rest.len() < width + 1 {
+            return Err(error("record is too short for its address"));
+        }
+        let addr = rest[..width]
+            .iter()
+            .fold(0u32, |addr, &b| (addr << 8) | u32::from(b));
+        let data = &rest[width..rest.len() - 1];
+        match ty {
+            1..=3 => records.push((addr, data.to_vec())),
+            5 | 6 if addr as usize != records.len() => {
+                return Err(error(&format!(
+                    "count record says {} data records, but there were {}",
+                    addr,
+                    records.len()
+                )))
+            }
+            7..=9 => entry = Some(addr),
+            _ => {}
+        }
+    }
+
+    let sections = merge(records);
+    Ok(Image {
+        entry: entry
+            .or_else(|| sections.first().map(|s| s.addr))
+            .unwrap_or(0),
+        sections,
+    })
+}
+
+/// One record: its type, address bytes and data, with count and checksum.
+fn record(ty: u8, addr: &[u8], data: &[u8]) -> String {
+    let mut bytes = vec![(addr.len() + data.len() + 1) as u8];
+    bytes.extend(addr);
+    bytes.extend(data);
+    let sum = bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+    bytes.push(!sum);
+    let digits: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    format!("S{}{}\n", ty, digits)
+}
+
+/// `bytes` loaded at `base` as S-records: an S0 header holding `name`, S3
+/// data records, an S5 count and an S7 start address of `base`.
+pub fn write(bytes: &[u8], base: u32, name: &str) -> String {
+    let mut out = record(0, &[0, 0], name.as_bytes());
+    let chunks = bytes.chunks(RECORD_DATA);
+    let count = chunks.len();
+    for (i, chunk) in chunks.enumerate() {
+        let addr = base.wrapping_add((i * RECORD_DATA) as u32);
+        out += &record(3, &addr.to_be_bytes(), chunk);
+    }
+    out += &match count {
+        0..=0xFFFF => record(5, &(count as u16).to_be_bytes(), &[]),
+        _ => record(6, &(count as u32).to_be_bytes()[1..], &[]),
+    };
+    out + &record(7, &base.to_be_bytes(), &[])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_srec() {
+        let srec = "S00F000068656C6C6F202020202000003C\n\
+                    S11F00007C0802A6900100049421FFF07C6C1B787C8C23783C6000003863000026\n\
+                    S5030001FB\n\
+                    S9030000FC\n";
+        assert!(detect(srec.as_bytes()));
+        let image = parse(srec).unwrap();
+        assert_eq!(image.sections.len(), 1);
+        assert_eq!(image.sections[0].data[..4], [0x7c, 0x08, 0x02, 0xa6]);
+
+        let written = write(&image.sections[0].data, 0x8000_3100, "patch");
+        assert!(written.starts_with("S00800007061746368E7\nS3158000310"));
+        let image = parse(&written).unwrap();
+        assert_eq!(image.entry, 0x8000_3100);
+        assert_eq!(image.sections[0].data.len(), 28);
+
+        assert_eq!(
+            parse("S9030000FD\n").unwrap_err().to_string(),
+            "line 1: checksum should be FC"
+        );
+    }
+}
@@ -0,0 +1,1778 @@
+//! Gecko codes, the cheat format of the codehandler USB Gecko, Ocarina,
+//! Nintendont and Dolphin run: a list of `XXXXXXXX YYYYYYYY` lines, the top
+//! byte of the first word picking what the line does.
+//!
+//! Addresses are offsets from the base address, 0x80000000 unless a code
+//! sets it otherwise, in the low 25 bits of that word, so a write reaches
+//! 0x80000000 to 0x81FFFFFF: all of MEM1, cached.
+//!
+//! A C2 code inserts instructions at a hook: the codehandler overwrites
+//! the hooked instruction with a branch to the code's payload, which runs
+//! where it sits in the code list and ends in a word the codehandler makes
+//! a branch back to the instruction after the hook.
+
+use core::fmt;
+use std::iter;
+
+use crate::disasm::{instruction, Endian, HexDump, Listing, Options, Style};
+use crate::formats;
+use crate::image::Section;
+use crate::Width;
+
+/// The lowest address a write off the default base address reaches.
+pub const BASE_ADDRESS: u32 = 0x8000_0000;
+/// The bits of the first word holding the address.
+pub(crate) const ADDRESS_MASK: u32 = 0x01FF_FFFF;
+const NOP: u32 = 0x6000_0000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeckoError {
+    /// Outside 0x80000000..0x82000000
+    Address(u32),
+    /// A word write that isn't word-aligned
+    Misaligned(u32),
+    /// Bytes left over after the last whole word
+    Partial(usize),
+    /// A fill of more values than one line's count holds
+    Fill(u32),
+    /// A C2 code with no instructions
+    Empty,
+    /// A string write of no bytes
+    NoBytes,
+    /// A search pattern of this many words, rather than whole lines of them
+    Pattern(usize),
+    /// A code of more lines than its count's byte holds
+    Lines(usize),
+    /// A C2 payload whose last instruction, this word, branches away
+    /// unconditionally, so never reaches the branch back
+    EndsInBranch(u32),
+    /// A relative branch at this offset into a C2 payload to somewhere
+    /// outside it, which lands elsewhere once the payload runs from the
+    /// code list
+    BranchOut { offset: u32, target: u32 },
+}
+
+impl fmt::Display for GeckoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Address(addr) => write!(
+                f,
+                "{:08x} is outside 80000000..82000000, which a code's writes reach",
+                addr
+            ),
+            Self::Misaligned(addr) => write!(f, "{:08x} isn't word-aligned", addr),
+            Self::Partial(n) => write!(f, "{} byte(s) left over after the last word", n),
+            Self::Fill(n) => write!(f, "a fill of {} values doesn't fit one line", n),
+            Self::Empty => write!(f, "no instructions to insert"),
+            Self::NoBytes => write!(f, "no bytes to write"),
+            Self::Pattern(n) => write!(
+                f,
+                "a search pattern of {} word(s); it has to be whole lines, 2 words each",
+                n
+            ),
+            Self::Lines(n) => write!(f, "{} lines, more than the code's count holds (255)", n),
+            Self::EndsInBranch(word) => write!(
+                f,
+                "the last instruction ({:08X}) branches away, so the code never \
+                 returns to the hook",
+                word
+            ),
+            Self::BranchOut { offset, target } => write!(
+                f,
+                "the branch at +{:#x} goes to {:08x}, outside the code; C2 code runs \
+                 from the code list, so call out with lis/ori, mtctr and bctrl",
+                offset, target
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GeckoError {}
+
+/// One line of a code, `04003100 38600001`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Line(pub u32, pub u32);
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:08X} {:08X}", self.0, self.1)
+    }
+}
+
+impl std::str::FromStr for Line {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let word = |token: Option<&str>| match token {
+            Some(t) if t.len() == 8 => u32::from_str_radix(t, 16).ok(),
+            _ => None,
+        };
+        let mut tokens = s.split_whitespace();
+        match (word(tokens.next()), word(tokens.next()), tokens.next()) {
+            (Some(a), Some(b), None) => Ok(Line(a, b)),
+            _ => Err(format!("`{}` isn't two 8-digit hex words", s.trim())),
+        }
+    }
+}
+
+/// A code of a code list, as far as ppcheat reads them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Code {
+    /// 04: `value` written at `addr`
+    Write { addr: u32, value: u32 },
+    /// 00 and 02: `value`, a byte or half word, written `count` times from
+    /// `addr` on
+    Fill {
+        width: Width,
+        addr: u32,
+        value: u32,
+        count: u32,
+    },
+    /// C2: `payload` run in place of the instruction at `addr`, without the
+    /// word the codehandler puts the branch back in
+    InsertAsm { addr: u32, payload: Vec<u32> },
+    /// Any other code, its lines as they are
+    Other(Vec<Line>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeListError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for CodeListError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for CodeListError {}
+
+impl Code {
+    /// The lines that make up this code, or why it has none: an address
+    /// out of reach, or a fill too long for one line.
+    pub fn lines(&self) -> Result<Vec<Line>, GeckoError> {
+        Ok(match *self {
+            Code::Write { addr, value } => vec![Line(address_word(0x04, addr)?, value)],
+            Code::Fill {
+                width,
+                addr,
+                value,
+                count,
+            } => {
+                if count == 0 || count > 0x1_0000 {
+                    return Err(GeckoError::Fill(count));
+                }
+                let (code, mask) = match width {
+                    Width::Byte => (0x00, 0xFF),
+                    Width::Half => (0x02, 0xFFFF),
+                    Width::Word => return Ok(vec![Line(address_word(0x04, addr)?, value)]),
+                };
+                vec![Line(
+                    address_word(code, addr)?,
+                    (count - 1) << 16 | (value & mask),
+                )]
+            }
+            Code::InsertAsm { addr, ref payload } => {
+                let mut words = payload.clone();
+                if words.len().is_multiple_of(2) {
+                    words.push(NOP);
+                }
+                words.push(0);
+                let mut lines = vec![Line(address_word(0xC2, addr)?, words.len() as u32 / 2)];
+                lines.extend(words.chunks(2).map(|pair| Line(pair[0], pair[1])));
+                lines
+            }
+            Code::Other(ref lines) => lines.clone(),
+        })
+    }
+}
+
+/// The lines of code in a code list, as pasted from a forum or a code
+/// manager, with their line numbers. Lines that don't start with a hex
+/// number, like code names and notes, are skipped; ones that do have to be
+/// a line of code.
+pub fn code_lines(text: &str) -> Result<Vec<(usize, Line)>, CodeListError> {
+    let mut lines = Vec::new();
+    for (i, text) in text.lines().enumerate() {
+        if !is_code(text) {
+            continue;
+        }
+        let line = text.parse().map_err(|message| CodeListError {
+            line: i + 1,
+            message,
+        })?;
+        lines.push((i + 1, line));
+    }
+    Ok(lines)
+}
+
+/// Whether `text` starts with a hex number, so is meant as a line of code.
+pub(crate) fn is_code(text: &str) -> bool {
+    let first = text.split_whitespace().next().unwrap_or("");
+    first.len() >= 6 && first.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// The codes of a code list; see [`code_lines`].
+pub fn parse_list(text: &str) -> Result<Vec<Code>, CodeListError> {
+    let lines = code_lines(text)?;
+    let mut codes = Vec::new();
+    for code in split_codes(&lines)? {
+        let Line(a, b) = code[0].1;
+        let addr = BASE_ADDRESS | (a & ADDRESS_MASK);
+        codes.push(match (a >> 24) & 0xFE {
+            0x00 => Code::Fill {
+                width: Width::Byte,
+                addr,
+                value: b & 0xFF,
+                count: (b >> 16) + 1,
+            },
+            0x02 => Code::Fill {
+                width: Width::Half,
+                addr,
+                value: b & 0xFFFF,
+                count: (b >> 16) + 1,
+            },
+            0x04 => Code::Write { addr, value: b },
+            0xC2 => {
+                let mut payload: Vec<_> = code[1..].iter().flat_map(|&(_, l)| [l.0, l.1]).collect();
+                payload.pop();
+                Code::InsertAsm { addr, payload }
+            }
+            _ => Code::Other(code.iter().map(|&(_, l)| l).collect()),
+        });
+    }
+    Ok(codes)
+}
+
+/// `lines` split into codes, each its first line and those after it.
+fn split_codes(lines: &[(usize, Line)]) -> Result<Vec<&[(usize, Line)]>, CodeListError> {
+    let mut codes = Vec::new();
+    let mut rest = lines;
+    while let Some(&(number, first)) = rest.first() {
+        let count = 1 + extra_lines(first);
+        let code = rest.get(..count).ok_or_else(|| CodeListError {
+            line: number,
+            message: format!(
+                "a {:02X} code needs {} lines, and there are {} left",
+                first.0 >> 24,
+                count,
+                rest.len()
+            ),
+        })?;
+        codes.push(code);
+        rest = &rest[count..];
+    }
+    Ok(codes)
+}
+
+/// How many lines of a code follow its first, `line`.
+pub(crate) fn extra_lines(line: Line) -> usize {
+    let Line(a, b) = line;
+    match (a >> 24) & 0xFE {
+        // string writes: a byte count
+        0x06 | 0x16 => (b as usize).div_ceil(8),
+        // serial writes
+        0x08 | 0x18 => 1,
+        // execute and insert ASM: a line count
+        0xC0 | 0xC2 | 0xD2 => b as usize,
+        // checksummed insert ASM, and searches: a line count in a byte
+        0xF2 | 0xF4 => (b & 0xFF) as usize,
+        0xF6 => (a & 0xFF) as usize,
+        _ => 0,
+    }
+}
+
+/// Which of the codehandler's two address registers an address is off:
+/// the base address, `ba`, or the pointer offset, `po`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Base {
+    BaseAddress,
+    Pointer,
+}
+
+impl Base {
+    /// `ba` for a clear `bit`, `po` for a set one.
+    fn from_bit(bit: u32) -> Base {
+        match bit {
+            0 => Self::BaseAddress,
+            _ => Self::Pointer,
+        }
+    }
+}
+
+impl fmt::Display for Base {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::BaseAddress => "ba",
+            Self::Pointer => "po",
+        })
+    }
+}
+
+/// An address `offset` bytes off `base`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Address {
+    pub base: Base,
+    pub offset: u32,
+}
+
+impl Address {
+    /// The address of a code with first word `a`: bit 28 picks `po`, and
+    /// the low 25 bits are the offset.
+    fn of(a: u32) -> Address {
+        Address {
+            base: Base::from_bit(a & 0x1000_0000),
+            offset: a & ADDRESS_MASK,
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}+{:x}", self.base, self.offset)
+    }
+}
+
+/// A value with `ba` or `po`, and a Gecko register, optionally added, as
+/// the 4x and 8x codes take one.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Operand {
+    pub value: u32,
+    pub plus: Option<Base>,
+    pub register: Option<u8>,
+}
+
+impl Operand {
+    /// `value`, plus `ba` or `po` as the nibble `plus` is 1 or 2, plus
+    /// `register` if there is one.
+    fn new(value: u32, plus: u32, register: Option<u8>) -> Operand {
+        Operand {
+            value,
+            plus: match plus {
+                1 => Some(Base::BaseAddress),
+                2 => Some(Base::Pointer),
+                _ => None,
+            },
+            register,
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:x}", self.value)?;
+        if let Some(base) = self.plus {
+            write!(f, "+{}", base)?;
+        }
+        if let Some(register) = self.register {
+            write!(f, "+gr{}", register)?;
+        }
+        Ok(())
+    }
+}
+
+/// What a conditional code compares with.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    /// Unsigned
+    Greater,
+    /// Unsigned
+    Less,
+}
+
+impl Comparison {
+    /// The comparison a code type's bits 1 and 2 pick.
+    fn of(code: u32) -> Comparison {
+        match (code >> 1) & 3 {
+            0 => Self::Equal,
+            1 => Self::NotEqual,
+            2 => Self::Greater,
+            _ => Self::Less,
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Equal => "==",
+            Self::NotEqual => "!=",
+            Self::Greater => ">",
+            Self::Less => "<",
+        })
+    }
+}
+
+/// When a 6x code jumps.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum When {
+    /// While the code execution status is true
+    True,
+    False,
+    Always,
+}
+
+impl fmt::Display for When {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::True => "if true",
+            Self::False => "if false",
+            Self::Always => "always",
+        })
+    }
+}
+
+/// What an 86 or 88 code does to a Gecko register.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Operation {
+    Add,
+    Multiply,
+    Or,
+    And,
+    Xor,
+    ShiftLeft,
+    ShiftRight,
+    RotateLeft,
+    /// Arithmetic shift right
+    ShiftRightSigned,
+    /// Single-precision
+    FloatAdd,
+    /// Single-precision
+    FloatMultiply,
+}
+
+impl Operation {
+    const ALL: [Operation; 11] = [
+        Self::Add,
+        Self::Multiply,
+        Self::Or,
+        Self::And,
+        Self::Xor,
+        Self::ShiftLeft,
+        Self::ShiftRight,
+        Self::RotateLeft,
+        Self::ShiftRightSigned,
+        Self::FloatAdd,
+        Self::FloatMultiply,
+    ];
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Add => "+",
+            Self::Multiply => "*",
+            Self::Or => "|",
+            Self::And => "&",
+            Self::Xor => "^",
+            Self::ShiftLeft => "<<",
+            Self::ShiftRight => ">>",
+            Self::RotateLeft => "rotated left by",
+            Self::ShiftRightSigned => ">> (signed)",
+            Self::FloatAdd => "+ (float)",
+            Self::FloatMultiply => "* (float)",
+        })
+    }
+}
+
+/// A code of any type the codehandler runs, with its fields picked out.
+/// Offsets are off `ba` or `po`, as the code's type says; conditionals
+/// with `endif` set apply an endif before they test.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CodeType {
+    /// 00, 02, 04: `value` written `count` times from `addr` on
+    Write {
+        width: Width,
+        addr: Address,
+        value: u32,
+        count: u32,
+    },
+    /// 06: `bytes` written from `addr` on
+    StringWrite { addr: Address, bytes: Vec<u8> },
+    /// 08: `value` written `count` times, `addr` and `value` stepped by
+    /// `addr_step` and `value_step` after each
+    SerialWrite {
+        width: Width,
+        addr: Address,
+        value: u32,
+        count: u32,
+        addr_step: u32,
+        value_step: u32,
+    },
+    /// 20 to 2E: whether the word, or the half word with `mask`'s bits
+    /// cleared, at `addr` compares to `value`
+    If {
+        comparison: Comparison,
+        width: Width,
+        addr: Address,
+        value: u32,
+        mask: u32,
+        endif: bool,
+    },
+    /// 40, 48: `register` set to (or with `add`, added) the word at
+    /// `address`
+    LoadAddress {
+        register: Base,
+        add: bool,
+        address: Operand,
+    },
+    /// 42, 4A: `register` set to (or added) `value`
+    SetAddress {
+        register: Base,
+        add: bool,
+        value: Operand,
+    },
+    /// 44, 4C: `register` stored at `address`
+    StoreAddress { register: Base, address: Operand },
+    /// 46, 4E: `register` set to the address of the next line, plus `offset`
+    CodeAddress { register: Base, offset: i16 },
+    /// 60: the next line and `count` kept in `block`, for 62 to repeat
+    SetRepeat { count: u16, block: u8 },
+    /// 62: the codes since `block`'s 60 run again, `count` times in all
+    ExecuteRepeat { block: u8 },
+    /// 64: back to the line kept in `block`
+    Return { when: When, block: u8 },
+    /// 66: `lines` lines on (or back)
+    Goto { when: When, lines: i16 },
+    /// 68: like 66, keeping the next line in `block` for a 64
+    Gosub { when: When, lines: i16, block: u8 },
+    /// 80: Gecko register `register` set to (or added) `value`
+    SetRegister {
+        register: u8,
+        add: bool,
+        value: Operand,
+    },
+    /// 82: Gecko register `register` loaded from `address`
+    LoadRegister {
+        register: u8,
+        width: Width,
+        address: Operand,
+    },
+    /// 84: Gecko register `register` stored `count` times from `address` on
+    StoreRegister {
+        register: u8,
+        width: Width,
+        address: Operand,
+        count: u32,
+    },
+    /// 86: `register` set to itself `operation` `value`; `indirect` says
+    /// whether each is instead the word it points at
+    Operation {
+        register: u8,
+        operation: Operation,
+        value: u32,
+        indirect: (bool, bool),
+    },
+    /// 88: like 86, with Gecko register `other` for the value
+    RegisterOperation {
+        register: u8,
+        operation: Operation,
+        other: u8,
+        indirect: (bool, bool),
+    },
+    /// 8A, 8C: `size` bytes copied from the address in Gecko register
+    /// `from` to the one in `to` (`ba` for register 15), plus `offset` on
+    /// the destination for 8A, the source for 8C
+    Copy {
+        size: u16,
+        from: u8,
+        to: u8,
+        offset: u32,
+        offset_to: bool,
+    },
+    /// A0 to A6: Gecko registers `first` and `second` compared, with
+    /// `mask`'s bits cleared; register 15 is the half word at `addr`
+    RegisterIf {
+        comparison: Comparison,
+        addr: Address,
+        first: u8,
+        second: u8,
+        mask: u16,
+        endif: bool,
+    },
+    /// A8 to AE: the code's own counter, counted up each time it passes,
+    /// compared with `value`, `mask`'s bits cleared; `reset` sets it back
+    /// to 0 when the test fails
+    CounterIf {
+        comparison: Comparison,
+        counter: u16,
+        value: u16,
+        mask: u16,
+        reset: bool,
+        endif: bool,
+    },
+    /// C0: `payload` called as a function, ending in `blr`
+    ExecuteAsm { payload: Vec<u32> },
+    /// C2, D2: `payload` run in place of the instruction at `addr`; its last
+    /// word is the branch back
+    InsertAsm { addr: Address, payload: Vec<u32> },
+    /// C6, D6: a branch to `target` written at `addr`
+    Branch { addr: Address, target: u32 },
+    /// CC: true every other time the code list runs
+    Switch,
+    /// CE, DE: whether `register` is from `start` << 16 up to `end` << 16
+    AddressRangeIf {
+        register: Base,
+        start: u16,
+        end: u16,
+        endif: bool,
+    },
+    /// E0: the end of a code's conditionals, and the high halves of `ba`
+    /// and `po` set, where nonzero
+    FullTerminator { ba: u16, po: u16 },
+    /// E2: `count` conditionals ended, or with `otherwise`, the last one's
+    /// status flipped; `ba` and `po` as for E0
+    Endif {
+        count: u8,
+        otherwise: bool,
+        ba: u16,
+        po: u16,
+    },
+    /// F0: the end of the code list
+    End,
+    /// F2, F4: like C2 at `addr`, only if the 16-bit XOR of the `around`
+    /// instructions before (or after, for negative) is `checksum`
+    ChecksumAsm {
+        addr: Address,
+        around: i8,
+        checksum: u16,
+        payload: Vec<u32>,
+    },
+    /// F6: `po` set to where `pattern` is first found from `start` << 16
+    /// up to `end` << 16; false if nowhere
+    Search {
+        start: u16,
+        end: u16,
+        pattern: Vec<u32>,
+    },
+    /// A code type the codehandler doesn't have, or a field it doesn't
+    /// allow
+    Unknown(Vec<Line>),
+}
+
+/// The width a 2-bit size field picks.
+fn width(size: u32) -> Option<Width> {
+    match size {
+        0 => Some(Width::Byte),
+        1 => Some(Width::Half),
+        2 => Some(Width::Word),
+        _ => None,
+    }
+}
+
+/// What a 6x code's T nibble says about when it jumps.
+fn when(t: u32) -> Option<When> {
+    match t {
+        0 => Some(When::True),
+        1 => Some(When::False),
+        2 => Some(When::Always),
+        _ => None,
+    }
+}
+
+impl CodeType {
+    /// The type of the code made of `lines`, a whole code as
+    /// [`parse_types`] splits a list into them.
+    pub fn parse(lines: &[Line]) -> CodeType {
+        Self::fields(lines).unwrap_or_else(|| CodeType::Unknown(lines.to_vec()))
+    }
+
+    fn fields(lines: &[Line]) -> Option<CodeType> {
+        let Line(a, b) = *lines.first()?;
+        let code = a >> 24;
+        let addr = Address::of(a);
+        let nibble = |shift: u32| (a >> shift) & 0xF;
+        let rest = || {
+            lines[1..]
+                .iter()
+                .flat_map(|l| [l.0, l.1])
+                .collect::<Vec<_>>()
+        };
+        // for the 4x codes, bit 27 picks po rather than bit 28
+        let register = Base::from_bit(a & 0x0800_0000);
+        let endif = a & 1 != 0;
+        let bare = Address {
+            offset: addr.offset & !1,
+            ..addr
+        };
+        Some(match code & 0xEE {
+            0x00 | 0x02 | 0x04 if code < 0x20 => {
+                let width = width((code >> 1) & 3)?;
+                match width {
+                    Width::Word => CodeType::Write {
+                        width,
+                        addr,
+                        value: b,
+                        count: 1,
+                    },
+                    _ => CodeType::Write {
+                        width,
+                        addr,
+                        value: b & if width == Width::Byte { 0xFF } else { 0xFFFF },
+                        count: (b >> 16) + 1,
+                    },
+                }
+            }
+            0x06 if code < 0x20 => CodeType::StringWrite {
+                addr,
+                bytes: rest()
+                    .iter()
+                    .flat_map(|w| w.to_be_bytes())
+                    .take(b as usize)
+                    .collect(),
+            },
+            0x08 if code < 0x20 => {
+                let Line(c, d) = *lines.get(1)?;
+                CodeType::SerialWrite {
+                    width: width(c >> 28)?,
+                    addr,
+                    value: b,
+                    count: ((c >> 16) & 0xFFF) + 1,
+                    addr_step: c & 0xFFFF,
+                    value_step: d,
+                }
+            }
+            0x20 | 0x22 | 0x24 | 0x26 | 0x28 | 0x2A | 0x2C | 0x2E => {
+                let half = code & 0x08 != 0;
+                CodeType::If {
+                    comparison: Comparison::of(code),
+                    width: if half { Width::Half } else { Width::Word },
+                    addr: bare,
+                    value: if half { b & 0xFFFF } else { b },
+                    mask: if half { b >> 16 } else { 0 },
+                    endif,
+                }
+            }
+            _ if code & 0xF0 == 0x40 => {
+                let add = nibble(20) == 1;
+                let gr = match nibble(12) {
+                    1 => Some(nibble(0) as u8),
+                    _ => None,
+                };
+                let operand = Operand::new(b, nibble(16), gr);
+                match code & 0x06 {
+                    0 => CodeType::LoadAddress {
+                        register,
+                        add,
+                        address: operand,
+                    },
+                    2 => CodeType::SetAddress {
+                        register,
+                        add,
+                        value: operand,
+                    },
+                    4 => CodeType::StoreAddress {
+                        register,
+                        address: operand,
+                    },
+                    _ => CodeType::CodeAddress {
+                        register,
+                        offset: a as u16 as i16,
+                    },
+                }
+            }
+            _ => match code {
+                0x60 => CodeType::SetRepeat {
+                    count: a as u16,
+                    block: (b & 0xF) as u8,
+                },
+                0x62 => CodeType::ExecuteRepeat {
+                    block: (b & 0xF) as u8,
+                },
+                0x64 => CodeType::Return {
+                    when: when(nibble(20))?,
+                    block: (b & 0xF) as u8,
+                },
+                0x66 => CodeType::Goto {
+                    when: when(nibble(20))?,
+                    lines: a as u16 as i16,
+                },
+                0x68 => CodeType::Gosub {
+                    when: when(nibble(20))?,
+                    lines: a as u16 as i16,
+                    block: (b & 0xF) as u8,
+                },
+                0x80 => CodeType::SetRegister {
+                    register: nibble(0) as u8,
+                    add: nibble(20) == 1,
+                    value: Operand::new(b, nibble(16), None),
+                },
+                0x82 => CodeType::LoadRegister {
+                    register: nibble(0) as u8,
+                    width: width(nibble(20))?,
+                    address: Operand::new(b, nibble(16), None),
+                },
+                0x84 => CodeType::StoreRegister {
+                    register: nibble(0) as u8,
+                    width: width(nibble(20))?,
+                    address: Operand::new(b, nibble(16), None),
+                    count: ((a >> 4) & 0xFFF) + 1,
+                },
+                0x86 | 0x88 => {
+                    let operation = *Operation::ALL.get(nibble(20) as usize)?;
+                    let indirect = (nibble(16) & 1 != 0, nibble(16) & 2 != 0);
+                    let register = nibble(0) as u8;
+                    match code {
+                        0x86 => CodeType::Operation {
+                            register,
+                            operation,
+                            value: b,
+                            indirect,
+                        },
+                        _ => CodeType::RegisterOperation {
+                            register,
+                            operation,
+                            other: (b & 0xF) as u8,
+                            indirect,
+                        },
+                    }
+                }
+                0x8A | 0x8C => CodeType::Copy {
+                    size: (a >> 8) as u16,
+                    from: nibble(4) as u8,
+                    to: nibble(0) as u8,
+                    offset: b,
+                    offset_to: code == 0x8A,
+                },
+                0xA0..=0xA7 => CodeType::RegisterIf {
+                    comparison: Comparison::of(code),
+                    addr: bare,
+                    first: (b >> 28) as u8,
+                    second: ((b >> 24) & 0xF) as u8,
+                    mask: b as u16,
+                    endif,
+                },
+                0xA8..=0xAF => CodeType::CounterIf {
+                    comparison: Comparison::of(code),
+                    counter: (a >> 8) as u16,
+                    value: b as u16,
+                    mask: (b >> 16) as u16,
+                    reset: nibble(4) & 8 != 0,
+                    endif,
+                },
+                0xC0 => CodeType::ExecuteAsm { payload: rest() },
+                0xC2 | 0xC3 | 0xD2 | 0xD3 => CodeType::InsertAsm {
+                    addr,
+                    payload: rest(),
+                },
+                0xC6 | 0xC7 | 0xD6 | 0xD7 => CodeType::Branch { addr, target: b },
+                0xCC => CodeType::Switch,
+                0xCE | 0xDE => CodeType::AddressRangeIf {
+                    register: Base::from_bit(code & 0x10),
+                    start: (b >> 16) as u16,
+                    end: b as u16,
+                    endif,
+                },
+                0xE0 => CodeType::FullTerminator {
+                    ba: (b >> 16) as u16,
+                    po: b as u16,
+                },
+                0xE2 => CodeType::Endif {
+                    count: a as u8,
+                    otherwise: nibble(20) == 1,
+                    ba: (b >> 16) as u16,
+                    po: b as u16,
+                },
+                0xF0 => CodeType::End,
+                0xF2..=0xF5 => CodeType::ChecksumAsm {
+                    addr: Address {
+                        base: Base::from_bit(code & 0x04),
+                        offset: a & ADDRESS_MASK,
+                    },
+                    around: (b >> 24) as i8,
+                    checksum: (b >> 8) as u16,
+                    payload: rest(),
+                },
+                0xF6 => CodeType::Search {
+                    start: (b >> 16) as u16,
+                    end: b as u16,
+                    pattern: rest(),
+                },
+                _ => return None,
+            },
+        })
+    }
+}
+
+impl CodeType {
+    /// The address the code writes, tests or hooks, if it has one of its
+    /// own.
+    pub fn address(&self) -> Option<Address> {
+        match *self {
+            CodeType::Write { addr, .. }
+            | CodeType::StringWrite { addr, .. }
+            | CodeType::SerialWrite { addr, .. }
+            | CodeType::If { addr, .. }
+            | CodeType::InsertAsm { addr, .. }
+            | CodeType::Branch { addr, .. }
+            | CodeType::ChecksumAsm { addr, .. } => Some(addr),
+            _ => None,
+        }
+    }
+}
+
+/// What `ba` is after `code`, whose first line is `first`, given what it
+/// was before: known only while it's the default, set by a 42 or E0 code.
+/// After a code that loads it or adds to it, it's `None` until set again.
+pub(crate) fn base_after(ba: Option<u32>, code: &CodeType, first: Line) -> Option<u32> {
+    match *code {
+        CodeType::SetAddress {
+            register: Base::BaseAddress,
+            add: false,
+            value:
+                Operand {
+                    plus: None,
+                    register: None,
+                    ..
+                },
+        } => Some(first.1),
+        CodeType::LoadAddress {
+            register: Base::BaseAddress,
+            ..
+        }
+        | CodeType::SetAddress {
+            register: Base::BaseAddress,
+            ..
+        }
+        | CodeType::CodeAddress {
+            register: Base::BaseAddress,
+            ..
+        } => None,
+        CodeType::FullTerminator { ba: high, .. } | CodeType::Endif { ba: high, .. }
+            if high != 0 =>
+        {
+            Some(u32::from(high) << 16)
+        }
+        _ => ba,
+    }
+}
+
+impl fmt::Display for CodeType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = |width: &Width| match width {
+            Width::Byte => "byte",
+            Width::Half => "half word",
+            Width::Word => "word",
+        };
+        let endif = |endif: bool| if endif { "endif, then " } else { "" };
+        let set = |add: bool| if add { "+=" } else { "=" };
+        let deref = |name: String, indirect: bool| match indirect {
+            true => format!("[{}]", name),
+            false => name,
+        };
+        let gr = |register: u8| match register {
+            0xF => "ba".to_string(),
+            n => format!("gr{}", n),
+        };
+        match self {
+            CodeType::Write {
+                width: w,
+                addr,
+                value,
+                count: 1,
+            } => write!(f, "write the {} {:x} at {}", width(w), value, addr),
+            CodeType::Write {
+                width: w,
+                addr,
+                value,
+                count,
+            } => write!(
+                f,
+                "fill {} {}s from {} with {:x}",
+                count,
+                width(w),
+                addr,
+                value
+            ),
+            CodeType::StringWrite { addr, bytes } => {
+                write!(f, "write {} bytes from {}", bytes.len(), addr)
+            }
+            CodeType::SerialWrite {
+                width: w,
+                addr,
+                value,
+                count,
+                addr_step,
+                value_step,
+            } => write!(
+                f,
+                "write {} {}s from {}, {:x} bytes apart, starting at {:x} and adding {:x}",
+                count,
+                width(w),
+                addr,
+                addr_step,
+                value,
+                value_step
+            ),
+            CodeType::If {
+                comparison,
+                width: w,
+                addr,
+                value,
+                mask,
+                endif: e,
+            } => {
+                write!(
+                    f,
+                    "{}if the {} at {} {} {:x}",
+                    endif(*e),
+                    width(w),
+                    addr,
+                    comparison,
+                    value
+                )?;
+                match mask {
+                    0 => Ok(()),
+                    mask => write!(f, ", ignoring bits {:04x}", mask),
+                }
+            }
+            CodeType::LoadAddress {
+                register,
+                add,
+                address,
+            } => write!(f, "{} {} [{}]", register, set(*add), address),
+            CodeType::SetAddress {
+                register,
+                add,
+                value,
+            } => write!(f, "{} {} {}", register, set(*add), value),
+            CodeType::StoreAddress { register, address } => {
+                write!(f, "[{}] = {}", address, register)
+            }
+            CodeType::CodeAddress { register, offset } => {
+                write!(f, "{} = the next line's address {:+#x}", register, offset)
+            }
+            CodeType::SetRepeat { count, block } => {
+                write!(f, "repeat {} times, in block {}", count, block)
+            }
+            CodeType::ExecuteRepeat { block } => write!(f, "repeat block {}", block),
+            CodeType::Return { when, block } => {
+                write!(f, "return to block {}, {}", block, when)
+            }
+            CodeType::Goto { when, lines } => write!(f, "go {:+} lines, {}", lines, when),
+            CodeType::Gosub { when, lines, block } => write!(
+                f,
+                "call {:+} lines, {}, returning to block {}",
+                lines, when, block
+            ),
+            CodeType::SetRegister {
+                register,
+                add,
+                value,
+            } => write!(f, "gr{} {} {}", register, set(*add), value),
+            CodeType::LoadRegister {
+                register,
+                width: w,
+                address,
+            } => write!(f, "gr{} = the {} at [{}]", register, width(w), address),
+            CodeType::StoreRegister {
+                register,
+                width: w,
+                address,
+                count,
+            } => write!(
+                f,
+                "write gr{} as a {} at [{}], {} time(s)",
+                register,
+                width(w),
+                address,
+                count
+            ),
+            CodeType::Operation {
+                register,
+                operation,
+                value,
+                indirect,
+            } => write!(
+                f,
+                "gr{} = {} {} {}",
+                register,
+                deref(format!("gr{}", register), indirect.0),
+                operation,
+                deref(format!("{:x}", value), indirect.1)
+            ),
+            CodeType::RegisterOperation {
+                register,
+                operation,
+                other,
+                indirect,
+            } => write!(
+                f,
+                "gr{} = {} {} {}",
+                register,
+                deref(format!("gr{}", register), indirect.0),
+                operation,
+                deref(format!("gr{}", other), indirect.1)
+            ),
+            CodeType::Copy {
+                size,
+                from,
+                to,
+                offset,
+                offset_to,
+            } => {
+                let (from, to) = match offset_to {
+                    true => (
+                        format!("[{}]", gr(*from)),
+                        format!("[{}]+{:x}", gr(*to), offset),
+                    ),
+                    false => (
+                        format!("[{}]+{:x}", gr(*from), offset),
+                        format!("[{}]", gr(*to)),
+                    ),
+                };
+                write!(f, "copy {:#x} bytes from {} to {}", size, from, to)
+            }
+            CodeType::RegisterIf {
+                comparison,
+                addr,
+                first,
+                second,
+                mask,
+                endif: e,
+            } => {
+                let side = |register: u8| match register {
+                    0xF => format!("the half word at {}", addr),
+                    n => format!("gr{}", n),
+                };
+                write!(
+                    f,
+                    "{}if {} {} {}, ignoring bits {:04x}",
+                    endif(*e),
+                    side(*first),
+                    comparison,
+                    side(*second),
+                    mask
+                )
+            }
+            CodeType::CounterIf {
+                comparison,
+                counter,
+                value,
+                mask,
+                reset,
+                endif: e,
+            } => write!(
+                f,
+                "{}if the counter (now {}) {} {}, ignoring bits {:04x}{}",
+                endif(*e),
+                counter,
+                comparison,
+                value,
+                mask,
+                if *reset { ", reset when false" } else { "" }
+            ),
+            CodeType::ExecuteAsm { payload } => {
+                write!(f, "call {} instruction(s)", payload.len())
+            }
+            CodeType::InsertAsm { addr, payload } => write!(
+                f,
+                "insert {} word(s) at {}, then the branch back",
+                payload.len().saturating_sub(1),
+                addr
+            ),
+            CodeType::Branch { addr, target } => {
+                write!(f, "write a branch to {:08x} at {}", target, addr)
+            }
+            CodeType::Switch => write!(f, "on/off switch"),
+            CodeType::AddressRangeIf {
+                register,
+                start,
+                end,
+                endif: e,
+            } => write!(
+                f,
+                "{}if {} is in {:04x}0000..{:04x}0000",
+                endif(*e),
+                register,
+                start,
+                end
+            ),
+            CodeType::FullTerminator { ba, po } => {
+                write!(f, "end of the conditionals")?;
+                high_halves(f, *ba, *po)
+            }
+            CodeType::Endif {
+                count,
+                otherwise,
+                ba,
+                po,
+            } => {
+                match otherwise {
+                    true => write!(f, "else")?,
+                    false => write!(f, "end {} conditional(s)", count)?,
+                }
+                high_halves(f, *ba, *po)
+            }
+            CodeType::End => write!(f, "end of the code list"),
+            CodeType::ChecksumAsm {
+                addr,
+                around,
+                checksum,
+                payload,
+            } => write!(
+                f,
+                "insert {} word(s) at {}, if the XOR of the {} instruction(s) {} is {:04x}",
+                payload.len().saturating_sub(1),
+                addr,
+                around.unsigned_abs(),
+                if *around < 0 { "after" } else { "before" },
+                checksum
+            ),
+            CodeType::Search {
+                start,
+                end,
+                pattern,
+            } => {
+                let words: Vec<_> = pattern.iter().map(|w| format!("{:08X}", w)).collect();
+                write!(
+                    f,
+                    "po = where {} is, in {:04x}0000..{:04x}0000",
+                    words.join(" "),
+                    start,
+                    end
+                )
+            }
+            CodeType::Unknown(lines) => write!(f, "unknown {:02X} code", lines[0].0 >> 24),
+        }
+    }
+}
+
+/// E0 and E2's setting of `ba` and `po`'s high halves, where nonzero.
+fn high_halves(f: &mut fmt::Formatter<'_>, ba: u16, po: u16) -> fmt::Result {
+    if ba != 0 {
+        write!(f, ", ba = {:04x}0000", ba)?;
+    }
+    if po != 0 {
+        write!(f, ", po = {:04x}0000", po)?;
+    }
+    Ok(())
+}
+
+/// Every code of a code list, with its lines; see [`code_lines`].
+pub fn parse_types(text: &str) -> Result<Vec<(Vec<Line>, CodeType)>, CodeListError> {
+    typed(&code_lines(text)?)
+}
+
+/// The codes `lines` make up, numbered from 1 in errors.
+pub fn types(lines: &[Line]) -> Result<Vec<(Vec<Line>, CodeType)>, CodeListError> {
+    let numbered: Vec<_> = lines
+        .iter()
+        .copied()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l))
+        .collect();
+    typed(&numbered)
+}
+
+fn typed(lines: &[(usize, Line)]) -> Result<Vec<(Vec<Line>, CodeType)>, CodeListError> {
+    Ok(split_codes(lines)?
+        .into_iter()
+        .map(|code| {
+            let lines: Vec<_> = code.iter().map(|&(_, l)| l).collect();
+            let parsed = CodeType::parse(&lines);
+            (lines, parsed)
+        })
+        .collect())
+}
+
+/// `codes` disassembled: each run of 04 writes to consecutive words at
+/// their addresses, and each C2 code's payload by offset, under a comment
+/// with the hook. Other codes are kept as comments, under what they do,
+/// with a hex dump of what 06 codes write. objdump's style names each
+/// section by its code type and address.
+pub fn listing(codes: &[Code], options: &Options) -> Listing {
+    let entries = codes.iter().map(|code| match code {
+        Code::Write { addr, value } => Entry::Write(*addr, *value),
+        Code::Fill {
+            width,
+            addr,
+            value,
+            count,
+        } => {
+            let line = code
+                .lines()
+                .map_or(String::new(), |l| format!("{}  ", l[0]));
+            let width = match width {
+                Width::Byte => "byte",
+                Width::Half => "half word",
+                Width::Word => "word",
+            };
+            Entry::Comment(format!(
+                "{}fill {} {}s from {:08x} with {:x}",
+                line, count, width, addr, value
+            ))
+        }
+        Code::InsertAsm { addr, payload } => Entry::InsertAsm(*addr, payload),
+        Code::Other(lines) => {
+            let code = CodeType::parse(lines);
+            let mut text = format!("{}\n", code);
+            for line in lines {
+                text += &format!("{}\n", line);
+            }
+            // what a string write writes, by offset
+            if let CodeType::StringWrite { bytes, .. } = &code {
+                text += &HexDump {
+                    base: Some(0),
+                    bytes: bytes.clone(),
+                }
+                .to_string();
+            }
+            // what a search looks for, and what a checksummed hook inserts
+            // ahead of the word for the branch back
+            let words = match &code {
+                CodeType::Search { pattern, .. } => &pattern[..],
+                CodeType::ChecksumAsm { payload, .. } => {
+                    &payload[..payload.len().saturating_sub(1)]
+                }
+                _ => &[],
+            };
+            for (i, &word) in words.iter().enumerate() {
+                text += &format!("{:08x}: {:08X}  {}\n", 4 * i, word, instruction(word));
+            }
+            Entry::Comment(text)
+        }
+    });
+    code_listing(entries, options)
+}
+
+/// A code as [`code_listing`] lists it.
+pub(crate) enum Entry<'a> {
+    /// A word written at an address
+    Write(u32, u32),
+    /// A C2 code's hook and payload
+    InsertAsm(u32, &'a [u32]),
+    /// Lines to keep as comments
+    Comment(String),
+}
+
+/// The listing of a code list: see [`listing`].
+pub(crate) fn code_listing<'a>(
+    entries: impl IntoIterator<Item = Entry<'a>>,
+    options: &Options,
+) -> Listing {
+    let entries: Vec<_> = entries.into_iter().collect();
+    let mut out = Listing::default();
+    let mut rest = &entries[..];
+    while let Some(entry) = rest.first() {
+        // objdump's listings start with a blank line of their own
+        let objdump = options.style == Style::Objdump && !matches!(entry, Entry::Comment(_));
+        if !out.text.is_empty() && !objdump {
+            out.text.push('\n');
+        }
+        let (name, header, section) = match *entry {
+            Entry::Write(addr, _) => {
+                let mut data = Vec::new();
+                while let Some(&Entry::Write(at, value)) = rest.first() {
+                    if at != addr.wrapping_add(data.len() as u32) {
+                        break;
+                    }
+                    data.extend(value.to_be_bytes());
+                    rest = &rest[1..];
+                }
+                let end = addr.wrapping_add(data.len() as u32);
+                let header = format!("04 writes, {:08x}..{:08x}", addr, end);
+                (
+                    format!("04 {:08x}", addr),
+                    header,
+                    Section {
+                        addr,
+                        data,
+                        executable: true,
+                    },
+                )
+            }
+            Entry::InsertAsm(addr, payload) => {
+                rest = &rest[1..];
+                let header = format!(
+                    "C2 at {:08x}, by offset, then the branch back to {:08x}",
+                    addr,
+                    addr.wrapping_add(4)
+                );
+                let data = payload.iter().flat_map(|w| w.to_be_bytes()).collect();
+                (
+                    format!("C2 {:08x}", addr),
+                    header,
+                    Section {
+                        addr: 0,
+                        data,
+                        executable: true,
+                    },
+                )
+            }
+            Entry::Comment(ref text) => {
+                rest = &rest[1..];
+                for line in text.lines() {
+                    out.text += &format!("# {}\n", line);
+                }
+                continue;
+            }
+        };
+        let section = iter::once((name, header, section));
+        out.extend(formats::listing(section, Endian::Big, options));
+    }
+    out
+}
+
+/// The first word of a line of code type `code` at `addr`: the type in the
+/// top byte, with the address's bit 24 in its low bit.
+pub(crate) fn address_word(code: u8, addr: u32) -> Result<u32, GeckoError> {
+    if addr.wrapping_sub(BASE_ADDRESS) > ADDRESS_MASK {
+        return Err(GeckoError::Address(addr));
+    }
+    Ok(u32::from(code) << 24 | (addr & ADDRESS_MASK))
+}
+
+/// `bytes` as big-endian words, to go at `addr`.
+pub(crate) fn words(bytes: &[u8], addr: u32) -> Result<Vec<u32>, GeckoError> {
+    if !addr.is_multiple_of(4) {
+        return Err(GeckoError::Misaligned(addr));
+    }
+    let words = bytes.chunks_exact(4);
+    if !words.remainder().is_empty() {
+        return Err(GeckoError::Partial(words.remainder().len()));
+    }
+    Ok(words
+        .map(|w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]))
+        .collect())
+}
+
+/// `bytes` written at `addr` as 04 codes, a 32-bit write per word.
+pub fn word_writes(bytes: &[u8], addr: u32) -> Result<Vec<Line>, GeckoError> {
+    words(bytes, addr)?
+        .into_iter()
+        .enumerate()
+        .map(|(i, word)| {
+            let at = addr.wrapping_add(4 * i as u32);
+            Ok(Line(address_word(0x04, at)?, word))
+        })
+        .collect()
+}
+
+/// `bytes` written from `addr` on as an 06 code: a line with the address
+/// and the count of bytes, then the bytes 8 to a line, the last padded with
+/// zeroes the codehandler doesn't write.
+pub fn string_write(bytes: &[u8], addr: u32) -> Result<Vec<Line>, GeckoError> {
+    if bytes.is_empty() {
+        return Err(GeckoError::NoBytes);
+    }
+    let mut lines = vec![Line(address_word(0x06, addr)?, bytes.len() as u32)];
+    for chunk in bytes.chunks(8) {
+        let mut padded = [0; 8];
+        padded[..chunk.len()].copy_from_slice(chunk);
+        let word = |b: &[u8]| u32::from_be_bytes([b[0], b[1], b[2], b[3]]);
+        lines.push(Line(word(&padded[..4]), word(&padded[4..])));
+    }
+    Ok(lines)
+}
+
+/// An F6 code setting `po` to where `pattern` is first found, from
+/// `start` up to `end`, each rounded out to 64KB.
+pub fn search(pattern: &[u32], start: u32, end: u32) -> Result<Vec<Line>, GeckoError> {
+    if pattern.is_empty() || !pattern.len().is_multiple_of(2) {
+        return Err(GeckoError::Pattern(pattern.len()));
+    }
+    let count = pattern.len() / 2;
+    if count > 0xFF {
+        return Err(GeckoError::Lines(count));
+    }
+    let end = (end.saturating_add(0xFFFF) >> 16).min(0xFFFF);
+    let mut lines = vec![Line(0xF600_0000 | count as u32, start & 0xFFFF_0000 | end)];
+    lines.extend(pattern.chunks(2).map(|pair| Line(pair[0], pair[1])));
+    Ok(lines)
+}
+
+/// `payload` at the hook `addr` as an F2 code: a C2 code that only hooks
+/// if the `around` instructions before `addr` (after, for negative) XOR to
+/// `checksum`, half word by half word.
+pub fn checksum_asm(
+    payload: &[u8],
+    addr: u32,
+    around: i8,
+    checksum: u16,
+) -> Result<Vec<Line>, GeckoError> {
+    let mut lines = insert_asm(payload, addr)?;
+    let count = lines.len() - 1;
+    if count > 0xFF {
+        return Err(GeckoError::Lines(count));
+    }
+    lines[0] = Line(
+        address_word(0xF2, addr)?,
+        u32::from(around as u8) << 24 | u32::from(checksum) << 8 | count as u32,
+    );
+    Ok(lines)
+}
+
+/// `payload`, assembled at the hook `addr`, as a C2 code: the payload
+/// padded with a `nop` if need be so the branch back ends the last line,
+/// under a line with the hook and the count of lines after it.
+///
+/// The payload has to fall through to the branch back, and runs from the
+/// code list rather than the hook, so it can't branch relatively out of
+/// itself.
+pub fn insert_asm(payload: &[u8], addr: u32) -> Result<Vec<Line>, GeckoError> {
+    let words = words(payload, addr)?;
+    let &last = words.last().ok_or(GeckoError::Empty)?;
+    if branches_away(last) {
+        return Err(GeckoError::EndsInBranch(last));
+    }
+    // the branch back counts as part of the code
+    let size = 4 * words.len() as u32;
+    for (i, &word) in words.iter().enumerate() {
+        let at = addr.wrapping_add(4 * i as u32);
+        if let Some(target) = relative_target(word, at) {
+            if target.wrapping_sub(addr) > size {
+                return Err(GeckoError::BranchOut {
+                    offset: 4 * i as u32,
+                    target,
+                });
+            }
+        }
+    }
+
+    Code::InsertAsm {
+        addr,
+        payload: words,
+    }
+    .lines()
+}
+
+/// Where `word` at `at` branches to, if it's a relative `b` or `bc`.
+pub(crate) fn relative_target(word: u32, at: u32) -> Option<u32> {
+    if word & 2 != 0 {
+        return None;
+    }
+    let disp = match word >> 26 {
+        18 => ((word & 0x03FF_FFFC) << 6) as i32 >> 6,
+        16 => i32::from((word & 0xFFFC) as u16 as i16),
+        _ => return None,
+    };
+    Some(at.wrapping_add(disp as u32))
+}
+
+/// Whether `word` always branches without linking: `b`, `blr`, `bctr` or
+/// `rfi`.
+pub(crate) fn branches_away(word: u32) -> bool {
+    let always = (word >> 21) & 0x14 == 0x14;
+    let xo = (word >> 1) & 0x3FF;
+    match word >> 26 {
+        18 => word & 1 == 0,
+        19 => xo == 50 || (always && word & 1 == 0 && (xo == 16 || xo == 528)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_writes() {
+        // li r3,1; nop
+        let bytes = [0x38, 0x60, 0x00, 0x01, 0x60, 0x00, 0x00, 0x00];
+        let lines: Vec<_> = word_writes(&bytes, 0x8000_3100)
+            .unwrap()
+            .iter()
+            .map(Line::to_string)
+            .collect();
+        assert_eq!(lines, ["04003100 38600001", "04003104 60000000"]);
+        assert_eq!(
+            word_writes(&bytes[..4], 0x817F_FFFC).unwrap(),
+            [Line(0x057F_FFFC, 0x3860_0001)]
+        );
+        assert_eq!(
+            word_writes(&bytes, 0x9000_0000),
+            Err(GeckoError::Address(0x9000_0000))
+        );
+        assert_eq!(
+            word_writes(&bytes, 0x8000_3102),
+            Err(GeckoError::Misaligned(0x8000_3102))
+        );
+        assert_eq!(
+            word_writes(&bytes[..6], 0x8000_3100),
+            Err(GeckoError::Partial(2))
+        );
+    }
+
+    #[test]
+    fn test_string_write() {
+        let lines = string_write(b"Player 1\0", 0x8000_3102).unwrap();
+        assert_eq!(
+            lines,
+            [
+                Line(0x0600_3102, 9),
+                Line(0x506C_6179, 0x6572_2031),
+                Line(0, 0)
+            ]
+        );
+        let text: String = lines.iter().map(|l| format!("{}\n", l)).collect();
+        let listing = listing(&parse_list(&text).unwrap(), &Options::default());
+        assert!(listing
+            .text
+            .contains("# 00000000: 506c6179 65722031 00                 |Player 1.|\n"));
+        assert_eq!(string_write(b"", 0x8000_3100), Err(GeckoError::NoBytes));
+    }
+
+    #[test]
+    fn test_search() {
+        let lines = search(&[0x7C08_02A6, 0x9421_FFF0], 0x8000_3100, 0x8040_0001).unwrap();
+        assert_eq!(
+            lines,
+            [
+                Line(0xF600_0001, 0x8000_8041),
+                Line(0x7C08_02A6, 0x9421_FFF0)
+            ]
+        );
+        assert_eq!(search(&[1, 2, 3], 0, 0), Err(GeckoError::Pattern(3)));
+        // li r3,1, if the word before the hook is a mflr r0
+        let lines = checksum_asm(&[0x38, 0x60, 0x00, 0x01], 0x8000_3104, 1, 0x7EAE).unwrap();
+        assert_eq!(lines[0], Line(0xF200_3104, 0x017E_AE01));
+        assert_eq!(
+            CodeType::parse(&lines),
+            CodeType::ChecksumAsm {
+                addr: Address {
+                    base: Base::BaseAddress,
+                    offset: 0x3104
+                },
+                around: 1,
+                checksum: 0x7EAE,
+                payload: vec![0x3860_0001, 0]
+            }
+        );
+    }
+
+    #[test]
+    fn test_insert_asm() {
+        // li r3,1; beq .+8; addi r3,r3,1
+        let words: [u32; 3] = [0x3860_0001, 0x4182_0008, 0x3863_0001];
+        let bytes: Vec<_> = words.iter().flat_map(|w| w.to_be_bytes()).collect();
+        let lines: Vec<_> = insert_asm(&bytes, 0x8000_3100)
+            .unwrap()
+            .iter()
+            .map(Line::to_string)
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "C2003100 00000002",
+                "38600001 41820008",
+                "38630001 00000000"
+            ]
+        );
+        let even = insert_asm(&[&bytes[..4], &bytes[8..]].concat(), 0x8000_3100).unwrap();
+        assert_eq!(even[0], Line(0xC200_3100, 2));
+        assert_eq!(even[2], Line(NOP, 0));
+        // blr
+        assert_eq!(
+            insert_asm(&[0x4e, 0x80, 0x00, 0x20], 0x8000_3100),
+            Err(GeckoError::EndsInBranch(0x4e80_0020))
+        );
+        // bl .-0x100; li r3,1
+        assert_eq!(
+            insert_asm(
+                &[0x4b, 0xff, 0xff, 0x01, 0x38, 0x60, 0x00, 0x01],
+                0x8000_3100
+            ),
+            Err(GeckoError::BranchOut {
+                offset: 0,
+                target: 0x8000_3000
+            })
+        );
+        assert_eq!(insert_asm(&[], 0x8000_3100), Err(GeckoError::Empty));
+    }
+
+    #[test]
+    fn test_code_types() {
+        let codes = parse_types(
+            "12003100 0003FFFF\n\
+             28003201 FF000003\n\
+             48000000 80003000\n\
+             86310003 00000010\n\
+             08003300 00000001\n\
+             10030004 00000001\n\
+             E2000001 80000000\n\
+             F2003400 FE123401\n\
+             38600001 00000000\n\
+             9A000000 00000000\n",
+        )
+        .unwrap();
+        let described: Vec<_> = codes.iter().map(|(_, c)| c.to_string()).collect();
+        assert_eq!(
+            described,
+            [
+                "fill 4 half words from po+3100 with ffff",
+                "endif, then if the half word at ba+3200 == 3, ignoring bits ff00",
+                "po = [80003000]",
+                "gr3 = [gr3] & 10",
+                "write 4 half words from ba+3300, 4 bytes apart, starting at 1 and adding 1",
+                "end 1 conditional(s), ba = 80000000",
+                "insert 1 word(s) at ba+3400, if the XOR of the 2 instruction(s) after is 1234",
+                "unknown 9A code",
+            ]
+        );
+        assert_eq!(codes[4].0.len(), 2);
+        assert_eq!(
+            codes[0].1,
+            CodeType::Write {
+                width: Width::Half,
+                addr: Address {
+                    base: Base::Pointer,
+                    offset: 0x3100
+                },
+                value: 0xFFFF,
+                count: 4
+            }
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        let codes = parse_list(
+            "Infinite lives [ppcheat]\n\
+             04003100 38600001\n\
+             04003104 4BFFFFFC\n\
+             C2003200 00000001\n\
+             38600001 00000000\n\
+             06003300 00000004\n\
+             04000000 00000000\n\
+             F6000001 80008010\n\
+             7C7F1B78 2C030000\n",
+        )
+        .unwrap();
+        assert_eq!(
+            codes[2],
+            Code::InsertAsm {
+                addr: 0x8000_3200,
+                payload: vec![0x3860_0001]
+            }
+        );
+        assert_eq!(codes.len(), 5);
+        assert_eq!(
+            listing(&codes, &Options::default()).text,
+            "# 04 writes, 80003100..80003108\n\
+             80003100: 38 60 00 01  li r3,1\n\
+             80003104: 4b ff ff fc  b .-4\n\
+             \n\
+             # C2 at 80003200, by offset, then the branch back to 80003204\n\
+             00000000: 38 60 00 01  li r3,1\n\
+             \n\
+             # write 4 bytes from ba+3300\n\
+             # 06003300 00000004\n\
+             # 04000000 00000000\n\
+             # 00000000: 04000000                             |....|\n\
+             \n\
+             # po = where 7C7F1B78 2C030000 is, in 80000000..80100000\n\
+             # F6000001 80008010\n\
+             # 7C7F1B78 2C030000\n\
+             # 00000000: 7C7F1B78  (looks like mr)\n\
+             # 00000004: 2C030000  cmpwi cr0,r3,0\n"
+        );
+        let error = parse_list("C2003200 00000002\n38600001 00000000\n").unwrap_err();
+        assert_eq!(error.line, 1);
+        assert_eq!(parse_list("0400310 38600001").unwrap_err().line, 1);
+    }
+}
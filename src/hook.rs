@@ -0,0 +1,366 @@
+//! Trampolines: a payload wrapped so it can run at a hook without the game
+//! noticing. It runs in a stack frame of its own, with the volatile
+//! registers (r0, r3 to r12), CR and LR saved around it, then the
+//! instruction the hook displaced, then a branch back past the hook:
+//!
+//! ```text
+//! stwu r1,-64(r1)       save
+//! stw r0,12(r1)
+//! mflr r0
+//! ...
+//! li r3,99              payload
+//! ...
+//! addi r1,r1,64         restore
+//! lwz r0,8(r3)          the displaced instruction
+//! b hook+4              back
+//! ```
+//!
+//! As a C2 code the codehandler branches to the trampoline and supplies the
+//! branch back itself; in a code cave of its own the trampoline has both,
+//! and the hook is overwritten with a branch to the cave.
+
+use core::fmt;
+
+use crate::gecko::{self, GeckoError, Line};
+use crate::{Opcode, Register, Width};
+
+/// The frame's size, a multiple of 16 as the EABI wants: the back chain
+/// and the LR save word of anything the payload calls, CR, r0, and r3 to
+/// r12.
+const FRAME: i16 = 0x40;
+const CR_SLOT: i16 = 0x08;
+const R0_SLOT: i16 = 0x0C;
+/// r3's slot; r4 to r12 follow it.
+const GPR_SLOTS: i16 = 0x10;
+/// LR goes in the LR save word of the caller's frame, as a function's does.
+const LR_SLOT: i16 = FRAME + 4;
+
+const MFLR_R0: u32 = 0x7C08_02A6;
+const MTLR_R0: u32 = 0x7C08_03A6;
+const MFCR_R0: u32 = 0x7C00_0026;
+const MTCRF_R0: u32 = 0x7C0F_F120;
+/// The trampoline's own instructions that ppcheat doesn't decode, as
+/// they're written.
+const UNDECODED: [(u32, &str); 4] = [
+    (MFLR_R0, "mflr r0"),
+    (MTLR_R0, "mtlr r0"),
+    (MFCR_R0, "mfcr r0"),
+    (MTCRF_R0, "mtcrf 0xff,r0"),
+];
+
+/// The word of `insn` if it's one of the trampoline's own instructions
+/// that ppcheat doesn't otherwise assemble: `mflr r0` above all, the
+/// instruction a hook at the start of a function most often displaces.
+pub fn undecoded_word(insn: &str) -> Option<u32> {
+    let insn = insn
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace(", ", ",")
+        .to_lowercase();
+    UNDECODED
+        .iter()
+        .find(|&&(_, text)| text == insn)
+        .map(|&(word, _)| word)
+}
+
+/// How far into the trampoline the payload starts, where it's assembled.
+pub const PAYLOAD_OFFSET: u32 = 4 * 16;
+
+/// Which part of the trampoline a word is.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Part {
+    Save,
+    Payload,
+    Restore,
+    /// The instruction the hook displaced
+    Original,
+    /// The branch back to the instruction after the hook
+    Back,
+}
+
+impl fmt::Display for Part {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Save => "save the volatile registers, CR and LR",
+            Self::Payload => "payload",
+            Self::Restore => "restore them",
+            Self::Original => "the instruction the hook displaced",
+            Self::Back => "back to the instruction after the hook",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HookError {
+    /// The hook or the cave isn't word-aligned
+    Misaligned(u32),
+    /// Bytes left over after the payload's last word
+    Partial(usize),
+    /// The payload's last instruction, this word, branches away, so never
+    /// reaches the restores
+    EndsInBranch(u32),
+    /// The displaced instruction, this word, branches relatively, and the
+    /// trampoline has no address to correct it for
+    Relative(u32),
+    /// A branch from `from` can't reach `to`
+    OutOfReach { from: u32, to: u32 },
+}
+
+impl fmt::Display for HookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Misaligned(addr) => write!(f, "{:08x} isn't word-aligned", addr),
+            Self::Partial(n) => write!(f, "{} byte(s) left over after the last word", n),
+            Self::EndsInBranch(word) => write!(
+                f,
+                "the payload's last instruction ({:08X}) branches away, so the \
+                 registers are never restored",
+                word
+            ),
+            Self::Relative(word) => write!(
+                f,
+                "the displaced instruction ({:08X}) branches relatively, which a C2 \
+                 code can't; put the trampoline in a cave with --at, or hook another \
+                 instruction",
+                word
+            ),
+            Self::OutOfReach { from, to } => {
+                write!(f, "a branch at {:08x} can't reach {:08x}", from, to)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HookError {}
+
+/// A payload wrapped for the hook at `hook`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trampoline {
+    pub hook: u32,
+    /// Where the trampoline goes, if it has a cave of its own rather than
+    /// running as a C2 code
+    pub at: Option<u32>,
+    pub words: Vec<(Part, u32)>,
+}
+
+fn encode(op: Opcode) -> u32 {
+    op.encode().expect("the trampoline's instructions encode")
+}
+
+fn stw(r: u8, d: i16) -> u32 {
+    encode(Opcode::Store {
+        width: Width::Word,
+        update: false,
+        rs: Register(r),
+        d,
+        ra: Register::SP,
+    })
+}
+
+fn lwz(r: u8, d: i16) -> u32 {
+    encode(Opcode::Load {
+        width: Width::Word,
+        update: false,
+        rd: Register(r),
+        d,
+        ra: Register::SP,
+    })
+}
+
+/// The relative branch `word`, at `from`, changed to reach the same target
+/// from `to`.
+fn relocate(word: u32, from: u32, to: u32) -> Result<u32, HookError> {
+    let target = match gecko::relative_target(word, from) {
+        Some(target) => target,
+        None => return Ok(word),
+    };
+    let disp = target.wrapping_sub(to) as i32;
+    let (mask, reach) = match word >> 26 {
+        18 => (0x03FF_FFFC, 1 << 25),
+        _ => (0xFFFC, 1 << 15),
+    };
+    if disp < -reach || disp >= reach {
+        return Err(HookError::OutOfReach {
+            from: to,
+            to: target,
+        });
+    }
+    Ok((word & !mask) | (disp as u32 & mask))
+}
+
+/// A `b` at `from` to `to`.
+fn branch(from: u32, to: u32) -> Result<u32, HookError> {
+    relocate(0x4800_0000, to, from)
+}
+
+impl Trampoline {
+    /// `payload`, assembled at [`PAYLOAD_OFFSET`] into the trampoline,
+    /// wrapped for the hook at `hook`, whose instruction was `original`.
+    /// With a cave `at`, the trampoline branches back itself, and a
+    /// relative `original` is corrected for where it now runs.
+    pub fn new(
+        payload: &[u8],
+        hook: u32,
+        original: u32,
+        at: Option<u32>,
+    ) -> Result<Self, HookError> {
+        for addr in Some(hook).into_iter().chain(at) {
+            if !addr.is_multiple_of(4) {
+                return Err(HookError::Misaligned(addr));
+            }
+        }
+        let chunks = payload.chunks_exact(4);
+        if !chunks.remainder().is_empty() {
+            return Err(HookError::Partial(chunks.remainder().len()));
+        }
+        let payload: Vec<_> = chunks
+            .map(|w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]))
+            .collect();
+        if let Some(&last) = payload.last().filter(|&&w| gecko::branches_away(w)) {
+            return Err(HookError::EndsInBranch(last));
+        }
+
+        let mut words = vec![
+            (
+                Part::Save,
+                encode(Opcode::Store {
+                    width: Width::Word,
+                    update: true,
+                    rs: Register::SP,
+                    d: -FRAME,
+                    ra: Register::SP,
+                }),
+            ),
+            (Part::Save, stw(0, R0_SLOT)),
+            (Part::Save, MFLR_R0),
+            (Part::Save, stw(0, LR_SLOT)),
+            (Part::Save, MFCR_R0),
+            (Part::Save, stw(0, CR_SLOT)),
+        ];
+        let gprs = (3..=12).zip((GPR_SLOTS..).step_by(4));
+        words.extend(gprs.clone().map(|(r, d)| (Part::Save, stw(r, d))));
+        words.extend(payload.into_iter().map(|w| (Part::Payload, w)));
+        words.extend(gprs.map(|(r, d)| (Part::Restore, lwz(r, d))));
+        words.extend(vec![
+            (Part::Restore, lwz(0, CR_SLOT)),
+            (Part::Restore, MTCRF_R0),
+            (Part::Restore, lwz(0, LR_SLOT)),
+            (Part::Restore, MTLR_R0),
+            (Part::Restore, lwz(0, R0_SLOT)),
+            (
+                Part::Restore,
+                encode(Opcode::Addi {
+                    rd: Register::SP,
+                    ra: Register::SP,
+                    simm: FRAME,
+                }),
+            ),
+        ]);
+
+        let mut trampoline = Trampoline { hook, at, words };
+        let here = |t: &Self| t.start().wrapping_add(4 * t.words.len() as u32);
+        let original = match at {
+            Some(_) => relocate(original, hook, here(&trampoline))?,
+            None if gecko::relative_target(original, hook).is_some() => {
+                return Err(HookError::Relative(original))
+            }
+            None => original,
+        };
+        trampoline.words.push((Part::Original, original));
+        if at.is_some() {
+            let back = branch(here(&trampoline), hook.wrapping_add(4))?;
+            trampoline.words.push((Part::Back, back));
+        }
+        Ok(trampoline)
+    }
+
+    /// Where the trampoline starts: its cave, or for a C2 code the hook,
+    /// which is where the payload's assembled relative to.
+    fn start(&self) -> u32 {
+        self.at.unwrap_or(self.hook)
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        self.words
+            .iter()
+            .flat_map(|(_, w)| w.to_be_bytes())
+            .collect()
+    }
+
+    /// The branch to the cave to write over the hook, for a trampoline
+    /// with one.
+    pub fn hook_branch(&self) -> Option<Result<u32, HookError>> {
+        self.at.map(|at| branch(self.hook, at))
+    }
+
+    /// The trampoline as a C2 code at the hook.
+    pub fn c2(&self) -> Result<Vec<Line>, GeckoError> {
+        gecko::insert_asm(&self.bytes(), self.hook)
+    }
+
+    /// The trampoline as assembly to assemble at its start, each part under
+    /// a comment. Words that don't disassemble are kept as `.long`, with the
+    /// instruction each of the trampoline's own is.
+    pub fn asm(&self) -> String {
+        let mut out = String::new();
+        let mut part = None;
+        for &(this, word) in &self.words {
+            if part != Some(this) {
+                out += &format!("# {}\n", this);
+                part = Some(this);
+            }
+            let known = UNDECODED
+                .iter()
+                .find(|&&(w, _)| w == word)
+                .map(|&(_, text)| text);
+            out += &match (Opcode::decode(word), known) {
+                (Ok(op), _) => format!("{}\n", op),
+                (Err(_), Some(insn)) if this != Part::Payload => {
+                    format!(".long 0x{:08X}  # {}\n", word, insn)
+                }
+                (Err(_), _) => format!(".long 0x{:08X}\n", word),
+            };
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trampoline() {
+        // li r3,99, hooking an lwz r0,8(r3)
+        let payload = [0x38, 0x60, 0x00, 0x63];
+        let c2 = Trampoline::new(&payload, 0x8000_3100, 0x8003_0008, None).unwrap();
+        assert_eq!(c2.words[0], (Part::Save, 0x9421_FFC0));
+        assert_eq!(
+            4 * c2.words.iter().position(|w| w.0 == Part::Payload).unwrap(),
+            PAYLOAD_OFFSET as usize
+        );
+        assert_eq!(c2.words.last(), Some(&(Part::Original, 0x8003_0008)));
+        let lines = c2.c2().unwrap();
+        // 34 words, a nop and the branch back
+        assert_eq!(lines[0], Line(0xC200_3100, 18));
+        assert!(c2.asm().contains(".long 0x7C0802A6  # mflr r0\n"));
+
+        // a bl from the hook, run from a cave 0x1000 on
+        let cave = Trampoline::new(&payload, 0x8000_3100, 0x4800_0101, Some(0x8000_4100)).unwrap();
+        let tail = &cave.words[cave.words.len() - 2..];
+        assert_eq!(
+            tail,
+            [(Part::Original, 0x4BFF_F07D), (Part::Back, 0x4BFF_EF7C)]
+        );
+        assert_eq!(cave.hook_branch(), Some(Ok(0x4800_1000)));
+        assert_eq!(
+            Trampoline::new(&payload, 0x8000_3100, 0x4800_0101, None),
+            Err(HookError::Relative(0x4800_0101))
+        );
+        assert_eq!(
+            Trampoline::new(&[0x4E, 0x80, 0x00, 0x20], 0x8000_3100, 0, None),
+            Err(HookError::EndsInBranch(0x4E80_0020))
+        );
+    }
+}
@@ -0,0 +1,228 @@
+//! Executable images (DOL and ELF), a code-cave finder
+//! over their loaded sections, and verification data for patches to them.
+
+use core::fmt;
+
+use crate::formats::dol::Dol;
+use crate::formats::elf::Elf;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub addr: u32,
+    pub data: Vec<u8>,
+    pub executable: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Image {
+    pub entry: u32,
+    pub sections: Vec<Section>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageError {
+    /// A header field points past the end of the file.
+    Truncated,
+    /// Neither a DOL nor an ELF file.
+    UnknownFormat,
+    /// A header field of the format asked for makes no sense; `why` says
+    /// which.
+    BadHeader(&'static str),
+    /// A relocation that can't be applied, e.g. a branch out of reach.
+    Relocation { addr: u32, why: &'static str },
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "image is truncated"),
+            Self::UnknownFormat => write!(f, "not a DOL or ELF image"),
+            Self::BadHeader(why) => write!(f, "bad header: {}", why),
+            Self::Relocation { addr, why } => write!(f, "relocation at {:08x}: {}", addr, why),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+pub(crate) fn be32(bytes: &[u8], at: usize) -> Result<u32, ImageError> {
+    let word = bytes.get(at..at + 4).ok_or(ImageError::Truncated)?;
+    Ok(u32::from_be_bytes([word[0], word[1], word[2], word[3]]))
+}
+
+pub(crate) fn be16(bytes: &[u8], at: usize) -> Result<u16, ImageError> {
+    let half = bytes.get(at..at + 2).ok_or(ImageError::Truncated)?;
+    Ok(u16::from_be_bytes([half[0], half[1]]))
+}
+
+pub(crate) fn slice(bytes: &[u8], offset: u32, size: u32) -> Result<Vec<u8>, ImageError> {
+    let start = offset as usize;
+    let end = start
+        .checked_add(size as usize)
+        .ok_or(ImageError::Truncated)?;
+    bytes
+        .get(start..end)
+        .map(<[u8]>::to_vec)
+        .ok_or(ImageError::Truncated)
+}
+
+impl Image {
+    /// A raw binary with no header, all code, loaded at `base`.
+    pub fn raw(bytes: &[u8], base: u32) -> Self {
+        Image {
+            entry: base,
+            sections: vec![Section {
+                addr: base,
+                data: bytes.to_vec(),
+                executable: true,
+            }],
+        }
+    }
+
+    /// Loads a DOL or an ELF, picked by the ELF magic.
+    pub fn parse(bytes: &[u8]) -> Result<Self, ImageError> {
+        if bytes.starts_with(b"\x7fELF") {
+            Self::parse_elf(bytes)
+        } else {
+            Self::parse_dol(bytes)
+        }
+    }
+
+    /// DOL: see [`Dol`](crate::formats::dol::Dol).
+    pub fn parse_dol(bytes: &[u8]) -> Result<Self, ImageError> {
+        Dol::parse(bytes).map(|dol| dol.image())
+    }
+
+    /// ELF: see [`Elf`](crate::formats::elf::Elf).
+    pub fn parse_elf(bytes: &[u8]) -> Result<Self, ImageError> {
+        Elf::parse(bytes)?.image()
+    }
+
+    /// `size` bytes loaded at `addr`, if one section holds all of them.
+    pub fn read(&self, addr: u32, size: u32) -> Option<&[u8]> {
+        self.sections.iter().find_map(|s| {
+            let start = addr.checked_sub(s.addr)? as usize;
+            s.data.get(start..start.checked_add(size as usize)?)
+        })
+    }
+}
+
+/// CRC-32 (IEEE, as zlib and most loaders compute it).
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// What a patch over `size` bytes at `addr` expects to find there, so a
+/// loader can refuse to apply it to the wrong game revision.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verification {
+    pub addr: u32,
+    pub original: Vec<u8>,
+    pub crc32: u32,
+}
+
+impl Verification {
+    pub fn new(image: &Image, addr: u32, size: u32) -> Option<Self> {
+        let original = image.read(addr, size)?.to_vec();
+        Some(Verification {
+            addr,
+            crc32: crc32(&original),
+            original,
+        })
+    }
+
+    /// Riivolution's `original` attribute: the bytes as one hex string.
+    pub fn original_hex(&self) -> String {
+        self.original.iter().map(|b| format!("{:02X}", b)).collect()
+    }
+}
+
+/// A run of zero words inside a loaded text section.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Cave {
+    pub addr: u32,
+    pub size: u32,
+}
+
+/// Zero-filled regions of at least `min_size` bytes in executable sections,
+/// largest first. Only word-aligned zero words count, so a cave can always
+/// hold instructions.
+pub fn find_caves(image: &Image, min_size: u32) -> Vec<Cave> {
+    let mut caves = Vec::new();
+
+    for section in image.sections.iter().filter(|s| s.executable) {
+        let mut start = None;
+        // the non-zero sentinel closes a run that reaches the section end
+        let words = section
+            .data
+            .chunks_exact(4)
+            .chain(std::iter::once(&[1u8][..]));
+        for (i, word) in words.enumerate() {
+            let offset = 4 * i as u32;
+            match (word == [0, 0, 0, 0], start) {
+                (true, None) => start = Some(offset),
+                (false, Some(from)) => {
+                    if offset - from >= min_size {
+                        caves.push(Cave {
+                            addr: section.addr.wrapping_add(from),
+                            size: offset - from,
+                        });
+                    }
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    caves.sort_by_key(|cave| std::cmp::Reverse(cave.size));
+    caves
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dol_caves() {
+        let mut dol = vec![0u8; 0x100];
+        dol[0x00..0x04].copy_from_slice(&0x100u32.to_be_bytes());
+        dol[0x48..0x4C].copy_from_slice(&0x8000_3100u32.to_be_bytes());
+        dol[0x90..0x94].copy_from_slice(&0x30u32.to_be_bytes());
+        dol[0xE0..0xE4].copy_from_slice(&0x8000_3100u32.to_be_bytes());
+
+        let mut text = vec![0u8; 0x30];
+        text[0..4].copy_from_slice(&0x4e80_0020u32.to_be_bytes());
+        text[0x14..0x18].copy_from_slice(&0x6000_0000u32.to_be_bytes());
+        dol.extend(text);
+
+        let image = Image::parse(&dol).unwrap();
+        assert_eq!(image.entry, 0x8000_3100);
+        assert_eq!(
+            find_caves(&image, 8),
+            vec![
+                Cave {
+                    addr: 0x8000_3118,
+                    size: 0x18
+                },
+                Cave {
+                    addr: 0x8000_3104,
+                    size: 0x10
+                },
+            ]
+        );
+        assert_eq!(Image::parse(&dol[..0x80]), Err(ImageError::UnknownFormat));
+
+        let check = Verification::new(&image, 0x8000_3100, 4).unwrap();
+        assert_eq!(check.original_hex(), "4E800020");
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(Verification::new(&image, 0x8000_312c, 8), None);
+    }
+}
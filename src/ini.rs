@@ -0,0 +1,225 @@
+//! Dolphin's per-game INI files (`GameSettings/GALE01.ini`), whose
+//! `[Gecko]` and `[ActionReplay]` sections hold codes under `$Name` lines,
+//! notes on them as `*` lines, and whose `[Gecko_Enabled]` and
+//! `[ActionReplay_Enabled]` sections name the codes turned on:
+//!
+//! ```text
+//! [Gecko]
+//! $Infinite lives [ppcheat]
+//! 04003100 38600001
+//! *Player 1 only
+//! [Gecko_Enabled]
+//! $Infinite lives [ppcheat]
+//! ```
+//!
+//! Only the code sections are edited; the rest of the file is kept line for
+//! line.
+
+use core::fmt;
+
+use crate::codelist::NamedCode;
+use crate::gecko::{is_code, CodeListError};
+
+/// Which of the code sections.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Codes {
+    Gecko,
+    ActionReplay,
+}
+
+impl Codes {
+    fn section(self) -> &'static str {
+        match self {
+            Self::Gecko => "Gecko",
+            Self::ActionReplay => "ActionReplay",
+        }
+    }
+
+    fn enabled_section(self) -> &'static str {
+        match self {
+            Self::Gecko => "Gecko_Enabled",
+            Self::ActionReplay => "ActionReplay_Enabled",
+        }
+    }
+}
+
+/// The section's name, `Gecko` or `ActionReplay`.
+impl fmt::Display for Codes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.section())
+    }
+}
+
+/// An INI file's lines, under the name of the section each is in; lines
+/// before the first heading are under an empty one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GameIni {
+    sections: Vec<(String, Vec<String>)>,
+}
+
+impl GameIni {
+    pub fn parse(text: &str) -> Self {
+        let mut sections = vec![(String::new(), Vec::new())];
+        for line in text.lines() {
+            let heading = line.trim().strip_prefix('[');
+            match heading.and_then(|h| h.strip_suffix(']')) {
+                Some(name) => sections.push((name.to_string(), Vec::new())),
+                None => sections.last_mut().unwrap().1.push(line.to_string()),
+            }
+        }
+        GameIni { sections }
+    }
+
+    fn lines<'a>(&'a self, section: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.sections
+            .iter()
+            .filter(move |(name, _)| name == section)
+            .flat_map(|(_, lines)| lines.iter().map(|l| l.trim()))
+    }
+
+    /// The lines of `section`, which is added at the end if it isn't there.
+    fn lines_mut(&mut self, section: &str) -> &mut Vec<String> {
+        let at = match self.sections.iter().position(|(name, _)| name == section) {
+            Some(at) => at,
+            None => {
+                self.sections.push((section.to_string(), Vec::new()));
+                self.sections.len() - 1
+            }
+        };
+        &mut self.sections[at].1
+    }
+
+    /// The codes of one of the code sections.
+    pub fn codes(&self, codes: Codes) -> Result<Vec<NamedCode>, CodeListError> {
+        let mut out: Vec<NamedCode> = Vec::new();
+        for (i, line) in self.lines(codes.section()).enumerate() {
+            if let Some(name) = line.strip_prefix('$') {
+                out.push(NamedCode {
+                    name: name.trim().to_string(),
+                    lines: Vec::new(),
+                    notes: Vec::new(),
+                });
+                continue;
+            }
+            let code = match out.last_mut() {
+                Some(code) => code,
+                None if line.is_empty() => continue,
+                None => {
+                    return Err(CodeListError {
+                        line: i + 1,
+                        message: format!(
+                            "`{}` before the first $name in [{}]",
+                            line,
+                            codes.section()
+                        ),
+                    })
+                }
+            };
+            if let Some(note) = line.strip_prefix('*') {
+                code.notes.push(note.to_string());
+            } else if is_code(line) {
+                let parsed = line.parse().map_err(|message| CodeListError {
+                    line: i + 1,
+                    message,
+                })?;
+                code.lines.push(parsed);
+            }
+        }
+        Ok(out)
+    }
+
+    /// The names of the codes turned on.
+    pub fn enabled(&self, codes: Codes) -> Vec<&str> {
+        self.lines(codes.enabled_section())
+            .filter_map(|line| line.strip_prefix('$'))
+            .map(str::trim)
+            .collect()
+    }
+
+    /// Puts `code` in the section for `codes`, replacing one of the same
+    /// name, and turns it on.
+    pub fn set_code(&mut self, codes: Codes, code: NamedCode) -> Result<(), CodeListError> {
+        let mut list = self.codes(codes)?;
+        match list.iter_mut().find(|c| c.name == code.name) {
+            Some(existing) => *existing = code.clone(),
+            None => list.push(code.clone()),
+        }
+        let lines = self.lines_mut(codes.section());
+        lines.clear();
+        for code in &list {
+            lines.push(format!("${}", code.name));
+            lines.extend(code.lines.iter().map(|l| l.to_string()));
+            lines.extend(code.notes.iter().map(|n| format!("*{}", n)));
+        }
+
+        if !self.enabled(codes).contains(&code.name.as_str()) {
+            let enabled = self.lines_mut(codes.enabled_section());
+            // after the last name, ahead of any blank lines between sections
+            let at = enabled
+                .iter()
+                .rposition(|l| !l.trim().is_empty())
+                .map_or(0, |i| i + 1);
+            enabled.insert(at, format!("${}", code.name));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for GameIni {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (name, lines)) in self.sections.iter().enumerate() {
+            if i > 0 || !name.is_empty() {
+                writeln!(f, "[{}]", name)?;
+            }
+            for line in lines {
+                writeln!(f, "{}", line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gecko::Line;
+
+    #[test]
+    fn test_game_ini() {
+        let mut ini = GameIni::parse(
+            "# GALE01 - Super Smash Bros. Melee\n\
+             [Core]\n\
+             CPUThread = False\n\
+             [Gecko]\n\
+             $Old code\n\
+             04003000 60000000\n\
+             *A note\n",
+        );
+        let code = |name: &str, value| NamedCode {
+            name: name.to_string(),
+            lines: vec![Line(0x0400_3100, value)],
+            notes: Vec::new(),
+        };
+        ini.set_code(Codes::Gecko, code("Infinite lives [ppcheat]", 1))
+            .unwrap();
+        ini.set_code(Codes::Gecko, code("Infinite lives [ppcheat]", 2))
+            .unwrap();
+        assert_eq!(
+            ini.to_string(),
+            "# GALE01 - Super Smash Bros. Melee\n\
+             [Core]\n\
+             CPUThread = False\n\
+             [Gecko]\n\
+             $Old code\n\
+             04003000 60000000\n\
+             *A note\n\
+             $Infinite lives [ppcheat]\n\
+             04003100 00000002\n\
+             [Gecko_Enabled]\n\
+             $Infinite lives [ppcheat]\n"
+        );
+        let parsed = GameIni::parse(&ini.to_string());
+        assert_eq!(parsed.codes(Codes::Gecko).unwrap()[0].notes, ["A note"]);
+        assert_eq!(parsed.enabled(Codes::Gecko), ["Infinite lives [ppcheat]"]);
+    }
+}
@@ -0,0 +1,1569 @@
+use core::fmt;
+use std::cell::Cell;
+
+pub mod action_replay;
+pub mod asm;
+pub mod browse;
+pub mod budget;
+pub mod clobber;
+pub mod codelist;
+pub mod compare;
+pub mod describe;
+pub mod diff;
+pub mod disasm;
+pub mod encoding;
+pub mod eval;
+pub mod explanation;
+mod expr;
+pub mod formats;
+pub mod gecko;
+pub mod hook;
+pub mod image;
+pub mod ini;
+pub mod locale;
+pub mod macros;
+pub mod memory_map;
+pub mod objdump;
+pub mod parser;
+pub mod patches;
+pub mod port;
+pub mod profile;
+pub mod python;
+pub mod reference;
+pub mod relocate;
+pub mod render;
+pub mod riivolution;
+#[cfg(test)]
+mod roundtrip;
+pub mod rust;
+pub mod signature;
+pub mod symbols;
+pub mod tidy;
+pub mod trace;
+pub mod vectors;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Register(pub u8);
+
+impl Register {
+    pub const SP: Register = Register(1);
+    pub const RTOC: Register = Register(2);
+    pub const FP: Register = Register(31);
+
+    /// ABI name (`sp`, `rtoc`, `fp`) as used in CodeWarrior listings.
+    pub fn alias(self) -> Option<&'static str> {
+        match self {
+            Self::SP => Some("sp"),
+            Self::RTOC => Some("rtoc"),
+            Self::FP => Some("fp"),
+            _ => None,
+        }
+    }
+}
+
+/// `{}` always prints `rN`; `{:#}` prefers the ABI alias where there is one.
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.alias() {
+            Some(alias) if f.alternate() => f.write_str(alias),
+            _ => write!(f, "r{}", self.0),
+        }
+    }
+}
+
+/// Condition register field, `cr0`..`cr7`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CrField(pub u8);
+
+impl fmt::Display for CrField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cr{}", self.0)
+    }
+}
+
+/// Condition register bit 0..31, i.e. `4*crN + {lt,gt,eq,so}`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CrBit(pub u8);
+
+impl CrBit {
+    pub const CONDITIONS: [&'static str; 4] = ["lt", "gt", "eq", "so"];
+
+    pub fn field(self) -> CrField {
+        CrField(self.0 / 4)
+    }
+
+    pub fn condition(self) -> &'static str {
+        Self::CONDITIONS[self.0 as usize % 4]
+    }
+}
+
+/// Assembler spelling as objdump prints it: `eq`, `4*cr1+gt`. `{:#}` gives
+/// the `cr1.gt` form used in explanations.
+impl fmt::Display for CrBit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.field().0 {
+            _ if f.alternate() => write!(f, "{}.{}", self.field(), self.condition()),
+            0 => f.write_str(self.condition()),
+            n => write!(f, "4*cr{}+{}", n, self.condition()),
+        }
+    }
+}
+
+/// Operation of a condition register logical instruction.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CrLogic {
+    And,
+    Or,
+    Xor,
+    Nand,
+    Nor,
+    Eqv,
+    Andc,
+    Orc,
+}
+
+impl CrLogic {
+    pub fn apply(self, a: bool, b: bool) -> bool {
+        match self {
+            Self::And => a & b,
+            Self::Or => a | b,
+            Self::Xor => a ^ b,
+            Self::Nand => !(a & b),
+            Self::Nor => !(a | b),
+            Self::Eqv => a == b,
+            Self::Andc => a & !b,
+            Self::Orc => a | !b,
+        }
+    }
+}
+
+/// Access size of a load or store.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Width {
+    Byte,
+    Half,
+    Word,
+}
+
+impl Width {
+    pub fn bytes(self) -> u32 {
+        match self {
+            Self::Byte => 1,
+            Self::Half => 2,
+            Self::Word => 4,
+        }
+    }
+
+    pub(crate) fn c_type(self) -> &'static str {
+        match self {
+            Self::Byte => "u8",
+            Self::Half => "u16",
+            Self::Word => "u32",
+        }
+    }
+}
+
+/// How bit positions are numbered in mask operands and explanations. The
+/// encoding, and the assembly ppcheat prints, always use IBM numbering.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BitOrder {
+    /// IBM numbering, as in the manuals: bit 0 is the most significant
+    Msb0,
+    /// Bit 0 is the least significant, as in C and most other ISAs
+    Lsb0,
+}
+
+thread_local! {
+    static BIT_ORDER: Cell<BitOrder> = const { Cell::new(BitOrder::Msb0) };
+}
+
+impl BitOrder {
+    /// The numbering in effect, [`BitOrder::Msb0`] unless [`BitOrder::set`].
+    pub fn current() -> Self {
+        BIT_ORDER.with(Cell::get)
+    }
+
+    /// Use this numbering from now on, for parsing and explaining alike.
+    pub fn set(self) {
+        BIT_ORDER.with(|order| order.set(self));
+    }
+
+    /// IBM bit `bit` in this numbering. It's its own inverse, so it also
+    /// turns a position in this numbering into an IBM one.
+    pub fn number(self, bit: u8) -> u8 {
+        match self {
+            Self::Msb0 => bit,
+            Self::Lsb0 => 31 - (bit & 31),
+        }
+    }
+
+    /// `bit 12`, or `bit 19 (LSB=0)` so the numbering is never in doubt.
+    pub(crate) fn bit(bit: u8) -> String {
+        match Self::current() {
+            Self::Msb0 => locale::message("bit", &[("bit", &bit)]),
+            Self::Lsb0 => locale::message("bit-lsb0", &[("bit", &Self::Lsb0.number(bit))]),
+        }
+    }
+
+    /// IBM bits `first..last` in this numbering, as `12..19` or `19..12`.
+    pub(crate) fn range(first: u8, last: u8) -> String {
+        let order = Self::current();
+        format!("{}..{}", order.number(first), order.number(last))
+    }
+}
+
+/// Relocation operator on a 16-bit immediate, as in `lis r3,sym@ha`, or a
+/// branch to a symbol.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Reloc {
+    /// `@l`: low half
+    Lo,
+    /// `@h`: high half
+    Hi,
+    /// `@ha`: high half, adjusted for `@l` being added as a signed value
+    Ha,
+    /// `@sda21`: offset from the small data base, r13 or r2
+    Sda21,
+    /// `b sym`: displacement from the branch to the symbol
+    Rel,
+}
+
+impl Reloc {
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Self::Lo => "l",
+            Self::Hi => "h",
+            Self::Ha => "ha",
+            Self::Sda21 => "sda21",
+            Self::Rel => "rel",
+        }
+    }
+
+    /// Field value for a known address; `@sda21` needs the linker and a
+    /// branch the address of the branch.
+    pub fn apply(self, value: i64) -> Option<i16> {
+        let half = match self {
+            Self::Lo => value,
+            Self::Hi => value >> 16,
+            Self::Ha => (value + 0x8000) >> 16,
+            Self::Sda21 | Self::Rel => return None,
+        };
+        Some(half as u16 as i16)
+    }
+}
+
+/// Symbol reference the linker fills into an instruction's immediate. The
+/// parsed `Opcode` carries 0 in that field, like an unlinked object file.
+/// A branch to an absolute address, `b 0x80001000`, has no symbol, and the
+/// address as its addend.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relocation {
+    pub reloc: Reloc,
+    pub symbol: String,
+    pub addend: i64,
+}
+
+impl Relocation {
+    /// `sym`, `sym+8` or `sym-4`, or the address without a symbol
+    pub fn target(&self) -> String {
+        match self.addend {
+            a if self.symbol.is_empty() => format!("{:#x}", a),
+            0 => self.symbol.clone(),
+            a if a < 0 => format!("{}-{}", self.symbol, -a),
+            a => format!("{}+{}", self.symbol, a),
+        }
+    }
+}
+
+impl fmt::Display for Relocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reloc {
+            Reloc::Rel => f.write_str(&self.target()),
+            reloc => write!(f, "{}@{}", self.target(), reloc.suffix()),
+        }
+    }
+}
+
+/// A hex address, `80003100` or `0x80003100`, as given on the command line
+/// or in a symbol map, trace or profile.
+pub fn parse_hex_u32(text: &str) -> Option<u32> {
+    let text = text.trim();
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+    match digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        true => u32::from_str_radix(digits, 16).ok(),
+        false => None,
+    }
+}
+
+/// A branch's target as an offset from the branch: `.`, `.+8` or `.-16`.
+pub(crate) fn here(disp: i32) -> String {
+    match disp {
+        0 => ".".to_string(),
+        disp => format!(".{:+}", disp),
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Opcode {
+    /// Rotate Left Word Immediate then AND with Mask. With `rc`, the record
+    /// form `rlwinm.`, which also compares the result with 0 into cr0; the
+    /// other rotates' `rc` is the same.
+    Rlwinm {
+        ra: Register,
+        rs: Register,
+        sh: u8,
+        mb: u8,
+        me: u8,
+        rc: bool,
+    },
+
+    /// Rotate Left Word Immediate then Mask Insert
+    Rlwimi {
+        ra: Register,
+        rs: Register,
+        sh: u8,
+        mb: u8,
+        me: u8,
+        rc: bool,
+    },
+
+    /// Rotate Left Word then AND with Mask
+    Rlwnm {
+        ra: Register,
+        rs: Register,
+        rb: Register,
+        mb: u8,
+        me: u8,
+        rc: bool,
+    },
+
+    /// Add Immediate
+    Addi {
+        rd: Register,
+        ra: Register,
+        simm: i16,
+    },
+
+    /// Add Immediate Shifted
+    Addis {
+        rd: Register,
+        ra: Register,
+        simm: i16,
+    },
+
+    /// OR Immediate: `uimm` zero-extended
+    Ori {
+        ra: Register,
+        rs: Register,
+        uimm: u16,
+    },
+
+    /// OR Immediate Shifted: `uimm` in the high half
+    Oris {
+        ra: Register,
+        rs: Register,
+        uimm: u16,
+    },
+
+    /// Load Byte/Half Word/Word and Zero (with Update)
+    Load {
+        width: Width,
+        update: bool,
+        rd: Register,
+        d: i16,
+        ra: Register,
+    },
+
+    /// Load Half Word Algebraic (with Update)
+    Lha {
+        update: bool,
+        rd: Register,
+        d: i16,
+        ra: Register,
+    },
+
+    /// Store Byte/Half Word/Word (with Update)
+    Store {
+        width: Width,
+        update: bool,
+        rs: Register,
+        d: i16,
+        ra: Register,
+    },
+
+    /// Compare Word (Logical): signed, or unsigned if `logical`
+    Cmp {
+        logical: bool,
+        crf: CrField,
+        ra: Register,
+        rb: Register,
+    },
+
+    /// Compare Word (Logical) Immediate. `imm` holds the raw 16-bit field,
+    /// sign-extended for cmpwi and zero-extended for cmplwi.
+    Cmpi {
+        logical: bool,
+        crf: CrField,
+        ra: Register,
+        imm: i16,
+    },
+
+    /// Condition Register AND/OR/XOR/NAND/NOR/EQV/AND with Complement/OR with Complement
+    CrOp {
+        op: CrLogic,
+        bt: CrBit,
+        ba: CrBit,
+        bb: CrBit,
+    },
+
+    /// Branch (and Link): `disp` is a byte offset from the instruction,
+    /// 26 bits signed (±32MB); `bl` leaves the return address in lr
+    B {
+        disp: i32,
+        link: bool,
+    },
+
+    /// Branch Conditional, printed as its simplified form where there is one
+    /// (`beq cr7,target`, `bdnz target`). `bo` picks the test of CR bit `bi`
+    /// and ctr; `disp` is 16 bits signed (±32KB).
+    Bc {
+        bo: u8,
+        bi: CrBit,
+        disp: i16,
+    },
+
+    // ---- pseudomnemonics ----
+    Extlwi {
+        ra: Register,
+        rs: Register,
+        n: u8,
+        b: u8,
+    },
+
+    Extrwi {
+        ra: Register,
+        rs: Register,
+        n: u8,
+        b: u8,
+    },
+
+    Rotlwi {
+        ra: Register,
+        rs: Register,
+        n: u8,
+    },
+
+    Rotrwi {
+        ra: Register,
+        rs: Register,
+        n: u8,
+    },
+
+    Slwi {
+        ra: Register,
+        rs: Register,
+        n: u8,
+    },
+
+    Srwi {
+        ra: Register,
+        rs: Register,
+        n: u8,
+    },
+
+    Clrlwi {
+        ra: Register,
+        rs: Register,
+        n: u8,
+    },
+
+    Clrrwi {
+        ra: Register,
+        rs: Register,
+        n: u8,
+    },
+
+    Clrlslwi {
+        ra: Register,
+        rs: Register,
+        b: u8,
+        n: u8,
+    },
+
+    Rotlw {
+        ra: Register,
+        rs: Register,
+        rb: Register,
+    },
+
+    Inslwi {
+        ra: Register,
+        rs: Register,
+        n: u8,
+        b: u8,
+    },
+
+    Insrwi {
+        ra: Register,
+        rs: Register,
+        n: u8,
+        b: u8,
+    },
+
+    Li {
+        rd: Register,
+        simm: i16,
+    },
+
+    Lis {
+        rd: Register,
+        simm: i16,
+    },
+}
+
+impl Opcode {
+    pub fn mnemonic(&self) -> &'static str {
+        match *self {
+            Self::Rlwinm { rc: false, .. } => "rlwinm",
+            Self::Rlwinm { rc: true, .. } => "rlwinm.",
+            Self::Rlwimi { rc: false, .. } => "rlwimi",
+            Self::Rlwimi { rc: true, .. } => "rlwimi.",
+            Self::Rlwnm { rc: false, .. } => "rlwnm",
+            Self::Rlwnm { rc: true, .. } => "rlwnm.",
+            Self::Addi { .. } => "addi",
+            Self::Addis { .. } => "addis",
+            Self::Ori { .. } => "ori",
+            Self::Oris { .. } => "oris",
+            Self::Load { width, update, .. } => match (width, update) {
+                (Width::Byte, false) => "lbz",
+                (Width::Byte, true) => "lbzu",
+                (Width::Half, false) => "lhz",
+                (Width::Half, true) => "lhzu",
+                (Width::Word, false) => "lwz",
+                (Width::Word, true) => "lwzu",
+            },
+            Self::Lha { update: false, .. } => "lha",
+            Self::Lha { update: true, .. } => "lhau",
+            Self::Store { width, update, .. } => match (width, update) {
+                (Width::Byte, false) => "stb",
+                (Width::Byte, true) => "stbu",
+                (Width::Half, false) => "sth",
+                (Width::Half, true) => "sthu",
+                (Width::Word, false) => "stw",
+                (Width::Word, true) => "stwu",
+            },
+            Self::Cmp { logical: false, .. } => "cmpw",
+            Self::Cmp { logical: true, .. } => "cmplw",
+            Self::Cmpi { logical: false, .. } => "cmpwi",
+            Self::Cmpi { logical: true, .. } => "cmplwi",
+            Self::B { link: false, .. } => "b",
+            Self::B { link: true, .. } => "bl",
+            Self::Bc { bo, bi, .. } => match (bo, bi.0 % 4) {
+                (12, 0) => "blt",
+                (12, 1) => "bgt",
+                (12, 2) => "beq",
+                (12, 3) => "bso",
+                (4, 0) => "bge",
+                (4, 1) => "ble",
+                (4, 2) => "bne",
+                (4, 3) => "bns",
+                (16, _) if bi.0 == 0 => "bdnz",
+                (18, _) if bi.0 == 0 => "bdz",
+                _ => "bc",
+            },
+            Self::CrOp { op, .. } => match op {
+                CrLogic::And => "crand",
+                CrLogic::Or => "cror",
+                CrLogic::Xor => "crxor",
+                CrLogic::Nand => "crnand",
+                CrLogic::Nor => "crnor",
+                CrLogic::Eqv => "creqv",
+                CrLogic::Andc => "crandc",
+                CrLogic::Orc => "crorc",
+            },
+            Self::Extlwi { .. } => "extlwi",
+            Self::Extrwi { .. } => "extrwi",
+            Self::Rotlwi { .. } => "rotlwi",
+            Self::Rotrwi { .. } => "rotrwi",
+            Self::Slwi { .. } => "slwi",
+            Self::Srwi { .. } => "srwi",
+            Self::Clrlwi { .. } => "clrlwi",
+            Self::Clrrwi { .. } => "clrrwi",
+            Self::Clrlslwi { .. } => "clrlslwi",
+            Self::Rotlw { .. } => "rotlw",
+            Self::Inslwi { .. } => "inslwi",
+            Self::Insrwi { .. } => "insrwi",
+            Self::Li { .. } => "li",
+            Self::Lis { .. } => "lis",
+        }
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let m = self.mnemonic();
+        let alt = f.alternate();
+        let r = |r: Register| {
+            if alt {
+                format!("{:#}", r)
+            } else {
+                r.to_string()
+            }
+        };
+        match *self {
+            Self::Rlwinm {
+                ra, rs, sh, mb, me, ..
+            }
+            | Self::Rlwimi {
+                ra, rs, sh, mb, me, ..
+            } => write!(f, "{} {},{},{},{},{}", m, r(ra), r(rs), sh, mb, me),
+            Self::Rlwnm {
+                ra, rs, rb, mb, me, ..
+            } => {
+                write!(f, "{} {},{},{},{},{}", m, r(ra), r(rs), r(rb), mb, me)
+            }
+            Self::Addi { rd, ra, simm } => write!(f, "{} {},{},{}", m, r(rd), r(ra), simm),
+            Self::Addis { rd, ra, simm } => {
+                write!(f, "{} {},{},{:#x}", m, r(rd), r(ra), simm as u16)
+            }
+            Self::Ori { ra, rs, uimm } | Self::Oris { ra, rs, uimm } => {
+                write!(f, "{} {},{},{:#x}", m, r(ra), r(rs), uimm)
+            }
+            Self::Load { rd, d, ra, .. } | Self::Lha { rd, d, ra, .. } => {
+                write!(f, "{} {},{}({})", m, r(rd), d, r(ra))
+            }
+            Self::Store { rs, d, ra, .. } => write!(f, "{} {},{}({})", m, r(rs), d, r(ra)),
+            Self::Cmp { crf, ra, rb, .. } => write!(f, "{} {},{},{}", m, crf, r(ra), r(rb)),
+            Self::Cmpi {
+                logical: false,
+                crf,
+                ra,
+                imm,
+            } => write!(f, "{} {},{},{}", m, crf, r(ra), imm),
+            Self::Cmpi { crf, ra, imm, .. } => write!(f, "{} {},{},{}", m, crf, r(ra), imm as u16),
+            Self::CrOp { bt, ba, bb, .. } => write!(f, "{} {},{},{}", m, bt, ba, bb),
+            Self::B { disp, .. } => write!(f, "{} {}", m, here(disp)),
+            Self::Bc { bo, bi, disp } => match m {
+                "bc" => write!(f, "{} {},{},{}", m, bo, bi.0, here(disp.into())),
+                "bdnz" | "bdz" => write!(f, "{} {}", m, here(disp.into())),
+                _ if bi.field().0 == 0 => write!(f, "{} {}", m, here(disp.into())),
+                _ => write!(f, "{} {},{}", m, bi.field(), here(disp.into())),
+            },
+            Self::Extlwi { ra, rs, n, b }
+            | Self::Extrwi { ra, rs, n, b }
+            | Self::Inslwi { ra, rs, n, b }
+            | Self::Insrwi { ra, rs, n, b } => write!(f, "{} {},{},{},{}", m, r(ra), r(rs), n, b),
+            Self::Clrlslwi { ra, rs, b, n } => write!(f, "{} {},{},{},{}", m, r(ra), r(rs), b, n),
+            Self::Rotlwi { ra, rs, n }
+            | Self::Rotrwi { ra, rs, n }
+            | Self::Slwi { ra, rs, n }
+            | Self::Srwi { ra, rs, n }
+            | Self::Clrlwi { ra, rs, n }
+            | Self::Clrrwi { ra, rs, n } => write!(f, "{} {},{},{}", m, r(ra), r(rs), n),
+            Self::Rotlw { ra, rs, rb } => write!(f, "{} {},{},{}", m, r(ra), r(rs), r(rb)),
+            Self::Li { rd, simm } => write!(f, "{} {},{}", m, r(rd), simm),
+            Self::Lis { rd, simm } => write!(f, "{} {},{:#x}", m, r(rd), simm as u16),
+        }
+    }
+}
+
+/// A mask as C would write it: `0xFFFF`, or `~7` when that's shorter.
+fn c_constant(m: u32) -> String {
+    let hex = |v: u32| match v {
+        0..=9 => v.to_string(),
+        _ => format!("0x{:X}", v),
+    };
+    match m.count_zeros() < 8 {
+        true => format!("~{}", hex(!m)),
+        false => hex(m),
+    }
+}
+
+/// `MASK(mb..me)=0xFFFF0000`: the mask in IBM bit numbering and its value.
+/// A mask that wraps around is shown as its two runs, `MASK(0..7,24..31)`.
+/// With [`BitOrder::Lsb0`] the bits are renumbered and labelled,
+/// `MASK(31..16, LSB=0)`.
+pub(crate) fn mask_name(mb: u8, me: u8) -> String {
+    let runs = match wrapped(mb, me) {
+        Some(_) => format!("{},{}", BitOrder::range(0, me), BitOrder::range(mb, 31)),
+        None => BitOrder::range(mb, me),
+    };
+    let label = match BitOrder::current() {
+        BitOrder::Msb0 => "",
+        BitOrder::Lsb0 => ", LSB=0",
+    };
+    format!("MASK({}{})=0x{:08X}", runs, label, eval::mask(mb, me))
+}
+
+/// How many `(top, bottom)` bits a mask with mb > me keeps, unless it
+/// wraps all the way around to keep everything.
+pub(crate) fn wrapped(mb: u8, me: u8) -> Option<(u8, u8)> {
+    let (mb, me) = (mb & 31, me & 31);
+    match mb > me && mb != me + 1 {
+        true => Some((me + 1, 32 - mb)),
+        false => None,
+    }
+}
+
+impl Opcode {
+    /// The AND mask of a rotate (after canonicalizing), e.g. `0xFFFF0000`
+    /// for `rlwinm r3,r4,0,0,15`. When mb > me the mask wraps around.
+    pub fn mask_value(&self) -> Option<u32> {
+        match self.canonicalize() {
+            Self::Rlwinm { mb, me, .. }
+            | Self::Rlwimi { mb, me, .. }
+            | Self::Rlwnm { mb, me, .. } => Some(eval::mask(mb, me)),
+            _ => None,
+        }
+    }
+
+    /// `(destination, source)` when the instruction only copies a register,
+    /// like `rlwinm r3,r4,0,0,31` or `addi r3,r4,0`. They're the same
+    /// register when it does nothing at all. A record form also sets cr0,
+    /// so it never just copies.
+    pub(crate) fn copies(&self) -> Option<(Register, Register)> {
+        match self.canonicalize() {
+            Self::Rlwinm {
+                ra,
+                rs,
+                sh,
+                mb,
+                me,
+                rc: false,
+            }
+            | Self::Rlwimi {
+                ra,
+                rs,
+                sh,
+                mb,
+                me,
+                rc: false,
+            } if sh % 32 == 0 && eval::mask(mb, me) == u32::MAX => Some((ra, rs)),
+            Self::Rlwimi {
+                ra,
+                rs,
+                sh,
+                rc: false,
+                ..
+            } if sh % 32 == 0 && ra == rs => Some((ra, rs)),
+            Self::Addi { rd, ra, simm: 0 } | Self::Addis { rd, ra, simm: 0 } if ra.0 != 0 => {
+                Some((rd, ra))
+            }
+            Self::Ori { ra, rs, uimm: 0 } | Self::Oris { ra, rs, uimm: 0 } => Some((ra, rs)),
+            _ => None,
+        }
+    }
+
+    /// The instruction as C-like pseudocode, e.g. `r3 = (r4 << 8) &
+    /// MASK(24..31)=0x000000FF`; [`Opcode::explain`] has the pieces.
+    pub fn highlevel(&self) -> String {
+        self.explain().to_string()
+    }
+
+    /// `highlevel` as plain C: a rotate that degenerates into a shift is
+    /// written as one, e.g. `r3 = (r4 >> 8) & 0xFFFF`; any other rotate
+    /// calls `rotl32`. A record form goes on to set cr0. Instructions other
+    /// than rotates read as `highlevel`, as do rotates whose operands
+    /// [`Opcode::check`] refuses.
+    pub fn highlevel_c(&self) -> String {
+        if self.check().is_err() {
+            return self.highlevel();
+        }
+        let c = match self.canonicalize() {
+            Self::Rlwnm {
+                ra, rs, rb, mb, me, ..
+            } => match eval::mask(mb, me) {
+                u32::MAX => format!("{} = rotl32({}, {})", ra, rs, rb),
+                m => format!("{} = rotl32({}, {}) & {}", ra, rs, rb, c_constant(m)),
+            },
+            _ => match self.rotate_c() {
+                Some(c) => c,
+                None => return self.highlevel(),
+            },
+        };
+        match self.record() {
+            Some(ra) => format!("{}; cr0 = compare({}, 0)", c, ra),
+            None => c,
+        }
+    }
+
+    /// [`highlevel_c`](Self::highlevel_c) of a rotate by an immediate.
+    fn rotate_c(&self) -> Option<String> {
+        let (ra, rs, sh, mb, me) = match self.canonicalize() {
+            Self::Rlwinm {
+                ra, rs, sh, mb, me, ..
+            }
+            | Self::Rlwimi {
+                ra, rs, sh, mb, me, ..
+            } => (ra, rs, sh, mb, me),
+            _ => return None,
+        };
+        let mask = eval::mask(mb, me);
+        let sh = u32::from(sh);
+        // the bits a shift in either direction leaves set
+        let (left, right) = (u32::MAX.wrapping_shl(sh), u32::MAX.wrapping_shr(32 - sh));
+        let (value, bits, shift) = if sh == 0 {
+            (rs.to_string(), u32::MAX, false)
+        } else if mask & !left == 0 {
+            (format!("{} << {}", rs, sh), left, true)
+        } else if mask & !right == 0 {
+            (format!("{} >> {}", rs, 32 - sh), right, true)
+        } else {
+            (format!("rotl32({}, {})", rs, sh), u32::MAX, false)
+        };
+        let masked = match (mask == bits, shift) {
+            (true, _) => value,
+            (false, true) => format!("({}) & {}", value, c_constant(mask)),
+            (false, false) => format!("{} & {}", value, c_constant(mask)),
+        };
+
+        Some(match self.canonicalize() {
+            Self::Rlwimi { .. } => {
+                format!("{} = ({} & {}) | ({})", ra, ra, c_constant(!mask), masked)
+            }
+            _ => format!("{} = {}", ra, masked),
+        })
+    }
+
+    /// The register a record form compares with 0 into cr0, if it is one.
+    pub(crate) fn record(&self) -> Option<Register> {
+        match *self {
+            Self::Rlwinm { ra, rc: true, .. }
+            | Self::Rlwimi { ra, rc: true, .. }
+            | Self::Rlwnm { ra, rc: true, .. } => Some(ra),
+            _ => None,
+        }
+    }
+
+    /// The rotate a rotate or pseudo-op stands for, as its record form with
+    /// `rc` (`rlwinm.` for `rlwinm` or `clrlwi`) and without it otherwise;
+    /// `None` for anything else, which has no record form here.
+    pub fn with_record(&self, rc: bool) -> Option<Self> {
+        Some(match self.canonicalize() {
+            Self::Rlwinm {
+                ra, rs, sh, mb, me, ..
+            } => Self::Rlwinm {
+                ra,
+                rs,
+                sh,
+                mb,
+                me,
+                rc,
+            },
+            Self::Rlwimi {
+                ra, rs, sh, mb, me, ..
+            } => Self::Rlwimi {
+                ra,
+                rs,
+                sh,
+                mb,
+                me,
+                rc,
+            },
+            Self::Rlwnm {
+                ra, rs, rb, mb, me, ..
+            } => Self::Rlwnm {
+                ra,
+                rs,
+                rb,
+                mb,
+                me,
+                rc,
+            },
+            _ => return None,
+        })
+    }
+
+    /// What a pseudo-op is for, in C terms; `None` for real instructions,
+    /// and for pseudo-ops whose operands break their rules.
+    fn intent(&self) -> Option<String> {
+        self.check().ok()?;
+        let low_bits = |n: u8| ((1u64 << n) - 1) as u32;
+        Some(match *self {
+            Self::Extlwi { ra, rs, n, b } => locale::message(
+                "intent-extlwi",
+                &[
+                    ("ra", &ra),
+                    ("rs", &rs),
+                    ("range", &BitOrder::range(b, b + n - 1)),
+                ],
+            ),
+            Self::Extrwi { ra, rs, n, b } => {
+                format!("{} = ({} >> {}) & {:#x}", ra, rs, 32 - (b + n), low_bits(n))
+            }
+            Self::Rotlwi { ra, rs, n } => format!("{} = rotl({}, {})", ra, rs, n),
+            Self::Rotrwi { ra, rs, n } => format!("{} = rotr({}, {})", ra, rs, n),
+            Self::Slwi { ra, rs, n } => format!("{} = {} << {}", ra, rs, n),
+            Self::Srwi { ra, rs, n } => format!("{} = {} >> {}", ra, rs, n),
+            Self::Clrlwi { ra, rs, n } => format!("{} = {} & {:#x}", ra, rs, low_bits(32 - n)),
+            Self::Clrrwi { ra, rs, n } => format!("{} = {} & {:#x}", ra, rs, !low_bits(n)),
+            Self::Clrlslwi { ra, rs, b, n } => {
+                format!("{} = ({} & {:#x}) << {}", ra, rs, low_bits(32 - b), n)
+            }
+            Self::Rotlw { ra, rs, rb } => format!("{} = rotl({}, {})", ra, rs, rb),
+            Self::Inslwi { ra, rs, n, b } => locale::message(
+                "intent-inslwi",
+                &[
+                    ("ra", &ra),
+                    ("dest", &BitOrder::range(b, b + n - 1)),
+                    ("rs", &rs),
+                    ("src", &BitOrder::range(0, n - 1)),
+                ],
+            ),
+            Self::Insrwi { ra, rs, n, b } => locale::message(
+                "intent-insrwi",
+                &[
+                    ("ra", &ra),
+                    ("dest", &BitOrder::range(b, b + n - 1)),
+                    ("n", &n),
+                    ("rs", &rs),
+                ],
+            ),
+            _ => return None,
+        })
+    }
+
+    /// `highlevel` with the effective address (or added immediate) of an
+    /// addi/li/load/store rendered by `ea`; `None` for other instructions.
+    pub(crate) fn highlevel_ea(&self, ea: impl Fn(Register) -> String) -> Option<String> {
+        Some(match *self {
+            Self::Addi { rd, ra, .. } => format!("{} = {}", rd, ea(ra)),
+            Self::Li { rd, .. } => format!("{} = {}", rd, ea(Register(0))),
+            Self::Load { width, rd, ra, .. } => {
+                format!("{} = *({} *)({})", rd, width.c_type(), ea(ra))
+            }
+            Self::Lha { rd, ra, .. } => format!("{} = (s32)*(s16 *)({})", rd, ea(ra)),
+            Self::Store { width, rs, ra, .. } => {
+                format!("*({} *)({}) = {}", width.c_type(), ea(ra), rs)
+            }
+            _ => return None,
+        })
+    }
+
+    /// `highlevel` for an instruction whose immediate is `reloc`.
+    pub fn highlevel_reloc(&self, reloc: &Relocation) -> String {
+        let ea = |ra: Register| match reloc.reloc {
+            Reloc::Sda21 => format!("&{}", reloc.target()),
+            _ if ra.0 == 0 => reloc.to_string(),
+            _ => format!("{} + {}", ra, reloc),
+        };
+        match *self {
+            Self::B { .. } | Self::Bc { .. } => {
+                let effects = self.branch_effects(explanation::Expr::Label(reloc.target()));
+                let effects: Vec<_> = effects.iter().map(ToString::to_string).collect();
+                effects.join("; ")
+            }
+            Self::Addis { rd, ra, .. } if ra.0 != 0 => {
+                format!("{} = {} + ({} << 16)", rd, ra, reloc)
+            }
+            Self::Addis { rd, .. } | Self::Lis { rd, .. } => format!("{} = {} << 16", rd, reloc),
+            Self::Ori { ra, rs, .. } => format!("{} = {} | {}", ra, rs, reloc),
+            Self::Oris { ra, rs, .. } => format!("{} = {} | ({} << 16)", ra, rs, reloc),
+            _ => self.highlevel_ea(ea).unwrap_or_else(|| self.highlevel()),
+        }
+    }
+
+    /// A branch with displacement `disp`, if it's a multiple of 4 the branch
+    /// can reach; anything but a branch is returned as is.
+    pub fn with_displacement(&self, disp: i64) -> Option<Self> {
+        let reach = match self {
+            Self::B { .. } => 1 << 25,
+            Self::Bc { .. } => 1 << 15,
+            _ => return Some(*self),
+        };
+        if disp % 4 != 0 || !(-reach..reach).contains(&disp) {
+            return None;
+        }
+        Some(match *self {
+            Self::B { link, .. } => Self::B {
+                disp: disp as i32,
+                link,
+            },
+            Self::Bc { bo, bi, .. } => Self::Bc {
+                bo,
+                bi,
+                disp: disp as i16,
+            },
+            op => op,
+        })
+    }
+
+    /// The same instruction with `imm` in the field a relocation fills in;
+    /// instructions without one are returned as is.
+    pub fn with_immediate(&self, imm: i16) -> Self {
+        match *self {
+            Self::Addi { rd, ra, .. } => Self::Addi { rd, ra, simm: imm },
+            Self::Addis { rd, ra, .. } => Self::Addis { rd, ra, simm: imm },
+            Self::Ori { ra, rs, .. } => Self::Ori {
+                ra,
+                rs,
+                uimm: imm as u16,
+            },
+            Self::Oris { ra, rs, .. } => Self::Oris {
+                ra,
+                rs,
+                uimm: imm as u16,
+            },
+            Self::Li { rd, .. } => Self::Li { rd, simm: imm },
+            Self::Lis { rd, .. } => Self::Lis { rd, simm: imm },
+            Self::Load {
+                width,
+                update,
+                rd,
+                ra,
+                ..
+            } => Self::Load {
+                width,
+                update,
+                rd,
+                d: imm,
+                ra,
+            },
+            Self::Lha { update, rd, ra, .. } => Self::Lha {
+                update,
+                rd,
+                d: imm,
+                ra,
+            },
+            Self::Store {
+                width,
+                update,
+                rs,
+                ra,
+                ..
+            } => Self::Store {
+                width,
+                update,
+                rs,
+                d: imm,
+                ra,
+            },
+            Self::Cmpi {
+                logical, crf, ra, ..
+            } => Self::Cmpi {
+                logical,
+                crf,
+                ra,
+                imm,
+            },
+            _ => *self,
+        }
+    }
+
+    /// The real instruction a pseudo-op stands for; real instructions are
+    /// returned as is. A pseudo-op whose operands break the rules
+    /// [`check`](Self::check) enforces doesn't panic: its fields wrap to 5
+    /// bits, as the encoding's would, and mean whatever that rotate does.
+    pub fn canonicalize(&self) -> Self {
+        // 5-bit field arithmetic
+        let add = |a: u8, b: u8| a.wrapping_add(b) & 31;
+        let sub = |a: u8, b: u8| a.wrapping_sub(b) & 31;
+        match *self {
+            Self::Rlwinm { .. } => *self,
+            Self::Rlwimi { .. } => *self,
+            Self::Rlwnm { .. } => *self,
+            Self::Addi { .. } => *self,
+            Self::Addis { .. } => *self,
+            Self::Ori { .. } => *self,
+            Self::Oris { .. } => *self,
+            Self::Load { .. } => *self,
+            Self::Lha { .. } => *self,
+            Self::Store { .. } => *self,
+            Self::Cmp { .. } => *self,
+            Self::Cmpi { .. } => *self,
+            Self::CrOp { .. } => *self,
+            Self::B { .. } => *self,
+            Self::Bc { .. } => *self,
+
+            Self::Inslwi { ra, rs, n, b } => Self::Rlwimi {
+                ra,
+                rs,
+                sh: sub(32, b),
+                mb: b & 31,
+                me: sub(add(b, n), 1),
+                rc: false,
+            },
+            Self::Insrwi { ra, rs, n, b } => Self::Rlwimi {
+                ra,
+                rs,
+                sh: sub(32, add(b, n)),
+                mb: b & 31,
+                me: sub(add(b, n), 1),
+                rc: false,
+            },
+
+            Self::Extlwi { ra, rs, n, b } => Self::Rlwinm {
+                ra,
+                rs,
+                sh: b & 31,
+                mb: 0,
+                me: sub(n, 1),
+                rc: false,
+            },
+            Self::Extrwi { ra, rs, n, b } => Self::Rlwinm {
+                ra,
+                rs,
+                sh: add(b, n),
+                mb: sub(32, n),
+                me: 31,
+                rc: false,
+            },
+
+            Self::Rotlwi { ra, rs, n } => Self::Rlwinm {
+                ra,
+                rs,
+                sh: n & 31,
+                mb: 0,
+                me: 31,
+                rc: false,
+            },
+            Self::Rotrwi { ra, rs, n } => Self::Rlwinm {
+                ra,
+                rs,
+                sh: sub(32, n),
+                mb: 0,
+                me: 31,
+                rc: false,
+            },
+
+            Self::Slwi { ra, rs, n } => Self::Rlwinm {
+                ra,
+                rs,
+                sh: n & 31,
+                mb: 0,
+                me: sub(31, n),
+                rc: false,
+            },
+            Self::Srwi { ra, rs, n } => Self::Rlwinm {
+                ra,
+                rs,
+                sh: sub(32, n),
+                mb: n & 31,
+                me: 31,
+                rc: false,
+            },
+
+            Self::Clrlwi { ra, rs, n } => Self::Rlwinm {
+                ra,
+                rs,
+                sh: 0,
+                mb: n & 31,
+                me: 31,
+                rc: false,
+            },
+            Self::Clrrwi { ra, rs, n } => Self::Rlwinm {
+                ra,
+                rs,
+                sh: 0,
+                mb: 0,
+                me: sub(31, n),
+                rc: false,
+            },
+
+            Self::Clrlslwi { ra, rs, b, n } => Self::Rlwinm {
+                ra,
+                rs,
+                sh: n & 31,
+                mb: sub(b, n),
+                me: sub(31, n),
+                rc: false,
+            },
+
+            Self::Rotlw { ra, rs, rb } => Self::Rlwnm {
+                ra,
+                rs,
+                rb,
+                mb: 0,
+                me: 31,
+                rc: false,
+            },
+
+            Self::Li { rd, simm } => Self::Addi {
+                rd,
+                ra: Register(0),
+                simm,
+            },
+            Self::Lis { rd, simm } => Self::Addis {
+                rd,
+                ra: Register(0),
+                simm,
+            },
+        }
+    }
+
+    /// The inverse of [`canonicalize`](Self::canonicalize): a raw rotate
+    /// written as the most specific pseudo-op its fields match, and why. `None`
+    /// if no pseudo-op fits or it already is one, and for record forms: the
+    /// pseudo-ops don't have them here.
+    pub fn simplify(&self) -> Option<(Self, String)> {
+        Some(match *self {
+            Self::Rlwinm {
+                ra,
+                rs,
+                sh,
+                mb,
+                me,
+                rc: false,
+            } => match (sh, mb, me) {
+                (n, 0, 31) => (Self::Rotlwi { ra, rs, n }, "an unmasked rotate".to_string()),
+                (0, n, 31) => (
+                    Self::Clrlwi { ra, rs, n },
+                    format!("no rotate and a mask clearing the high {} bits", n),
+                ),
+                (0, 0, me) => (
+                    Self::Clrrwi { ra, rs, n: 31 - me },
+                    format!("no rotate and a mask clearing the low {} bits", 31 - me),
+                ),
+                (n, 0, me) if me == 31 - n => (
+                    Self::Slwi { ra, rs, n },
+                    format!("the mask drops the {} bits rotated in at the bottom", n),
+                ),
+                (sh, n, 31) if sh + n == 32 => (
+                    Self::Srwi { ra, rs, n },
+                    format!("rotating by 32-{} and dropping the high {} bits", n, n),
+                ),
+                (sh, mb, 31) if sh + mb > 32 => {
+                    let n = 32 - mb;
+                    (
+                        Self::Extrwi {
+                            ra,
+                            rs,
+                            n,
+                            b: sh - n,
+                        },
+                        format!(
+                            "the mask keeps the low {} bits after rotating bit {} down to bit 31",
+                            n,
+                            sh - 1
+                        ),
+                    )
+                }
+                // a field running past bit 31 wraps around; that's no extlwi
+                (b, 0, me) if b + me < 32 => (
+                    Self::Extlwi {
+                        ra,
+                        rs,
+                        n: me + 1,
+                        b,
+                    },
+                    format!(
+                        "the mask keeps the high {} bits after rotating bit {} up to bit 0",
+                        me + 1,
+                        b
+                    ),
+                ),
+                (n, mb, me) if n + me == 31 && mb + n < 32 => (
+                    Self::Clrlslwi {
+                        ra,
+                        rs,
+                        b: mb + n,
+                        n,
+                    },
+                    format!(
+                        "the mask clears the high {} bits, then the {} bits shifted in",
+                        mb + n,
+                        n
+                    ),
+                ),
+                _ => return None,
+            },
+            Self::Rlwnm {
+                ra,
+                rs,
+                rb,
+                mb: 0,
+                me: 31,
+                rc: false,
+            } => (Self::Rotlw { ra, rs, rb }, "an unmasked rotate".to_string()),
+            Self::Rlwimi {
+                ra,
+                rs,
+                sh,
+                mb,
+                me,
+                rc: false,
+            } if mb <= me => {
+                let n = me - mb + 1;
+                if sh == (32 - mb) % 32 {
+                    (
+                        Self::Inslwi { ra, rs, n, b: mb },
+                        format!("the rotate lines up the high {} bits with bit {}", n, mb),
+                    )
+                } else if sh == 31 - me {
+                    (
+                        Self::Insrwi { ra, rs, n, b: mb },
+                        format!("the rotate lines up the low {} bits with bit {}", n, mb),
+                    )
+                } else {
+                    return None;
+                }
+            }
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_opcode;
+
+    #[test]
+    fn test_rlwinm() {
+        let asm = "rlwinm r0,r7,0x10,0x0,0xf";
+        let (_, op) = parse_opcode(asm).expect("parse failed");
+
+        assert_eq!(
+            op,
+            Opcode::Rlwinm {
+                ra: Register(0),
+                rs: Register(7),
+                sh: 16,
+                mb: 0,
+                me: 15,
+                rc: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_ra_zero_explanation() {
+        let (_, op) = parse_opcode("lwz r5,8(r0)").expect("parse failed");
+        assert_eq!(
+            op.highlevel(),
+            "r5 = *(u32 *)(0x8)  (rA=0 reads as 0, not r0)"
+        );
+
+        let (_, op) = parse_opcode("addi r3,r0,16").expect("parse failed");
+        assert!(!op.highlevel().contains("r0 +"));
+    }
+
+    #[test]
+    fn test_record_forms() {
+        let op = |asm| parse_opcode(asm).expect("parse failed").1;
+        let note = "the dot also sets cr0 from a signed compare of r3 with 0, \
+                    so a `beq` after it branches if r3 is 0 and a `bne` if it isn't";
+        assert_eq!(
+            op("rlwinm. r3,r4,0,24,31").highlevel(),
+            format!(
+                "r3 = (r4 << 0) & MASK(24..31)=0x000000FF; cr0 = compare(r3, 0)  ({})",
+                note
+            )
+        );
+        assert_eq!(
+            op("rlwimi. r3,r4,8,16,23").highlevel_c(),
+            "r3 = (r3 & 0xFFFF00FF) | ((r4 << 8) & 0xFF00); cr0 = compare(r3, 0)"
+        );
+        let rlwnm = op("rlwnm. r3,r4,r5,0,31");
+        assert_eq!(rlwnm.explain().notes, [note]);
+        assert_eq!(
+            rlwnm.highlevel_c(),
+            "r3 = rotl32(r4, r5); cr0 = compare(r3, 0)"
+        );
+        // setting cr0, it's no plain copy
+        assert_eq!(op("rlwinm. r3,r4,0,0,31").copies(), None);
+        assert_eq!(
+            op("clrlwi. r3,r4,24").describe(),
+            "copy r4 into r3 with the high 24 bits cleared, \
+             then compare r3 with 0 as signed numbers, setting cr0"
+        );
+    }
+
+    #[test]
+    fn test_highlevel_c() {
+        let c = |asm| parse_opcode(asm).expect("parse failed").1.highlevel_c();
+        assert_eq!(c("srwi r3,r4,16"), "r3 = r4 >> 16");
+        assert_eq!(c("extrwi r3,r4,16,0"), "r3 = r4 >> 16");
+        assert_eq!(c("rlwinm r3,r4,16,16,31"), "r3 = r4 >> 16");
+        assert_eq!(c("rlwinm r3,r4,24,24,31"), "r3 = (r4 >> 8) & 0xFF");
+        assert_eq!(c("rlwinm r3,r4,3,0,27"), "r3 = (r4 << 3) & ~0xF");
+        assert_eq!(c("clrrwi r3,r4,3"), "r3 = r4 & ~7");
+        assert_eq!(c("rlwinm r3,r4,8,4,19"), "r3 = (r4 << 8) & 0xFFFF000");
+        assert_eq!(c("rotlwi r3,r4,8"), "r3 = rotl32(r4, 8)");
+        assert_eq!(c("rlwinm r3,r4,8,28,3"), "r3 = rotl32(r4, 8) & 0xF000000F");
+        assert_eq!(
+            c("rlwimi r3,r4,8,16,23"),
+            "r3 = (r3 & 0xFFFF00FF) | ((r4 << 8) & 0xFF00)"
+        );
+        assert_eq!(c("rotlw r3,r4,r5"), "r3 = rotl32(r4, r5)");
+        assert_eq!(c("li r3,1"), "r3 = 1");
+
+        let rotate = Opcode::Rlwinm {
+            ra: Register(3),
+            rs: Register(4),
+            sh: 36,
+            mb: 0,
+            me: 31,
+            rc: false,
+        };
+        assert!(parse_opcode("rlwinm r3,r4,36,0,31").is_err());
+        assert_eq!(rotate.highlevel_c(), rotate.highlevel());
+        assert!(rotate
+            .highlevel_c()
+            .ends_with("(`rlwinm r3,r4,36,0,31` needs sh, mb and me < 32)"));
+    }
+
+    #[test]
+    fn test_mask_value() {
+        let mask = |asm| parse_opcode(asm).expect("parse failed").1.mask_value();
+        assert_eq!(mask("rlwinm r0,r7,16,0,15"), Some(0xFFFF_0000));
+        assert_eq!(mask("rlwinm r0,r7,0,28,3"), Some(0xF000_000F));
+        assert_eq!(mask("clrlwi r3,r4,24"), Some(0x0000_00FF));
+        assert_eq!(mask("li r3,0"), None);
+    }
+
+    #[test]
+    fn test_simplify() {
+        let simplified = |asm| {
+            let op = parse_opcode(asm).expect("parse failed").1;
+            op.simplify().map(|(op, _)| op.to_string())
+        };
+        assert_eq!(
+            simplified("rlwinm r3,r4,24,8,31").as_deref(),
+            Some("srwi r3,r4,8")
+        );
+        assert_eq!(
+            simplified("rlwinm r3,r4,28,28,31").as_deref(),
+            Some("extrwi r3,r4,4,24")
+        );
+        assert_eq!(
+            simplified("rlwinm r3,r4,2,0,29").as_deref(),
+            Some("slwi r3,r4,2")
+        );
+        assert_eq!(simplified("rlwinm r3,r4,8,4,19"), None);
+        assert_eq!(simplified("srwi r3,r4,8"), None);
+
+        for sh in 0..32 {
+            for mb in 0..32 {
+                for me in 0..32 {
+                    let (ra, rs) = (Register(3), Register(4));
+                    for op in [
+                        Opcode::Rlwinm {
+                            ra,
+                            rs,
+                            sh,
+                            mb,
+                            me,
+                            rc: false,
+                        },
+                        Opcode::Rlwimi {
+                            ra,
+                            rs,
+                            sh,
+                            mb,
+                            me,
+                            rc: false,
+                        },
+                    ] {
+                        if let Some((simple, _)) = op.simplify() {
+                            assert_eq!(simple.canonicalize(), op, "{} -> {}", op, simple);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_bit_order() {
+        BitOrder::Lsb0.set();
+        let op = |asm| parse_opcode(asm).expect("parse failed").1;
+        // tests run on their own threads, so this doesn't leak into others
+        assert_eq!(op("rlwinm r3,r4,0,31,16"), op("rlwinm r3,r4,0,0xFFFF0000"));
+        assert_eq!(op("extrwi r0,r7,8,19").to_string(), "extrwi r0,r7,8,12");
+        assert!(parse_opcode("rlwinm r3,r4,0,32,16").is_err());
+        assert_eq!(
+            op("rlwinm r3,r4,8,7,24").highlevel(),
+            "r3 = (r4 << 8) & MASK(31..24,7..0, LSB=0)=0xFF0000FF  \
+             (keeps the top 8 and bottom 8 bits)"
+        );
+        assert_eq!(
+            op("extrwi r0,r7,8,19").describe(),
+            "extract the 8-bit field starting at bit 19 (LSB=0) of r7 into the low bits of r0"
+        );
+        BitOrder::Msb0.set();
+    }
+
+    #[test]
+    fn test_bad_operands() {
+        let (ra, rs) = (Register(3), Register(4));
+        let bad = [
+            ("extlwi r3,r4,0,0", Opcode::Extlwi { ra, rs, n: 0, b: 0 }),
+            ("slwi r3,r4,40", Opcode::Slwi { ra, rs, n: 40 }),
+            (
+                "clrlslwi r3,r4,2,5",
+                Opcode::Clrlslwi { ra, rs, b: 2, n: 5 },
+            ),
+            (
+                "extrwi r3,r4,20,20",
+                Opcode::Extrwi {
+                    ra,
+                    rs,
+                    n: 20,
+                    b: 20,
+                },
+            ),
+            (
+                "rlwinm r3,r4,40,0,31",
+                Opcode::Rlwinm {
+                    ra,
+                    rs,
+                    sh: 40,
+                    mb: 0,
+                    me: 31,
+                    rc: false,
+                },
+            ),
+        ];
+        for (asm, op) in bad {
+            assert!(parse_opcode(asm).is_err(), "{} parsed", asm);
+            // built by hand, it's explained with why it's wrong, not a panic
+            let why = op.check().unwrap_err().to_string();
+            assert!(op.highlevel().ends_with(&format!("  ({})", why)), "{}", asm);
+            op.reads();
+            op.writes();
+        }
+    }
+
+    #[test]
+    fn test_highlevel() {
+        let highlevel = |asm| parse_opcode(asm).expect("parse failed").1.highlevel();
+        assert_eq!(
+            highlevel("srwi r3,r4,8"),
+            "r3 = (r4 << 24) & MASK(8..31)=0x00FFFFFF  (r3 = r4 >> 8)"
+        );
+        assert_eq!(
+            highlevel("extrwi r3,r4,4,24"),
+            "r3 = (r4 << 28) & MASK(28..31)=0x0000000F  (r3 = (r4 >> 4) & 0xf)"
+        );
+        assert_eq!(
+            highlevel("clrrwi r3,r3,2"),
+            "r3 = (r3 << 0) & MASK(0..29)=0xFFFFFFFC  (r3 = r3 & 0xfffffffc)"
+        );
+        assert_eq!(
+            highlevel("rlwimi r3,r4,8,16,23"),
+            "r3 = (r3 & ~MASK(16..23)=0x0000FF00) | (rotl32(r4, 8) & MASK(16..23)=0x0000FF00)  \
+             (insert an 8-bit field from r4 into r3 at bit 16)"
+        );
+        assert_eq!(
+            highlevel("rotlw r3,r4,r5"),
+            "r3 = (r4 << r5) & MASK(0..31)=0xFFFFFFFF  (r3 = rotl(r4, r5))"
+        );
+        assert_eq!(
+            highlevel("rlwinm r3,r4,0,0,31"),
+            "r3 = r4  (this is just `mr r3,r4`)"
+        );
+        assert_eq!(highlevel("rotlwi r3,r4,0"), highlevel("addi r3,r4,0"));
+        assert_eq!(
+            highlevel("rlwinm r3,r4,8,24,7"),
+            "r3 = (r4 << 8) & MASK(0..7,24..31)=0xFF0000FF  (keeps the top 8 and bottom 8 bits)"
+        );
+        assert_eq!(
+            highlevel("rlwimi r5,r5,0,8,15"),
+            "r5 = r5  (this does nothing)"
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! The language of the prose in explanations. Messages live in Fluent
+//! files under `locales/`, one per language, compiled in; only the subset
+//! the catalogs need is understood: single-line messages whose variables
+//! are written `{ $name }`. A message missing from a translation falls back
+//! to English.
+
+use core::fmt;
+use std::cell::Cell;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Locale {
+    En,
+    Ja,
+}
+
+thread_local! {
+    static LOCALE: Cell<Locale> = const { Cell::new(Locale::En) };
+}
+
+impl Locale {
+    /// The language in effect, [`Locale::En`] unless [`Locale::set`].
+    pub fn current() -> Self {
+        LOCALE.with(Cell::get)
+    }
+
+    /// Explain in this language from now on.
+    pub fn set(self) {
+        LOCALE.with(|locale| locale.set(self));
+    }
+
+    /// The locale for a language tag like `ja`, `ja-JP` or `ja_JP.UTF-8`.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let language = tag.split(['-', '_', '.']).next()?;
+        match language.to_ascii_lowercase().as_str() {
+            "en" => Some(Self::En),
+            "ja" => Some(Self::Ja),
+            _ => None,
+        }
+    }
+
+    fn catalog(self) -> &'static str {
+        match self {
+            Self::En => include_str!("../locales/en.ftl"),
+            Self::Ja => include_str!("../locales/ja.ftl"),
+        }
+    }
+}
+
+/// The pattern of message `id` in `catalog`.
+fn lookup(catalog: &'static str, id: &str) -> Option<&'static str> {
+    catalog.lines().find_map(|line| {
+        let (name, pattern) = line.split_once('=')?;
+        (!line.starts_with('#') && name.trim() == id).then(|| pattern.trim())
+    })
+}
+
+/// Message `id` in the current locale with its `{ $name }` variables
+/// filled in from `args`.
+pub(crate) fn message(id: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+    let pattern = lookup(Locale::current().catalog(), id)
+        .or_else(|| lookup(Locale::En.catalog(), id))
+        .unwrap_or_else(|| panic!("no message `{}`", id));
+    let mut out = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find("{ $") {
+        out.push_str(&rest[..start]);
+        let end = start + rest[start..].find('}').expect("unclosed placeable");
+        let name = rest[start + 3..end].trim();
+        match args.iter().find(|(arg, _)| *arg == name) {
+            Some((_, value)) => out.push_str(&value.to_string()),
+            None => panic!("message `{}` needs `{}`", id, name),
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(catalog: &str) -> Vec<&str> {
+        catalog
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| Some(line.split_once('=')?.0.trim()))
+            .collect()
+    }
+
+    #[test]
+    fn test_locale() {
+        assert_eq!(ids(Locale::En.catalog()), ids(Locale::Ja.catalog()));
+        assert_eq!(Locale::from_tag("ja_JP.UTF-8"), Some(Locale::Ja));
+
+        let op = crate::parser::parse_opcode("extrwi r0,r7,8,12").unwrap().1;
+        Locale::Ja.set();
+        assert_eq!(
+            op.describe(),
+            "r7のビット12から始まる8ビットのフィールドを取り出してr0の下位ビットに入れる"
+        );
+        assert_eq!(message("bits", &[("n", &3)]), "3ビット");
+    }
+}
@@ -0,0 +1,141 @@
+//! GNU as style macros, the kind decomp projects keep their hook shims in:
+//!
+//! ```text
+//! .macro hook target, reg=r12
+//!     lis \reg, \target@ha
+//!     addi \reg, \reg, \target@l
+//! .endm
+//! ```
+//!
+//! In the body, `\name` is replaced by the argument for parameter `name`,
+//! `\()` by nothing (to glue an argument to following text) and `\@` by the
+//! number of macro expansions so far (for unique labels).
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Macro {
+    /// Parameter names with their default values
+    pub params: Vec<(String, Option<String>)>,
+    /// Body lines as written, between `.macro` and `.endm`
+    pub body: Vec<String>,
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$'
+}
+
+impl Macro {
+    /// Name and parameters from the text after `.macro`, e.g.
+    /// `hook target, reg=r12`. Parameters may also be separated by spaces.
+    pub fn parse_header(header: &str) -> Result<(String, Macro), String> {
+        let header = header.trim();
+        let end = header.find(|c| !is_ident(c)).unwrap_or(header.len());
+        let (name, rest) = header.split_at(end);
+        if name.is_empty() {
+            return Err("expected a macro name".to_string());
+        }
+
+        let mut params: Vec<(String, Option<String>)> = Vec::new();
+        for param in rest
+            .trim_start_matches(|c: char| c == ',' || c.is_whitespace())
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|p| !p.is_empty())
+        {
+            let (param, default) = match param.split_once('=') {
+                Some((param, default)) => (param, Some(default.to_string())),
+                None => (param, None),
+            };
+            if param.is_empty() || !param.chars().all(is_ident) {
+                return Err(format!("invalid macro parameter `{}`", param));
+            }
+            if params.iter().any(|(p, _)| p == param) {
+                return Err(format!("duplicate macro parameter `{}`", param));
+            }
+            params.push((param.to_string(), default));
+        }
+
+        Ok((
+            name.to_string(),
+            Macro {
+                params,
+                body: Vec::new(),
+            },
+        ))
+    }
+
+    /// The body with the parameters replaced by `args` (the comma-separated
+    /// text after the macro name), as expansion number `count`.
+    pub fn expand(&self, args: &str, count: usize) -> Result<Vec<String>, String> {
+        let args: Vec<_> = match args.trim() {
+            "" => Vec::new(),
+            args => args.split(',').map(str::trim).collect(),
+        };
+        if args.len() > self.params.len() {
+            return Err(format!(
+                "expected at most {} macro arguments, found {}",
+                self.params.len(),
+                args.len()
+            ));
+        }
+        let value = |name: &str| {
+            let i = self.params.iter().position(|(p, _)| p == name)?;
+            match args.get(i) {
+                Some(arg) if !arg.is_empty() => Some(arg.to_string()),
+                _ => Some(self.params[i].1.clone().unwrap_or_default()),
+            }
+        };
+
+        let mut lines = Vec::new();
+        for line in &self.body {
+            let mut out = String::new();
+            let mut rest = line.as_str();
+            while let Some(at) = rest.find('\\') {
+                out.push_str(&rest[..at]);
+                rest = &rest[at + 1..];
+                if let Some(after) = rest.strip_prefix("()") {
+                    rest = after;
+                } else if let Some(after) = rest.strip_prefix('@') {
+                    out.push_str(&count.to_string());
+                    rest = after;
+                } else {
+                    let end = rest.find(|c| !is_ident(c)).unwrap_or(rest.len());
+                    match value(&rest[..end]) {
+                        Some(arg) => {
+                            out.push_str(&arg);
+                            rest = &rest[end..];
+                        }
+                        None => out.push('\\'),
+                    }
+                }
+            }
+            out.push_str(rest);
+            lines.push(out);
+        }
+        Ok(lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let (name, mut hook) = Macro::parse_header("hook target, reg=r12").unwrap();
+        assert_eq!(name, "hook");
+        hook.body = vec![
+            "lis \\reg, \\target@ha".to_string(),
+            "\\target\\()_\\@:".to_string(),
+        ];
+
+        assert_eq!(
+            hook.expand("gState", 3).unwrap(),
+            vec!["lis r12, gState@ha", "gState_3:"]
+        );
+        assert_eq!(
+            hook.expand("gState, r3", 0).unwrap()[0],
+            "lis r3, gState@ha"
+        );
+        assert!(hook.expand("a, b, c", 0).is_err());
+        assert!(Macro::parse_header("m a, a").is_err());
+    }
+}
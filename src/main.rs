@@ -1,247 +1,2153 @@
-use core::fmt;
-
-mod parser;
-use parser::parse_opcode;
-
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub struct Register(pub u8);
-
-impl fmt::Display for Register {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "r{}", self.0)
-    }
-}
-
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub enum Opcode {
-    /// Rotate Left Word Immediate then AND with Mask
-    Rlwinm {
-        ra: Register,
-        rs: Register,
-        sh: u8,
-        mb: u8,
-        me: u8,
-    },
-
-    /// Rotate Left Word Immediate then Mask Insert
-    Rlwimi {
-        ra: Register,
-        rs: Register,
-        sh: u8,
-        mb: u8,
-        me: u8,
-    },
-
-    /// Rotate Left Word then AND with Mask
-    Rlwnm {
-        ra: Register,
-        rs: Register,
-        rb: Register,
-        mb: u8,
-        me: u8,
-    },
-
-    // ---- pseudomnemonics ----
-    Extlwi {
-        ra: Register,
-        rs: Register,
-        n: u8,
-        b: u8,
-    },
-
-    Extrwi {
-        ra: Register,
-        rs: Register,
-        n: u8,
-        b: u8,
-    },
-
-    Rotlwi {
-        ra: Register,
-        rs: Register,
-        n: u8,
-    },
-
-    Rotrwi {
-        ra: Register,
-        rs: Register,
-        n: u8,
-    },
-
-    Slwi {
-        ra: Register,
-        rs: Register,
-        n: u8,
-    },
-
-    Srwi {
-        ra: Register,
-        rs: Register,
-        n: u8,
-    },
-
-    Clrlwi {
-        ra: Register,
-        rs: Register,
-        n: u8,
-    },
-
-    Clrrwi {
-        ra: Register,
-        rs: Register,
-        n: u8,
-    },
-
-    Clrlslwi {
-        ra: Register,
-        rs: Register,
-        b: u8,
-        n: u8,
-    },
-
-    Rotlw {
-        ra: Register,
-        rs: Register,
-        rb: Register,
-    },
-
-    Inslwi {
-        ra: Register,
-        rs: Register,
-        n: u8,
-        b: u8,
-    },
-
-    Insrwi {
-        ra: Register,
-        rs: Register,
-        n: u8,
-        b: u8,
-    },
-}
-
-impl Opcode {
-    pub fn highlevel(&self) -> String {
-        match self {
-            Self::Rlwinm { ra, rs, sh, mb, me } => {
-                format!(
-                    "{dest} = ({src} << {sh}) & MASK({mb}..{me})",
-                    dest = ra,
-                    src = rs,
-                    sh = sh,
-                    mb = mb,
-                    me = me
-                )
-            }
-            _ => unimplemented!(),
-        }
-    }
-
-    pub fn canonicalize(&self) -> Self {
-        match self {
-            &Self::Rlwinm { .. } => self.clone(),
-            &Self::Rlwimi { .. } => self.clone(),
-            &Self::Rlwnm { .. } => self.clone(),
-
-            &Self::Inslwi { ra, rs, n, b } => Self::Rlwinm {
-                ra,
-                rs,
-                sh: 32 - b,
-                mb: b,
-                me: b + n - 1,
-            },
-            &Self::Insrwi { ra, rs, n, b } => Self::Rlwinm {
-                ra,
-                rs,
-                sh: 32 - (b + n),
-                mb: b,
-                me: (b + n) - 1,
-            },
-
-            &Self::Extlwi { ra, rs, n, b } => Self::Rlwinm {
-                ra,
-                rs,
-                sh: b,
-                mb: 0,
-                me: n - 1,
-            },
-            &Self::Extrwi { ra, rs, n, b } => Self::Rlwinm {
-                ra,
-                rs,
-                sh: b + n,
-                mb: 32 - n,
-                me: 31,
-            },
-
-            &Self::Rotlwi { ra, rs, n } => Self::Rlwinm {
-                ra,
-                rs,
-                sh: n,
-                mb: 0,
-                me: 31,
-            },
-            &Self::Rotrwi { ra, rs, n } => Self::Rlwinm {
-                ra,
-                rs,
-                sh: 32 - n,
-                mb: 0,
-                me: 31,
-            },
-
-            &Self::Slwi { ra, rs, n } => Self::Rlwinm {
-                ra,
-                rs,
-                sh: n,
-                mb: 0,
-                me: 31 - n,
-            },
-            &Self::Srwi { ra, rs, n } => Self::Rlwinm {
-                ra,
-                rs,
-                sh: 32 - n,
-                mb: n,
-                me: 31,
-            },
-
-            &Self::Clrlwi { ra, rs, n } => Self::Rlwinm {
-                ra,
-                rs,
-                sh: 0,
-                mb: n,
-                me: 31,
-            },
-            &Self::Clrrwi { ra, rs, n } => Self::Rlwinm {
-                ra,
-                rs,
-                sh: 0,
-                mb: 0,
-                me: 31 - n,
-            },
-
-            &Self::Clrlslwi { ra, rs, b, n } => Self::Rlwinm {
-                ra,
-                rs,
-                sh: n,
-                mb: b - n,
-                me: 31 - n,
-            },
-
-            &Self::Rotlw { ra, rs, rb } => Self::Rlwnm {
-                ra,
-                rs,
-                rb,
-                mb: 0,
-                me: 31,
-            },
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ppcheat::action_replay;
+use ppcheat::asm::{self, Format};
+use ppcheat::browse::Browser;
+use ppcheat::budget::{self, Budget};
+use ppcheat::clobber::{self, Convention};
+use ppcheat::codelist::{CodeList, NamedCode};
+use ppcheat::diff::Change;
+use ppcheat::disasm::{self, best_guess, Endian, HexDump, Listing};
+use ppcheat::formats::disc::{self, Disc};
+use ppcheat::formats::dol::Dol;
+use ppcheat::formats::elf::Elf;
+use ppcheat::formats::gct::{self, Gct};
+use ppcheat::formats::ram::RamDump;
+use ppcheat::formats::rel::Rel;
+use ppcheat::formats::{ihex, image_listing, srec};
+use ppcheat::gecko::{self, Line};
+use ppcheat::hook;
+use ppcheat::image::{self, Image, Verification};
+use ppcheat::ini::{self, GameIni};
+use ppcheat::locale::Locale;
+use ppcheat::memory_map::{self, Console};
+use ppcheat::parser::{self, Flavor, Program, SourceOptions, Statement};
+use ppcheat::patches::{Patch, PatchLog};
+use ppcheat::port::{self, PortError, Ported};
+use ppcheat::profile::{self, Profile};
+use ppcheat::relocate;
+use ppcheat::render::{self, Style, Verbosity};
+use ppcheat::riivolution;
+use ppcheat::signature;
+use ppcheat::symbols::SymbolTable;
+use ppcheat::tidy;
+use ppcheat::{parse_hex_u32, trace, vectors, BitOrder, Opcode};
+
+const USAGE: &str = "usage: ppcheat [--side-by-side | --html | --markdown | --tikz | --masks
+                | --explain-encoding]
+                [--c | --lang c|rust|python]
+                [--verbosity terse|normal|verbose]
+                [--width N] [FILE]
+       ppcheat export-md --symbol NAME [--symbols MAP] [FILE]
+       ppcheat vectors [--seed N] [--rounds N]
+       ppcheat replay [TRACE]
+       ppcheat annotate-log [DOLPHIN_LOG]
+       ppcheat profile --hits PROFILE --base ADDR [--threshold N] [FILE]
+       ppcheat browse [--base ADDR] FILE|DOL|ELF
+       ppcheat diff [--width N] LEFT RIGHT
+       ppcheat diff [--width N] --symbol NAME [--symbols MAP[,MAP]]
+                DOL|ELF DOL|ELF
+       ppcheat compare INSN INSN
+       ppcheat doc MNEMONIC
+       ppcheat decode [--base ADDR] [--endian E] [HEX...]
+       ppcheat disasm [LISTING] [--base ADDR [--endian E]] FILE
+       ppcheat disasm [LISTING] --addr ADDR [--size N] DOL|DISC
+       ppcheat disasm [LISTING] --rel --base ADDR FILE
+       ppcheat ram [LISTING] [--mem1 MEM1] [--mem2 MEM2] --addr ADDR
+                [--size N]
+       ppcheat assemble --base ADDR [--format F] [--name NAME]
+                [--output OUT] [FILE]
+       ppcheat clobber [--convention PROFILE] [FILE]
+       ppcheat budget [--max-size N] [--align N] [--base ADDR] [FILE]
+       ppcheat caves [--min-size N] IMAGE
+       ppcheat verify --addr ADDR --size N IMAGE
+       ppcheat relocate --targets ADDR[,ADDR...] [--format 04|ini]
+                [--name NAME] [FILE]
+       ppcheat patches --log LOG list
+       ppcheat patches --log LOG add --addr ADDR --old INSN --new INSN
+                [--format F] [--note TEXT]
+       ppcheat patches --log LOG revert N
+       ppcheat cheat 04|c2|ar --addr ADDR
+                [--list LIST --name NAME [--game ID]] [FILE]
+       ppcheat cheat 06 --addr ADDR
+                [--list LIST --name NAME [--game ID]]
+                --text TEXT|--hex HEX|FILE
+       ppcheat cheat f6 --addr ADDR --dol DOL [--c2]
+                [--list LIST --name NAME [--game ID]] [FILE]
+       ppcheat cheat f2 --addr ADDR --dol DOL [--around N]
+                [--list LIST --name NAME [--game ID]] [FILE]
+       ppcheat cheat signature --addr ADDR DOL
+       ppcheat cheat riivolution --addr ADDR [--name NAME] [--game ID]
+                [--dol DOL] [FILE]
+       ppcheat cheat decode [--ar] [LISTING] [CODES]
+       ppcheat cheat describe [CODES]
+       ppcheat cheat check [--wii] [CODES]
+       ppcheat cheat tidy [--output OUT] [CODES]
+       ppcheat cheat convert --to gecko|ar [CODES]
+       ppcheat cheat list LIST
+       ppcheat gct list GCT
+       ppcheat gct merge --output OUT GCT...
+       ppcheat gct write --output OUT [CODES]
+       ppcheat gct build [--wii] [--manifest FILE] --output OUT LIST
+       ppcheat patch --dol DOL --addr ADDR [--output OUT] [FILE]
+       ppcheat hook --addr ADDR --original INSN|--dol DOL [--at CAVE]
+                [--format c2|asm|hex] [FILE]
+       ppcheat port --from MAP|DOL --to MAP|DOL ADDR...
+       ppcheat port --from MAP|DOL --to MAP|DOL --codes [CODES]
+
+Commands reading assembly take -I DIR to search DIR for `.include`d
+files, and -D NAME[=VALUE] to define NAME (as 1 by default) for `.if`;
+both can be repeated. --lsb0 numbers bits from the least significant
+(bit 0) instead of IBM's most significant, in mask positions read from
+the source and in explanations; assembly is still printed IBM-numbered.
+--locale ja explains in Japanese.
+
+disasm lists the text segments of a DOL, or the executable sections of
+an ELF (32- or 64-bit, either byte order) with its functions as labels,
+or the data of S-records or Intel HEX, at their load addresses, or with
+--base, a raw binary loaded there. A GameCube .iso or .gcm is read for
+its main DOL (Wii discs are encrypted, so extract theirs first). With
+--addr, only the function at ADDR of a DOL or disc is listed, up to its
+first blr, or N bytes with --size. With --rel it links a REL module at
+--base, applying its relocations against itself and the DOL, and lists
+its executable sections; relocations against other modules are left as
+they are, and listed on stderr. decode and disasm read big-endian words
+unless --endian is little, or auto to guess from which order reads as
+more plausible instructions. Words they don't decode are kept as .long,
+with a guess at what they are, and counted on stderr. --objdump lays the
+listing out as powerpc-eabi-objdump -d does, to diff against its output,
+with branch targets named by the nearest ELF symbol.
+
+--symbols names addresses from MAP, a Dolphin or CodeWarrior .map, a
+decomp-toolkit symbols.txt, a linker script's `name = 0x80003100;` lines
+or nm output, alongside an ELF's own symbols. A comment names the target
+of each branch, small data access (off r13 or r2, if MAP has _SDA_BASE_
+or _SDA2_BASE_) and lis pair. Its functions become labels, except in a
+raw binary's listing. --find-data writes what looks like data rather
+than code as directives: runs of pointers into the listed code (jump
+tables) as .long, runs of floats as .float, and words that aren't
+instructions as .byte. LISTING is any of --objdump, --symbols MAP and
+--find-data.
+
+profile lists FILE, assembled at ADDR, with how often each instruction
+ran by PROFILE, `ADDRESS COUNT` lines as Dolphin's JIT profiler or a
+sampler gives, and then the runs of instructions hit at least N times (1
+unless --threshold says otherwise), hottest first, with how many are
+rotates. Hints for those instructions follow: rotates to merge, an li
+and oris better written as lis and ori, moves that do nothing, and a
+compare before a branch that a record form does for free.
+
+diff lines two listings up instruction by instruction, side by side,
+marking each row as the same, the same written another way (~), with
+other operands (*), replaced (!), or only on one side (< or >), and
+exits with 1 unless they're the same. With --symbol, it diffs function
+NAME in two builds of a game: DOLs, with a --symbols MAP for each, in
+order and comma-separated, or ELFs, by their own symbols. The function
+is the symbol's size, or up to its first blr without one.
+
+ram disassembles N bytes (0x100 unless --size says otherwise) at ADDR
+from Dolphin's RAM dumps, mem1.raw and on Wii mem2.raw. ADDR can be
+cached (0x80000000 and 0x90000000 up), uncached (0xC0000000, 0xD0000000)
+or physical.
+
+assemble writes hex words by default; --format c or rust writes an array
+named NAME (`patch` unless --name says otherwise) to paste into source,
+srec or ihex records at --base for a flash tool, and bin the raw bytes,
+to OUT if given. A branch to an address, `bl 0x80001000`, branches there
+from where --base puts it, if it's in reach.
+
+relocate assembles FILE once for each ADDR, say each code cave big
+enough for it, with its labels moved to there: @ha/@l references to
+them, and branches out of the payload, are resolved for each. Each is
+written as 04 codes, data and all, named NAME @ ADDR (`ppcheat` unless
+--name says otherwise), or with --format ini as the [Gecko] section of a
+Dolphin game INI, to paste the one wanted into the game's.
+
+cheat 04 assembles FILE at ADDR and writes it as Gecko 04 codes, one
+32-bit write per instruction, for a code list or Dolphin's Gecko tab. c2
+writes it as a C2 code hooking ADDR instead, padded with a nop where the
+branch back needs one. The payload has to end by falling through to the
+branch back, and runs from the code list, so it can't branch relatively
+to anywhere outside itself.
+
+cheat 06 writes bytes from ADDR as an 06 string write: TEXT, with a NUL
+after it as C strings have, HEX (a hex dump, addresses and all, or just
+digits), or FILE's bytes as they are.
+
+cheat f6 finds where to patch at run time, for games that load the code
+elsewhere: an F6 code searches RAM for 16 bytes of ADDR's function from
+DOL, found just once in it, then FILE is written off where they are as
+14 codes, or with --c2 hooked as a D2 code. cheat signature lists the
+function, each instruction with how many times the 16 bytes from it are
+in DOL, and which of them f6 picks: found just once, as close to ADDR as
+it can, and preferring stable ones, without branch displacements or
+addresses to change in another build.
+
+cheat f2 writes a C2 code that only hooks if the N instructions before
+ADDR (4 unless --around says otherwise; after, for negative N) are as
+DOL has them.
+
+cheat decode disassembles the 04 and C2 codes of a Gecko code list, as
+pasted from a forum or Dolphin: 04 writes at their addresses, and C2
+payloads by offset, under the address they hook, and dumps what 06 codes
+write in hex and ASCII.
+
+cheat describe says what each code of a Gecko code list does, of any
+code type the codehandler has, with addresses off ba, the base address,
+or po, the pointer offset.
+
+cheat check checks where each code writes, tests, hooks and points ba
+and po at, and says which aren't RAM (with the address a typo like 90
+for 80 likely meant), or are RAM a code rarely means: low memory below
+80003100, where the OS's globals and the codehandler are, or the
+uncached mirror. A GameCube has no MEM2 unless --wii says it's a Wii
+game. It exits with 1 for any that aren't RAM or aren't aligned. Codes
+written by cheat and riivolution are checked the same way.
+
+cheat tidy checks a code list as pasted, and writes it back with each
+line of code as one `XXXXXXXX YYYYYYYY` line in upper case, to OUT if
+given: words run together or split over lines are paired up, and names
+and notes kept. With each run of lines of code taken as a code, it says
+what's wrong by line: words that aren't hex, a word left over, a count
+running past the code, a C2 code whose count misses the 00000000 its
+last line ends in, writes and hooks at misaligned addresses, and
+conditionals the code doesn't end. It exits with 1 for anything that
+can't be right, rather than just likely wrong.
+
+cheat ar writes FILE as GameCube Action Replay 32-bit writes, and decode
+--ar reads a list of AR codes, disassembling their 32-bit writes and
+saying what each other line does. AR codes are read and written
+decrypted, as Dolphin keeps them: ppcheat doesn't implement the cipher
+of the encrypted XXXX-XXXX-XXXXX form the AR itself takes, and refuses
+codes in it. Dolphin's AR editor decrypts a code pasted into it.
+
+cheat convert --to gecko converts AR codes to Gecko codes, and --to ar
+back: writes and fills convert, and each line that doesn't is listed on
+stderr with why.
+
+With --list, a code is put in LIST, a code list in the text form Ocarina
+and most code managers read (the game ID, its title, then each code
+under its name), as the code called NAME: replacing the one of that name
+or added after the rest, with everything else in LIST kept as it was.
+--game gives the ID to start a new list with. If LIST ends in .ini, it's
+a Dolphin game INI instead (from Dolphin's GameSettings folder), and the
+code goes in its [Gecko] section, or for ar, [ActionReplay], and is
+turned on; the rest of the file is kept. cheat list lists its codes, and
+which are on.
+
+cheat riivolution writes FILE assembled at ADDR as a Riivolution patch
+XML for a Wii, one <memory> write per word, as an option called NAME
+(`ppcheat` unless --name says otherwise), for the game whose ID starts
+with --game if given. With --dol, each write gets the original value DOL
+has there, so Riivolution only applies it to that build of the game.
+
+gct reads and writes GCTs, the binary code lists Gecko OS and Nintendont
+load from the SD card. A GCT has no names, so its codes are split as
+they're built: from a code that sets ba or po or opens a conditional to
+the E0 that ends it, or from one setting a Gecko register to the code
+using it. list writes a GCT as a code list, its codes named by number,
+for the game the file's named after. merge writes the codes of each GCT
+to OUT in turn, dropping any that are the same as one before. write
+writes the lines of a code list, or as pasted, as a GCT. build builds a
+GCT of a named code list, or a Dolphin game INI's Gecko codes turned on,
+checking each code as cheat tidy and cheat check do. Codes with errors
+are left out, as are codes the same as one before; what became of each
+code, and what was found, is listed as its manifest, to FILE if given.
+It exits with 1 if any were left out. -o is short for --output.
+
+patch assembles FILE at ADDR and writes it into DOL where ADDR is loaded
+from, or into a copy, OUT, leaving DOL as it is. Patching DOL itself
+first copies it to DOL.bak, unless there's a backup already, which is
+kept as the original. Each word changed is listed, with its offset in
+the file and the instructions before and after.
+
+hook wraps FILE in a trampoline for the hook at ADDR: a stack frame,
+with r0, r3 to r12, CR and LR saved before FILE runs and restored after,
+then the instruction the hook displaced, INSN (assembled at ADDR, or a
+word in hex) or what DOL has at ADDR. FILE is assembled where it lands
+in the trampoline, and has to fall through to the restores. By default
+it's written as a C2 code, whose branch back the codehandler supplies.
+With --at, the trampoline goes in a code cave at CAVE instead, branches
+back itself, and gets the branch to write over the hook, the C2 code
+becoming 04 codes writing the cave and then the hook; a displaced
+relative branch is corrected for running from the cave. --format asm
+writes the trampoline as assembly, each part under a comment, and hex as
+words, with the hook's branch on stderr.
+
+port finds where addresses of one build of a game (NTSC-U, say) are in
+another (PAL): by the symbol each is in and the offset into it, with a
+symbol map (any form --symbols reads) of each build, or given two DOLs
+(or ELFs), by where the 8 instructions from the address on are in the
+other, ignoring branch offsets and the immediates that point at data.
+With --codes, it ports a Gecko code list's addresses and writes the
+ported list, with what each address became on stderr. Each port that
+might be wrong says why; an address it can't port is left as it was, and
+makes port exit with 1.
+
+--verbosity normal (or --masks) adds a bit diagram to each rotate, and
+verbose adds the encoded fields and the manual's description. The bit
+diagrams are colored by source byte on a terminal, unless NO_COLOR is
+set. With --html, --masks writes a standalone page where hovering a
+result bit highlights the source bit it came from. --tikz writes a TikZ
+picture of each rotate for LaTeX documents, and --explain-encoding the
+fields of each instruction word as the manuals lay them out. --lang rust
+and --lang python explain in that language, and make the plain listing a
+function to paste into a port or script.";
+
+const COMMANDS: &[&str] = &[
+    "export-md",
+    "vectors",
+    "replay",
+    "annotate-log",
+    "profile",
+    "browse",
+    "diff",
+    "compare",
+    "doc",
+    "decode",
+    "disasm",
+    "ram",
+    "assemble",
+    "clobber",
+    "budget",
+    "caves",
+    "relocate",
+    "verify",
+    "patches",
+    "cheat",
+    "gct",
+    "patch",
+    "hook",
+    "port",
+];
+
+/// Options that take a value; everything else starting with `--` is a switch.
+const VALUE_FLAGS: &[&str] = &[
+    "--width",
+    "--symbol",
+    "--seed",
+    "--rounds",
+    "--hits",
+    "--base",
+    "--threshold",
+    "--convention",
+    "--max-size",
+    "--align",
+    "--min-size",
+    "--targets",
+    "--addr",
+    "--size",
+    "--log",
+    "--old",
+    "--new",
+    "--format",
+    "--note",
+    "--lang",
+    "--verbosity",
+    "--endian",
+    "--name",
+    "--output",
+    "--mem1",
+    "--mem2",
+    "--symbols",
+    "--to",
+    "--list",
+    "--game",
+    "--dol",
+    "--from",
+    "--original",
+    "--at",
+    "--manifest",
+    "--text",
+    "--hex",
+    "--around",
+];
+
+/// Hex address `text`, with or without `0x`, given for `name`.
+fn address(text: &str, name: &str) -> Result<u32, String> {
+    parse_hex_u32(text).ok_or_else(|| format!("invalid address `{}` for {}", text.trim(), name))
+}
+
+/// Command line split into positionals and `--flags`, consumed by each
+/// subcommand; whatever is left over is an error.
+struct Args {
+    positional: Vec<String>,
+    flags: HashMap<String, Option<String>>,
+    /// `-I` and `-D` options
+    source: SourceOptions,
+    /// `--lsb0`
+    bit_order: BitOrder,
+    /// `--locale`
+    locale: Locale,
+}
+
+impl Args {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Args {
+            positional: Vec::new(),
+            flags: HashMap::new(),
+            source: SourceOptions::default(),
+            bit_order: BitOrder::Msb0,
+            locale: Locale::En,
+        };
+
+        let mut args = args.peekable();
+        while let Some(arg) = args.next() {
+            if arg == "-h" || arg == "--help" {
+                return Err(USAGE.to_string());
+            } else if arg == "-I" {
+                let dir = args.next().ok_or("-I needs a directory")?;
+                parsed.source.include_dirs.push(PathBuf::from(dir));
+            } else if arg == "-D" {
+                let define = args.next().ok_or("-D needs NAME or NAME=VALUE")?;
+                let (name, value) = match define.split_once('=') {
+                    Some((name, value)) => {
+                        let parsed = match value.strip_prefix("0x") {
+                            Some(digits) => i64::from_str_radix(digits, 16),
+                            None => value.parse(),
+                        };
+                        let value =
+                            parsed.map_err(|_| format!("invalid value in -D {}", define))?;
+                        (name, value)
+                    }
+                    None => (define.as_str(), 1),
+                };
+                parsed.source.defines.define(name, value);
+            } else if arg == "--lsb0" {
+                parsed.bit_order = BitOrder::Lsb0;
+            } else if arg == "--locale" {
+                let tag = args.next().ok_or("--locale needs a language")?;
+                parsed.locale = Locale::from_tag(&tag)
+                    .ok_or(format!("unknown --locale `{}`; try en or ja", tag))?;
+            } else if arg == "-o" {
+                let path = args.next().ok_or("-o needs a file")?;
+                parsed.flags.insert("--output".to_string(), Some(path));
+            } else if VALUE_FLAGS.contains(&arg.as_str()) {
+                let value = args.next().ok_or(format!("{} needs a value", arg))?;
+                parsed.flags.insert(arg, Some(value));
+            } else if arg.starts_with("--") {
+                parsed.flags.insert(arg, None);
+            } else {
+                parsed.positional.push(arg);
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    fn switch(&mut self, name: &str) -> bool {
+        self.flags.remove(name).is_some()
+    }
+
+    fn value(&mut self, name: &str) -> Option<String> {
+        self.flags.remove(name).flatten()
+    }
+
+    fn number<T: std::str::FromStr>(&mut self, name: &str) -> Result<Option<T>, String> {
+        match self.value(name) {
+            Some(v) => v
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("invalid value `{}` for {}", v, name)),
+            None => Ok(None),
+        }
+    }
+
+    /// Decimal, or hex with `0x`.
+    fn integer(&mut self, name: &str) -> Result<Option<u32>, String> {
+        match self.value(name) {
+            Some(v) => match v.strip_prefix("0x") {
+                Some(digits) => u32::from_str_radix(digits, 16),
+                None => v.parse(),
+            }
+            .map(Some)
+            .map_err(|_| format!("invalid value `{}` for {}", v, name)),
+            None => Ok(None),
+        }
+    }
+
+    /// Hex address, with or without `0x`.
+    fn address(&mut self, name: &str) -> Result<Option<u32>, String> {
+        self.value(name).map(|v| address(&v, name)).transpose()
+    }
+
+    /// The optional input file, after which nothing may be left unconsumed.
+    fn finish(mut self) -> Result<Option<String>, String> {
+        if let Some(flag) = self.flags.keys().next() {
+            return Err(format!("unknown option `{}`", flag));
+        }
+        let input = if self.positional.is_empty() {
+            None
+        } else {
+            Some(self.positional.remove(0))
+        };
+        match self.positional.first() {
+            Some(extra) => Err(format!("unexpected argument `{}`", extra)),
+            None => Ok(input),
+        }
+    }
+}
+
+fn read_input(path: Option<&str>) -> io::Result<String> {
+    match path {
+        Some(path) if path != "-" => fs::read_to_string(path),
+        _ => {
+            let mut buf = String::new();
+            io::stdin().read_to_string(&mut buf)?;
+            Ok(buf)
+        }
+    }
+}
+
+fn fail(msg: impl std::fmt::Display) -> ! {
+    eprintln!("error: {}", msg);
+    process::exit(1);
+}
+
+/// `-I` and `-D`, for every command that reads assembly.
+static SOURCE_OPTIONS: OnceLock<SourceOptions> = OnceLock::new();
+
+/// Parses the input, reporting every bad line. Only the explain mode keeps
+/// going with what parsed; everything else needs the whole program.
+fn parse_input(path: Option<&str>, lenient: bool) -> Program {
+    let input = read_input(path).unwrap_or_else(|e| fail(e));
+    let (program, errors) = parser::parse_source(
+        &input,
+        Flavor::detect(&input),
+        path.filter(|&p| p != "-").map(Path::new),
+        SOURCE_OPTIONS.get().unwrap_or(&SourceOptions::default()),
+    );
+    for err in &errors {
+        eprintln!("error: {}\n", err);
+    }
+    if !errors.is_empty() && !lenient {
+        process::exit(1);
+    }
+    program
+}
+
+fn load_program(path: Option<&str>) -> Program {
+    parse_input(path, false)
+}
+
+fn terminal_width() -> usize {
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(80)
+}
+
+fn explain(mut args: Args) -> Result<(), String> {
+    let side_by_side = args.switch("--side-by-side");
+    let html = args.switch("--html");
+    let markdown = args.switch("--markdown");
+    let tikz = args.switch("--tikz");
+    let encoding = args.switch("--explain-encoding");
+    let masks = args.switch("--masks");
+    let style = match (args.switch("--c"), args.value("--lang").as_deref()) {
+        (true, None) | (false, Some("c")) => Style::C,
+        (false, Some("rust")) => Style::Rust,
+        (false, Some("python")) => Style::Python,
+        (false, None) => Style::Symbolic,
+        (false, Some(lang)) => {
+            return Err(format!("unknown --lang `{}`; try c, rust or python", lang))
+        }
+        (true, Some(_)) => return Err("--c is short for --lang c".to_string()),
+    };
+    let verbosity = match (masks, args.value("--verbosity").as_deref()) {
+        (false, None) | (false, Some("terse")) => Verbosity::Terse,
+        (true, None) | (false, Some("normal")) => Verbosity::Normal,
+        (false, Some("verbose")) => Verbosity::Verbose,
+        (false, Some(level)) => {
+            return Err(format!(
+                "unknown --verbosity `{}`; try terse, normal or verbose",
+                level
+            ))
+        }
+        (true, Some(_)) => return Err("--masks is short for --verbosity normal".to_string()),
+    };
+    let width = args.number("--width")?.unwrap_or_else(terminal_width);
+    let formats = [side_by_side, html, markdown, tikz, encoding];
+    if formats.iter().filter(|&&f| f).count() > 1 {
+        return Err(
+            "pick one of --side-by-side, --html, --markdown, --tikz and --explain-encoding"
+                .to_string(),
+        );
+    }
+    if verbosity != Verbosity::Terse && (side_by_side || markdown || tikz || encoding) {
+        return Err("--masks and --verbosity need the plain listing or --html".to_string());
+    }
+    let program = parse_input(args.finish()?.as_deref(), true);
+    let color = io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+
+    if encoding {
+        print!("{}", render::explain_encoding(&program));
+    } else if tikz {
+        print!("{}", render::explain_tikz(&program));
+    } else if markdown {
+        print!("{}", render::explain_markdown(&program, style));
+    } else if html && verbosity != Verbosity::Terse {
+        print!("{}", render::explain_html(&program, style));
+    } else if html {
+        print!(
+            "{}",
+            render::side_by_side_html(&render::rows_styled(&program, style))
+        );
+    } else if side_by_side {
+        print!(
+            "{}",
+            render::side_by_side(&render::rows_styled(&program, style), width)
+        );
+    } else if matches!(style, Style::Rust | Style::Python) {
+        // the plain listing in code is a function to paste into a port
+        let name = program
+            .lines
+            .iter()
+            .find_map(|line| match &line.statement {
+                Statement::Label(label) => Some(label.replace(|c: char| !c.is_alphanumeric(), "_")),
+                _ => None,
+            })
+            .unwrap_or_else(|| "f".to_string());
+        match style {
+            Style::Rust => print!("{}", render::rust_fn(&program, &name)),
+            _ => print!("{}", render::python_fn(&program, &name)),
+        }
+    } else {
+        print!(
+            "{}",
+            render::listing(&program, style, verbosity, color, width)
+        );
+    }
+    Ok(())
+}
+
+fn export_md(mut args: Args) -> Result<(), String> {
+    let symbol = args
+        .value("--symbol")
+        .ok_or("export-md needs --symbol NAME")?;
+    let map = match args.value("--symbols") {
+        Some(path) => symbol_map(&path)?,
+        None => SymbolTable::default(),
+    };
+    let program = load_program(args.finish()?.as_deref());
+    let range = render::function_range(&program, &symbol, &map).ok_or_else(|| {
+        format!(
+            "no function `{}`: it's neither a label nor, with --symbols, a symbol over the listing's addresses",
+            symbol
+        )
+    })?;
+    print!(
+        "{}",
+        render::export_markdown(&symbol, &render::rows(&program)[range])
+    );
+    Ok(())
+}
+
+fn gen_vectors(mut args: Args) -> Result<(), String> {
+    let seed = args.number("--seed")?.unwrap_or(1);
+    let rounds = args.number("--rounds")?.unwrap_or(16);
+    if let Some(extra) = args.finish()? {
+        return Err(format!("unexpected argument `{}`", extra));
+    }
+    print!("{}", vectors::to_json(&vectors::generate(seed, rounds)));
+    Ok(())
+}
+
+fn replay(args: Args) -> Result<(), String> {
+    let input = read_input(args.finish()?.as_deref()).unwrap_or_else(|e| fail(e));
+    let records = trace::parse_trace(&input).unwrap_or_else(|e| fail(e));
+
+    let mut diverged = 0;
+    for step in trace::replay(&records) {
+        let record = step.record;
+        println!(
+            "{:08x}: {:<28} {}",
+            record.pc,
+            record.op.to_string(),
+            record.op.highlevel()
+        );
+        for divergence in &step.divergences {
+            println!("  !! line {}: {}", record.line, divergence);
+        }
+        if !step.divergences.is_empty() {
+            diverged += 1;
+        }
+    }
+
+    println!("{} records replayed, {} diverged", records.len(), diverged);
+    if diverged > 0 {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+fn annotate_log(args: Args) -> Result<(), String> {
+    let input = read_input(args.finish()?.as_deref()).unwrap_or_else(|e| fail(e));
+    print!("{}", trace::annotate(&trace::parse_dolphin_log(&input)));
+    Ok(())
+}
+
+fn show_profile(mut args: Args) -> Result<(), String> {
+    let hits = args.value("--hits").ok_or("profile needs --hits PROFILE")?;
+    let base = args.address("--base")?.ok_or("profile needs --base ADDR")?;
+    let threshold = args.number("--threshold")?.unwrap_or(1);
+    let program = load_program(args.finish()?.as_deref());
+
+    let input = fs::read_to_string(&hits).unwrap_or_else(|e| fail(e));
+    let profile = Profile::parse(&input).unwrap_or_else(|e| fail(e));
+
+    print!("{}", profile::annotate(&program, base, &profile));
+    println!();
+    for span in profile::hot_spans(&program, base, &profile, threshold) {
+        println!(
+            "hot: {:08x}..{:08x} {} hits{}",
+            span.start,
+            span.end + 4,
+            span.hits,
+            match span.rotates {
+                0 => String::new(),
+                n => format!(", {} rotate/mask instructions", n),
+            }
+        );
+    }
+    for p in profile::peepholes(&program, base, &profile, threshold) {
+        println!("hint: {:08x} {} ({} hits)", p.addr, p.hint, p.hits);
+    }
+    Ok(())
+}
+
+const BROWSE_HELP: &str = "commands: [enter] follow a branch (else next), n next, p prev, \
+g ADDR goto, b back, m NAME bookmark, ' NAME go to bookmark, marks, q quit";
+
+fn print_browser(browser: &Browser) {
+    for (current, addr, op) in browser.window(5) {
+        let marker = if current { ">" } else { " " };
+        println!("{} {:08x}  {}", marker, addr, op);
+    }
+    if let Some((_, op)) = browser.current() {
+        println!("\n  {}", op.highlevel());
+    }
+}
+
+fn browse(mut args: Args) -> Result<(), String> {
+    let base = args.address("--base")?;
+    let path = args
+        .finish()?
+        .ok_or("browse needs a FILE (stdin takes commands)")?;
+    let mut browser = if path.ends_with(".dol") || path.ends_with(".elf") {
+        let bytes = fs::read(&path).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+        let image = Image::parse(&bytes).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+        Browser::from_image(&image)
+    } else {
+        // assembled, so a branch to a label has its displacement to follow
+        let program = load_program(Some(&path));
+        let base = base.or_else(|| program.lines.iter().find_map(|l| l.address));
+        let base = base.unwrap_or(0);
+        let bytes = asm::assemble(&program, base).map_err(|e| format!("{}: {}", path, e))?;
+        Browser::from_image(&Image::raw(&bytes, base))
+    };
+
+    println!("{}", BROWSE_HELP);
+    print_browser(&browser);
+
+    let stdin = io::stdin();
+    loop {
+        print!("browse> ");
+        io::stdout().flush().map_err(|e| e.to_string())?;
+        let mut line = String::new();
+        if stdin
+            .lock()
+            .read_line(&mut line)
+            .map_err(|e| e.to_string())?
+            == 0
+        {
+            return Ok(());
+        }
+
+        let mut words = line.split_whitespace();
+        let ok = match (words.next(), words.next()) {
+            (None, _) => {
+                if !browser.follow() {
+                    browser.step(1);
+                }
+                true
+            }
+            (Some("n"), _) => {
+                browser.step(1);
+                true
+            }
+            (Some("p"), _) => {
+                browser.step(-1);
+                true
+            }
+            (Some("g"), Some(addr)) => parse_hex_u32(addr).is_some_and(|addr| browser.jump(addr)),
+            (Some("b"), _) => browser.back(),
+            (Some("m"), Some(name)) => {
+                browser.bookmark(name);
+                true
+            }
+            (Some("'"), Some(name)) => browser.goto_bookmark(name),
+            (Some("marks"), _) => {
+                for (name, addr) in browser.bookmarks() {
+                    println!("  {:08x}  {}", addr, name);
+                }
+                continue;
+            }
+            (Some("q"), _) => return Ok(()),
+            _ => {
+                println!("{}", BROWSE_HELP);
+                continue;
+            }
+        };
+
+        if !ok {
+            println!("?");
+        }
+        print_browser(&browser);
+    }
+}
+
+fn diff(mut args: Args) -> Result<(), String> {
+    let width = args.number("--width")?.unwrap_or_else(terminal_width);
+    if args.positional.is_empty() {
+        return Err("diff needs LEFT and RIGHT files".to_string());
+    }
+    let symbol = args.value("--symbol");
+    let maps = args.value("--symbols");
+    let left = args.positional.remove(0);
+    let right = args.finish()?.ok_or("diff needs LEFT and RIGHT files")?;
+    let (left, right) = match symbol {
+        Some(name) => {
+            let mut maps = maps.as_deref().map(|m| m.split(',')).into_iter().flatten();
+            let (left_map, right_map) = (maps.next(), maps.next());
+            if maps.next().is_some() {
+                return Err("diff --symbols takes a MAP for each build, at most two".to_string());
+            }
+            (
+                build_function(&left, left_map, &name)?,
+                build_function(&right, right_map, &name)?,
+            )
+        }
+        None if maps.is_some() => return Err("diff --symbols goes with --symbol".to_string()),
+        None => (load_program(Some(&left)), load_program(Some(&right))),
+    };
+
+    let rows = ppcheat::diff::diff(&left, &right);
+    print!("{}", render::split_view(&rows, width));
+    if rows.iter().any(|row| row.change != Change::Same) {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// The function `name` of the DOL or ELF at `path`, named by the symbols
+/// of `map`, or of an ELF, its own.
+fn build_function(path: &str, map: Option<&str>, name: &str) -> Result<Program, String> {
+    let bytes = fs::read(path).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+    let (image, symbols) = match bytes.starts_with(b"\x7fELF") {
+        true => {
+            let elf = Elf::parse(&bytes).map_err(|e| format!("{}: {}", path, e))?;
+            let image = elf.image().map_err(|e| format!("{}: {}", path, e))?;
+            let mut symbols = elf.symbol_table();
+            if let Some(map) = map {
+                symbols.extend(symbol_map(map)?);
+            }
+            (image, symbols)
+        }
+        false => {
+            let image = Image::parse_dol(&bytes).map_err(|e| format!("{}: {}", path, e))?;
+            let map =
+                map.ok_or_else(|| format!("{}: a DOL has no symbols; give --symbols", path))?;
+            (image, symbol_map(map)?)
+        }
+    };
+    ppcheat::diff::function(&image, &symbols, name).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn compare(mut args: Args) -> Result<(), String> {
+    if args.positional.is_empty() {
+        return Err("compare needs two instructions".to_string());
+    }
+    let left = args.positional.remove(0);
+    let right = args.finish()?.ok_or("compare needs two instructions")?;
+    let parse = |insn: &str| match parser::parse_opcode(insn.trim()) {
+        Ok(("", op)) => Ok(op),
+        _ => Err(format!("not an instruction: `{}`", insn)),
+    };
+    let (left, right) = (parse(&left)?, parse(&right)?);
+
+    println!("{}", ppcheat::compare::explain(&left, &right)?);
+    let same = ppcheat::compare::compare(&left, &right)?
+        .iter()
+        .all(|span| span.same());
+    if !same {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+fn doc(args: Args) -> Result<(), String> {
+    let mnemonic = args.finish()?.ok_or("doc needs a mnemonic")?;
+    println!(
+        "{}",
+        ppcheat::reference::lookup(&mnemonic.to_ascii_lowercase())?
+    );
+    Ok(())
+}
+
+/// `--endian big|little|auto`, `None` for auto; big-endian by default.
+fn endian(args: &mut Args) -> Result<Option<Endian>, String> {
+    match args.value("--endian").as_deref() {
+        None | Some("big") => Ok(Some(Endian::Big)),
+        Some("little") => Ok(Some(Endian::Little)),
+        Some("auto") => Ok(None),
+        Some(other) => Err(format!(
+            "unknown --endian `{}`; try big, little or auto",
+            other
+        )),
+    }
+}
+
+/// A hex dump from the command line, or stdin if there is none, as a
+/// listing with each instruction's explanation. Addresses come from the
+/// dump or `--base`; without either, each line shows the word instead.
+fn decode(mut args: Args) -> Result<(), String> {
+    let base = args.address("--base")?;
+    let endian = endian(&mut args)?;
+    let hex = std::mem::take(&mut args.positional).join(" ");
+    args.finish()?;
+    let input = match hex.is_empty() {
+        true => read_input(None).unwrap_or_else(|e| fail(e)),
+        false => hex,
+    };
+    let dump = HexDump::parse(&input).map_err(|e| e.to_string())?;
+    if !dump.bytes.len().is_multiple_of(4) {
+        return Err(format!(
+            "{} bytes isn't a whole number of instructions",
+            dump.bytes.len()
+        ));
+    }
+    let endian = endian.unwrap_or_else(|| Endian::detect(&dump.bytes));
+    let addressed = dump.base.or(base).is_some();
+    let mut unknown = 0;
+    for (word, (addr, op)) in dump
+        .bytes
+        .chunks(4)
+        .zip(dump.disassemble(base.unwrap_or(0), endian))
+    {
+        let column = match addressed {
+            true => format!(
+                "{:08x}: {:02x} {:02x} {:02x} {:02x}",
+                addr, word[0], word[1], word[2], word[3]
+            ),
+            false => format!(
+                "{:02X}{:02X}{:02X}{:02X}",
+                word[0], word[1], word[2], word[3]
+            ),
+        };
+        match op {
+            Ok(op) => println!("{}  {}\n{}", column, op, op.highlevel()),
+            Err(e) => {
+                let value = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+                println!("{}  .long 0x{:08X}\n# {}", column, value, best_guess(&e));
+                unknown += 1;
+            }
+        }
+    }
+    if unknown > 0 {
+        eprintln!(
+            "{} of {} words didn't decode, and are kept as .long",
+            unknown,
+            dump.bytes.len() / 4
+        );
+    }
+    Ok(())
+}
+
+/// A raw binary, DOL, ELF, REL, S-record or Intel HEX file, or a disc
+/// image's main DOL, as a listing to explain or edit.
+fn disasm(mut args: Args) -> Result<(), String> {
+    let rel = args.switch("--rel");
+    let options = listing_options(&mut args)?;
+    let base = args.address("--base")?;
+    let endian = endian(&mut args)?;
+    let function = args.address("--addr")?;
+    let size = args.integer("--size")?;
+    let path = args.finish()?.ok_or("disasm needs a FILE")?;
+    if size.is_some() && function.is_none() {
+        return Err("disasm --size goes with --addr".to_string());
+    }
+    let only_dol = || match function {
+        Some(_) => Err("disasm --addr is for a DOL or a disc image".to_string()),
+        None => Ok(()),
+    };
+
+    if rel {
+        only_dol()?;
+        let base = base.ok_or("disasm --rel needs --base ADDR to link the module at")?;
+        if endian != Some(Endian::Big) {
+            return Err("a REL is big-endian; --endian is for raw binaries".to_string());
+        }
+        let bytes = fs::read(&path).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+        let linked = Rel::parse(&bytes)
+            .and_then(|rel| rel.link(base))
+            .map_err(|e| format!("{}: {}", path, e))?;
+        for import in &linked.unresolved {
+            eprintln!("unresolved: {}", import);
+        }
+        print_listing(&linked.listing(&options));
+        return Ok(());
+    }
+    if let Some(base) = base {
+        only_dol()?;
+        let listing = disasm::listing_file(Path::new(&path), base, endian, &options)
+            .unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+        print_listing(&listing);
+        return Ok(());
+    }
+    if endian != Some(Endian::Big) {
+        return Err(
+            "a DOL or ELF says its own byte order; --endian is for raw binaries".to_string(),
+        );
+    }
+
+    let mut file = File::open(&path).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+    let is_disc = disc::detect(&mut file).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+    let dol = if is_disc {
+        let disc = Disc::read(&mut file).map_err(|e| format!("{}: {}", path, e))?;
+        eprintln!("{}", disc);
+        disc.dol
+    } else {
+        let bytes = fs::read(&path).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+        if let Some(listing) =
+            listing_other(&bytes, &options).map_err(|e| format!("{}: {}", path, e))?
+        {
+            only_dol()?;
+            print_listing(&listing);
+            return Ok(());
+        }
+        Dol::parse(&bytes).map_err(|e| format!("{}: {} (pass --base for a raw binary)", path, e))?
+    };
+    let listing = match function {
+        Some(addr) => dol
+            .function_listing(addr, size, &options)
+            .ok_or_else(|| format!("{:08x} isn't in a text segment of {}", addr, path))?,
+        None => dol.listing(&options),
+    };
+    print_listing(&listing);
+    Ok(())
+}
+
+/// `--objdump`, `--symbols MAP` and `--find-data`, for a listing.
+fn listing_options(args: &mut Args) -> Result<disasm::Options, String> {
+    let style = match args.switch("--objdump") {
+        true => disasm::Style::Objdump,
+        false => disasm::Style::Ppcheat,
+    };
+    let find_data = args.switch("--find-data");
+    if find_data && style == disasm::Style::Objdump {
+        return Err("--find-data is for ppcheat's listing; objdump's lists it all as code".into());
+    }
+    let symbols = match args.value("--symbols") {
+        Some(path) => symbol_map(&path)?,
+        None => SymbolTable::default(),
+    };
+    Ok(disasm::Options {
+        style,
+        symbols,
+        find_data,
+    })
+}
+
+/// The symbol map at `path`, which has to have some.
+fn symbol_map(path: &str) -> Result<SymbolTable, String> {
+    let text = fs::read_to_string(path).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+    let symbols = SymbolTable::parse(&text);
+    if symbols.is_empty() {
+        return Err(format!("{}: no symbols in a form ppcheat reads", path));
+    }
+    Ok(symbols)
+}
+
+/// `listing` on stdout, and on stderr how many of its words didn't decode.
+fn print_listing(listing: &Listing) {
+    print!("{}", listing);
+    if listing.unknown > 0 {
+        eprintln!(
+            "{} of {} words didn't decode, and are kept as .long",
+            listing.unknown, listing.words
+        );
+    }
+    if listing.data > 0 {
+        eprintln!(
+            "{} of {} words look like data, and are kept as directives",
+            listing.data, listing.words
+        );
+    }
+}
+
+/// The listing of `bytes` if they're S-records, Intel HEX or an ELF.
+fn listing_other(bytes: &[u8], options: &disasm::Options) -> Result<Option<Listing>, String> {
+    if srec::detect(bytes) || ihex::detect(bytes) {
+        let text = String::from_utf8_lossy(bytes);
+        let image = match srec::detect(bytes) {
+            true => srec::parse(&text),
+            false => ihex::parse(&text),
+        };
+        return Ok(Some(image_listing(
+            &image.map_err(|e| e.to_string())?,
+            options,
+        )));
+    }
+    if bytes.starts_with(b"\x7fELF") {
+        let listing = Elf::parse(bytes).and_then(|elf| elf.listing(options));
+        return listing.map(Some).map_err(|e| e.to_string());
+    }
+    Ok(None)
+}
+
+/// A range of a Dolphin RAM dump as a listing.
+fn ram(mut args: Args) -> Result<(), String> {
+    let read = |path: Option<String>| -> Option<Vec<u8>> {
+        let path = path?;
+        Some(fs::read(&path).unwrap_or_else(|e| fail(format!("{}: {}", path, e))))
+    };
+    let mem1 = read(args.value("--mem1"));
+    let mem2 = read(args.value("--mem2"));
+    let options = listing_options(&mut args)?;
+    let addr = args.address("--addr")?.ok_or("ram needs --addr ADDR")?;
+    let size = args.integer("--size")?.unwrap_or(0x100);
+    args.finish()?;
+    if mem1.is_none() && mem2.is_none() {
+        return Err("ram needs a dump: --mem1 MEM1, --mem2 MEM2 or both".to_string());
+    }
+    let dump = RamDump::new(mem1, mem2).map_err(|e| e.to_string())?;
+    let listing = dump
+        .listing(addr, size, &options)
+        .ok_or_else(|| format!("{:08x}..+{:#x} isn't in the dumps given", addr, size))?;
+    print_listing(&listing);
+    Ok(())
+}
+
+fn assemble(mut args: Args) -> Result<(), String> {
+    let base = args
+        .address("--base")?
+        .ok_or("assemble needs --base ADDR")?;
+    let format = match args.value("--format") {
+        Some(name) => Format::from_name(&name).ok_or_else(|| {
+            format!(
+                "unknown --format `{}`; try {}",
+                name,
+                Format::NAMES.join(", ")
+            )
+        })?,
+        None => Format::Hex,
+    };
+    let name = args.value("--name").unwrap_or_else(|| "patch".to_string());
+    let output = args.value("--output");
+    let program = load_program(args.finish()?.as_deref());
+
+    let bytes = asm::assemble(&program, base).unwrap_or_else(|e| fail(e));
+    let out = format.write(&bytes, &name, base);
+    match output {
+        Some(path) => fs::write(&path, out).unwrap_or_else(|e| fail(format!("{}: {}", path, e))),
+        None => io::stdout().write_all(&out).unwrap_or_else(|e| fail(e)),
+    }
+    Ok(())
+}
+
+fn clobber(mut args: Args) -> Result<(), String> {
+    let convention = match args.value("--convention") {
+        Some(path) => {
+            let input = fs::read_to_string(&path).unwrap_or_else(|e| fail(e));
+            Convention::parse(&input).unwrap_or_else(|e| fail(format!("{}: {}", path, e)))
+        }
+        None => Convention::default(),
+    };
+    let program = load_program(args.finish()?.as_deref());
+
+    let clobbers = clobber::check(&program, &convention);
+    for c in &clobbers {
+        println!("{}", c);
+    }
+    println!(
+        "{} problem(s) under the {} convention",
+        clobbers.len(),
+        convention.name
+    );
+    if !clobbers.is_empty() {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+fn check_budget(mut args: Args) -> Result<(), String> {
+    let budget = Budget {
+        max_size: args.integer("--max-size")?,
+        align: args.integer("--align")?,
+    };
+    let base = args.address("--base")?.unwrap_or(0);
+    let program = load_program(args.finish()?.as_deref());
+
+    let (problems, suggestions) = budget::check(&program, base, &budget);
+    println!("{:#x} bytes", budget::size(&program, base));
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    for s in &suggestions {
+        println!("  line {}: {} (saves {} bytes)", s.line, s.hint, s.saves);
+    }
+    if !problems.is_empty() {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+fn caves(mut args: Args) -> Result<(), String> {
+    let min_size = args.integer("--min-size")?.unwrap_or(0x20);
+    let path = args.finish()?.ok_or("caves needs an IMAGE")?;
+    let bytes = fs::read(&path).unwrap_or_else(|e| fail(e));
+    let image = Image::parse(&bytes).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+
+    for cave in image::find_caves(&image, min_size) {
+        println!(
+            "{:08x}..{:08x}  {:#x} bytes",
+            cave.addr,
+            cave.addr.wrapping_add(cave.size),
+            cave.size
+        );
+    }
+    Ok(())
+}
+
+fn verify(mut args: Args) -> Result<(), String> {
+    let addr = args.address("--addr")?.ok_or("verify needs --addr ADDR")?;
+    let size = args.integer("--size")?.ok_or("verify needs --size N")?;
+    let path = args.finish()?.ok_or("verify needs an IMAGE")?;
+    let bytes = fs::read(&path).unwrap_or_else(|e| fail(e));
+    let image = Image::parse(&bytes).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+
+    let check = Verification::new(&image, addr, size)
+        .ok_or_else(|| format!("{:08x}..+{:#x} is not loaded from {}", addr, size, path))?;
+    println!("original=\"{}\"", check.original_hex());
+    println!("crc32={:08x}", check.crc32);
+    Ok(())
+}
+
+fn relocate(mut args: Args) -> Result<(), String> {
+    let targets = args
+        .value("--targets")
+        .ok_or("relocate needs --targets ADDR[,ADDR...]")?;
+    let targets = targets
+        .split(',')
+        .map(|t| address(t, "--targets"))
+        .collect::<Result<Vec<_>, _>>()?;
+    let format = args.value("--format");
+    let name = args
+        .value("--name")
+        .unwrap_or_else(|| "ppcheat".to_string());
+    let program = load_program(args.finish()?.as_deref());
+
+    let codes = relocate::relocate(&program, &targets)
+        .unwrap_or_else(|e| fail(e))
+        .into_iter()
+        .map(|(base, bytes)| {
+            check_target(base, &bytes, 4, Console::GameCube);
+            NamedCode {
+                name: format!("{} @ {:08x}", name, base),
+                lines: gecko::word_writes(&bytes, base).unwrap_or_else(|e| fail(e)),
+                notes: Vec::new(),
+            }
+        })
+        .collect::<Vec<_>>();
+    match format.as_deref().unwrap_or("04") {
+        "04" => {
+            for (i, code) in codes.iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
+                println!("{}", code.name);
+                for line in &code.lines {
+                    println!("{}", line);
+                }
+            }
+        }
+        "ini" => {
+            let mut ini = GameIni::default();
+            for code in codes {
+                ini.set_code(ini::Codes::Gecko, code)
+                    .unwrap_or_else(|e| fail(e));
+            }
+            print!("{}", ini);
+        }
+        other => return Err(format!("relocate --format is 04 or ini, not `{}`", other)),
+    }
+    Ok(())
+}
+
+/// Checks that `insn` is one instruction ppcheat understands.
+fn instruction(insn: String) -> Result<String, String> {
+    match parser::parse_opcode(insn.trim()) {
+        Ok(("", _)) => Ok(insn.trim().to_string()),
+        _ => Err(format!("not an instruction: `{}`", insn)),
+    }
+}
+
+fn patches(mut args: Args) -> Result<(), String> {
+    let path = args.value("--log").ok_or("patches needs --log LOG")?;
+    if args.positional.is_empty() {
+        return Err("patches needs list, add or revert".to_string());
+    }
+    let action = args.positional.remove(0);
+    let mut log = match fs::read_to_string(&path) {
+        Ok(text) => PatchLog::parse(&text).unwrap_or_else(|e| fail(format!("{}: {}", path, e))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => PatchLog::default(),
+        Err(e) => fail(e),
+    };
+
+    match action.as_str() {
+        "list" => {
+            if let Some(extra) = args.finish()? {
+                return Err(format!("unexpected argument `{}`", extra));
+            }
+            for (i, patch) in log.patches.iter().enumerate() {
+                println!(
+                    "{:>3}  {:08x}  {:<12} {} -> {}  {}",
+                    i + 1,
+                    patch.addr,
+                    patch.format,
+                    patch.old,
+                    patch.new,
+                    patch.note
+                );
+            }
+            return Ok(());
+        }
+        "add" => {
+            let addr = args.address("--addr")?.ok_or("add needs --addr ADDR")?;
+            let old = instruction(args.value("--old").ok_or("add needs --old INSN")?)?;
+            let new = instruction(args.value("--new").ok_or("add needs --new INSN")?)?;
+            let format = args
+                .value("--format")
+                .unwrap_or_else(|| "gecko".to_string());
+            let note = args.value("--note").unwrap_or_default();
+            if let Some(extra) = args.finish()? {
+                return Err(format!("unexpected argument `{}`", extra));
+            }
+            log.patches.push(Patch {
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs()),
+                addr,
+                format,
+                old,
+                new,
+                note,
+            });
+        }
+        "revert" => {
+            let n = args.finish()?.ok_or("revert needs a patch number")?;
+            let undo = n
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| log.revert(n.checked_sub(1)?))
+                .ok_or(format!("no patch {} in {}", n, path))?;
+            println!("{:08x}: {}  # was {}", undo.addr, undo.new, undo.old);
+        }
+        other => return Err(format!("unknown patches action `{}`", other)),
+    }
+
+    fs::write(&path, log.to_string()).unwrap_or_else(|e| fail(e));
+    Ok(())
+}
+
+fn cheat(mut args: Args) -> Result<(), String> {
+    if args.positional.is_empty() {
+        return Err(
+            "cheat needs 04, c2, 06, f2, f6, signature, ar, riivolution, decode, describe, check, \
+             tidy, convert or list"
+                .to_string(),
+        );
+    }
+    let action = args.positional.remove(0);
+    let write = match action.as_str() {
+        "04" => gecko::word_writes,
+        "c2" => gecko::insert_asm,
+        "ar" => action_replay::word_writes,
+        "06" => {
+            let addr = args.address("--addr")?.ok_or("06 needs --addr ADDR")?;
+            let text = args.value("--text");
+            let hex = args.value("--hex");
+            let (list, name, game) = (
+                args.value("--list"),
+                args.value("--name"),
+                args.value("--game"),
+            );
+            let file = args.finish()?;
+            let bytes = match (text, hex, file) {
+                (Some(text), None, None) => [text.as_bytes(), &[0]].concat(),
+                (None, Some(hex), None) => HexDump::parse(&hex).map_err(|e| e.to_string())?.bytes,
+                (None, None, Some(path)) => {
+                    fs::read(&path).unwrap_or_else(|e| fail(format!("{}: {}", path, e)))
+                }
+                _ => return Err("06 needs one of --text TEXT, --hex HEX and FILE".to_string()),
+            };
+            check_target(addr, &bytes, 1, Console::GameCube);
+            let lines = gecko::string_write(&bytes, addr).unwrap_or_else(|e| fail(e));
+            return save_code(&action, lines, list, name, game);
+        }
+        "signature" => {
+            let addr = args
+                .address("--addr")?
+                .ok_or("signature needs --addr ADDR")?;
+            let path = args.finish()?.ok_or("signature needs a DOL")?;
+            let image = read_dol(&path);
+            let candidates = signatures(&image, addr, &path);
+            let (start, end) = signature::function(&image, addr).unwrap_or((addr, addr));
+            for at in (start..end).step_by(4) {
+                let word = image.read(at, 4).unwrap();
+                let word = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+                let insn = disasm::instruction(word);
+                match candidates.iter().find(|c| c.addr == at) {
+                    Some(c) => println!(
+                        "{:08x}: {:08X}  {:<32}  {} match(es){}",
+                        at,
+                        word,
+                        insn,
+                        c.matches,
+                        if c.stable { ", stable" } else { "" }
+                    ),
+                    None => println!("{:08x}: {:08X}  {}", at, word, insn),
+                }
+            }
+            match signature::best(&candidates) {
+                Some(best) => println!(
+                    "best: {:08x}, {:#x} byte(s) before {:08x}",
+                    best.addr,
+                    addr - best.addr,
+                    addr
+                ),
+                None => fail(no_signature(addr, &path)),
+            }
+            return Ok(());
+        }
+        "f6" => {
+            let addr = args.address("--addr")?.ok_or("f6 needs --addr ADDR")?;
+            let path = args.value("--dol").ok_or("f6 needs --dol DOL")?;
+            let insert = args.switch("--c2");
+            let (list, name, game) = (
+                args.value("--list"),
+                args.value("--name"),
+                args.value("--game"),
+            );
+            let program = load_program(args.finish()?.as_deref());
+            let bytes = asm::assemble(&program, addr).unwrap_or_else(|e| fail(e));
+            check_target(addr, &bytes, 4, Console::GameCube);
+            let image = read_dol(&path);
+            let candidates = signatures(&image, addr, &path);
+            let best =
+                signature::best(&candidates).unwrap_or_else(|| fail(no_signature(addr, &path)));
+            if !best.stable {
+                eprintln!(
+                    "warning: the signature at {:08x} has a branch displacement or an address \
+                     in it, so another build of the game may not have it",
+                    best.addr
+                );
+            }
+            let (start, end) = signature::search_range(&image);
+            let search = gecko::search(&best.words, start, end).unwrap_or_else(|e| fail(e));
+            let lines = signature::found(search, &bytes, addr - best.addr, insert)
+                .unwrap_or_else(|e| fail(e));
+            return save_code(&action, lines, list, name, game);
+        }
+        "f2" => {
+            let addr = args.address("--addr")?.ok_or("f2 needs --addr ADDR")?;
+            let path = args.value("--dol").ok_or("f2 needs --dol DOL")?;
+            let around = args.number::<i8>("--around")?.unwrap_or(4);
+            let (list, name, game) = (
+                args.value("--list"),
+                args.value("--name"),
+                args.value("--game"),
+            );
+            let program = load_program(args.finish()?.as_deref());
+            let bytes = asm::assemble(&program, addr).unwrap_or_else(|e| fail(e));
+            check_target(addr, &bytes, 4, Console::GameCube);
+            let checksum =
+                signature::checksum(&read_dol(&path), addr, around).unwrap_or_else(|| {
+                    fail(format!(
+                        "the {} instruction(s) around {:08x} are not loaded from {}",
+                        around.unsigned_abs(),
+                        addr,
+                        path
+                    ))
+                });
+            let lines =
+                gecko::checksum_asm(&bytes, addr, around, checksum).unwrap_or_else(|e| fail(e));
+            return save_code(&action, lines, list, name, game);
+        }
+        "decode" => {
+            let ar = args.switch("--ar");
+            let options = listing_options(&mut args)?;
+            let input = read_input(args.finish()?.as_deref()).unwrap_or_else(|e| fail(e));
+            let listing = match ar {
+                true => action_replay::parse_list(&input)
+                    .map(|commands| action_replay::listing(&commands, &options)),
+                false => gecko::parse_list(&input).map(|codes| gecko::listing(&codes, &options)),
+            };
+            print_listing(&listing.unwrap_or_else(|e| fail(e)));
+            return Ok(());
+        }
+        "check" => {
+            let console = match args.switch("--wii") {
+                true => Console::Wii,
+                false => Console::GameCube,
+            };
+            let input = read_input(args.finish()?.as_deref()).unwrap_or_else(|e| fail(e));
+            let codes = gecko::parse_types(&input).unwrap_or_else(|e| fail(e));
+            let problems = memory_map::check_codes(&codes, console);
+            for (line, problem) in &problems {
+                let kind = if problem.is_error() {
+                    "error"
+                } else {
+                    "warning"
+                };
+                println!("{}  {}: {}", line, kind, problem);
+            }
+            if problems.iter().any(|(_, p)| p.is_error()) {
+                process::exit(1);
+            }
+            return Ok(());
+        }
+        "tidy" => {
+            let output = args.value("--output");
+            let input = read_input(args.finish()?.as_deref()).unwrap_or_else(|e| fail(e));
+            let tidied = tidy::tidy(&input);
+            for (line, issue) in &tidied.issues {
+                let kind = if issue.is_error() { "error" } else { "warning" };
+                eprintln!("line {}: {}: {}", line, kind, issue);
+            }
+            match output {
+                Some(path) => fs::write(&path, &tidied.text)
+                    .unwrap_or_else(|e| fail(format!("{}: {}", path, e))),
+                None => print!("{}", tidied.text),
+            }
+            if tidied.issues.iter().any(|(_, issue)| issue.is_error()) {
+                process::exit(1);
+            }
+            return Ok(());
+        }
+        "describe" => {
+            let input = read_input(args.finish()?.as_deref()).unwrap_or_else(|e| fail(e));
+            for (lines, code) in gecko::parse_types(&input).unwrap_or_else(|e| fail(e)) {
+                println!("{}  {}", lines[0], code);
+                for line in &lines[1..] {
+                    println!("{}", line);
+                }
+            }
+            return Ok(());
+        }
+        "riivolution" => {
+            let addr = args
+                .address("--addr")?
+                .ok_or("riivolution needs --addr ADDR")?;
+            let name = args
+                .value("--name")
+                .unwrap_or_else(|| "ppcheat".to_string());
+            let game = args.value("--game");
+            let dol = args.value("--dol");
+            let program = load_program(args.finish()?.as_deref());
+            let bytes = asm::assemble(&program, addr).unwrap_or_else(|e| fail(e));
+            check_target(addr, &bytes, 4, Console::Wii);
+            let mut patch = riivolution::Patch::new(&name, &bytes, addr);
+            if let Some(path) = dol {
+                let bytes = fs::read(&path).unwrap_or_else(|e| fail(e));
+                let image =
+                    Image::parse_dol(&bytes).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+                patch.capture_originals(&image).unwrap_or_else(|addr| {
+                    fail(format!("{:08x} is not loaded from {}", addr, path))
+                });
+            }
+            print!("{}", riivolution::document(game.as_deref(), &[patch]));
+            return Ok(());
+        }
+        "list" => {
+            let path = args.finish()?.ok_or("list needs a code LIST")?;
+            let text = fs::read_to_string(&path).unwrap_or_else(|e| fail(e));
+            if path.ends_with(".ini") {
+                let game = GameIni::parse(&text);
+                for codes in [ini::Codes::Gecko, ini::Codes::ActionReplay] {
+                    let list = game
+                        .codes(codes)
+                        .unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+                    let enabled = game.enabled(codes);
+                    for code in &list {
+                        let on = enabled.contains(&code.name.as_str());
+                        println!(
+                            "{:<12}  {}  {}",
+                            codes,
+                            if on { "on " } else { "off" },
+                            code.name
+                        );
+                    }
+                }
+                return Ok(());
+            }
+            let list = CodeList::parse(&text).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+            println!("{}", format!("{} {}", list.game_id, list.title).trim_end());
+            for (i, code) in list.codes.iter().enumerate() {
+                println!("{:>3}  {} ({} line(s))", i + 1, code.name, code.lines.len());
+            }
+            return Ok(());
+        }
+        "convert" => {
+            let to = args.value("--to").ok_or("convert needs --to gecko|ar")?;
+            let input = read_input(args.finish()?.as_deref()).unwrap_or_else(|e| fail(e));
+            let conversion = match to.as_str() {
+                "gecko" => action_replay::parse_list(&input).map(|c| action_replay::to_gecko(&c)),
+                "ar" => gecko::parse_list(&input).map(|c| action_replay::from_gecko(&c)),
+                other => return Err(format!("unknown --to `{}`; try gecko or ar", other)),
+            };
+            let conversion = conversion.unwrap_or_else(|e| fail(e));
+            for line in &conversion.lines {
+                println!("{}", line);
+            }
+            for (lines, why) in &conversion.skipped {
+                let lines: Vec<_> = lines.iter().map(Line::to_string).collect();
+                eprintln!("not converted: {}: {}", lines.join(", "), why);
+            }
+            return Ok(());
+        }
+        other => {
+            return Err(format!(
+                "unknown cheat action `{}`; try 04, c2, 06, f2, f6, signature, ar, riivolution, \
+                 decode, describe, check, tidy, convert or list",
+                other
+            ))
+        }
+    };
+    let addr = args
+        .address("--addr")?
+        .ok_or_else(|| format!("{} needs --addr ADDR", action))?;
+    let list = args.value("--list");
+    let name = args.value("--name");
+    let game = args.value("--game");
+    let program = load_program(args.finish()?.as_deref());
+    let bytes = asm::assemble(&program, addr).unwrap_or_else(|e| fail(e));
+    check_target(addr, &bytes, 4, Console::GameCube);
+    let lines = write(&bytes, addr).unwrap_or_else(|e| fail(e));
+    save_code(&action, lines, list, name, game)
+}
+
+/// A code `cheat` wrote, put under `name` in the code list or game INI
+/// `list`, or printed.
+fn save_code(
+    action: &str,
+    lines: Vec<Line>,
+    list: Option<String>,
+    name: Option<String>,
+    game: Option<String>,
+) -> Result<(), String> {
+    match list {
+        Some(path) => {
+            let name = name.ok_or("--list needs --name NAME for the code")?;
+            let code = NamedCode {
+                name,
+                lines,
+                notes: Vec::new(),
+            };
+            match path.ends_with(".ini") {
+                true => {
+                    let codes = match action {
+                        "ar" => ini::Codes::ActionReplay,
+                        _ => ini::Codes::Gecko,
+                    };
+                    add_to_ini(&path, codes, code);
+                }
+                false => add_to_list(&path, code, game)?,
+            }
+        }
+        None => {
+            for line in lines {
+                println!("{}", line);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Fails on `bytes` at `addr` landing anywhere but RAM, and warns of RAM
+/// a code rarely means to write.
+fn check_target(addr: u32, bytes: &[u8], align: u32, console: Console) {
+    let problems = memory_map::check(addr, bytes.len() as u32, align, console);
+    for problem in problems.iter().filter(|p| !p.is_error()) {
+        eprintln!("warning: {}", problem);
+    }
+    if let Some(error) = problems.iter().find(|p| p.is_error()) {
+        fail(error);
+    }
+}
+
+/// Puts `code` in the code list at `path`, replacing a code of the same
+/// name or adding it, and starting a list for `game` if there isn't one.
+fn add_to_list(path: &str, code: NamedCode, game: Option<String>) -> Result<(), String> {
+    let mut list = match fs::read_to_string(path) {
+        Ok(text) => CodeList::parse(&text).unwrap_or_else(|e| fail(format!("{}: {}", path, e))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => CodeList {
+            game_id: game.ok_or_else(|| format!("{} is new, so needs --game ID", path))?,
+            ..CodeList::default()
+        },
+        Err(e) => fail(e),
+    };
+    match list.get_mut(&code.name) {
+        Some(existing) => {
+            println!("replaced {} in {}", code.name, path);
+            existing.lines = code.lines;
+        }
+        None => {
+            println!("added {} to {}", code.name, path);
+            list.codes.push(code);
+        }
+    }
+    fs::write(path, list.to_string()).unwrap_or_else(|e| fail(e));
+    Ok(())
+}
+
+/// Puts `code` in the Dolphin game INI at `path`, turned on, starting the
+/// file if there isn't one.
+fn add_to_ini(path: &str, codes: ini::Codes, code: NamedCode) {
+    let mut game = match fs::read_to_string(path) {
+        Ok(text) => GameIni::parse(&text),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => GameIni::default(),
+        Err(e) => fail(e),
+    };
+    println!("{} in {}, turned on", code.name, path);
+    game.set_code(codes, code)
+        .unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+    fs::write(path, game.to_string()).unwrap_or_else(|e| fail(e));
+}
+
+fn read_dol(path: &str) -> Image {
+    let bytes = fs::read(path).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+    Image::parse_dol(&bytes).unwrap_or_else(|e| fail(format!("{}: {}", path, e)))
+}
+
+/// The signatures an F6 code could search for to find `addr`.
+fn signatures(image: &Image, addr: u32, path: &str) -> Vec<signature::Candidate> {
+    if signature::function(image, addr).is_none() {
+        fail(format!("{:08x} is not loaded from {}", addr, path));
+    }
+    signature::candidates(image, addr)
+}
+
+fn no_signature(addr: u32, path: &str) -> String {
+    format!(
+        "no 16 bytes from the start of {:08x}'s function up to it are found just once in {}",
+        addr, path
+    )
+}
+
+fn read_gct(path: &str) -> Gct {
+    let bytes = fs::read(path).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+    Gct::parse(&bytes).unwrap_or_else(|e| fail(format!("{}: {}", path, e)))
+}
+
+fn write_gct(path: &str, gct: &Gct) {
+    fs::write(path, gct.to_bytes()).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+}
+
+fn gct(mut args: Args) -> Result<(), String> {
+    if args.positional.is_empty() {
+        return Err("gct needs list, merge, write or build".to_string());
+    }
+    let action = args.positional.remove(0);
+    match action.as_str() {
+        "list" => {
+            let path = args.finish()?.ok_or("list needs a GCT")?;
+            let game = Path::new(&path)
+                .file_stem()
+                .map_or(String::new(), |s| s.to_string_lossy().into_owned());
+            print!("{}", read_gct(&path).to_list(&game));
+        }
+        "merge" => {
+            let output = args.value("--output").ok_or("merge needs --output OUT")?;
+            let paths = std::mem::take(&mut args.positional);
+            args.finish()?;
+            if paths.is_empty() {
+                return Err("merge needs a GCT or more".to_string());
+            }
+            let gcts: Vec<_> = paths.iter().map(|path| read_gct(path)).collect();
+            for (path, gct) in paths.iter().zip(&gcts) {
+                println!("{} code(s) from {}", gct.codes.len(), path);
+            }
+            let (merged, dropped) = Gct::merge(&gcts);
+            println!(
+                "{} code(s) to {}, {} duplicate(s) dropped",
+                merged.codes.len(),
+                output,
+                dropped
+            );
+            write_gct(&output, &merged);
+        }
+        "write" => {
+            let output = args.value("--output").ok_or("write needs --output OUT")?;
+            let input = read_input(args.finish()?.as_deref()).unwrap_or_else(|e| fail(e));
+            let lines: Vec<_> = gecko::code_lines(&input)
+                .unwrap_or_else(|e| fail(e))
+                .into_iter()
+                .map(|(_, line)| line)
+                .collect();
+            let gct = Gct::from_lines(&lines).unwrap_or_else(|e| fail(e));
+            println!("{} code(s) to {}", gct.codes.len(), output);
+            write_gct(&output, &gct);
+        }
+        "build" => {
+            let output = args.value("--output").ok_or("build needs --output OUT")?;
+            let manifest = args.value("--manifest");
+            let console = match args.switch("--wii") {
+                true => Console::Wii,
+                false => Console::GameCube,
+            };
+            let path = args.finish()?.ok_or("build needs a code LIST")?;
+            let text =
+                fs::read_to_string(&path).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+            let (title, codes) = match path.ends_with(".ini") {
+                true => {
+                    let game = GameIni::parse(&text);
+                    let codes = game
+                        .codes(ini::Codes::Gecko)
+                        .unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+                    let enabled = game.enabled(ini::Codes::Gecko);
+                    let codes: Vec<_> = codes
+                        .into_iter()
+                        .filter(|c| enabled.contains(&c.name.as_str()))
+                        .collect();
+                    let stem = Path::new(&path).file_stem().unwrap_or_default();
+                    (stem.to_string_lossy().into_owned(), codes)
+                }
+                false => {
+                    let list =
+                        CodeList::parse(&text).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+                    let title = format!("{} {}", list.game_id, list.title);
+                    (title.trim_end().to_string(), list.codes)
+                }
+            };
+            let built = gct::build(&title, &codes, console);
+            write_gct(&output, &built.gct);
+            match manifest {
+                Some(path) => fs::write(&path, built.to_string())
+                    .unwrap_or_else(|e| fail(format!("{}: {}", path, e))),
+                None => print!("{}", built),
+            }
+            if built.skipped_any() {
+                process::exit(1);
+            }
+        }
+        other => {
+            return Err(format!(
+                "unknown gct action `{}`; try list, merge, write or build",
+                other
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// A patch's bytes as an instruction, if they're one word and decode.
+fn patch_insn(bytes: &[u8]) -> String {
+    match bytes {
+        &[a, b, c, d] => match Opcode::decode(u32::from_be_bytes([a, b, c, d])) {
+            Ok(op) => op.to_string(),
+            Err(e) => best_guess(&e),
+        },
+        _ => String::new(),
+    }
+}
+
+fn patch_dol(mut args: Args) -> Result<(), String> {
+    let path = args.value("--dol").ok_or("patch needs --dol DOL")?;
+    let addr = args.address("--addr")?.ok_or("patch needs --addr ADDR")?;
+    let output = args.value("--output");
+    let program = load_program(args.finish()?.as_deref());
+    let bytes = asm::assemble(&program, addr).unwrap_or_else(|e| fail(e));
+
+    let mut file = fs::read(&path).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+    let dol = Dol::parse(&file).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+    let changes = dol
+        .patch(&mut file, addr, &bytes)
+        .unwrap_or_else(|at| fail(format!("{:08x} is not loaded from {}", at, path)));
+    if changes.is_empty() {
+        println!("{} already has those bytes at {:08x}", path, addr);
+        return Ok(());
+    }
+    for change in &changes {
+        let hex = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        };
+        println!(
+            "{:08x}  file {:#x}  {} -> {}  {} -> {}",
+            change.addr,
+            change.offset,
+            hex(&change.old),
+            hex(&change.new),
+            patch_insn(&change.old),
+            patch_insn(&change.new)
+        );
+    }
+
+    let target = match output {
+        Some(output) => output,
+        None => {
+            let backup = format!("{}.bak", path);
+            match Path::new(&backup).exists() {
+                true => println!("{} is kept as the original", backup),
+                false => {
+                    fs::copy(&path, &backup).unwrap_or_else(|e| fail(format!("{}: {}", backup, e)));
+                    println!("the original is in {}", backup);
+                }
+            }
+            path
+        }
+    };
+    fs::write(&target, &file).unwrap_or_else(|e| fail(format!("{}: {}", target, e)));
+    println!("{} word(s) changed in {}", changes.len(), target);
+    Ok(())
+}
+
+/// One build of a game, as a symbol map or an image.
+enum Build {
+    Map(SymbolTable),
+    Image(Image),
+}
+
+fn load_build(path: &str) -> Build {
+    if path.ends_with(".dol") || path.ends_with(".elf") {
+        let bytes = fs::read(path).unwrap_or_else(|e| fail(e));
+        return Build::Image(
+            Image::parse(&bytes).unwrap_or_else(|e| fail(format!("{}: {}", path, e))),
+        );
+    }
+    let text = fs::read_to_string(path).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+    let symbols = SymbolTable::parse(&text);
+    if symbols.is_empty() {
+        fail(format!("{}: no symbols in a form ppcheat reads", path));
+    }
+    Build::Map(symbols)
+}
+
+/// `addr` and where it went, or why it didn't.
+fn port_report(addr: u32, result: &Result<Ported, PortError>) -> String {
+    match result {
+        Ok(ported) => {
+            let doubt = ported.doubt.as_ref();
+            let doubt = doubt.map_or(String::new(), |d| format!("  (check: {})", d));
+            format!(
+                "{:08x} -> {:08x}  {}{}",
+                addr, ported.addr, ported.by, doubt
+            )
+        }
+        Err(e) => format!("{:08x} not ported: {}", addr, e),
+    }
+}
+
+/// The instruction at the hook, given as INSN (or its word in hex),
+/// assembled at the hook so a branch goes where it did there, or read from
+/// a DOL.
+fn displaced(original: Option<String>, dol: Option<String>, addr: u32) -> Result<u32, String> {
+    match (original, dol) {
+        (Some(insn), None) => {
+            if insn.trim().trim_start_matches("0x").len() == 8 {
+                if let Some(word) = parse_hex_u32(&insn) {
+                    return Ok(word);
+                }
+            }
+            if let Some(word) = hook::undecoded_word(&insn) {
+                return Ok(word);
+            }
+            let program = parser::parse_program(&insn).map_err(|e| format!("--original: {}", e))?;
+            let bytes = asm::assemble(&program, addr).map_err(|e| format!("--original: {}", e))?;
+            match bytes[..] {
+                [a, b, c, d] => Ok(u32::from_be_bytes([a, b, c, d])),
+                _ => Err(format!("--original isn't one instruction: `{}`", insn)),
+            }
+        }
+        (None, Some(path)) => {
+            let bytes = fs::read(&path).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+            let image =
+                Image::parse_dol(&bytes).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+            match image.read(addr, 4) {
+                Some(w) => Ok(u32::from_be_bytes([w[0], w[1], w[2], w[3]])),
+                None => fail(format!("{:08x} is not loaded from {}", addr, path)),
+            }
+        }
+        _ => Err("hook needs one of --original INSN and --dol DOL".to_string()),
+    }
+}
+
+fn hook(mut args: Args) -> Result<(), String> {
+    let addr = args.address("--addr")?.ok_or("hook needs --addr ADDR")?;
+    let at = args.address("--at")?;
+    let original = displaced(args.value("--original"), args.value("--dol"), addr)?;
+    let format = args.value("--format").unwrap_or_else(|| "c2".to_string());
+    if !["c2", "asm", "hex"].contains(&format.as_str()) {
+        return Err(format!("unknown --format `{}`; try c2, asm or hex", format));
+    }
+    let program = load_program(args.finish()?.as_deref());
+    let start = at.unwrap_or(addr);
+    let payload = asm::assemble(&program, start.wrapping_add(hook::PAYLOAD_OFFSET))
+        .unwrap_or_else(|e| fail(e));
+    let trampoline =
+        hook::Trampoline::new(&payload, addr, original, at).unwrap_or_else(|e| fail(e));
+    check_target(start, &trampoline.bytes(), 4, Console::GameCube);
+
+    match format.as_str() {
+        "c2" if at.is_none() => {
+            for line in trampoline.c2().unwrap_or_else(|e| fail(e)) {
+                println!("{}", line);
+            }
+        }
+        "c2" => {
+            // the cave written first, so the hook never branches to half of it
+            let mut lines = gecko::word_writes(&trampoline.bytes(), start);
+            if let Some(branch) = trampoline.hook_branch() {
+                let branch = branch.unwrap_or_else(|e| fail(e));
+                let hook = gecko::word_writes(&branch.to_be_bytes(), addr);
+                lines = lines.and_then(|lines| Ok([lines, hook?].concat()));
+            }
+            for line in lines.unwrap_or_else(|e| fail(e)) {
+                println!("{}", line);
+            }
+            return Ok(());
+        }
+        "asm" => {
+            println!("# at {:08x}, for the hook at {:08x}", start, addr);
+            print!("{}", trampoline.asm());
         }
+        _ => {
+            let out = Format::Hex.write(&trampoline.bytes(), "hook", start);
+            io::stdout().write_all(&out).unwrap_or_else(|e| fail(e));
+        }
+    }
+    if let Some(branch) = trampoline.hook_branch() {
+        let branch = branch.unwrap_or_else(|e| fail(e));
+        eprintln!("write {:08X} at {:08x} to branch to the cave", branch, addr);
     }
+    Ok(())
+}
+
+fn port_addresses(mut args: Args) -> Result<(), String> {
+    let from = args.value("--from").ok_or("port needs --from MAP|DOL")?;
+    let to = args.value("--to").ok_or("port needs --to MAP|DOL")?;
+    let codes = args.switch("--codes");
+    let addresses = match codes {
+        true => Vec::new(),
+        false => std::mem::take(&mut args.positional),
+    };
+    let input = args.finish()?;
+    let port: Box<dyn Fn(u32) -> Result<Ported, PortError>> =
+        match (load_build(&from), load_build(&to)) {
+            (Build::Map(from), Build::Map(to)) => {
+                Box::new(move |addr| port::by_symbols(addr, &from, &to))
+            }
+            (Build::Image(from), Build::Image(to)) => {
+                Box::new(move |addr| port::by_images(addr, &from, &to))
+            }
+            _ => {
+                return Err("--from and --to have to be both symbol maps, or both DOLs".to_string())
+            }
+        };
+
+    let mut ok = true;
+    if codes {
+        let input = read_input(input.as_deref()).unwrap_or_else(|e| fail(e));
+        let codes = gecko::parse_types(&input).unwrap_or_else(|e| fail(e));
+        let ported = port::port_codes(&codes, port);
+        for line in &ported.lines {
+            println!("{}", line);
+        }
+        for (addr, result) in &ported.addresses {
+            eprintln!("{}", port_report(*addr, result));
+            ok &= result.is_ok();
+        }
+        for line in &ported.skipped {
+            eprintln!(
+                "{} not ported: its address is off a ba only known when it runs",
+                line
+            );
+            ok = false;
+        }
+    } else {
+        if addresses.is_empty() {
+            return Err("port needs an ADDR, or --codes".to_string());
+        }
+        for addr in &addresses {
+            let addr = address(addr, "port")?;
+            let result = port(addr);
+            println!("{}", port_report(addr, &result));
+            ok &= result.is_ok();
+        }
+    }
+    if !ok {
+        process::exit(1);
+    }
+    Ok(())
 }
 
 fn main() {
-    let asm = "rlwinm r0,r7,0x10,0x0,0xf";
-    let (_, op) = parse_opcode(asm).unwrap();
-    println!("{}", asm);
-    println!("{}", op.highlevel());
+    let mut args = Args::parse(env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+    let _ = SOURCE_OPTIONS.set(std::mem::take(&mut args.source));
+    args.bit_order.set();
+    args.locale.set();
+
+    let command = match args.positional.first() {
+        Some(first) if COMMANDS.contains(&first.as_str()) => args.positional.remove(0),
+        _ => String::new(),
+    };
+
+    let result = match command.as_str() {
+        "export-md" => export_md(args),
+        "vectors" => gen_vectors(args),
+        "replay" => replay(args),
+        "annotate-log" => annotate_log(args),
+        "profile" => show_profile(args),
+        "browse" => browse(args),
+        "diff" => diff(args),
+        "compare" => compare(args),
+        "doc" => doc(args),
+        "decode" => decode(args),
+        "disasm" => disasm(args),
+        "assemble" => assemble(args),
+        "clobber" => clobber(args),
+        "budget" => check_budget(args),
+        "caves" => caves(args),
+        "relocate" => relocate(args),
+        "verify" => verify(args),
+        "ram" => ram(args),
+        "patches" => patches(args),
+        "cheat" => cheat(args),
+        "gct" => gct(args),
+        "patch" => patch_dol(args),
+        "hook" => hook(args),
+        "port" => port_addresses(args),
+        _ => explain(args),
+    };
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(2);
+    }
 }
 
 #[cfg(test)]
@@ -249,19 +2155,39 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_rlwinm() {
-        let asm = "rlwinm r0,r7,0x10,0x0,0xf";
-        let (_, op) = parse_opcode(asm).expect("parse failed");
+    fn test_displaced() {
+        let original = |insn: &str| displaced(Some(insn.to_string()), None, 0x8000_3100);
+        assert_eq!(original("mflr r0"), Ok(0x7C08_02A6));
+        assert_eq!(original("mflr  r0"), Ok(0x7C08_02A6));
+        assert_eq!(original("7c0802a6"), Ok(0x7C08_02A6));
+        assert_eq!(original(".long 0x7c0802a6"), Ok(0x7C08_02A6));
+        assert_eq!(original("stwu r1,-16(r1)"), Ok(0x9421_FFF0));
+        assert_eq!(original("b .+8"), Ok(0x4800_0008));
+        assert_eq!(original("bl 0x80001000"), Ok(0x4BFF_DF01));
+        assert!(original("li r3,1\nli r4,2").is_err());
+        assert!(original("bogus r0")
+            .unwrap_err()
+            .starts_with("--original: "));
+        assert!(displaced(None, None, 0x8000_3100).is_err());
+    }
 
+    #[test]
+    fn test_usage() {
+        for line in USAGE.lines() {
+            assert!(line.len() <= 72, "usage line too long: {}", line);
+        }
+    }
+
+    #[test]
+    fn test_address() {
+        assert_eq!(address("80003100", "--addr"), Ok(0x8000_3100));
+        assert_eq!(address(" 0x80003100", "--targets"), Ok(0x8000_3100));
+        assert_eq!(address("0X8000310c", "port"), Ok(0x8000_310C));
         assert_eq!(
-            op,
-            Opcode::Rlwinm {
-                ra: Register(0),
-                rs: Register(7),
-                sh: 16,
-                mb: 0,
-                me: 15
-            }
+            address("0x+8000", "--base"),
+            Err("invalid address `0x+8000` for --base".to_string())
         );
+        assert!(address("0x", "--base").is_err());
+        assert!(address("100000000", "--base").is_err());
     }
 }
@@ -0,0 +1,330 @@
+//! The GameCube's and Wii's address spaces, as a game sees them through
+//! the BATs the OS sets up, and checks that the addresses codes and patches
+//! write to are RAM a game writes to:
+//!
+//! ```text
+//! 80000000..81800000  MEM1, cached      (00000000 physical)
+//! 90000000..94000000  MEM2, cached      (10000000 physical, Wii only)
+//! C0000000..C1800000  MEM1, uncached
+//! D0000000..D4000000  MEM2, uncached    (Wii only)
+//! CC000000..          hardware registers (and CD000000 on Wii)
+//! E0000000..E0004000  the locked half of the L1 data cache
+//! ```
+//!
+//! The bottom of MEM1, up to 80003100 where a DOL's text usually starts,
+//! holds the OS's globals and exception vectors, and the codehandler from
+//! 80001800.
+
+use core::fmt;
+
+use crate::formats::ram::Bank;
+use crate::gecko::{base_after, Base, CodeType, Line, Operand, BASE_ADDRESS};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Console {
+    GameCube,
+    Wii,
+}
+
+impl Console {
+    fn banks(self) -> &'static [Bank] {
+        match self {
+            Self::GameCube => &[Bank::Mem1],
+            Self::Wii => &[Bank::Mem1, Bank::Mem2],
+        }
+    }
+}
+
+/// How an address reaches RAM.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Mapping {
+    Cached,
+    Uncached,
+    /// Not through the BATs at all, which a game never does with its MMU on
+    Physical,
+}
+
+/// What an address is.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Region {
+    Ram {
+        bank: Bank,
+        mapping: Mapping,
+    },
+    /// Memory-mapped hardware registers
+    Registers,
+    LockedCache,
+    Unmapped,
+}
+
+/// What `addr` is on `console`.
+pub fn region(addr: u32, console: Console) -> Region {
+    let registers = match console {
+        Console::GameCube => 0xCC00_0000..0xCC01_0000,
+        Console::Wii => 0xCC00_0000..0xCD80_0000,
+    };
+    if registers.contains(&addr) {
+        return Region::Registers;
+    }
+    if (0xE000_0000..0xE000_4000).contains(&addr) {
+        return Region::LockedCache;
+    }
+    let mapping = match addr >> 29 {
+        0 => Mapping::Physical,
+        4 => Mapping::Cached,
+        6 => Mapping::Uncached,
+        _ => return Region::Unmapped,
+    };
+    let physical = addr & 0x1FFF_FFFF;
+    console
+        .banks()
+        .iter()
+        .find(|bank| {
+            let start = bank.base() & 0x1FFF_FFFF;
+            (start..start + bank.size()).contains(&physical)
+        })
+        .map_or(Region::Unmapped, |&bank| Region::Ram { bank, mapping })
+}
+
+/// Something wrong, or worth a second look, about where a code writes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// Not RAM, with the address it might have meant
+    Unmapped { addr: u32, meant: Option<u32> },
+    /// A physical address, which the game sees at the cached one
+    Physical(u32),
+    /// Hardware registers rather than RAM
+    Registers(u32),
+    /// RAM through the uncached mirror, where a game keeps little but
+    /// buffers for hardware
+    Uncached(u32),
+    /// In the low memory below 80003100
+    LowMemory(u32),
+    /// A write of `align` bytes at an address that isn't a multiple of them
+    Misaligned { addr: u32, align: u32 },
+}
+
+impl Problem {
+    /// Whether the code can't be right, rather than just unusual.
+    pub fn is_error(&self) -> bool {
+        !matches!(self, Self::Uncached(_) | Self::LowMemory(_))
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Unmapped { addr, meant } => {
+                write!(f, "{:08x} isn't RAM", addr)?;
+                match meant {
+                    Some(meant) => write!(f, "; did you mean {:08x}?", meant),
+                    None => Ok(()),
+                }
+            }
+            Self::Physical(addr) => write!(
+                f,
+                "{:08x} is a physical address; the game sees it at {:08x}",
+                addr,
+                addr | 0x8000_0000
+            ),
+            Self::Registers(addr) => write!(f, "{:08x} is a hardware register, not RAM", addr),
+            Self::Uncached(addr) => write!(
+                f,
+                "{:08x} is the uncached mirror of {:08x}",
+                addr,
+                addr & !0x4000_0000
+            ),
+            Self::LowMemory(addr) if addr >= 0x8000_1800 => write!(
+                f,
+                "{:08x} is where the codehandler runs, below the game's code",
+                addr
+            ),
+            Self::LowMemory(addr) => write!(
+                f,
+                "{:08x} is in low memory, the OS's globals and exception vectors",
+                addr
+            ),
+            Self::Misaligned { addr, align } => write!(
+                f,
+                "{:08x} isn't a multiple of {}, the size of what's written there",
+                addr, align
+            ),
+        }
+    }
+}
+
+/// The address RAM `addr` likely meant, mistyped in its top digit (90 for
+/// 80 on a GameCube, 88 for 80) or missing it.
+fn meant(addr: u32, console: Console) -> Option<u32> {
+    [(addr & 0x0FFF_FFFF) | 0x8000_0000, addr | 0x8000_0000]
+        .iter()
+        .copied()
+        .find(|&meant| {
+            meant != addr
+                && matches!(
+                    region(meant, console),
+                    Region::Ram {
+                        mapping: Mapping::Cached,
+                        ..
+                    }
+                )
+        })
+}
+
+/// What's wrong with writing `size` bytes at `addr` on `console`, `align`
+/// bytes at a time.
+pub fn check(addr: u32, size: u32, align: u32, console: Console) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    if align > 1 && !addr.is_multiple_of(align) {
+        problems.push(Problem::Misaligned { addr, align });
+    }
+    let last = addr.wrapping_add(size.max(1) - 1);
+    for at in [addr, last] {
+        let problem = match region(at, console) {
+            Region::Ram {
+                mapping: Mapping::Physical,
+                ..
+            } => Some(Problem::Physical(at)),
+            Region::Ram {
+                mapping: Mapping::Uncached,
+                ..
+            } => Some(Problem::Uncached(at)),
+            Region::Ram { .. } if at < 0x8000_3100 => Some(Problem::LowMemory(at)),
+            Region::Ram { .. } | Region::LockedCache => None,
+            Region::Registers => Some(Problem::Registers(at)),
+            Region::Unmapped => Some(Problem::Unmapped {
+                addr: at,
+                meant: meant(at, console),
+            }),
+        };
+        // a range whose ends are both in the same wrong place says so once
+        if let Some(problem) = problem {
+            let kind = std::mem::discriminant(&problem);
+            if !problems.iter().any(|p| std::mem::discriminant(p) == kind) {
+                problems.push(problem);
+            }
+        }
+    }
+    problems
+}
+
+/// The problems with where each of `codes` writes, tests, hooks and points
+/// `ba` and `po` at, with the first line of the code concerned. Addresses
+/// off `po`, or off a `ba` only known when the codes run, aren't checked.
+pub fn check_codes(codes: &[(Vec<Line>, CodeType)], console: Console) -> Vec<(Line, Problem)> {
+    let mut problems = Vec::new();
+    let mut ba = Some(BASE_ADDRESS);
+    for (lines, code) in codes {
+        let mut checks = Vec::new();
+        if let (Some(addr), Some(ba)) = (code.address(), ba) {
+            if addr.base == Base::BaseAddress {
+                let at = ba.wrapping_add(addr.offset);
+                checks.push(match code {
+                    CodeType::Write { width, count, .. } => {
+                        (at, width.bytes() * count, width.bytes())
+                    }
+                    CodeType::StringWrite { bytes, .. } => (at, bytes.len() as u32, 1),
+                    CodeType::SerialWrite { width, .. } | CodeType::If { width, .. } => {
+                        (at, width.bytes(), width.bytes())
+                    }
+                    _ => (at, 4, 4),
+                });
+            }
+        }
+        match *code {
+            CodeType::Branch { target, .. } => checks.push((target, 4, 4)),
+            CodeType::SetAddress {
+                add: false,
+                value:
+                    Operand {
+                        value,
+                        plus: None,
+                        register: None,
+                    },
+                ..
+            }
+            | CodeType::LoadAddress {
+                add: false,
+                address:
+                    Operand {
+                        value,
+                        plus: None,
+                        register: None,
+                    },
+                ..
+            } if value != BASE_ADDRESS => checks.push((value, 1, 1)),
+            _ => {}
+        }
+        for (addr, size, align) in checks {
+            let found = check(addr, size, align, console);
+            problems.extend(found.into_iter().map(|p| (lines[0], p)));
+        }
+        ba = base_after(ba, code, lines[0]);
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gecko::parse_types;
+
+    #[test]
+    fn test_check() {
+        assert_eq!(
+            region(0x9000_1234, Console::Wii),
+            Region::Ram {
+                bank: Bank::Mem2,
+                mapping: Mapping::Cached
+            }
+        );
+        assert_eq!(region(0x9000_1234, Console::GameCube), Region::Unmapped);
+        assert_eq!(
+            check(0x9000_3100, 4, 4, Console::GameCube),
+            [Problem::Unmapped {
+                addr: 0x9000_3100,
+                meant: Some(0x8000_3100)
+            }]
+        );
+        assert_eq!(
+            check(0x817F_FFFE, 4, 2, Console::GameCube),
+            [Problem::Unmapped {
+                addr: 0x8180_0001,
+                meant: None
+            }]
+        );
+        assert_eq!(
+            check(0x0000_3102, 4, 4, Console::Wii),
+            [
+                Problem::Misaligned {
+                    addr: 0x0000_3102,
+                    align: 4
+                },
+                Problem::Physical(0x0000_3102)
+            ]
+        );
+        assert!(!check(0xC000_3100, 4, 4, Console::Wii)[0].is_error());
+
+        let codes = parse_types(
+            "04003100 38600001\n\
+             02001801 0000FFFF\n\
+             4A000000 90001000\n\
+             42000000 81000000\n\
+             04900000 00000000\n",
+        )
+        .unwrap();
+        let problems: Vec<_> = check_codes(&codes, Console::GameCube)
+            .iter()
+            .map(|(line, p)| format!("{}: {}", line, p))
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                "02001801 0000FFFF: 80001801 isn't a multiple of 2, the size of what's written there",
+                "02001801 0000FFFF: 80001801 is where the codehandler runs, below the game's code",
+                "4A000000 90001000: 90001000 isn't RAM; did you mean 80001000?",
+                "04900000 00000000: 81900000 isn't RAM",
+            ]
+        );
+    }
+}
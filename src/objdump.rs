@@ -0,0 +1,288 @@
+//! Listings laid out the way `powerpc-eabi-objdump -d` lays them out, close
+//! enough to diff one against the other: a tab after the address and after
+//! the bytes, the mnemonic padded to eight columns, objdump's choice of
+//! extended mnemonics, and branch targets as absolute addresses with the
+//! nearest symbol, `bl 800b3c20 <OSReport>`.
+
+use crate::disasm::{disassemble, Endian, Listing};
+use crate::image::Section;
+use crate::symbols::SymbolTable;
+use crate::{CrBit, CrField, CrLogic, Opcode, Register};
+
+/// `section` disassembled under objdump's `Disassembly of section NAME:`,
+/// with a `80003100 <main>:` line wherever one of the code `symbols` starts.
+/// Branches are annotated with the nearest symbol at or before their
+/// target, or the section itself if there is none.
+pub fn section(name: &str, section: &Section, endian: Endian, symbols: &SymbolTable) -> Listing {
+    let mut listing = Listing {
+        text: format!("\nDisassembly of section {}:\n", name),
+        ..Listing::default()
+    };
+
+    // the section first, so a symbol at the same address wins
+    let mut nearest = vec![(section.addr, name)];
+    nearest.extend(symbols.iter().map(|s| (s.addr, s.name.as_str())));
+    nearest.sort_by_key(|&(addr, _)| addr);
+    let labels = symbols.labels();
+
+    let end = section.addr.wrapping_add(section.data.len() as u32);
+    let width = format!("{:x}", end.saturating_sub(1)).len().max(4);
+    let out = &mut listing.text;
+    for (word, (addr, op)) in
+        section
+            .data
+            .chunks_exact(4)
+            .zip(disassemble(&section.data, section.addr, endian))
+    {
+        if let Some(&(_, symbol)) = labels.iter().find(|&&(at, _)| at == addr) {
+            *out += &format!("\n{:08x} <{}>:\n", addr, symbol);
+        }
+        listing.words += 1;
+        let text = match op {
+            Ok(op) => instruction(&op, addr, &nearest),
+            Err(_) => {
+                listing.unknown += 1;
+                let value = endian.word([word[0], word[1], word[2], word[3]]);
+                format!(".long 0x{:x}", value)
+            }
+        };
+        *out += &format!(
+            "{:>width$x}:\t{:02x} {:02x} {:02x} {:02x} \t{}\n",
+            addr,
+            word[0],
+            word[1],
+            word[2],
+            word[3],
+            text,
+            width = width
+        );
+    }
+    let tail = section.data.chunks_exact(4).remainder();
+    if !tail.is_empty() {
+        let addr = end.wrapping_sub(tail.len() as u32);
+        let bytes: Vec<_> = tail.iter().map(|b| format!("{:02x}", b)).collect();
+        let values: Vec<_> = tail.iter().map(|b| format!("{:#x}", b)).collect();
+        *out += &format!(
+            "{:>width$x}:\t{:<11} \t.byte {}\n",
+            addr,
+            bytes.join(" "),
+            values.join(","),
+            width = width
+        );
+    }
+    listing
+}
+
+/// One instruction as objdump prints it, `addi    r1,r1,-16`: the
+/// mnemonic padded to eight columns, then the operands. `symbols`, sorted
+/// by address, name branch targets.
+pub fn instruction(op: &Opcode, addr: u32, symbols: &[(u32, &str)]) -> String {
+    let op = op.canonicalize();
+    let r = |r: Register| format!("r{}", r.0);
+    // the base of a D-form access, where r0 reads as 0
+    let base = |r: Register| match r.0 {
+        0 => "0".to_string(),
+        n => format!("r{}", n),
+    };
+    // a CR field operand objdump leaves out when it's cr0
+    let field = |crf: CrField| match crf.0 {
+        0 => String::new(),
+        _ => format!("{},", crf),
+    };
+    let target = |disp: i32| {
+        let to = addr.wrapping_add(disp as u32);
+        format!("{:x}{}", to, annotation(to, symbols))
+    };
+
+    let (mnemonic, operands) = match op {
+        Opcode::Addi {
+            rd,
+            ra: Register(0),
+            simm,
+        } => ("li", format!("{},{}", r(rd), simm)),
+        Opcode::Addis {
+            rd,
+            ra: Register(0),
+            simm,
+        } => ("lis", format!("{},{}", r(rd), simm)),
+        Opcode::Addi { rd, ra, simm } | Opcode::Addis { rd, ra, simm } => {
+            (op.mnemonic(), format!("{},{},{}", r(rd), r(ra), simm))
+        }
+        Opcode::Ori {
+            ra: Register(0),
+            rs: Register(0),
+            uimm: 0,
+        } => ("nop", String::new()),
+        Opcode::Ori { ra, rs, uimm } | Opcode::Oris { ra, rs, uimm } => {
+            (op.mnemonic(), format!("{},{},{}", r(ra), r(rs), uimm))
+        }
+        Opcode::Rlwinm {
+            ra,
+            rs,
+            sh,
+            mb: 0,
+            me: 31,
+            rc,
+        } => (
+            match rc {
+                true => "rotlwi.",
+                false => "rotlwi",
+            },
+            format!("{},{},{}", r(ra), r(rs), sh),
+        ),
+        Opcode::Rlwinm {
+            ra,
+            rs,
+            sh: 0,
+            mb,
+            me: 31,
+            rc,
+        } => (
+            match rc {
+                true => "clrlwi.",
+                false => "clrlwi",
+            },
+            format!("{},{},{}", r(ra), r(rs), mb),
+        ),
+        Opcode::Rlwinm {
+            ra, rs, sh, mb, me, ..
+        }
+        | Opcode::Rlwimi {
+            ra, rs, sh, mb, me, ..
+        } => (
+            op.mnemonic(),
+            format!("{},{},{},{},{}", r(ra), r(rs), sh, mb, me),
+        ),
+        Opcode::Rlwnm {
+            ra,
+            rs,
+            rb,
+            mb: 0,
+            me: 31,
+            rc,
+        } => (
+            match rc {
+                true => "rotlw.",
+                false => "rotlw",
+            },
+            format!("{},{},{}", r(ra), r(rs), r(rb)),
+        ),
+        Opcode::Rlwnm {
+            ra, rs, rb, mb, me, ..
+        } => (
+            op.mnemonic(),
+            format!("{},{},{},{},{}", r(ra), r(rs), r(rb), mb, me),
+        ),
+        Opcode::Load { rd, d, ra, .. } | Opcode::Lha { rd, d, ra, .. } => {
+            (op.mnemonic(), format!("{},{}({})", r(rd), d, base(ra)))
+        }
+        Opcode::Store { rs, d, ra, .. } => {
+            (op.mnemonic(), format!("{},{}({})", r(rs), d, base(ra)))
+        }
+        Opcode::Cmp { crf, ra, rb, .. } => {
+            (op.mnemonic(), format!("{}{},{}", field(crf), r(ra), r(rb)))
+        }
+        Opcode::Cmpi {
+            logical,
+            crf,
+            ra,
+            imm,
+        } => {
+            let imm = match logical {
+                true => (imm as u16).to_string(),
+                false => imm.to_string(),
+            };
+            (op.mnemonic(), format!("{}{},{}", field(crf), r(ra), imm))
+        }
+        Opcode::CrOp {
+            op: logic,
+            bt,
+            ba,
+            bb,
+        } => cr_op(logic, bt, ba, bb)
+            .unwrap_or_else(|| (op.mnemonic(), format!("{},{},{}", bt, ba, bb))),
+        Opcode::B { disp, .. } => (op.mnemonic(), target(disp)),
+        Opcode::Bc { bo, bi, disp } => {
+            let disp = disp.into();
+            let operands = match op.mnemonic() {
+                "bc" => format!("{},{},{}", bo, bi, target(disp)),
+                "bdnz" | "bdz" => target(disp),
+                _ => format!("{}{}", field(bi.field()), target(disp)),
+            };
+            (op.mnemonic(), operands)
+        }
+        _ => unreachable!("canonicalize leaves no extended mnemonics"),
+    };
+    format!("{:<7} {}", mnemonic, operands)
+        .trim_end()
+        .to_string()
+}
+
+/// objdump's names for a CR logical op whose operands repeat: `crset`,
+/// `crclr`, `crmove` and `crnot`.
+fn cr_op(logic: CrLogic, bt: CrBit, ba: CrBit, bb: CrBit) -> Option<(&'static str, String)> {
+    match logic {
+        CrLogic::Eqv if bt == ba && ba == bb => Some(("crset", bt.to_string())),
+        CrLogic::Xor if bt == ba && ba == bb => Some(("crclr", bt.to_string())),
+        CrLogic::Or if ba == bb => Some(("crmove", format!("{},{}", bt, ba))),
+        CrLogic::Nor if ba == bb => Some(("crnot", format!("{},{}", bt, ba))),
+        _ => None,
+    }
+}
+
+/// ` <OSReport>` or ` <main+0x1c>`, naming `addr` by the last of the sorted
+/// `symbols` at or before it.
+fn annotation(addr: u32, symbols: &[(u32, &str)]) -> String {
+    match symbols.iter().rev().find(|&&(at, _)| at <= addr) {
+        Some(&(at, name)) if at == addr => format!(" <{}>", name),
+        Some(&(at, name)) => format!(" <{}+{:#x}>", name, addr - at),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols::Symbol;
+
+    #[test]
+    fn test_objdump_section() {
+        // li r3,1; lwz r0,20(r1); mflr r0; cmpwi cr7,r3,0; bne cr7,.+8;
+        // bl .-16; crclr 4*cr1+eq
+        let words: [u32; 7] = [
+            0x3860_0001,
+            0x8001_0014,
+            0x7c08_02a6,
+            0x2f83_0000,
+            0x409e_0008,
+            0x4bff_fff1,
+            0x4cc6_3182,
+        ];
+        let section = Section {
+            addr: 0x8000_3100,
+            data: words.iter().flat_map(|w| w.to_be_bytes()).collect(),
+            executable: true,
+        };
+        let mut symbols = SymbolTable::default();
+        symbols.insert(Symbol {
+            addr: 0x8000_3100,
+            size: None,
+            name: "main".to_string(),
+            data: false,
+        });
+        let listing = super::section(".text", &section, Endian::Big, &symbols);
+        assert_eq!(
+            listing.text,
+            "\nDisassembly of section .text:\n\
+             \n80003100 <main>:\n\
+             80003100:\t38 60 00 01 \tli      r3,1\n\
+             80003104:\t80 01 00 14 \tlwz     r0,20(r1)\n\
+             80003108:\t7c 08 02 a6 \t.long 0x7c0802a6\n\
+             8000310c:\t2f 83 00 00 \tcmpwi   cr7,r3,0\n\
+             80003110:\t40 9e 00 08 \tbne     cr7,80003118 <main+0x18>\n\
+             80003114:\t4b ff ff f1 \tbl      80003104 <main+0x4>\n\
+             80003118:\t4c c6 31 82 \tcrclr   4*cr1+eq\n"
+        );
+        assert_eq!(listing.unknown, 1);
+    }
+}
@@ -1,276 +1,2373 @@
+use core::fmt;
+use core::ops::Range;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+use std::{fs, iter};
+
 use nom::{
     branch::alt,
-    bytes::complete::tag,
-    character::complete::{digit1, hex_digit1, multispace0},
-    combinator::{map, map_res},
-    sequence::{preceded, tuple},
+    bytes::complete::{tag, take_while1, take_while_m_n},
+    character::complete::{digit1, multispace0, multispace1, space1},
+    combinator::{all_consuming, map, map_opt, map_res, opt, peek, verify},
+    error::{ErrorKind, ParseError as _},
+    multi::separated_list1,
+    number::complete::recognize_float,
+    sequence::{delimited, preceded, terminated, tuple},
     IResult,
 };
 
+pub use crate::expr::Symbols;
+
+use crate::eval::mask_bounds;
+use crate::expr::{parse_condition, parse_expr, parse_identifier, Failure, PResult};
+use crate::macros::Macro;
+use crate::BitOrder;
 use crate::Opcode;
 use crate::Register;
+use crate::Width;
+use crate::{CrBit, CrField, CrLogic, Reloc, Relocation};
 
-fn parse_register(inp: &str) -> IResult<&str, Register> {
-    preceded(
-        tag("r"),
-        map_res(digit1, |x: &str| x.parse::<u8>().map(Register)),
-    )(inp)
-}
+use Kind::{
+    Bo, Count, CrBit as Bit, CrField as Crf, Disp, Imm, Mask, Pos, Reg, ShortTarget, Simm, Target,
+};
 
-fn parse_immediate(inp: &str) -> IResult<&str, u8> {
+/// GPR operand: `rN`, `%rN`, `gprN`, a bare `N` as printed by objdump
+/// without `-Mregnames`, or one of the ABI aliases `sp`, `rtoc` and `fp`.
+/// Operand position decides whether a bare number is a register.
+pub(crate) fn parse_register(inp: &str) -> PResult<'_, Register> {
     alt((
-        map_res(preceded(tag("0x"), hex_digit1), |x: &str| {
-            u8::from_str_radix(x, 16)
-        }),
-        map_res(digit1, |x: &str| x.parse::<u8>()),
+        map(tag("sp"), |_| Register::SP),
+        map(tag("rtoc"), |_| Register::RTOC),
+        map(tag("fp"), |_| Register::FP),
+        preceded(
+            opt(alt((tag("r"), tag("%r"), tag("gpr")))),
+            verify(
+                map_res(digit1, |x: &str| x.parse::<u8>().map(Register)),
+                |r: &Register| r.0 < 32,
+            ),
+        ),
     ))(inp)
 }
 
-fn whitespace(inp: &str) -> IResult<&str, ()> {
-    map(multispace0, |_| ())(inp)
+/// A small number operand of `kind`, within its [`Kind::bounds`].
+fn parse_number<'a>(inp: &'a str, syms: &Symbols, kind: Kind) -> PResult<'a, u8> {
+    let (bounds, _) = kind.bounds().expect("a kind of number");
+    map(
+        verify(|i| parse_expr(i, syms), move |v| bounds.contains(v)),
+        |v| v as u8,
+    )(inp)
 }
 
-fn comma_sep(inp: &str) -> IResult<&str, ()> {
-    map(tuple((multispace0, tag(","), multispace0)), |_| ())(inp)
+/// Bit position operand, numbered as [`BitOrder::current`] says; the
+/// result is always an IBM bit number.
+fn parse_position<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, u8> {
+    let order = BitOrder::current();
+    map(
+        |i| parse_number(i, syms, Kind::Pos),
+        move |bit| order.number(bit),
+    )(inp)
 }
 
-fn parse_rlwinm(inp: &str) -> IResult<&str, Opcode> {
-    preceded(
-        tag("rlwinm"),
-        map(
-            tuple((
-                preceded(whitespace, parse_register),
-                preceded(comma_sep, parse_register),
-                preceded(comma_sep, parse_immediate),
-                preceded(comma_sep, parse_immediate),
-                preceded(comma_sep, parse_immediate),
-            )),
-            |(ra, rs, sh, mb, me)| Opcode::Rlwinm { ra, rs, sh, mb, me },
-        ),
+/// 32-bit mask operand, `0xFFFF0000`, as `(mb, me)`. It has to be one run
+/// of ones, possibly wrapping around.
+fn parse_mask<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, (u8, u8)> {
+    map_opt(
+        |i| parse_expr(i, syms),
+        |v| mask_bounds(u32::try_from(v).ok()?),
     )(inp)
 }
 
-fn parse_rlwimi(inp: &str) -> IResult<&str, Opcode> {
-    preceded(
-        tag("rlwimi"),
-        map(
-            tuple((
-                preceded(whitespace, parse_register),
-                preceded(comma_sep, parse_register),
-                preceded(comma_sep, parse_immediate),
-                preceded(comma_sep, parse_immediate),
-                preceded(comma_sep, parse_immediate),
-            )),
-            |(ra, rs, sh, mb, me)| Opcode::Rlwimi { ra, rs, sh, mb, me },
-        ),
+/// `cr7`, or a bare field number.
+fn parse_cr_field<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, CrField> {
+    map(
+        verify(|i| parse_expr(i, syms), |&v| (0..8).contains(&v)),
+        |v| CrField(v as u8),
     )(inp)
 }
 
-fn parse_rlwnm(inp: &str) -> IResult<&str, Opcode> {
-    preceded(
-        tag("rlwnm"),
-        map(
-            tuple((
-                preceded(whitespace, parse_register),
-                preceded(comma_sep, parse_register),
-                preceded(comma_sep, parse_register),
-                preceded(comma_sep, parse_immediate),
-                preceded(comma_sep, parse_immediate),
-            )),
-            |(ra, rs, rb, mb, me)| Opcode::Rlwnm { ra, rs, rb, mb, me },
-        ),
+/// `eq`, `4*cr1+gt`, or a bare bit number.
+fn parse_cr_bit<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, CrBit> {
+    map(
+        verify(|i| parse_expr(i, syms), |&v| (0..32).contains(&v)),
+        |v| CrBit(v as u8),
     )(inp)
 }
 
-fn parse_extlwi(inp: &str) -> IResult<&str, Opcode> {
-    preceded(
-        tag("extlwi"),
-        map(
-            tuple((
-                preceded(whitespace, parse_register),
-                preceded(comma_sep, parse_register),
-                preceded(comma_sep, parse_immediate),
-                preceded(comma_sep, parse_immediate),
+/// `+ 8` or `-4` after a symbol, or nothing for 0.
+fn parse_addend<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, i64> {
+    map(
+        opt(preceded(
+            multispace0,
+            alt((
+                preceded(tuple((tag("+"), multispace0)), |i| parse_expr(i, syms)),
+                map(
+                    preceded(tuple((tag("-"), multispace0)), |i| parse_expr(i, syms)),
+                    |v| -v,
+                ),
             )),
-            |(ra, rs, n, b)| Opcode::Extlwi { ra, rs, n, b },
-        ),
+        )),
+        |addend| addend.unwrap_or(0),
     )(inp)
 }
 
-fn parse_extrwi(inp: &str) -> IResult<&str, Opcode> {
-    preceded(
-        tag("extrwi"),
-        map(
-            tuple((
-                preceded(whitespace, parse_register),
-                preceded(comma_sep, parse_register),
-                preceded(comma_sep, parse_immediate),
-                preceded(comma_sep, parse_immediate),
+/// `sym@ha`, `sym+8@l`, `sym@sda21`: symbol, addend and operator.
+fn parse_reloc<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, (&'a str, i64, Reloc)> {
+    tuple((
+        parse_identifier,
+        |i| parse_addend(i, syms),
+        preceded(
+            tag("@"),
+            alt((
+                map(tag("sda21"), |_| Reloc::Sda21),
+                map(tag("ha"), |_| Reloc::Ha),
+                map(tag("h"), |_| Reloc::Hi),
+                map(tag("l"), |_| Reloc::Lo),
             )),
-            |(ra, rs, n, b)| Opcode::Extrwi { ra, rs, n, b },
         ),
-    )(inp)
+    ))(inp)
 }
 
-fn parse_rotlwi(inp: &str) -> IResult<&str, Opcode> {
-    preceded(
-        tag("rotlwi"),
-        map(
-            tuple((
-                preceded(whitespace, parse_register),
-                preceded(comma_sep, parse_register),
-                preceded(comma_sep, parse_immediate),
-            )),
-            |(ra, rs, n)| Opcode::Rotlwi { ra, rs, n },
+/// 16-bit immediate. Both signed (`-1`) and unsigned (`0xFFFF`) spellings are
+/// accepted and stored as the same bit pattern. A relocation against a known
+/// constant is folded; against anything else it reads as 0 until linked.
+fn parse_simm16<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, i16> {
+    alt((
+        map_res(
+            |i| parse_reloc(i, syms),
+            |(name, addend, reloc)| match syms.get(name) {
+                Some(value) => reloc.apply(value + addend).ok_or(ErrorKind::Verify),
+                None => Ok(0),
+            },
         ),
-    )(inp)
+        map_res(
+            |i| parse_expr(i, syms),
+            |v| {
+                if (-0x8000..=0xFFFF).contains(&v) {
+                    Ok(v as u16 as i16)
+                } else {
+                    Err(ErrorKind::TooLarge)
+                }
+            },
+        ),
+    ))(inp)
 }
 
-fn parse_rotrwi(inp: &str) -> IResult<&str, Opcode> {
-    preceded(
-        tag("rotrwi"),
-        map(
-            tuple((
-                preceded(whitespace, parse_register),
-                preceded(comma_sep, parse_register),
-                preceded(comma_sep, parse_immediate),
-            )),
-            |(ra, rs, n)| Opcode::Rotrwi { ra, rs, n },
-        ),
-    )(inp)
+/// A branch target symbol and addend: `loop`, `done+4`. `.` is the branch
+/// itself, not a symbol.
+fn parse_branch_symbol<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, (&'a str, i64)> {
+    tuple((verify(parse_identifier, |name: &str| name != "."), |i| {
+        parse_addend(i, syms)
+    }))(inp)
 }
 
-fn parse_slwi(inp: &str) -> IResult<&str, Opcode> {
-    preceded(
-        tag("slwi"),
-        map(
-            tuple((
-                preceded(whitespace, parse_register),
-                preceded(comma_sep, parse_register),
-                preceded(comma_sep, parse_immediate),
-            )),
-            |(ra, rs, n)| Opcode::Slwi { ra, rs, n },
-        ),
+/// An absolute branch target, `0x80001000`: a 32-bit address the branch's
+/// displacement is taken from once it's known where the branch is.
+fn parse_absolute<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, i64> {
+    verify(
+        |i| parse_expr(i, syms),
+        |addr| (0..=i64::from(u32::MAX)).contains(addr),
     )(inp)
 }
 
-fn parse_srwi(inp: &str) -> IResult<&str, Opcode> {
-    preceded(
-        tag("srwi"),
+/// Branch target as a displacement from the branch: `.+8`, `.-16`, or a
+/// symbol or an absolute address, which read as 0 until they're resolved.
+/// `.+N` has to be a multiple of 4 within `reach` bytes; the rest are
+/// checked as they're resolved.
+fn parse_target<'a>(inp: &'a str, syms: &Symbols, reach: i64) -> PResult<'a, i32> {
+    alt((
+        map(|i| parse_branch_symbol(i, syms), |_| 0),
         map(
-            tuple((
-                preceded(whitespace, parse_register),
-                preceded(comma_sep, parse_register),
-                preceded(comma_sep, parse_immediate),
-            )),
-            |(ra, rs, n)| Opcode::Srwi { ra, rs, n },
+            verify(preceded(tag("."), |i| parse_addend(i, syms)), |&disp| {
+                disp % 4 == 0 && (-reach..reach).contains(&disp)
+            }),
+            |disp| disp as i32,
         ),
-    )(inp)
+        map(|i| parse_absolute(i, syms), |_| 0),
+    ))(inp)
 }
 
-fn parse_clrlwi(inp: &str) -> IResult<&str, Opcode> {
-    preceded(
-        tag("clrlwi"),
-        map(
-            tuple((
-                preceded(whitespace, parse_register),
-                preceded(comma_sep, parse_register),
-                preceded(comma_sep, parse_immediate),
-            )),
-            |(ra, rs, n)| Opcode::Clrlwi { ra, rs, n },
+/// The relocation in an instruction's operands, unless it was folded.
+fn find_relocation(text: &str, syms: &Symbols) -> Option<Relocation> {
+    let at = text.find('@')?;
+    let start = text[..at].rfind(',').map_or(0, |i| i + 1);
+    let (_, (name, addend, reloc)) = parse_reloc(text[start..].trim_start(), syms).ok()?;
+    match syms.get(name) {
+        Some(_) => None,
+        None => Some(Relocation {
+            reloc,
+            symbol: name.to_string(),
+            addend,
+        }),
+    }
+}
+
+/// The symbol a branch's target operand names, or the address it gives,
+/// if it isn't `.+N`.
+fn find_branch_target(target: &str, syms: &Symbols) -> Option<Relocation> {
+    if let Ok((_, (name, addend))) = parse_branch_symbol(target, syms) {
+        return Some(Relocation {
+            reloc: Reloc::Rel,
+            symbol: name.to_string(),
+            addend,
+        });
+    }
+    if target.starts_with('.') {
+        return None;
+    }
+    let (_, addr) = parse_absolute(target, syms).ok()?;
+    Some(Relocation {
+        reloc: Reloc::Rel,
+        symbol: String::new(),
+        addend: addr,
+    })
+}
+
+/// `d(rA)` memory operand.
+fn parse_displacement<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, (i16, Register)> {
+    tuple((
+        |i| parse_simm16(i, syms),
+        delimited(
+            tuple((multispace0, tag("("), multispace0)),
+            parse_register,
+            tuple((multispace0, tag(")"))),
         ),
-    )(inp)
+    ))(inp)
 }
 
-fn parse_clrrwi(inp: &str) -> IResult<&str, Opcode> {
-    preceded(
-        tag("clrrwi"),
-        map(
+fn whitespace(inp: &str) -> PResult<'_, ()> {
+    map(multispace0, |_| ())(inp)
+}
+
+fn comma_sep(inp: &str) -> PResult<'_, ()> {
+    map(tuple((multispace0, tag(","), multispace0)), |_| ())(inp)
+}
+
+/// Operand kinds in an instruction signature.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Kind {
+    Reg,
+    /// Small unsigned field: a shift, or a count of bits to shift out or
+    /// clear
+    Imm,
+    /// Bit position: a mask bound or where a field starts
+    Pos,
+    /// How many bits wide a field is, 1 to 32
+    Count,
+    /// 16-bit immediate, see [`parse_simm16`]
+    Simm,
+    /// `d(rA)`
+    Disp,
+    /// 32-bit mask constant standing in for `mb,me`
+    Mask,
+    CrField,
+    CrBit,
+    /// BO field of `bc`: what it tests
+    Bo,
+    /// `b` target, ±32MB
+    Target,
+    /// `bc` target, ±32KB
+    ShortTarget,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Operand {
+    Reg(Register),
+    Imm(u8),
+    Simm(i16),
+    Disp(i16, Register),
+    Mask(u8, u8),
+    CrField(CrField),
+    CrBit(CrBit),
+    Target(i32),
+}
+
+impl Kind {
+    /// The values an operand that's just a number can have, and what it is.
+    fn bounds(self) -> Option<(Range<i64>, &'static str)> {
+        match self {
+            Self::Imm => Some((0..32, "a shift or bit count")),
+            Self::Pos => Some((0..32, "a bit position")),
+            Self::Count => Some((1..33, "a field's width")),
+            Self::Bo => Some((0..32, "BO")),
+            _ => None,
+        }
+    }
+}
+
+/// Operands parsed against a signature, read back by position. Asking for
+/// the wrong kind is a bug in [`FORMS`].
+struct Operands(Vec<Operand>);
+
+impl Operands {
+    fn reg(&self, i: usize) -> Register {
+        match self.0[i] {
+            Operand::Reg(r) => r,
+            other => unreachable!("operand {} is {:?}", i, other),
+        }
+    }
+
+    fn imm(&self, i: usize) -> u8 {
+        match self.0[i] {
+            Operand::Imm(v) => v,
+            other => unreachable!("operand {} is {:?}", i, other),
+        }
+    }
+
+    fn simm(&self, i: usize) -> i16 {
+        match self.0[i] {
+            Operand::Simm(v) => v,
+            other => unreachable!("operand {} is {:?}", i, other),
+        }
+    }
+
+    fn disp(&self, i: usize) -> (i16, Register) {
+        match self.0[i] {
+            Operand::Disp(d, ra) => (d, ra),
+            other => unreachable!("operand {} is {:?}", i, other),
+        }
+    }
+
+    /// `(mb, me)`
+    fn mask(&self, i: usize) -> (u8, u8) {
+        match self.0[i] {
+            Operand::Mask(mb, me) => (mb, me),
+            other => unreachable!("operand {} is {:?}", i, other),
+        }
+    }
+
+    fn crf(&self, i: usize) -> CrField {
+        match self.0[i] {
+            Operand::CrField(crf) => crf,
+            other => unreachable!("operand {} is {:?}", i, other),
+        }
+    }
+
+    fn bit(&self, i: usize) -> CrBit {
+        match self.0[i] {
+            Operand::CrBit(bit) => bit,
+            other => unreachable!("operand {} is {:?}", i, other),
+        }
+    }
+
+    fn target(&self, i: usize) -> i32 {
+        match self.0[i] {
+            Operand::Target(disp) => disp,
+            other => unreachable!("operand {} is {:?}", i, other),
+        }
+    }
+}
+
+/// One way of writing an instruction: a mnemonic, its operand signature,
+/// and how the operands make an `Opcode`.
+struct Form {
+    mnemonic: &'static str,
+    signature: &'static [Kind],
+    build: fn(&Operands) -> Opcode,
+}
+
+const fn form(
+    mnemonic: &'static str,
+    signature: &'static [Kind],
+    build: fn(&Operands) -> Opcode,
+) -> Form {
+    Form {
+        mnemonic,
+        signature,
+        build,
+    }
+}
+
+/// Every instruction ppcheat parses. Rows sharing a mnemonic are tried in
+/// order, so optional leading operands come first.
+const FORMS: &[Form] = &[
+    form("rlwinm", &[Reg, Reg, Imm, Pos, Pos], |o| Opcode::Rlwinm {
+        ra: o.reg(0),
+        rs: o.reg(1),
+        sh: o.imm(2),
+        mb: o.imm(3),
+        me: o.imm(4),
+        rc: false,
+    }),
+    form("rlwinm", &[Reg, Reg, Imm, Mask], |o| Opcode::Rlwinm {
+        ra: o.reg(0),
+        rs: o.reg(1),
+        sh: o.imm(2),
+        mb: o.mask(3).0,
+        me: o.mask(3).1,
+        rc: false,
+    }),
+    form("rlwimi", &[Reg, Reg, Imm, Pos, Pos], |o| Opcode::Rlwimi {
+        ra: o.reg(0),
+        rs: o.reg(1),
+        sh: o.imm(2),
+        mb: o.imm(3),
+        me: o.imm(4),
+        rc: false,
+    }),
+    form("rlwimi", &[Reg, Reg, Imm, Mask], |o| Opcode::Rlwimi {
+        ra: o.reg(0),
+        rs: o.reg(1),
+        sh: o.imm(2),
+        mb: o.mask(3).0,
+        me: o.mask(3).1,
+        rc: false,
+    }),
+    form("rlwnm", &[Reg, Reg, Reg, Pos, Pos], |o| Opcode::Rlwnm {
+        ra: o.reg(0),
+        rs: o.reg(1),
+        rb: o.reg(2),
+        mb: o.imm(3),
+        me: o.imm(4),
+        rc: false,
+    }),
+    form("rlwnm", &[Reg, Reg, Reg, Mask], |o| Opcode::Rlwnm {
+        ra: o.reg(0),
+        rs: o.reg(1),
+        rb: o.reg(2),
+        mb: o.mask(3).0,
+        me: o.mask(3).1,
+        rc: false,
+    }),
+    form("extlwi", &[Reg, Reg, Count, Pos], |o| Opcode::Extlwi {
+        ra: o.reg(0),
+        rs: o.reg(1),
+        n: o.imm(2),
+        b: o.imm(3),
+    }),
+    form("extrwi", &[Reg, Reg, Count, Pos], |o| Opcode::Extrwi {
+        ra: o.reg(0),
+        rs: o.reg(1),
+        n: o.imm(2),
+        b: o.imm(3),
+    }),
+    form("rotlwi", &[Reg, Reg, Imm], |o| Opcode::Rotlwi {
+        ra: o.reg(0),
+        rs: o.reg(1),
+        n: o.imm(2),
+    }),
+    form("rotrwi", &[Reg, Reg, Imm], |o| Opcode::Rotrwi {
+        ra: o.reg(0),
+        rs: o.reg(1),
+        n: o.imm(2),
+    }),
+    form("slwi", &[Reg, Reg, Imm], |o| Opcode::Slwi {
+        ra: o.reg(0),
+        rs: o.reg(1),
+        n: o.imm(2),
+    }),
+    form("srwi", &[Reg, Reg, Imm], |o| Opcode::Srwi {
+        ra: o.reg(0),
+        rs: o.reg(1),
+        n: o.imm(2),
+    }),
+    form("clrlwi", &[Reg, Reg, Imm], |o| Opcode::Clrlwi {
+        ra: o.reg(0),
+        rs: o.reg(1),
+        n: o.imm(2),
+    }),
+    form("clrrwi", &[Reg, Reg, Imm], |o| Opcode::Clrrwi {
+        ra: o.reg(0),
+        rs: o.reg(1),
+        n: o.imm(2),
+    }),
+    form("clrlslwi", &[Reg, Reg, Imm, Imm], |o| Opcode::Clrlslwi {
+        ra: o.reg(0),
+        rs: o.reg(1),
+        b: o.imm(2),
+        n: o.imm(3),
+    }),
+    form("rotlw", &[Reg, Reg, Reg], |o| Opcode::Rotlw {
+        ra: o.reg(0),
+        rs: o.reg(1),
+        rb: o.reg(2),
+    }),
+    form("inslwi", &[Reg, Reg, Count, Pos], |o| Opcode::Inslwi {
+        ra: o.reg(0),
+        rs: o.reg(1),
+        n: o.imm(2),
+        b: o.imm(3),
+    }),
+    form("insrwi", &[Reg, Reg, Count, Pos], |o| Opcode::Insrwi {
+        ra: o.reg(0),
+        rs: o.reg(1),
+        n: o.imm(2),
+        b: o.imm(3),
+    }),
+    form("addi", &[Reg, Reg, Simm], |o| Opcode::Addi {
+        rd: o.reg(0),
+        ra: o.reg(1),
+        simm: o.simm(2),
+    }),
+    form("addis", &[Reg, Reg, Simm], |o| Opcode::Addis {
+        rd: o.reg(0),
+        ra: o.reg(1),
+        simm: o.simm(2),
+    }),
+    form("ori", &[Reg, Reg, Simm], |o| Opcode::Ori {
+        ra: o.reg(0),
+        rs: o.reg(1),
+        uimm: o.simm(2) as u16,
+    }),
+    form("oris", &[Reg, Reg, Simm], |o| Opcode::Oris {
+        ra: o.reg(0),
+        rs: o.reg(1),
+        uimm: o.simm(2) as u16,
+    }),
+    form("li", &[Reg, Simm], |o| Opcode::Li {
+        rd: o.reg(0),
+        simm: o.simm(1),
+    }),
+    form("lis", &[Reg, Simm], |o| Opcode::Lis {
+        rd: o.reg(0),
+        simm: o.simm(1),
+    }),
+    form("lbz", &[Reg, Disp], |o| load(o, Width::Byte, false)),
+    form("lbzu", &[Reg, Disp], |o| load(o, Width::Byte, true)),
+    form("lhz", &[Reg, Disp], |o| load(o, Width::Half, false)),
+    form("lhzu", &[Reg, Disp], |o| load(o, Width::Half, true)),
+    form("lwz", &[Reg, Disp], |o| load(o, Width::Word, false)),
+    form("lwzu", &[Reg, Disp], |o| load(o, Width::Word, true)),
+    form("lha", &[Reg, Disp], |o| lha(o, false)),
+    form("lhau", &[Reg, Disp], |o| lha(o, true)),
+    form("stb", &[Reg, Disp], |o| store(o, Width::Byte, false)),
+    form("stbu", &[Reg, Disp], |o| store(o, Width::Byte, true)),
+    form("sth", &[Reg, Disp], |o| store(o, Width::Half, false)),
+    form("sthu", &[Reg, Disp], |o| store(o, Width::Half, true)),
+    form("stw", &[Reg, Disp], |o| store(o, Width::Word, false)),
+    form("stwu", &[Reg, Disp], |o| store(o, Width::Word, true)),
+    // crD defaults to cr0
+    form("cmpw", &[Crf, Reg, Reg], |o| cmp(o, false, o.crf(0), 1)),
+    form("cmpw", &[Reg, Reg], |o| cmp(o, false, CrField(0), 0)),
+    form("cmplw", &[Crf, Reg, Reg], |o| cmp(o, true, o.crf(0), 1)),
+    form("cmplw", &[Reg, Reg], |o| cmp(o, true, CrField(0), 0)),
+    form("cmpwi", &[Crf, Reg, Simm], |o| cmpi(o, false, o.crf(0), 1)),
+    form("cmpwi", &[Reg, Simm], |o| cmpi(o, false, CrField(0), 0)),
+    form("cmplwi", &[Crf, Reg, Simm], |o| cmpi(o, true, o.crf(0), 1)),
+    form("cmplwi", &[Reg, Simm], |o| cmpi(o, true, CrField(0), 0)),
+    form("crand", &[Bit, Bit, Bit], |o| crop(o, CrLogic::And)),
+    form("cror", &[Bit, Bit, Bit], |o| crop(o, CrLogic::Or)),
+    form("crxor", &[Bit, Bit, Bit], |o| crop(o, CrLogic::Xor)),
+    form("crnand", &[Bit, Bit, Bit], |o| crop(o, CrLogic::Nand)),
+    form("crnor", &[Bit, Bit, Bit], |o| crop(o, CrLogic::Nor)),
+    form("creqv", &[Bit, Bit, Bit], |o| crop(o, CrLogic::Eqv)),
+    form("crandc", &[Bit, Bit, Bit], |o| crop(o, CrLogic::Andc)),
+    form("crorc", &[Bit, Bit, Bit], |o| crop(o, CrLogic::Orc)),
+    form("b", &[Target], |o| Opcode::B {
+        disp: o.target(0),
+        link: false,
+    }),
+    form("bl", &[Target], |o| Opcode::B {
+        disp: o.target(0),
+        link: true,
+    }),
+    form("bc", &[Bo, Bit, ShortTarget], |o| Opcode::Bc {
+        bo: o.imm(0),
+        bi: o.bit(1),
+        disp: o.target(2) as i16,
+    }),
+    // crN defaults to cr0
+    form("blt", &[Crf, ShortTarget], |o| bc(o, 12, 0, o.crf(0), 1)),
+    form("blt", &[ShortTarget], |o| bc(o, 12, 0, CrField(0), 0)),
+    form("bgt", &[Crf, ShortTarget], |o| bc(o, 12, 1, o.crf(0), 1)),
+    form("bgt", &[ShortTarget], |o| bc(o, 12, 1, CrField(0), 0)),
+    form("beq", &[Crf, ShortTarget], |o| bc(o, 12, 2, o.crf(0), 1)),
+    form("beq", &[ShortTarget], |o| bc(o, 12, 2, CrField(0), 0)),
+    form("bso", &[Crf, ShortTarget], |o| bc(o, 12, 3, o.crf(0), 1)),
+    form("bso", &[ShortTarget], |o| bc(o, 12, 3, CrField(0), 0)),
+    form("bge", &[Crf, ShortTarget], |o| bc(o, 4, 0, o.crf(0), 1)),
+    form("bge", &[ShortTarget], |o| bc(o, 4, 0, CrField(0), 0)),
+    form("ble", &[Crf, ShortTarget], |o| bc(o, 4, 1, o.crf(0), 1)),
+    form("ble", &[ShortTarget], |o| bc(o, 4, 1, CrField(0), 0)),
+    form("bne", &[Crf, ShortTarget], |o| bc(o, 4, 2, o.crf(0), 1)),
+    form("bne", &[ShortTarget], |o| bc(o, 4, 2, CrField(0), 0)),
+    form("bns", &[Crf, ShortTarget], |o| bc(o, 4, 3, o.crf(0), 1)),
+    form("bns", &[ShortTarget], |o| bc(o, 4, 3, CrField(0), 0)),
+    form("bdnz", &[ShortTarget], |o| bc(o, 16, 0, CrField(0), 0)),
+    form("bdz", &[ShortTarget], |o| bc(o, 18, 0, CrField(0), 0)),
+];
+
+fn load(o: &Operands, width: Width, update: bool) -> Opcode {
+    let (d, ra) = o.disp(1);
+    Opcode::Load {
+        width,
+        update,
+        rd: o.reg(0),
+        d,
+        ra,
+    }
+}
+
+fn lha(o: &Operands, update: bool) -> Opcode {
+    let (d, ra) = o.disp(1);
+    Opcode::Lha {
+        update,
+        rd: o.reg(0),
+        d,
+        ra,
+    }
+}
+
+fn store(o: &Operands, width: Width, update: bool) -> Opcode {
+    let (d, ra) = o.disp(1);
+    Opcode::Store {
+        width,
+        update,
+        rs: o.reg(0),
+        d,
+        ra,
+    }
+}
+
+/// `cmpw`/`cmplw` with rA at operand `first`.
+fn cmp(o: &Operands, logical: bool, crf: CrField, first: usize) -> Opcode {
+    Opcode::Cmp {
+        logical,
+        crf,
+        ra: o.reg(first),
+        rb: o.reg(first + 1),
+    }
+}
+
+/// `cmpwi`/`cmplwi` with rA at operand `first`.
+fn cmpi(o: &Operands, logical: bool, crf: CrField, first: usize) -> Opcode {
+    Opcode::Cmpi {
+        logical,
+        crf,
+        ra: o.reg(first),
+        imm: o.simm(first + 1),
+    }
+}
+
+/// A `bc` testing condition bit `condition` (lt, gt, eq, so) of `crf`,
+/// with the target at operand `first`.
+fn bc(o: &Operands, bo: u8, condition: u8, crf: CrField, first: usize) -> Opcode {
+    Opcode::Bc {
+        bo,
+        bi: CrBit(4 * crf.0 + condition),
+        disp: o.target(first) as i16,
+    }
+}
+
+fn crop(o: &Operands, op: CrLogic) -> Opcode {
+    Opcode::CrOp {
+        op,
+        bt: o.bit(0),
+        ba: o.bit(1),
+        bb: o.bit(2),
+    }
+}
+
+fn parse_operand<'a>(inp: &'a str, syms: &Symbols, kind: Kind) -> PResult<'a, Operand> {
+    match kind {
+        Kind::Reg => map(parse_register, Operand::Reg)(inp),
+        Kind::Imm | Kind::Count | Kind::Bo => {
+            map(|i| parse_number(i, syms, kind), Operand::Imm)(inp)
+        }
+        Kind::Pos => map(|i| parse_position(i, syms), Operand::Imm)(inp),
+        Kind::Simm => map(|i| parse_simm16(i, syms), Operand::Simm)(inp),
+        Kind::Disp => map(
+            |i| parse_displacement(i, syms),
+            |(d, ra)| Operand::Disp(d, ra),
+        )(inp),
+        Kind::Mask => map(|i| parse_mask(i, syms), |(mb, me)| Operand::Mask(mb, me))(inp),
+        Kind::CrField => map(|i| parse_cr_field(i, syms), Operand::CrField)(inp),
+        Kind::CrBit => map(|i| parse_cr_bit(i, syms), Operand::CrBit)(inp),
+        Kind::Target => map(|i| parse_target(i, syms, 1 << 25), Operand::Target)(inp),
+        Kind::ShortTarget => map(|i| parse_target(i, syms, 1 << 15), Operand::Target)(inp),
+    }
+}
+
+/// Comma-separated operands matching `signature`, with the byte range of
+/// each within `inp`.
+fn parse_operands<'a>(
+    inp: &'a str,
+    syms: &Symbols,
+    signature: &[Kind],
+) -> PResult<'a, (Operands, Vec<Range<usize>>)> {
+    let mut rest = inp;
+    let mut operands = Vec::with_capacity(signature.len());
+    let mut spans = Vec::with_capacity(signature.len());
+    for (i, &kind) in signature.iter().enumerate() {
+        let (start, _) = if i == 0 {
+            whitespace(rest)?
+        } else {
+            comma_sep(rest)?
+        };
+        let (after, operand) = parse_operand(start, syms, kind)?;
+        operands.push(operand);
+        spans.push(inp.len() - start.len()..inp.len() - after.len());
+        rest = after;
+    }
+    Ok((rest, (Operands(operands), spans)))
+}
+
+/// [`parse_instruction`], also returning where each operand is in `inp`.
+/// With `check`, a form whose operands break [`Opcode::check`] doesn't
+/// match; without, such a pseudo-op is returned without its `.`, so it
+/// can still say why.
+fn parse_instruction_spanned<'a>(
+    inp: &'a str,
+    syms: &Symbols,
+    check: bool,
+) -> PResult<'a, (Opcode, Vec<Range<usize>>)> {
+    let (rest, mnemonic) = take_while1(|c: char| c.is_ascii_alphanumeric())(inp)?;
+    let (rest, dot) = opt(tag("."))(rest)?;
+    let offset = inp.len() - rest.len();
+
+    let mut failure = Failure::from_error_kind(inp, ErrorKind::Tag);
+    for form in FORMS.iter().filter(|f| f.mnemonic == mnemonic) {
+        match parse_operands(rest, syms, form.signature) {
+            Ok((after, (operands, spans))) => {
+                let op = (form.build)(&operands);
+                if check && op.check().is_err() {
+                    failure = failure.or(Failure::from_error_kind(rest, ErrorKind::Verify));
+                    continue;
+                }
+                // a record form is the rotate its mnemonic stands for, with Rc
+                let op = match (dot, op.with_record(true)) {
+                    (None, _) => op,
+                    (Some(_), None) => continue,
+                    (Some(_), Some(_)) if op.check().is_err() => op,
+                    (Some(_), Some(record)) => record,
+                };
+                let spans = spans
+                    .into_iter()
+                    .map(|s| s.start + offset..s.end + offset)
+                    .collect();
+                return Ok((after, (op, spans)));
+            }
+            Err(nom::Err::Error(e)) => failure = failure.or(e),
+            Err(e) => return Err(e),
+        }
+    }
+    Err(nom::Err::Error(failure))
+}
+
+/// Instruction whose operands may name constants from `syms`: the mnemonic
+/// picks the rows of [`FORMS`] to try.
+fn parse_instruction<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, Opcode> {
+    map(|i| parse_instruction_spanned(i, syms, true), |(op, _)| op)(inp)
+}
+
+/// The mnemonics with a record form, written with a `.`: the rotates and
+/// their pseudo-ops, which all set Rc.
+const RECORD_FORMS: &[&str] = &[
+    "rlwinm", "rlwimi", "rlwnm", "extlwi", "extrwi", "rotlwi", "rotrwi", "slwi", "srwi", "clrlwi",
+    "clrrwi", "clrlslwi", "rotlw", "inslwi", "insrwi",
+];
+
+/// Every mnemonic in [`FORMS`], once.
+fn mnemonics() -> Vec<&'static str> {
+    let mut names: Vec<_> = FORMS.iter().map(|f| f.mnemonic).collect();
+    names.dedup();
+    names
+}
+
+const DIRECTIVES: &[&str] = &[
+    ".set", ".equ", ".text", ".data", ".section", ".global", ".globl", ".byte", ".short", ".long",
+    ".float", ".align", ".space", ".macro", ".endm", ".include", ".if", ".ifdef", ".ifndef",
+    ".elseif", ".else", ".endif",
+];
+
+/// Edit distance counting an adjacent transposition as one edit, so
+/// `rlwimn` is as close to `rlwinm` as to `rlwimi`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<_>, Vec<_>) = (a.chars().collect(), b.chars().collect());
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    d[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// The entries of `table` closest to `name`, if any are within two edits.
+pub(crate) fn did_you_mean(name: &str, table: &[&'static str]) -> Vec<&'static str> {
+    let distance = |m: &&str| edit_distance(name, m);
+    match table.iter().map(distance).min() {
+        Some(best) if best <= 2 => table
+            .iter()
+            .filter(|m| distance(m) == best)
+            .copied()
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// One instruction with nothing but whitespace after it; trailing junk is
+/// an `Eof` error at the junk.
+pub fn parse_opcode(inp: &str) -> IResult<&str, Opcode> {
+    let syms = Symbols::default();
+    let mut parse = all_consuming(terminated(|i| parse_instruction(i, &syms), multispace0));
+    parse(inp).map_err(|e| e.map(|f| nom::error::Error::new(f.input, f.kind)))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Directive {
+    /// `.set NAME, value`, `.equ NAME, value` or `NAME = value`
+    Set { name: String, value: i64 },
+    /// `.text`, `.data` or `.section NAME`
+    Section(String),
+    /// `.global NAME` (or `.globl`)
+    Global(String),
+    /// `.byte`, `.short` or `.long` with one or more values, or `.float`
+    /// with the bits of its single-precision values
+    Data { width: Width, values: Vec<i64> },
+    /// `.align N`: pad to a 2^N byte boundary
+    Align(u8),
+    /// `.space N`: N zero bytes
+    Space(u32),
+}
+
+impl Directive {
+    /// Bytes emitted when placed at `addr`.
+    pub fn size(&self, addr: u32) -> u32 {
+        match *self {
+            Self::Data { width, ref values } => width.bytes() * values.len() as u32,
+            Self::Align(n) => {
+                let align = 1u32 << n.min(31);
+                (align - addr % align) % align
+            }
+            Self::Space(n) => n,
+            _ => 0,
+        }
+    }
+}
+
+fn parse_set<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, Directive> {
+    let value = |i| parse_expr(i, syms);
+    map(
+        alt((
             tuple((
-                preceded(whitespace, parse_register),
-                preceded(comma_sep, parse_register),
-                preceded(comma_sep, parse_immediate),
+                preceded(
+                    tuple((alt((tag(".set"), tag(".equ"))), multispace1)),
+                    parse_identifier,
+                ),
+                preceded(comma_sep, value),
             )),
-            |(ra, rs, n)| Opcode::Clrrwi { ra, rs, n },
-        ),
+            tuple((
+                parse_identifier,
+                preceded(tuple((multispace0, tag("="), multispace0)), value),
+            )),
+        )),
+        |(name, value)| Directive::Set {
+            name: name.to_string(),
+            value,
+        },
     )(inp)
 }
 
-fn parse_clrlslwi(inp: &str) -> IResult<&str, Opcode> {
-    preceded(
-        tag("clrlslwi"),
+fn parse_directive<'a>(inp: &'a str, syms: &Symbols) -> PResult<'a, Directive> {
+    let value = |i| parse_expr(i, syms);
+    let data = |mnemonic, width| {
         map(
-            tuple((
-                preceded(whitespace, parse_register),
-                preceded(comma_sep, parse_register),
-                preceded(comma_sep, parse_immediate),
-                preceded(comma_sep, parse_immediate),
-            )),
-            |(ra, rs, b, n)| Opcode::Clrlslwi { ra, rs, b, n },
+            preceded(
+                tuple((tag(mnemonic), multispace1)),
+                separated_list1(comma_sep, value),
+            ),
+            move |values| Directive::Data { width, values },
+        )
+    };
+
+    alt((
+        |i| parse_set(i, syms),
+        map(tag(".text"), |_| Directive::Section(".text".to_string())),
+        map(tag(".data"), |_| Directive::Section(".data".to_string())),
+        map(
+            preceded(tuple((tag(".section"), multispace1)), parse_identifier),
+            |name| Directive::Section(name.to_string()),
+        ),
+        map(
+            preceded(
+                tuple((alt((tag(".globl"), tag(".global"))), multispace1)),
+                parse_identifier,
+            ),
+            |name| Directive::Global(name.to_string()),
         ),
+        data(".byte", Width::Byte),
+        data(".short", Width::Half),
+        data(".long", Width::Word),
+        map(
+            preceded(
+                tuple((tag(".float"), multispace1)),
+                separated_list1(comma_sep, map_res(recognize_float, str::parse::<f32>)),
+            ),
+            |values| Directive::Data {
+                width: Width::Word,
+                values: values.iter().map(|v| i64::from(v.to_bits())).collect(),
+            },
+        ),
+        map(
+            preceded(
+                tuple((tag(".align"), multispace1)),
+                map_res(value, u8::try_from),
+            ),
+            Directive::Align,
+        ),
+        map(
+            preceded(
+                tuple((tag(".space"), multispace1)),
+                map_res(value, u32::try_from),
+            ),
+            Directive::Space,
+        ),
+    ))(inp)
+}
+
+/// `name:`, on a line of its own or before a statement, or objdump's `<name>:`.
+fn parse_label(inp: &str) -> PResult<'_, &str> {
+    terminated(
+        alt((
+            parse_identifier,
+            delimited(tag("<"), parse_identifier, tag(">")),
+        )),
+        preceded(multispace0, tag(":")),
     )(inp)
 }
 
-fn parse_rotlw(inp: &str) -> IResult<&str, Opcode> {
-    preceded(
-        tag("rotlw"),
+fn parse_hex_word(inp: &str) -> PResult<'_, u32> {
+    map_res(take_while_m_n(8, 8, |c: char| c.is_ascii_hexdigit()), |x| {
+        u32::from_str_radix(x, 16)
+    })(inp)
+}
+
+/// Address and encoding columns of an objdump listing line,
+/// `80045678: 54 00 84 3e  srwi r0,r0,16`, or just the address of a symbol
+/// line, `80045678 <fn_80045678>:`.
+fn parse_listing_columns(inp: &str) -> PResult<'_, (u32, Option<u32>)> {
+    let byte = |i| {
+        map_res(take_while_m_n(2, 2, |c: char| c.is_ascii_hexdigit()), |x| {
+            u8::from_str_radix(x, 16)
+        })(i)
+    };
+    let encoding = alt((
         map(
             tuple((
-                preceded(whitespace, parse_register),
-                preceded(comma_sep, parse_register),
-                preceded(comma_sep, parse_register),
+                byte,
+                preceded(tag(" "), byte),
+                preceded(tag(" "), byte),
+                preceded(tag(" "), byte),
             )),
-            |(ra, rs, rb)| Opcode::Rotlw { ra, rs, rb },
+            |(a, b, c, d)| u32::from_be_bytes([a, b, c, d]),
         ),
-    )(inp)
-}
+        parse_hex_word,
+    ));
 
-fn parse_inslwi(inp: &str) -> IResult<&str, Opcode> {
-    preceded(
-        tag("inslwi"),
+    alt((
         map(
             tuple((
-                preceded(whitespace, parse_register),
-                preceded(comma_sep, parse_register),
-                preceded(comma_sep, parse_immediate),
-                preceded(comma_sep, parse_immediate),
+                terminated(parse_hex_word, tag(":")),
+                delimited(space1, encoding, multispace1),
             )),
-            |(ra, rs, n, b)| Opcode::Inslwi { ra, rs, n, b },
+            |(addr, word)| (addr, Some(word)),
         ),
-    )(inp)
+        map(
+            terminated(parse_hex_word, tuple((space1, peek(tag("<"))))),
+            |addr| (addr, None),
+        ),
+    ))(inp)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Instruction(Opcode),
+    Directive(Directive),
+    Label(String),
+}
+
+/// A single non-blank source line and what it parsed to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    /// The `.include`d file the line is from, `None` for the main input
+    pub file: Option<PathBuf>,
+    /// 1-based line number within its file
+    pub number: usize,
+    pub source: String,
+    pub statement: Statement,
+    /// Symbol reference in the immediate operand, if it has one
+    pub reloc: Option<Relocation>,
+    /// Address column, when the line came from a disassembly listing
+    pub address: Option<u32>,
+    /// Instruction word from the listing's raw bytes column
+    pub encoding: Option<u32>,
+    /// Byte range of the statement within `source`, without listing columns
+    /// and comments
+    pub span: Range<usize>,
+    /// Byte ranges of an instruction's operands within `source`
+    pub operands: Vec<Range<usize>>,
+}
+
+/// A parsed value and the byte range of `Line::source` it came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Range<usize>,
 }
 
-fn parse_insrwi(inp: &str) -> IResult<&str, Opcode> {
+impl Line {
+    /// The instruction on this line with its span, for mapping analysis
+    /// results back to the source.
+    pub fn spanned(&self) -> Option<Spanned<Opcode>> {
+        match self.statement {
+            Statement::Instruction(op) => Some(Spanned {
+                node: op,
+                span: self.span.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Source text of operand `i`.
+    pub fn operand(&self, i: usize) -> Option<Spanned<&str>> {
+        let span = self.operands.get(i)?.clone();
+        Some(Spanned {
+            node: &self.source[span.clone()],
+            span,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Program {
+    pub lines: Vec<Line>,
+    /// Every constant defined by the program or predefined for it, with its
+    /// final value
+    pub symbols: Symbols,
+    pub macros: BTreeMap<String, Macro>,
+}
+
+impl Program {
+    pub fn instructions(&self) -> impl Iterator<Item = &Opcode> {
+        self.lines.iter().filter_map(|line| match &line.statement {
+            Statement::Instruction(op) => Some(op),
+            _ => None,
+        })
+    }
+
+    /// Each line with the address it's placed at from `base`: after the 4
+    /// bytes of each instruction and what each directive emits before it.
+    /// With `listed`, the address column of a listing's line takes
+    /// precedence, and the lines after it follow on from there.
+    pub fn layout(&self, base: u32, listed: bool) -> Vec<(u32, &Line)> {
+        let mut addr = base;
+        let mut placed = Vec::with_capacity(self.lines.len());
+        for line in &self.lines {
+            if listed {
+                addr = line.address.unwrap_or(addr);
+            }
+            placed.push((addr, line));
+            addr = addr.wrapping_add(match &line.statement {
+                Statement::Instruction(_) => 4,
+                Statement::Directive(directive) => directive.size(addr),
+                Statement::Label(_) => 0,
+            });
+        }
+        placed
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// The `.include`d file the line is from, `None` for the main input
+    pub file: Option<PathBuf>,
+    /// 1-based line number of the offending line
+    pub line: usize,
+    pub source: String,
+    /// Byte range of the offending text within `source`
+    pub span: Range<usize>,
+    /// The operand the parser gave up in, if it got past the mnemonic
+    pub operand: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    /// `line N: message`, then the source line with the span underlined.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+        let start = self.source[..self.span.start].chars().count();
+        let width = self.source[self.span.clone()].chars().count().max(1);
+        match &self.file {
+            Some(file) => writeln!(
+                f,
+                "line {} of {}: {}",
+                self.line,
+                file.display(),
+                self.message
+            )?,
+            None => writeln!(f, "line {}: {}", self.line, self.message)?,
+        }
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", number, self.source)?;
+        write!(f, "{} | {}{}", gutter, " ".repeat(start), "^".repeat(width))
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// `line` up to `marker`, unless it's inside a `'#'` or `"a#b"` literal.
+fn strip_comment(line: &str, marker: char) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (idx, c) in line.char_indices() {
+        match (quote, c) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, _) if c == marker => return &line[..idx],
+            (None, _) => {}
+        }
+    }
+    line
+}
+
+/// Where a listing came from, which decides the columns and clutter around
+/// each statement.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Flavor {
+    /// Assembler source or an objdump listing
+    Asm,
+    /// Ghidra listing export: `ram:80045678 54 00 843e  rlwinm r0,r0,0x10,0x10,0x1f`
+    Ghidra,
+    /// IDA listing: `.text:80045678  rlwinm r0, r0, 16, 16, 31  # CODE XREF: ...`
+    Ida,
+}
+
+impl Flavor {
+    /// Guesses the flavor from the first line with an address column.
+    pub fn detect(inp: &str) -> Self {
+        for line in inp.lines().map(str::trim) {
+            let (segment, rest) = match line.split_once(':') {
+                Some(split) => split,
+                None => continue,
+            };
+            if parse_hex_word(rest).is_err() || segment.contains(char::is_whitespace) {
+                continue;
+            }
+            if segment.starts_with('.') {
+                return Flavor::Ida;
+            }
+            if matches!(parse_identifier(segment), Ok(("", _))) {
+                return Flavor::Ghidra;
+            }
+        }
+        Flavor::Asm
+    }
+}
+
+/// `SEGMENT:ADDR` as Ghidra (`ram:80045678`) and IDA (`.text:80045678`)
+/// print it.
+fn parse_segment_address(inp: &str) -> PResult<'_, u32> {
     preceded(
-        tag("insrwi"),
-        map(
-            tuple((
-                preceded(whitespace, parse_register),
-                preceded(comma_sep, parse_register),
-                preceded(comma_sep, parse_immediate),
-                preceded(comma_sep, parse_immediate),
-            )),
-            |(ra, rs, n, b)| Opcode::Insrwi { ra, rs, n, b },
+        terminated(
+            take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.'),
+            tag(":"),
         ),
+        parse_hex_word,
     )(inp)
 }
 
-pub fn parse_opcode(inp: &str) -> IResult<&str, Opcode> {
-    alt((
-        parse_rlwinm,
-        parse_rlwimi,
-        parse_rlwnm,
-        parse_extlwi,
-        parse_extrwi,
-        parse_rotlwi,
-        parse_rotrwi,
-        parse_slwi,
-        parse_srwi,
-        parse_clrlwi,
-        parse_clrrwi,
-        parse_clrlslwi,
-        parse_rotlw,
-        parse_inslwi,
-        parse_insrwi,
-    ))(inp)
+/// A leading column of raw bytes adding up to one instruction word, in any
+/// grouping (`54 00 843e`). `None` if the line doesn't start with one.
+fn split_bytes(text: &str) -> (Option<u32>, &str) {
+    let mut digits = String::new();
+    let mut rest = text;
+    while digits.len() < 8 {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let group = &rest[..end];
+        if group.is_empty()
+            || !group.len().is_multiple_of(2)
+            || digits.len() + group.len() > 8
+            || !group.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            return (None, text);
+        }
+        digits.push_str(group);
+        rest = rest[end..].trim_start();
+    }
+    (u32::from_str_radix(&digits, 16).ok(), rest)
+}
+
+/// The statement text of `raw` with the flavor's address, bytes and comment
+/// columns split off. `None` for blank lines and listing clutter such as
+/// Ghidra's function banners and xref lines.
+fn split_columns(raw: &str, flavor: Flavor) -> Option<(String, Option<u32>, Option<u32>)> {
+    match flavor {
+        Flavor::Asm => {
+            let text = strip_comment(raw, '#').trim();
+            let (text, address, encoding) = match parse_listing_columns(text) {
+                Ok((rest, (addr, word))) => (rest, Some(addr), word),
+                Err(_) => (text, None, None),
+            };
+            Some((text.to_string(), address, encoding)).filter(|(t, ..)| !t.is_empty())
+        }
+        Flavor::Ghidra => {
+            let text = strip_comment(raw, ';');
+            let text = text.split("XREF[").next().unwrap_or(text).trim();
+            match parse_segment_address(text).or_else(|_| parse_hex_word(text)) {
+                Ok((rest, addr)) => {
+                    let (encoding, rest) = split_bytes(rest.trim_start());
+                    Some((rest.to_string(), Some(addr), encoding)).filter(|(t, ..)| !t.is_empty())
+                }
+                // labels such as `LAB_80045690` sit on a line of their own
+                Err(_) => match parse_identifier(text) {
+                    Ok(("", name)) => Some((format!("{}:", name), None, None)),
+                    _ => None,
+                },
+            }
+        }
+        Flavor::Ida => {
+            let text = strip_comment(raw, '#').trim();
+            let (rest, addr) = parse_segment_address(text).ok()?;
+            let (encoding, rest) = split_bytes(rest.trim_start());
+            Some((rest.to_string(), Some(addr), encoding)).filter(|(t, ..)| !t.is_empty())
+        }
+    }
+}
+
+/// Where and why `text` fails to parse. A known mnemonic or directive
+/// means a bad operand; only the parser the line is meant for is consulted
+/// to find it, so `NAME = 1+` is a bad constant, not an unknown mnemonic.
+fn diagnose(text: &str, syms: &Symbols) -> (Range<usize>, Option<String>, String) {
+    let mut assignment = tuple((parse_identifier, multispace0, tag("=")));
+    let assignment = assignment(text).is_ok();
+    let mnemonic = text.find(char::is_whitespace).unwrap_or(text.len());
+    let name = &text[..mnemonic];
+    let mnemonics = mnemonics();
+    let (what, table) = if name.starts_with('.') {
+        ("directive", DIRECTIVES)
+    } else {
+        ("mnemonic", &mnemonics[..])
+    };
+
+    let stem = name.strip_suffix('.').filter(|n| mnemonics.contains(n));
+    let plain = stem.filter(|s| !RECORD_FORMS.contains(s));
+    if let (Some(stem), false) = (plain, assignment) {
+        let message = format!(
+            "`{}` has no record form: of what ppcheat parses, only the rotates \
+             and their pseudo-ops do",
+            stem
+        );
+        return (0..mnemonic, None, message);
+    }
+    let name = stem.filter(|_| !assignment).unwrap_or(name);
+    if !assignment && !table.contains(&name) {
+        let mut message = format!("unknown {} `{}`", what, name);
+        let close: Vec<_> = did_you_mean(name, table)
+            .iter()
+            .map(|m| format!("`{}`", m))
+            .collect();
+        if !close.is_empty() {
+            message += &format!("; did you mean {}?", close.join(" or "));
+        }
+        return (0..mnemonic, None, message);
+    }
+
+    // operands that each parse, but together break the rules of a rotate
+    // or an update form
+    let unchecked = all_consuming(terminated(
+        |i| parse_instruction_spanned(i, syms, false),
+        multispace0,
+    ))(text);
+    if let (Ok((_, (op, spans))), false) = (unchecked, assignment) {
+        if let Err(e) = op.check() {
+            let start = spans.first().map_or(mnemonic, |s| s.start);
+            let end = spans.last().map_or(text.len(), |s| s.end);
+            return (start..end, None, e.to_string());
+        }
+    }
+
+    let failure = if name.starts_with('.') || assignment {
+        parse_directive(text, syms).err()
+    } else {
+        parse_instruction(text, syms).err()
+    };
+    let pos = match failure {
+        Some(nom::Err::Error(f)) | Some(nom::Err::Failure(f)) => text.len() - f.input.len(),
+        _ => 0,
+    }
+    .max(mnemonic);
+    if text[pos..].trim().is_empty() {
+        return (text.len()..text.len(), None, "missing operand".to_string());
+    }
+
+    let start = text[..pos].rfind(',').map_or(mnemonic, |i| i + 1);
+    let end = text[pos..].find(',').map_or(text.len(), |i| pos + i);
+    let operand = text[start..end].trim();
+    let start = start + text[start..].find(operand).unwrap_or(0);
+    // what the operand is, by the forms with as many operands as the line
+    let index = text[mnemonic..start].matches(',').count();
+    let count = text[mnemonic..].matches(',').count() + 1;
+    let kinds: Vec<_> = FORMS
+        .iter()
+        .filter(|f| f.mnemonic == name && f.signature.len() == count)
+        .map(|f| f.signature[index])
+        .collect();
+    let bounds = kinds.iter().find_map(|k| k.bounds());
+    let message = match parse_expr(operand, syms) {
+        Ok(("", v)) if kinds.contains(&Kind::Mask) && u32::try_from(v).is_ok() => {
+            format!("`{}` is not a contiguous mask", operand)
+        }
+        Ok(("", v)) if bounds.as_ref().is_some_and(|(b, _)| !b.contains(&v)) => {
+            let (bounds, what) = bounds.unwrap();
+            format!(
+                "`{}` is out of range: {} is {} to {}",
+                operand,
+                what,
+                bounds.start,
+                bounds.end - 1
+            )
+        }
+        _ => format!("invalid operand `{}`", operand),
+    };
+    (
+        start..start + operand.len(),
+        Some(operand.to_string()),
+        message,
+    )
+}
+
+/// Parses `inp`, detecting whether it's assembler source, an objdump
+/// listing or a Ghidra or IDA export.
+pub fn parse_program(inp: &str) -> Result<Program, ParseError> {
+    parse_listing(inp, Flavor::detect(inp))
+}
+
+pub fn parse_listing(inp: &str, flavor: Flavor) -> Result<Program, ParseError> {
+    let (program, errors) = parse_recovering(inp, flavor);
+    match errors.into_iter().next() {
+        Some(err) => Err(err),
+        None => Ok(program),
+    }
+}
+
+/// Parses every line it can: the lines that parsed, and an error for each
+/// one that didn't. A bad line is left out of the program. `.include`d
+/// files are looked up relative to the working directory.
+pub fn parse_recovering(inp: &str, flavor: Flavor) -> (Program, Vec<ParseError>) {
+    parse_source(inp, flavor, None, &SourceOptions::default())
+}
+
+/// Settings from outside the source, e.g. from the command line.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceOptions {
+    /// Where `.include` looks after the including file's directory
+    pub include_dirs: Vec<PathBuf>,
+    /// Constants defined before the first line, for `.if` and `.ifdef` to
+    /// test (e.g. which region to build for)
+    pub defines: Symbols,
+}
+
+/// Where `.include "name"` inside `from` (the main input if `None`) finds
+/// `name`.
+fn find_include(name: &str, from: Option<&Path>, include_dirs: &[PathBuf]) -> Option<PathBuf> {
+    let here = from.and_then(Path::parent).unwrap_or_else(|| Path::new(""));
+    iter::once(here)
+        .chain(include_dirs.iter().map(PathBuf::as_path))
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// A line yet to be parsed.
+struct Pending {
+    /// 1-based line number within its file; lines expanded from a macro
+    /// keep the number of the invocation
+    number: usize,
+    raw: String,
+    /// Index into the files read so far
+    file: usize,
+    /// How many macro expansions the line is nested in
+    depth: usize,
+}
+
+impl Pending {
+    /// An error spanning the whole line, for a construct it opened and
+    /// nothing closed.
+    fn unclosed(self, files: &[SourceFile], message: String) -> ParseError {
+        ParseError {
+            file: match self.file {
+                0 => None,
+                file => files[file].path.clone(),
+            },
+            line: self.number,
+            span: 0..self.raw.len(),
+            source: self.raw,
+            operand: None,
+            message,
+        }
+    }
+}
+
+/// An `.if` awaiting its `.endif`.
+struct Conditional {
+    /// The `.if` line and which of the `.if` directives it is
+    start: Pending,
+    directive: String,
+    /// Whether the enclosing lines are being assembled
+    outer: bool,
+    /// Whether the current branch is
+    active: bool,
+    /// Whether any branch so far was chosen
+    taken: bool,
+    else_seen: bool,
+}
+
+/// A file read for the program, and the file that included it.
+struct SourceFile {
+    path: Option<PathBuf>,
+    canonical: Option<PathBuf>,
+    parent: Option<usize>,
+}
+
+fn pending_lines(inp: &str, file: usize) -> impl DoubleEndedIterator<Item = Pending> + '_ {
+    inp.lines()
+        .enumerate()
+        .map(move |(idx, raw)| Pending {
+            number: idx + 1,
+            raw: raw.to_string(),
+            file,
+            depth: 0,
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+}
+
+/// [`parse_recovering`] for the contents of the file at `origin`. It and
+/// the files it `.include`s look for included files next to themselves
+/// first, then in each of the include directories in turn.
+pub fn parse_source(
+    inp: &str,
+    flavor: Flavor,
+    origin: Option<&Path>,
+    options: &SourceOptions,
+) -> (Program, Vec<ParseError>) {
+    /// Nesting limit for macro expansion, to stop a macro invoking itself
+    const MAX_DEPTH: usize = 64;
+
+    let mut program = Program {
+        symbols: options.defines.clone(),
+        ..Program::default()
+    };
+    let mut errors = Vec::new();
+    let mut files = vec![SourceFile {
+        path: origin.map(Path::to_path_buf),
+        canonical: origin.and_then(|path| path.canonicalize().ok()),
+        parent: None,
+    }];
+    // last one first
+    let mut pending: Vec<Pending> = pending_lines(inp, 0).collect();
+    // the macro being defined: its `.macro` line, name, definition so far
+    // and the depth of `.macro`s nested within it
+    let mut defining: Option<(Pending, String, Macro, usize)> = None;
+    let mut conditionals: Vec<Conditional> = Vec::new();
+    let mut expansions = 0;
+
+    while let Some(line) = pending.pop() {
+        let (number, depth, file) = (line.number, line.depth, line.file);
+        let raw = line.raw.as_str();
+        // lines of an included file name it; the main input's don't
+        let included = match file {
+            0 => None,
+            _ => files[file].path.clone(),
+        };
+        let (text, address, encoding) = match split_columns(raw, flavor) {
+            Some(columns) => columns,
+            None => {
+                if let Some((_, _, ref mut definition, _)) = defining {
+                    definition.body.push(raw.to_string());
+                }
+                continue;
+            }
+        };
+        let text = text.as_str();
+        // Ghidra labels get their `:` added by `split_columns`
+        let offset = raw
+            .find(text)
+            .or_else(|| raw.find(text.trim_end_matches(':')))
+            .unwrap_or(0);
+        let end = text.find(char::is_whitespace).unwrap_or(text.len());
+        let (word, rest) = text.split_at(end);
+        let error = |span: Range<usize>, message: String| ParseError {
+            file: included.clone(),
+            line: number,
+            source: raw.to_string(),
+            span: span.start + offset..span.end + offset,
+            operand: None,
+            message,
+        };
+
+        if let Some((_, _, ref mut definition, ref mut nested)) = defining {
+            match word {
+                ".endm" if *nested == 0 => {
+                    let (_, name, definition, _) = defining.take().unwrap();
+                    program.macros.insert(name, definition);
+                }
+                _ => {
+                    match word {
+                        ".macro" => *nested += 1,
+                        ".endm" => *nested -= 1,
+                        _ => {}
+                    }
+                    definition.body.push(raw.to_string());
+                }
+            }
+            continue;
+        }
+
+        let active = conditionals.last().is_none_or(|c| c.active);
+        let syms = &program.symbols;
+        let condition = |directive: &str| -> Result<bool, ParseError> {
+            let arg = rest.trim();
+            let value = match directive {
+                ".if" | ".elseif" => all_consuming(|i| parse_condition(i, syms))(arg)
+                    .map(|(_, value)| value != 0)
+                    .ok(),
+                _ => all_consuming(parse_identifier)(arg)
+                    .map(|(_, name)| syms.is_defined(name) == (directive == ".ifdef"))
+                    .ok(),
+            };
+            value.ok_or_else(|| {
+                let message = match arg {
+                    "" => format!("`{}` needs a condition", directive),
+                    _ => format!("invalid condition `{}`", arg),
+                };
+                error(text.len() - arg.len()..text.len(), message)
+            })
+        };
+        match word {
+            ".if" | ".ifdef" | ".ifndef" => {
+                // a bad condition skips every branch
+                let value = match active {
+                    true => condition(word).map_err(|e| errors.push(e)).ok(),
+                    false => None,
+                };
+                conditionals.push(Conditional {
+                    start: Pending {
+                        raw: raw.to_string(),
+                        ..line
+                    },
+                    directive: word.to_string(),
+                    outer: active,
+                    active: value == Some(true),
+                    taken: value != Some(false),
+                    else_seen: false,
+                });
+                continue;
+            }
+            ".elseif" | ".else" | ".endif" => {
+                let open = match conditionals.last_mut() {
+                    Some(open) => open,
+                    None => {
+                        errors.push(error(0..end, format!("`{}` without `.if`", word)));
+                        continue;
+                    }
+                };
+                if word == ".endif" {
+                    conditionals.pop();
+                    continue;
+                }
+                if open.else_seen {
+                    errors.push(error(0..end, format!("`{}` after `.else`", word)));
+                    continue;
+                }
+                open.else_seen = word == ".else";
+                open.active = false;
+                if open.outer && !open.taken {
+                    // a bad `.elseif` skips the remaining branches too
+                    let value = match word {
+                        ".else" => Ok(true),
+                        _ => condition(word),
+                    };
+                    open.active = matches!(value, Ok(true));
+                    open.taken = !matches!(value, Ok(false));
+                    if let Err(e) = value {
+                        errors.push(e);
+                    }
+                }
+                continue;
+            }
+            _ if !active => continue,
+            _ => {}
+        }
+
+        match word {
+            ".macro" => {
+                match Macro::parse_header(rest) {
+                    Ok((name, definition)) => {
+                        let start = Pending {
+                            raw: raw.to_string(),
+                            ..line
+                        };
+                        defining = Some((start, name, definition, 0))
+                    }
+                    Err(message) => errors.push(error(end..text.len(), message)),
+                }
+                continue;
+            }
+            ".endm" => {
+                errors.push(error(0..end, "`.endm` without `.macro`".to_string()));
+                continue;
+            }
+            ".include" => {
+                let arg = rest.trim();
+                let name = match arg.strip_prefix('"').and_then(|a| a.strip_suffix('"')) {
+                    Some(name) if !name.is_empty() => name,
+                    _ => {
+                        errors.push(error(
+                            end..text.len(),
+                            "expected a quoted file name".to_string(),
+                        ));
+                        continue;
+                    }
+                };
+                let span = text.len() - arg.len()..text.len();
+                let from = files[file].path.as_deref();
+                let path = match find_include(name, from, &options.include_dirs) {
+                    Some(path) => path,
+                    None => {
+                        errors.push(error(span, format!("cannot find `{}`", name)));
+                        continue;
+                    }
+                };
+                let canonical = path.canonicalize().ok();
+                let mut chain = vec![path.display().to_string()];
+                let mut at = Some(file);
+                while let Some(idx) = at {
+                    let including = &files[idx];
+                    chain.push(
+                        including
+                            .path
+                            .as_ref()
+                            .map_or_else(|| "<input>".to_string(), |p| p.display().to_string()),
+                    );
+                    if canonical.is_some() && including.canonical == canonical {
+                        break;
+                    }
+                    at = including.parent;
+                }
+                if at.is_some() {
+                    chain.reverse();
+                    errors.push(error(
+                        span,
+                        format!("include cycle: {}", chain.join(" -> ")),
+                    ));
+                    continue;
+                }
+                match fs::read_to_string(&path) {
+                    Ok(contents) => {
+                        files.push(SourceFile {
+                            path: Some(path),
+                            canonical,
+                            parent: Some(file),
+                        });
+                        pending.extend(pending_lines(&contents, files.len() - 1));
+                    }
+                    Err(e) => errors.push(error(span, format!("cannot read `{}`: {}", name, e))),
+                }
+                continue;
+            }
+            _ => {}
+        }
+        if let Some(definition) = program.macros.get(word) {
+            if depth >= MAX_DEPTH {
+                errors.push(error(
+                    0..end,
+                    format!("macro `{}` expands too deeply; is it recursive?", word),
+                ));
+                continue;
+            }
+            match definition.expand(rest, expansions) {
+                Ok(lines) => {
+                    expansions += 1;
+                    pending.extend(lines.into_iter().rev().map(|raw| Pending {
+                        number,
+                        raw,
+                        file,
+                        depth: depth + 1,
+                    }));
+                }
+                Err(message) => errors.push(error(end..text.len(), message)),
+            }
+            continue;
+        }
+
+        let syms = &program.symbols;
+        let parsed = alt((
+            map(|i| parse_directive(i, syms), Statement::Directive),
+            map(parse_label, |name| Statement::Label(name.to_string())),
+            map(|i| parse_instruction(i, syms), Statement::Instruction),
+        ))(text);
+        // `loop: bdnz loop`: the label, then the rest of the line as a line
+        // of its own, blanked up to where it starts so spans still fit
+        if let Ok((rest, Statement::Label(name))) = &parsed {
+            if !rest.trim().is_empty() {
+                let end = offset + text.len() - rest.len();
+                program.lines.push(Line {
+                    file: included,
+                    number,
+                    source: raw[..end].to_string(),
+                    statement: Statement::Label(name.clone()),
+                    reloc: None,
+                    address,
+                    encoding: None,
+                    span: offset..end,
+                    operands: Vec::new(),
+                });
+                pending.push(Pending {
+                    raw: format!("{:width$}{}", "", &raw[end..], width = end),
+                    ..line
+                });
+                continue;
+            }
+        }
+        let statement = match parsed {
+            Ok((rest, statement)) if rest.trim().is_empty() => Ok(statement),
+            Ok((rest, statement)) => {
+                let junk = rest.trim();
+                let start = text.len() - rest.trim_start().len();
+                let what = match statement {
+                    Statement::Instruction(_) => "instruction",
+                    Statement::Directive(_) => "directive",
+                    Statement::Label(_) => "label",
+                };
+                Err((
+                    start..start + junk.len(),
+                    None,
+                    format!("unexpected `{}` after the {}", junk, what),
+                ))
+            }
+            Err(_) => Err(diagnose(text, syms)),
+        };
+        let statement = match statement {
+            Ok(statement) => statement,
+            Err((span, operand, message)) => {
+                errors.push(ParseError {
+                    file: included,
+                    line: number,
+                    source: raw.to_string(),
+                    span: span.start + offset..span.end + offset,
+                    operand,
+                    message,
+                });
+                continue;
+            }
+        };
+
+        if let Statement::Directive(Directive::Set { name, value }) = &statement {
+            program.symbols.define(name, *value);
+        }
+        let (reloc, operands) = match statement {
+            Statement::Instruction(op) => {
+                let operands = parse_instruction_spanned(text, &program.symbols, true)
+                    .map(|(_, (_, spans))| spans)
+                    .unwrap_or_default();
+                let reloc = match op {
+                    Opcode::B { .. } | Opcode::Bc { .. } => operands
+                        .last()
+                        .and_then(|span| find_branch_target(&text[span.clone()], &program.symbols)),
+                    _ => find_relocation(text, &program.symbols),
+                };
+                (reloc, operands)
+            }
+            _ => (None, Vec::new()),
+        };
+        program.lines.push(Line {
+            file: included,
+            number,
+            source: raw.to_string(),
+            statement,
+            reloc,
+            address,
+            encoding,
+            span: offset..(offset + text.len()).min(raw.len()),
+            operands: operands
+                .into_iter()
+                .map(|s| s.start + offset..s.end + offset)
+                .collect(),
+        });
+    }
+    if let Some((start, name, ..)) = defining {
+        errors.push(start.unclosed(&files, format!("macro `{}` has no `.endm`", name)));
+    }
+    for open in conditionals {
+        let message = format!("`{}` has no `.endif`", open.directive);
+        errors.push(open.start.unclosed(&files, message));
+    }
+
+    (program, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_program() {
+        let src = "\n  rlwinm r0,r7,0x10,0x0,0xf  # swap halves\n\nsrwi r3,r4,8\n";
+        let program = parse_program(src).expect("parse failed");
+
+        assert_eq!(program.lines.len(), 2);
+        assert_eq!(program.lines[0].number, 2);
+        assert_eq!(program.lines[1].number, 4);
+
+        let err = parse_program("slwi r3,r4,2\nbogus r1\n").unwrap_err();
+        assert_eq!(err.line, 2);
+
+        // a statement after a label on the same line, as GNU as takes it
+        let program = parse_program("loop: bdnz loop\n").unwrap();
+        assert_eq!(
+            program.lines[0].statement,
+            Statement::Label("loop".to_string())
+        );
+        assert_eq!(program.lines[0].source, "loop:");
+        assert!(matches!(
+            program.lines[1].statement,
+            Statement::Instruction(Opcode::Bc { .. })
+        ));
+        assert_eq!(program.lines[1].number, 1);
+        assert_eq!(program.lines[1].operands, vec![11..15]);
+        assert_eq!(
+            crate::asm::assemble(&program, 0x8000_3100).unwrap(),
+            [0x42, 0x00, 0x00, 0x00]
+        );
+        let err = parse_program("top: bogus r1\n").unwrap_err();
+        assert_eq!(err.span, 5..10);
+
+        // a comment marker inside a literal doesn't start a comment
+        assert_eq!(strip_comment("li r3,'#' # hash", '#'), "li r3,'#' ");
+        assert_eq!(
+            strip_comment(".include \"a#b.s\"", '#'),
+            ".include \"a#b.s\""
+        );
+        assert_eq!(strip_comment("li r3,'\\'' ; quote", ';'), "li r3,'\\'' ");
+        let program = parse_program("li r3,'#'  # a hash\n").unwrap();
+        assert_eq!(
+            program.lines[0].statement,
+            Statement::Instruction(Opcode::Li {
+                rd: Register(3),
+                simm: 0x23
+            })
+        );
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let err = parse_program("slwi r3,r4,2\n  rlwimn r3,r4,0,0,31\n").unwrap_err();
+        assert_eq!(err.span, 2..8);
+        assert_eq!(
+            err.to_string(),
+            "line 2: unknown mnemonic `rlwimn`; did you mean `rlwinm` or `rlwimi`?\n  |\n\
+             2 |   rlwimn r3,r4,0,0,31\n  |   ^^^^^^"
+        );
+        assert_eq!(
+            parse_program(".lnog 1").unwrap_err().message,
+            "unknown directive `.lnog`; did you mean `.long`?"
+        );
+        assert_eq!(
+            parse_program("addi. r3,r4,1").unwrap_err().message,
+            "`addi` has no record form: of what ppcheat parses, only the rotates \
+             and their pseudo-ops do"
+        );
+        let err = parse_program("rlwinm. r3,r4,40,0,31").unwrap_err();
+        assert_eq!(err.span, 14..16);
+        assert_eq!(
+            err.message,
+            "`40` is out of range: a shift or bit count is 0 to 31"
+        );
+        let err = parse_program("extlwi. r3,r4,20,20").unwrap_err();
+        assert_eq!(err.span, 8..19);
+        assert_eq!(
+            err.message,
+            "`extlwi r3,r4,20,20` needs n > 0 and b + n <= 32"
+        );
+        assert_eq!(
+            parse_program("frobnicate r3").unwrap_err().message,
+            "unknown mnemonic `frobnicate`"
+        );
+
+        let err = parse_program("rlwinm r3,r4,40,0,31").unwrap_err();
+        assert_eq!(err.span, 13..15);
+        assert_eq!(
+            err.message,
+            "`40` is out of range: a shift or bit count is 0 to 31"
+        );
+        assert_eq!(
+            parse_program("rlwimi r3,r4,0,0,255").unwrap_err().message,
+            "`255` is out of range: a bit position is 0 to 31"
+        );
+        assert_eq!(
+            parse_program("inslwi r3,r4,0,8").unwrap_err().message,
+            "`0` is out of range: a field's width is 1 to 32"
+        );
+        let err = parse_program("insrwi r3,r4,8,28").unwrap_err();
+        assert_eq!(err.span, 7..17);
+        assert_eq!(
+            err.message,
+            "`insrwi r3,r4,8,28` needs n > 0 and b + n <= 32"
+        );
+
+        let err = parse_program("lwz r3, 8(r40)").unwrap_err();
+        assert_eq!(err.operand.as_deref(), Some("8(r40)"));
+        assert_eq!(err.span, 8..14);
+        assert_eq!(
+            parse_program("lwz r3,").unwrap_err().message,
+            "missing operand"
+        );
+
+        let (program, errors) = parse_recovering("li r3,1\nbogus\nli r4,2\nlwz\n", Flavor::Asm);
+        assert_eq!(program.lines.len(), 2);
+        let lines: Vec<_> = errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, vec![2, 4]);
+    }
+
+    #[test]
+    fn test_whitespace_and_trailing_junk() {
+        assert_eq!(
+            parse_opcode("rlwinm\tr3,\tr4,\t0,\t0,\t31\t"),
+            parse_opcode("rlwinm r3,r4,0,0,31")
+        );
+        assert_eq!(
+            parse_opcode("lwz   r3 ,  8 ( r1 )"),
+            parse_opcode("lwz r3,8(r1)")
+        );
+        assert!(parse_opcode("rlwinm r3,r4,0,0,31,5").is_err());
+
+        let err = parse_program("li r3,1  junk\n").unwrap_err();
+        assert_eq!(err.message, "unexpected `junk` after the instruction");
+        assert_eq!(err.span, 9..13);
+    }
+
+    #[test]
+    fn test_forms_table() {
+        for form in FORMS {
+            let operands: Vec<_> = form
+                .signature
+                .iter()
+                .map(|kind| match kind {
+                    Kind::Reg => "r3",
+                    Kind::Imm | Kind::Pos | Kind::Count => "4",
+                    Kind::Simm => "8",
+                    Kind::Disp => "8(r1)",
+                    Kind::Mask => "0xFF00",
+                    Kind::CrField => "cr1",
+                    Kind::CrBit => "eq",
+                    Kind::Bo => "20",
+                    Kind::Target | Kind::ShortTarget => ".+8",
+                })
+                .collect();
+            let asm = format!("{} {}", form.mnemonic, operands.join(","));
+            let (_, op) = parse_opcode(&asm).unwrap_or_else(|e| panic!("{}: {:?}", asm, e));
+            assert_eq!(op.mnemonic(), form.mnemonic, "{}", asm);
+            let record = RECORD_FORMS.contains(&form.mnemonic);
+            assert_eq!(op.with_record(true).is_some(), record, "{}", asm);
+        }
+    }
+
+    #[test]
+    fn test_record_forms() {
+        let parse = |asm| parse_opcode(asm).expect("parse failed").1;
+        for asm in &[
+            "rlwinm. r3,r4,0,24,31",
+            "rlwimi. r3,r4,8,16,23",
+            "rlwnm. r3,r4,r5,0,31",
+        ] {
+            let op = parse(asm);
+            assert_eq!(op.to_string(), *asm);
+            assert_eq!(op.record(), Some(Register(3)));
+        }
+        // a pseudo-op's record form is its rotate's
+        assert_eq!(parse("clrlwi. r0,r3,24"), parse("rlwinm. r0,r3,0,24,31"));
+        assert_eq!(parse("rotlw. r3,r4,r5"), parse("rlwnm. r3,r4,r5,0,31"));
+        assert_eq!(parse("rlwinm r3,r4,0,24,31").record(), None);
+    }
+
+    #[test]
+    fn test_mask_operand() {
+        assert_eq!(
+            parse_opcode("rlwinm r3,r4,0,0xFFFF0000"),
+            parse_opcode("rlwinm r3,r4,0,0,15")
+        );
+        assert_eq!(
+            parse_opcode("rlwinm r3,r4,8,0xFF0000FF"),
+            parse_opcode("rlwinm r3,r4,8,24,7")
+        );
+        assert_eq!(
+            parse_program("rlwinm r3,r4,0,0xF0F00000")
+                .unwrap_err()
+                .message,
+            "`0xF0F00000` is not a contiguous mask"
+        );
+    }
+
+    #[test]
+    fn test_spans() {
+        let program = parse_program("  lwz r3, 8(r1)  # load\n").unwrap();
+        let line = &program.lines[0];
+
+        assert_eq!(line.spanned().unwrap().span, 2..15);
+        assert_eq!(line.operands, vec![6..8, 10..15]);
+        assert_eq!(line.operand(1).unwrap().node, "8(r1)");
+    }
+
+    #[test]
+    fn test_bare_registers() {
+        assert_eq!(
+            parse_opcode("rlwinm 0,7,16,0,15"),
+            parse_opcode("rlwinm r0,r7,16,0,15")
+        );
+        assert_eq!(parse_opcode("lwz 3,8(1)"), parse_opcode("lwz r3,8(r1)"));
+        assert!(parse_opcode("rlwinm r32,r7,16,0,15").is_err());
+        assert_eq!(
+            parse_opcode("rlwinm %r0,gpr7,16,0,15"),
+            parse_opcode("rlwinm r0,r7,16,0,15")
+        );
+    }
+
+    #[test]
+    fn test_expression_operands() {
+        assert_eq!(
+            parse_opcode("rlwinm r3,r4,32-5,5,31"),
+            parse_opcode("rlwinm r3,r4,27,5,31")
+        );
+        assert_eq!(
+            parse_opcode("lwz r3,(2*4)(r1)"),
+            parse_opcode("lwz r3,8(r1)")
+        );
+        assert_eq!(parse_opcode("li r3,'A'"), parse_opcode("li r3,65"));
+    }
+
+    #[test]
+    fn test_signed_immediates() {
+        let (_, op) = parse_opcode("addi r3,r3,-1").unwrap();
+        assert_eq!(
+            op,
+            Opcode::Addi {
+                rd: Register(3),
+                ra: Register(3),
+                simm: -1
+            }
+        );
+        assert_eq!(parse_opcode("li r3,0xFFFF"), parse_opcode("li r3,-1"));
+        assert!(parse_opcode("li r3,0x10000").is_err());
+        assert!(parse_opcode("slwi r3,r4,-2").is_err());
+    }
+
+    #[test]
+    fn test_set_directives() {
+        let src = ".set FIELD_SHIFT, 12\n.equ WIDTH, 32 - FIELD_SHIFT\nMASK_END = 31\n\
+                   rlwinm r3,r4,32-FIELD_SHIFT,FIELD_SHIFT,MASK_END\n";
+        let program = parse_program(src).unwrap();
+
+        assert_eq!(program.symbols.get("WIDTH"), Some(20));
+        assert_eq!(
+            program.instructions().collect::<Vec<_>>(),
+            vec![&parse_opcode("rlwinm r3,r4,20,12,31").unwrap().1]
+        );
+        assert_eq!(parse_program("slwi r3,r4,SHIFT\n").unwrap_err().line, 1);
+    }
+
+    #[test]
+    fn test_assembler_directives() {
+        let src = "\
+.section .text
+.global fn_80003100
+fn_80003100:
+    slwi r3,r3,2
+.data
+.align 3
+.long 0x80003100, 1
+.float 1.5
+";
+        let program = parse_program(src).unwrap();
+        let statements: Vec<_> = program.lines.iter().map(|l| &l.statement).collect();
+        assert_eq!(
+            statements[..3],
+            [
+                &Statement::Directive(Directive::Section(".text".to_string())),
+                &Statement::Directive(Directive::Global("fn_80003100".to_string())),
+                &Statement::Label("fn_80003100".to_string()),
+            ]
+        );
+        assert_eq!(
+            statements[6],
+            &Statement::Directive(Directive::Data {
+                width: Width::Word,
+                values: vec![0x8000_3100, 1],
+            })
+        );
+        assert_eq!(
+            statements[7],
+            &Statement::Directive(Directive::Data {
+                width: Width::Word,
+                values: vec![0x3FC0_0000],
+            })
+        );
+        assert_eq!(Directive::Align(3).size(0x8000_3104), 4);
+    }
+
+    #[test]
+    fn test_macros() {
+        let src = "\
+.macro load_addr reg, sym
+    lis \\reg, \\sym@ha
+    addi \\reg, \\reg, \\sym@l
+.endm
+load_addr r3, gState
+.set OFFSET, 8
+load_addr r4, gState+OFFSET
+";
+        let program = parse_program(src).unwrap();
+        let ops: Vec<_> = program.instructions().map(|op| op.to_string()).collect();
+        assert_eq!(
+            ops,
+            vec!["lis r3,0x0", "addi r3,r3,0", "lis r4,0x0", "addi r4,r4,0"]
+        );
+        assert_eq!(program.lines[0].number, 5);
+        assert_eq!(
+            program.lines[4]
+                .reloc
+                .as_ref()
+                .map(|r| (r.symbol.as_str(), r.addend)),
+            Some(("gState", 8))
+        );
+
+        let (_, errors) = parse_recovering(".macro m\nslwi r3,r3,2\n", Flavor::Asm);
+        assert_eq!(errors[0].message, "macro `m` has no `.endm`");
+        let (_, errors) = parse_recovering(".macro m\nm\n.endm\nm\n", Flavor::Asm);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 4);
+    }
+
+    #[test]
+    fn test_includes() {
+        let dir = std::env::temp_dir().join(format!("ppcheat-include-{}", std::process::id()));
+        let inc = dir.join("inc");
+        fs::create_dir_all(&inc).unwrap();
+        fs::write(
+            inc.join("macros.inc"),
+            ".macro clear reg\nli \\reg, 0\n.endm\n.include \"consts.inc\"\n",
+        )
+        .unwrap();
+        fs::write(inc.join("consts.inc"), ".set BIAS, 4\nbogus\n").unwrap();
+        fs::write(dir.join("loop.inc"), ".include \"loop.inc\"\n").unwrap();
+        let main = dir.join("main.s");
+        let options = SourceOptions {
+            include_dirs: vec![inc.clone()],
+            ..SourceOptions::default()
+        };
+
+        let src = ".include \"macros.inc\"\nclear r3\naddi r3,r3,BIAS\n.include \"loop.inc\"\n";
+        let (program, errors) = parse_source(src, Flavor::Asm, Some(&main), &options);
+        let ops: Vec<_> = program.instructions().map(|op| op.to_string()).collect();
+        assert_eq!(ops, vec!["li r3,0", "addi r3,r3,4"]);
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].file, Some(inc.join("consts.inc")));
+        assert_eq!(errors[0].line, 2);
+        assert!(errors[1].message.starts_with("include cycle: "));
+        let (_, errors) = parse_source(src, Flavor::Asm, Some(&main), &SourceOptions::default());
+        assert_eq!(errors[0].message, "cannot find `macros.inc`");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_conditionals() {
+        let src = "\
+.if REGION == NTSC
+    li r3, 0
+.elseif REGION == PAL
+    li r3, 1
+.else
+    li r3, 2
+.endif
+.ifdef DEBUG
+    li r4, 1
+    .ifndef QUIET
+        li r5, 1
+    .endif
+.endif
+";
+        let build = |region, debug| {
+            let mut options = SourceOptions::default();
+            options.defines.define("NTSC", 0);
+            options.defines.define("PAL", 1);
+            options.defines.define("REGION", region);
+            if debug {
+                options.defines.define("DEBUG", 1);
+            }
+            let (program, errors) = parse_source(src, Flavor::Asm, None, &options);
+            assert!(errors.is_empty());
+            program
+                .instructions()
+                .map(|op| op.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(build(1, false), vec!["li r3,1"]);
+        assert_eq!(build(2, true), vec!["li r3,2", "li r4,1", "li r5,1"]);
+
+        let (program, errors) = parse_recovering(".if MISSING\nli r3,0\n.endif\n", Flavor::Asm);
+        assert_eq!(errors[0].message, "invalid condition `MISSING`");
+        assert!(program.lines.is_empty());
+        let (_, errors) = parse_recovering(".if 1\n.else\n.else\n", Flavor::Asm);
+        let messages: Vec<_> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["`.else` after `.else`", "`.if` has no `.endif`"]);
+    }
+
+    #[test]
+    fn test_objdump_listing() {
+        let src = "\
+80045678 <fn_80045678>:
+80045678:\t54 00 84 3e \tsrwi    r0,r0,16
+8004567c:\t5400843e\trlwinm  r0,r0,16,16,31
+";
+        let program = parse_program(src).unwrap();
+
+        assert_eq!(
+            program.lines[0].statement,
+            Statement::Label("fn_80045678".to_string())
+        );
+        assert_eq!(program.lines[0].address, Some(0x8004_5678));
+        assert_eq!(program.lines[1].address, Some(0x8004_5678));
+        assert_eq!(program.lines[1].encoding, Some(0x5400_843e));
+        assert_eq!(program.lines[2].encoding, Some(0x5400_843e));
+        assert_eq!(
+            program.lines[1].statement,
+            Statement::Instruction(parse_opcode("srwi r0,r0,16").unwrap().1)
+        );
+    }
+
+    #[test]
+    fn test_ghidra_and_ida_listings() {
+        let ghidra = "\
+                             **************************************************************
+                             *                          FUNCTION                          *
+                             **************************************************************
+                             undefined FUN_80045678()
+                             FUN_80045678                                    XREF[1]:     80012340(c)
+        ram:80045678 54 00 843e      rlwinm     r0,r0,0x10,0x10,0x1f
+                             LAB_8004567c                                    XREF[1]:     80045690(j)
+        ram:8004567c 90 03 00 08     stw        r0,0x8(r3)
+";
+        assert_eq!(Flavor::detect(ghidra), Flavor::Ghidra);
+        let program = parse_program(ghidra).unwrap();
+        let statements: Vec<_> = program.lines.iter().map(|l| &l.statement).collect();
+        assert_eq!(
+            statements,
+            vec![
+                &Statement::Label("FUN_80045678".to_string()),
+                &Statement::Instruction(parse_opcode("rlwinm r0,r0,16,16,31").unwrap().1),
+                &Statement::Label("LAB_8004567c".to_string()),
+                &Statement::Instruction(parse_opcode("stw r0,8(r3)").unwrap().1),
+            ]
+        );
+        assert_eq!(program.lines[1].address, Some(0x8004_5678));
+        assert_eq!(program.lines[1].encoding, Some(0x5400_843e));
+
+        let ida = "\
+.text:80045678 # =============== S U B R O U T I N E =======================================
+.text:80045678
+.text:80045678 sub_80045678:                           # CODE XREF: sub_80012300+40\u{2191}p
+.text:80045678                 rlwinm    r0, r0, 16, 16, 31
+";
+        assert_eq!(Flavor::detect(ida), Flavor::Ida);
+        let program = parse_program(ida).unwrap();
+        assert_eq!(program.lines.len(), 2);
+        assert_eq!(
+            program.lines[0].statement,
+            Statement::Label("sub_80045678".to_string())
+        );
+        assert_eq!(program.lines[1].address, Some(0x8004_5678));
+    }
+
+    #[test]
+    fn test_relocations() {
+        let program = parse_program("lis r3,gState@ha\nlwz r4,gState+8@l(r3)\n").unwrap();
+        assert_eq!(
+            program.lines[1].reloc,
+            Some(Relocation {
+                reloc: Reloc::Lo,
+                symbol: "gState".to_string(),
+                addend: 8,
+            })
+        );
+        assert_eq!(program.lines[1].statement, {
+            let (_, op) = parse_opcode("lwz r4,0(r3)").unwrap();
+            Statement::Instruction(op)
+        });
+
+        let program = parse_program(".set ADDR, 0x80459abc\nlis r3,ADDR@ha\n").unwrap();
+        assert_eq!(program.lines[1].reloc, None);
+        assert_eq!(
+            program.instructions().next(),
+            Some(&parse_opcode("lis r3,0x8046").unwrap().1)
+        );
+
+        let program = parse_program(
+            "bl 0x80001000
+b .+8
+",
+        )
+        .unwrap();
+        let reloc = program.lines[0].reloc.as_ref().unwrap();
+        assert_eq!(
+            reloc,
+            &Relocation {
+                reloc: Reloc::Rel,
+                symbol: String::new(),
+                addend: 0x8000_1000,
+            }
+        );
+        assert_eq!(reloc.to_string(), "0x80001000");
+        assert_eq!(program.lines[1].reloc, None);
+        assert!(parse_opcode("b 0x100000000").is_err());
+    }
+
+    #[test]
+    fn test_condition_register_operands() {
+        let (_, op) = parse_opcode("cror 4*cr1+eq,lt,4*cr7+gt").unwrap();
+        assert_eq!(
+            op,
+            Opcode::CrOp {
+                op: CrLogic::Or,
+                bt: CrBit(6),
+                ba: CrBit(0),
+                bb: CrBit(29),
+            }
+        );
+        assert_eq!(op.to_string(), "cror 4*cr1+eq,lt,4*cr7+gt");
+        assert_eq!(op.highlevel(), "cr1.eq = cr0.lt | cr7.gt");
+
+        assert_eq!(parse_opcode("cmpw r3,r4"), parse_opcode("cmpw cr0,r3,r4"));
+        assert_eq!(
+            parse_opcode("cmplwi 7,3,8"),
+            parse_opcode("cmplwi cr7,r3,8")
+        );
+        assert!(parse_opcode("cmpw cr8,r3,r4").is_err());
+    }
+
+    #[test]
+    fn test_register_aliases() {
+        let (_, op) = parse_opcode("stwu sp,-0x10(sp)").unwrap();
+        assert_eq!(op.to_string(), "stwu r1,-16(r1)");
+        assert_eq!(format!("{:#}", op), "stwu sp,-16(sp)");
+
+        let (_, op) = parse_opcode("lwz fp,8(rtoc)").unwrap();
+        assert_eq!(op.to_string(), "lwz r31,8(r2)");
+    }
 }
@@ -0,0 +1,147 @@
+//! A per-game log of the patches made to it, so a patch set stays auditable
+//! and can be undone. One patch per line, tab-separated:
+//!
+//! ```text
+//! # timestamp  address   format  old                         new         note
+//! 1760400000   80045678  gecko   rlwinm r0,r0,16,16,31       li r0,0     skip intro
+//! ```
+
+use core::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Patch {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub addr: u32,
+    /// Code format the patch was written as, e.g. `gecko` or `riivolution`
+    pub format: String,
+    pub old: String,
+    pub new: String,
+    pub note: String,
+}
+
+impl Patch {
+    /// The patch that undoes this one.
+    pub fn inverse(&self) -> Patch {
+        Patch {
+            old: self.new.clone(),
+            new: self.old.clone(),
+            note: format!("revert: {}", self.note),
+            ..self.clone()
+        }
+    }
+}
+
+impl fmt::Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{:08x}\t{}\t{}\t{}\t{}",
+            self.timestamp, self.addr, self.format, self.old, self.new, self.note
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchLogError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PatchLogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for PatchLogError {}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PatchLog {
+    /// Oldest first
+    pub patches: Vec<Patch>,
+}
+
+impl PatchLog {
+    pub fn parse(inp: &str) -> Result<Self, PatchLogError> {
+        let mut log = PatchLog::default();
+
+        for (idx, raw) in inp.lines().enumerate() {
+            let line = idx + 1;
+            let err = |message: &str| PatchLogError {
+                line,
+                message: message.to_string(),
+            };
+            if raw.trim().is_empty() || raw.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<_> = raw.splitn(6, '\t').collect();
+            if fields.len() < 5 {
+                return Err(err("expected timestamp, address, format, old and new"));
+            }
+            log.patches.push(Patch {
+                timestamp: fields[0]
+                    .trim()
+                    .parse()
+                    .map_err(|_| err("invalid timestamp"))?,
+                addr: u32::from_str_radix(fields[1].trim(), 16)
+                    .map_err(|_| err("invalid address"))?,
+                format: fields[2].trim().to_string(),
+                old: fields[3].trim().to_string(),
+                new: fields[4].trim().to_string(),
+                note: fields.get(5).map_or("", |n| n.trim()).to_string(),
+            });
+        }
+
+        Ok(log)
+    }
+
+    /// Drops patch `index` (0-based) from the log, returning the patch that
+    /// undoes it.
+    pub fn revert(&mut self, index: usize) -> Option<Patch> {
+        if index >= self.patches.len() {
+            return None;
+        }
+        Some(self.patches.remove(index).inverse())
+    }
+}
+
+impl fmt::Display for PatchLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# timestamp\taddress\tformat\told\tnew\tnote")?;
+        for patch in &self.patches {
+            writeln!(f, "{}", patch)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patch_log() {
+        let mut log = PatchLog::default();
+        log.patches.push(Patch {
+            timestamp: 1_760_400_000,
+            addr: 0x8004_5678,
+            format: "gecko".to_string(),
+            old: "rlwinm r0,r0,16,16,31".to_string(),
+            new: "li r0,0".to_string(),
+            note: "skip intro".to_string(),
+        });
+
+        let reparsed = PatchLog::parse(&log.to_string()).unwrap();
+        assert_eq!(reparsed, log);
+
+        let undo = log.revert(0).unwrap();
+        assert_eq!(
+            (undo.old.as_str(), undo.new.as_str()),
+            ("li r0,0", "rlwinm r0,r0,16,16,31")
+        );
+        assert!(log.patches.is_empty());
+        assert!(PatchLog::parse("1\tzz\tgecko\ta\tb\n").is_err());
+    }
+}
@@ -0,0 +1,324 @@
+//! Porting addresses, and the codes that use them, from one build of a game
+//! to another, NTSC-U to PAL say: by the symbol an address is in and how far
+//! into it, given a symbol map of each build, or by finding the instructions
+//! at it in the other build's DOL.
+//!
+//! Neither way is sure: a function can change between builds, or its
+//! instructions turn up twice. What might be wrong is said alongside each
+//! port, and what can't be ported at all is an error.
+
+use core::fmt;
+
+use crate::gecko::{base_after, Base, CodeType, Line, Operand, ADDRESS_MASK, BASE_ADDRESS};
+use crate::image::Image;
+use crate::symbols::SymbolTable;
+
+/// Where an address of one build is in the other.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ported {
+    pub addr: u32,
+    /// What it was matched by: `main+0x1c`, or the instructions at it
+    pub by: String,
+    /// Why the match might be wrong, if it might
+    pub doubt: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PortError {
+    /// No symbol of the first map covers the address
+    Unnamed(u32),
+    /// The second map has no symbol of this name
+    Missing(String),
+    /// One map or the other has more than one symbol of this name
+    Duplicate(String),
+    /// The address is `offset` into a symbol only `size` bytes long in the
+    /// second map
+    Outside {
+        name: String,
+        offset: u32,
+        size: u32,
+    },
+    /// The first image doesn't load the address
+    NotLoaded(u32),
+    /// The instructions at the address are in `count` places of the second
+    /// image, rather than one
+    Matches { addr: u32, count: usize },
+    /// The ported address is out of reach of a code's base address
+    OutOfReach { addr: u32, ported: u32 },
+}
+
+impl fmt::Display for PortError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unnamed(addr) => write!(f, "no symbol covers {:08x}", addr),
+            Self::Missing(name) => write!(f, "the other map has no {}", name),
+            Self::Duplicate(name) => write!(f, "there's more than one {}", name),
+            Self::Outside { name, offset, size } => write!(
+                f,
+                "{}+{:#x} is past the end of {}, only {:#x} bytes in the other map",
+                name, offset, name, size
+            ),
+            Self::NotLoaded(addr) => write!(f, "{:08x} isn't loaded from the image", addr),
+            Self::Matches { addr, count: 0 } => {
+                write!(
+                    f,
+                    "the instructions at {:08x} aren't in the other image",
+                    addr
+                )
+            }
+            Self::Matches { addr, count } => write!(
+                f,
+                "the instructions at {:08x} are in {} places of the other image",
+                addr, count
+            ),
+            Self::OutOfReach { addr, ported } => write!(
+                f,
+                "{:08x} ports to {:08x}, out of reach of the code's base address",
+                addr, ported
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PortError {}
+
+/// `addr` in the build `to` maps, by the symbol of `from` covering it.
+pub fn by_symbols(addr: u32, from: &SymbolTable, to: &SymbolTable) -> Result<Ported, PortError> {
+    let (symbol, offset) = from.enclosing(addr).ok_or(PortError::Unnamed(addr))?;
+    let named = |table: &SymbolTable| table.iter().filter(|s| s.name == symbol.name).count();
+    if named(from) > 1 {
+        return Err(PortError::Duplicate(symbol.name.clone()));
+    }
+    let mut targets = to.iter().filter(|s| s.name == symbol.name);
+    let target = targets
+        .next()
+        .ok_or_else(|| PortError::Missing(symbol.name.clone()))?;
+    if targets.next().is_some() {
+        return Err(PortError::Duplicate(symbol.name.clone()));
+    }
+    if let Some(size) = target.size.filter(|&size| size > 0 && offset >= size) {
+        return Err(PortError::Outside {
+            name: symbol.name.clone(),
+            offset,
+            size,
+        });
+    }
+
+    let doubt = match (symbol.size, target.size) {
+        (Some(a), Some(b)) if a != b => Some(format!(
+            "{} is {:#x} bytes in one map and {:#x} in the other",
+            symbol.name, a, b
+        )),
+        _ => None,
+    };
+    Ok(Ported {
+        addr: target.addr.wrapping_add(offset),
+        by: match offset {
+            0 => symbol.name.clone(),
+            _ => format!("{}+{:#x}", symbol.name, offset),
+        },
+        doubt,
+    })
+}
+
+/// How many words from the address on [`by_images`] matches.
+const SIGNATURE: u32 = 8;
+
+/// The bits of `word` that stay the same between builds: all but branch
+/// displacements, and the immediates of `addi`, `addis` and of loads and
+/// stores off r0, r2 or r13, which point at things that moved.
+pub(crate) fn signature_mask(word: u32) -> u32 {
+    let ra = (word >> 16) & 0x1F;
+    match word >> 26 {
+        18 => 0xFC00_0003,
+        16 => 0xFFFF_0003,
+        14 | 15 => 0xFFFF_0000,
+        32..=55 if ra == 0 || ra == 2 || ra == 13 => 0xFFFF_0000,
+        _ => 0xFFFF_FFFF,
+    }
+}
+
+/// `addr` in the image `to`, by where the instructions at it in `from` are.
+/// Up to 8 words from `addr` on are matched, ignoring the bits
+/// [`signature_mask`] masks, and have to match just one place.
+pub fn by_images(addr: u32, from: &Image, to: &Image) -> Result<Ported, PortError> {
+    let bytes = (1..=SIGNATURE)
+        .rev()
+        .find_map(|n| from.read(addr, 4 * n))
+        .ok_or(PortError::NotLoaded(addr))?;
+    let signature: Vec<_> = bytes
+        .chunks_exact(4)
+        .map(|w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]))
+        .map(|w| (w & signature_mask(w), signature_mask(w)))
+        .collect();
+
+    let mut found = Vec::new();
+    for section in &to.sections {
+        let words: Vec<_> = section
+            .data
+            .chunks_exact(4)
+            .map(|w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]))
+            .collect();
+        for (i, window) in words.windows(signature.len()).enumerate() {
+            let matches = window
+                .iter()
+                .zip(&signature)
+                .all(|(&word, &(bits, mask))| word & mask == bits);
+            if matches {
+                found.push(section.addr.wrapping_add(4 * i as u32));
+            }
+        }
+    }
+    match found[..] {
+        [ported] => Ok(Ported {
+            addr: ported,
+            by: format!("the {} word(s) at it", signature.len()),
+            doubt: match signature.len() {
+                n if n < 4 => Some(format!("only {} word(s) to match", n)),
+                _ => None,
+            },
+        }),
+        _ => Err(PortError::Matches {
+            addr,
+            count: found.len(),
+        }),
+    }
+}
+
+/// A code list ported, and how each address in it went.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PortedCodes {
+    pub lines: Vec<Line>,
+    /// Each address found in the codes, and where it went or why it didn't
+    pub addresses: Vec<(u32, Result<Ported, PortError>)>,
+    /// The first line of each code left as it is because where it writes
+    /// isn't known until it runs, after a code that loads `ba`
+    pub skipped: Vec<Line>,
+}
+
+/// `codes` with the addresses they write and test, off the default `ba` or
+/// one a 42 code sets, ported by `port`, as well as the addresses 42 and 4A
+/// codes set `ba` and `po` to, 40 and 48 codes load them from, and C6 codes
+/// branch to. Addresses off `po` are left as they are, its base being what
+/// moves. An address that doesn't port is left as it was.
+pub fn port_codes(
+    codes: &[(Vec<Line>, CodeType)],
+    port: impl Fn(u32) -> Result<Ported, PortError>,
+) -> PortedCodes {
+    let mut out = PortedCodes::default();
+    let mut ba = Some(BASE_ADDRESS);
+    let port_word = |word: &mut u32, addresses: &mut Vec<_>| {
+        let was = *word;
+        let result = port(was);
+        if let Ok(ported) = &result {
+            *word = ported.addr;
+        }
+        addresses.push((was, result));
+    };
+
+    for (lines, code) in codes {
+        let mut lines = lines.clone();
+        let first = lines[0];
+        match (code.address(), ba) {
+            (Some(addr), Some(ba)) if addr.base == Base::BaseAddress => {
+                let absolute = ba.wrapping_add(addr.offset);
+                let result = port(absolute).and_then(|ported| {
+                    match ported.addr.wrapping_sub(ba) > ADDRESS_MASK {
+                        true => Err(PortError::OutOfReach {
+                            addr: absolute,
+                            ported: ported.addr,
+                        }),
+                        false => Ok(ported),
+                    }
+                });
+                if let Ok(ported) = &result {
+                    let offset = ported.addr.wrapping_sub(ba).wrapping_sub(addr.offset);
+                    let moved = (first.0 & ADDRESS_MASK).wrapping_add(offset);
+                    lines[0].0 = (first.0 & !ADDRESS_MASK) | (moved & ADDRESS_MASK);
+                }
+                out.addresses.push((absolute, result));
+            }
+            (Some(addr), None) if addr.base == Base::BaseAddress => out.skipped.push(first),
+            _ => {}
+        }
+        match code {
+            CodeType::Branch { .. } => port_word(&mut lines[0].1, &mut out.addresses),
+            CodeType::SetAddress {
+                add: false,
+                value:
+                    Operand {
+                        value,
+                        plus: None,
+                        register: None,
+                    },
+                ..
+            } if *value != BASE_ADDRESS => port_word(&mut lines[0].1, &mut out.addresses),
+            CodeType::LoadAddress {
+                add: false,
+                address:
+                    Operand {
+                        plus: None,
+                        register: None,
+                        ..
+                    },
+                ..
+            } => port_word(&mut lines[0].1, &mut out.addresses),
+            _ => {}
+        }
+        ba = base_after(ba, code, lines[0]);
+        out.lines.extend(lines);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gecko::parse_types;
+
+    #[test]
+    fn test_port() {
+        let us = SymbolTable::parse(
+            "80003100 00000040 80003100 4 main\n\
+             80003140 00000080 80003140 4 updateLives\n",
+        );
+        let pal = SymbolTable::parse(
+            "80003200 00000040 80003200 4 main\n\
+             80003240 00000090 80003240 4 updateLives\n",
+        );
+        let ported = by_symbols(0x8000_3104, &us, &pal).unwrap();
+        assert_eq!((ported.addr, ported.by.as_str()), (0x8000_3204, "main+0x4"));
+        assert!(by_symbols(0x8000_3150, &us, &pal).unwrap().doubt.is_some());
+        assert_eq!(
+            by_symbols(0x8000_9000, &us, &pal),
+            Err(PortError::Unnamed(0x8000_9000))
+        );
+
+        // mflr r0; bl .+0x100; li r3,1; blr, then the same with another bl
+        let code = |bl: u32| {
+            [0x7C08_02A6, bl, 0x3860_0001, 0x4E80_0020]
+                .iter()
+                .flat_map(|w: &u32| w.to_be_bytes())
+                .collect::<Vec<_>>()
+        };
+        let from = Image::raw(&code(0x4800_0101), 0x8000_3100);
+        let to = Image::raw(&[vec![0; 8], code(0x4800_0201)].concat(), 0x8000_3200);
+        assert_eq!(
+            by_images(0x8000_3104, &from, &to).unwrap().addr,
+            0x8000_320C
+        );
+
+        let codes = parse_types(
+            "04003104 38600063\n\
+             42000000 80000000\n\
+             40000000 80003100\n\
+             04000010 00000001\n",
+        )
+        .unwrap();
+        let ported = port_codes(&codes, |addr| by_symbols(addr, &us, &pal));
+        assert_eq!(ported.lines[0], Line(0x0400_3204, 0x3860_0063));
+        assert_eq!(ported.lines[2], Line(0x4000_0000, 0x8000_3200));
+        assert_eq!(ported.skipped, [Line(0x0400_0010, 1)]);
+        assert_eq!(ported.addresses.len(), 2);
+    }
+}
@@ -0,0 +1,405 @@
+//! Hit-count profiles (address -> count, e.g. from Dolphin's JIT block
+//! profiler or a sampling tool) used to weight listings by hotness.
+//!
+//! The format is one `ADDRESS COUNT` pair per line, address in hex and count
+//! in decimal, separated by whitespace, `,` or `:`. A non-numeric first line
+//! is taken to be a CSV header.
+
+use core::fmt;
+use std::collections::BTreeMap;
+
+use crate::budget::{merge_rotates, shorten_constant};
+use crate::parser::{Program, Statement};
+use crate::{parse_hex_u32, CrField, Opcode, Register};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Profile {
+    hits: BTreeMap<u32, u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileError {
+    pub line: usize,
+    pub source: String,
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}: expected `ADDRESS COUNT`: {}",
+            self.line, self.source
+        )
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
+fn parse_entry(line: &str) -> Option<(u32, u64)> {
+    let mut fields = line
+        .split(|c: char| c.is_whitespace() || c == ',' || c == ':')
+        .filter(|f| !f.is_empty());
+    let addr = parse_hex_u32(fields.next()?)?;
+    let count = fields.next()?.parse().ok()?;
+    Some((addr, count))
+}
+
+impl Profile {
+    pub fn parse(inp: &str) -> Result<Self, ProfileError> {
+        let mut profile = Profile::default();
+
+        for (idx, raw) in inp.lines().enumerate() {
+            let text = raw.trim();
+            if text.is_empty() || text.starts_with('#') {
+                continue;
+            }
+            match parse_entry(text) {
+                Some((addr, count)) => *profile.hits.entry(addr).or_insert(0) += count,
+                None if idx == 0 => continue,
+                None => {
+                    return Err(ProfileError {
+                        line: idx + 1,
+                        source: raw.to_string(),
+                    })
+                }
+            }
+        }
+
+        Ok(profile)
+    }
+
+    pub fn hits(&self, addr: u32) -> u64 {
+        self.hits.get(&addr).copied().unwrap_or(0)
+    }
+}
+
+fn is_rotate(op: &Opcode) -> bool {
+    matches!(
+        op.canonicalize(),
+        Opcode::Rlwinm { .. } | Opcode::Rlwimi { .. } | Opcode::Rlwnm { .. }
+    )
+}
+
+/// Instructions of `program` laid out from `base`, after any data and
+/// padding emitted by directives before them. Addresses from a disassembly
+/// listing take precedence.
+pub fn layout(program: &Program, base: u32) -> Vec<(u32, &Opcode)> {
+    program
+        .layout(base, true)
+        .into_iter()
+        .filter_map(|(addr, line)| match &line.statement {
+            Statement::Instruction(op) => Some((addr, op)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A run of consecutive hot instructions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HotSpan {
+    pub start: u32,
+    pub end: u32,
+    pub hits: u64,
+    pub rotates: usize,
+}
+
+/// Maximal runs of instructions hit at least `threshold` times, hottest
+/// first. Rotate-heavy runs are the ones worth a closer look.
+pub fn hot_spans(program: &Program, base: u32, profile: &Profile, threshold: u64) -> Vec<HotSpan> {
+    let mut spans: Vec<HotSpan> = Vec::new();
+    let mut previous_hot = false;
+
+    for (addr, op) in layout(program, base) {
+        let hits = profile.hits(addr);
+        if hits < threshold || hits == 0 {
+            previous_hot = false;
+            continue;
+        }
+        match spans.last_mut() {
+            Some(span) if previous_hot => {
+                span.end = addr;
+                span.hits += hits;
+                span.rotates += is_rotate(op) as usize;
+            }
+            _ => spans.push(HotSpan {
+                start: addr,
+                end: addr,
+                hits,
+                rotates: is_rotate(op) as usize,
+            }),
+        }
+        previous_hot = true;
+    }
+
+    spans.sort_by_key(|span| std::cmp::Reverse(span.hits));
+    spans
+}
+
+/// A rewrite of the instructions from `addr` on, in code hit `hits` times.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Peephole {
+    pub addr: u32,
+    pub hits: u64,
+    pub hint: String,
+}
+
+/// `(rA, rS)` if `op` copies rS to rA as it is: a rotate by 0 under the
+/// full mask, an add of 0 or an OR of 0, each of them `mr` written out.
+fn copy(op: &Opcode) -> Option<(Register, Register)> {
+    match op.canonicalize() {
+        Opcode::Rlwinm {
+            ra,
+            rs,
+            sh: 0,
+            mb: 0,
+            me: 31,
+            rc: false,
+        }
+        | Opcode::Ori { ra, rs, uimm: 0 }
+        | Opcode::Oris { ra, rs, uimm: 0 } => Some((ra, rs)),
+        Opcode::Addi { rd, ra, simm: 0 } if ra.0 != 0 => Some((rd, ra)),
+        _ => None,
+    }
+}
+
+/// `mr rX,rX` does nothing; `ori r0,r0,0` is `nop`, there on purpose.
+fn redundant_move(op: &Opcode) -> Option<String> {
+    match copy(op)? {
+        (ra, rs)
+            if ra == rs
+                && !matches!(
+                    op,
+                    Opcode::Ori {
+                        ra: Register(0),
+                        ..
+                    }
+                ) =>
+        {
+            Some(format!("`{}` copies {} to itself; drop it", op, ra))
+        }
+        _ => None,
+    }
+}
+
+/// `mr rY,rX` straight after `mr rX,rY` copies back what's already there.
+fn move_back(first: &Opcode, second: &Opcode) -> Option<String> {
+    match (copy(first)?, copy(second)?) {
+        ((a, b), (c, d)) if a != b && (c, d) == (b, a) => Some(format!(
+            "`{}` copies back what `{}` copied; drop it",
+            second, first
+        )),
+        _ => None,
+    }
+}
+
+/// `li rX,lo` then `oris rX,rX,hi` is the usual pair the other way round,
+/// with the `li` sign-extending: `lis rX,hi` then `ori rX,rX,lo` loads
+/// 0xHHHHLLLL whatever `lo` is, where this pair loads 0xFFFFLLLL once `lo`
+/// is 0x8000 or more.
+fn li_oris(first: &Opcode, second: &Opcode) -> Option<String> {
+    match (first.canonicalize(), second.canonicalize()) {
+        (
+            Opcode::Addi {
+                rd,
+                ra: Register(0),
+                simm: lo,
+            },
+            Opcode::Oris { ra, rs, uimm: hi },
+        ) if ra == rd && rs == rd => {
+            let lis = Opcode::Lis {
+                rd,
+                simm: hi as i16,
+            };
+            let ori = Opcode::Ori {
+                ra: rd,
+                rs: rd,
+                uimm: lo as u16,
+            };
+            let wanted = (hi as u32) << 16 | lo as u16 as u32;
+            Some(match lo < 0 {
+                false => format!("use {}; {}", lis, ori),
+                true => format!(
+                    "the li sign-extends, loading {:#x}; for {:#x}, use {}; {}",
+                    (hi as u32) << 16 | lo as i32 as u32,
+                    wanted,
+                    lis,
+                    ori
+                ),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// A rotate, then `cmpwi rA,0` into cr0 for a conditional branch, is the
+/// rotate's record form, which compares its result with 0 into cr0 itself.
+fn record_form(first: &Opcode, second: &Opcode, third: &Opcode) -> Option<String> {
+    let record = first.with_record(true)?;
+    match (first.record(), second.canonicalize(), third.canonicalize()) {
+        (
+            None,
+            Opcode::Cmpi {
+                logical: false,
+                crf: CrField(0),
+                ra,
+                imm: 0,
+            },
+            Opcode::Bc { bo, bi, .. },
+        ) if record.record() == Some(ra) && bi.0 < 4 && bo & 0x10 == 0 => {
+            Some(format!("use {} and drop the cmpwi", record))
+        }
+        _ => None,
+    }
+}
+
+/// Peephole suggestions for the instructions of `program` hit at least
+/// `threshold` times, hottest first: rotates to merge, constants loaded
+/// the long way or the wrong way round, moves that do nothing, and
+/// compares a record form makes for free. A label between instructions
+/// keeps them apart, as a branch to it would skip the first.
+pub fn peepholes(program: &Program, base: u32, profile: &Profile, threshold: u64) -> Vec<Peephole> {
+    let mut runs: Vec<Vec<(u32, &Opcode)>> = vec![Vec::new()];
+    for (addr, line) in program.layout(base, true) {
+        match &line.statement {
+            Statement::Instruction(op) => runs.last_mut().unwrap().push((addr, op)),
+            _ => runs.push(Vec::new()),
+        }
+    }
+
+    let mut found = Vec::new();
+    for run in &runs {
+        for (i, &(addr, op)) in run.iter().enumerate() {
+            let hits = profile.hits(addr);
+            if hits < threshold || hits == 0 {
+                continue;
+            }
+            let next = |n: usize| run.get(i + n).map(|&(_, op)| op);
+            let hint = redundant_move(op)
+                .or_else(|| {
+                    let second = next(1)?;
+                    merge_rotates(op, second)
+                        .map(|op| format!("merge the two rotates into {}", op))
+                        .or_else(|| shorten_constant(op, second))
+                        .or_else(|| li_oris(op, second))
+                        .or_else(|| move_back(op, second))
+                })
+                .or_else(|| record_form(op, next(1)?, next(2)?));
+            if let Some(hint) = hint {
+                found.push(Peephole { addr, hits, hint });
+            }
+        }
+    }
+
+    found.sort_by_key(|p| (std::cmp::Reverse(p.hits), p.addr));
+    found
+}
+
+/// Listing of `program` with a hit count, share of total and heat bar per line.
+pub fn annotate(program: &Program, base: u32, profile: &Profile) -> String {
+    const BAR: usize = 10;
+
+    let layout = layout(program, base);
+    let total: u64 = layout.iter().map(|&(addr, _)| profile.hits(addr)).sum();
+    let hottest = layout
+        .iter()
+        .map(|&(addr, _)| profile.hits(addr))
+        .max()
+        .unwrap_or(0);
+
+    let mut out = String::new();
+    for (addr, op) in layout {
+        let hits = profile.hits(addr);
+        let share = if total == 0 {
+            0.0
+        } else {
+            100.0 * hits as f64 / total as f64
+        };
+        let bar = (hits * BAR as u64).checked_div(hottest).unwrap_or(0) as usize;
+        out.push_str(&format!(
+            "{:>10} {:>5.1}% {:<width$} {:08x}  {:<28} {}\n",
+            hits,
+            share,
+            "#".repeat(bar),
+            addr,
+            op.to_string(),
+            op.highlevel(),
+            width = BAR
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_program;
+
+    #[test]
+    fn test_hot_spans() {
+        let profile =
+            Profile::parse("address,hits\n80003000,1\n0x80003004: 900\n80003008 100\n").unwrap();
+        let program =
+            parse_program("li r3,0\nslwi r4,r3,2\nrlwinm r5,r4,8,24,31\nli r3,1\n").unwrap();
+
+        assert_eq!(
+            hot_spans(&program, 0x8000_3000, &profile, 10),
+            vec![HotSpan {
+                start: 0x8000_3004,
+                end: 0x8000_3008,
+                hits: 1000,
+                rotates: 2,
+            }]
+        );
+        assert!(Profile::parse("80003000 1\nbogus\n").is_err());
+    }
+
+    #[test]
+    fn test_peepholes() {
+        let program = parse_program(
+            "li r3,0x10\noris r3,r3,0x8040\nli r4,-4\noris r4,r4,0x8000\n\
+             ori r5,r3,0\naddi r3,r5,0\nrotlwi r6,r6,0\n\
+             clrlwi r7,r6,24\ncmpwi r7,0\nbeq .+8\n\
+             clrlwi r8,r6,24\ncmpwi r8,0\nnext: beq .+8\n\
+             ori r0,r0,0\n",
+        )
+        .unwrap();
+        let mut profile = String::new();
+        for n in 0..14u32 {
+            profile.push_str(&format!("{:x} {}\n", 0x8000_3000 + 4 * n, 100 + n));
+        }
+        let profile = Profile::parse(&profile).unwrap();
+
+        let hints: Vec<_> = peepholes(&program, 0x8000_3000, &profile, 104)
+            .into_iter()
+            .map(|p| (p.addr, p.hint))
+            .collect();
+        assert_eq!(
+            hints,
+            vec![
+                (
+                    0x8000_301C,
+                    "use rlwinm. r7,r6,0,24,31 and drop the cmpwi".to_string()
+                ),
+                (
+                    0x8000_3018,
+                    "`rotlwi r6,r6,0` copies r6 to itself; drop it".to_string()
+                ),
+                (
+                    0x8000_3010,
+                    "`addi r3,r5,0` copies back what `ori r5,r3,0x0` copied; drop it".to_string()
+                ),
+            ]
+        );
+        let hints: Vec<_> = peepholes(&program, 0x8000_3000, &profile, 1)
+            .into_iter()
+            .map(|p| p.hint)
+            .collect();
+        assert_eq!(
+            &hints[3..],
+            [
+                "the li sign-extends, loading 0xfffffffc; for 0x8000fffc, use lis r4,0x8000; ori r4,r4,0xfffc",
+                "use lis r3,0x8040; ori r3,r3,0x10",
+            ]
+        );
+    }
+}
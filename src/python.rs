@@ -0,0 +1,245 @@
+//! Python statements equivalent to each instruction, for cheat scripts.
+//! Python integers don't wrap, so every result is masked back to 32 bits.
+//! GPRs are a list `r` of 32 ints, condition register bits a list `cr` of
+//! 32 bools (bit `4*crN + lt/gt/eq/so`), and `mem` is whatever the script
+//! uses for big-endian memory, with `read_u8/u16/u32(ea)` and
+//! `write_u8/u16/u32(ea, value)`.
+
+use crate::{eval, CrLogic, Opcode, Register, Width};
+
+const WORD: &str = "0xFFFFFFFF";
+
+fn reg(r: Register) -> String {
+    format!("r[{}]", r.0)
+}
+
+/// `(rA|0) + d`: rA=0 is the literal 0.
+fn address(ra: Register, d: i16) -> String {
+    match (ra.0, d) {
+        (0, d) => format!("{:#x}", d as i32 as u32),
+        (_, 0) => reg(ra),
+        (_, d) if d < 0 => format!("({} - {}) & {}", reg(ra), -(d as i32), WORD),
+        (_, d) => format!("({} + {}) & {}", reg(ra), d, WORD),
+    }
+}
+
+/// `value` rotated left by `by` and masked; a full mask is still written
+/// out, since the shifted-out bits have to go.
+fn rotated(value: String, by: Option<String>, mask: u32) -> String {
+    let rotated = match by {
+        None => return format!("{} & 0x{:08X}", value, mask),
+        Some(by) => format!("({v} << {by} | {v} >> (32 - {by}))", v = value, by = by),
+    };
+    format!("{} & 0x{:08X}", rotated, mask)
+}
+
+/// `value` read as a signed 32-bit number.
+fn signed(value: String) -> String {
+    format!("(({} ^ 0x80000000) - 0x80000000)", value)
+}
+
+impl Opcode {
+    /// One line of Python doing what the instruction does, e.g.
+    /// `r[0] = (r[7] << 16 | r[7] >> (32 - 16)) & 0xFFFF0000`. See the
+    /// module docs for what `r`, `cr` and `mem` are. A record form goes on
+    /// to compare its result into cr0.
+    pub fn highlevel_python(&self) -> String {
+        let statement = match self.canonicalize() {
+            Self::Rlwinm {
+                ra, rs, sh, mb, me, ..
+            } => format!(
+                "{} = {}",
+                reg(ra),
+                rotated(
+                    reg(rs),
+                    Some(sh).filter(|&sh| sh % 32 != 0).map(|sh| sh.to_string()),
+                    eval::mask(mb, me)
+                )
+            ),
+            Self::Rlwimi {
+                ra, rs, sh, mb, me, ..
+            } => {
+                let mask = eval::mask(mb, me);
+                format!(
+                    "{ra} = {ra} & 0x{keep:08X} | {value}",
+                    ra = reg(ra),
+                    keep = !mask,
+                    value = rotated(
+                        reg(rs),
+                        Some(sh).filter(|&sh| sh % 32 != 0).map(|sh| sh.to_string()),
+                        mask
+                    )
+                )
+            }
+            Self::Rlwnm {
+                ra, rs, rb, mb, me, ..
+            } => format!(
+                "{} = {}",
+                reg(ra),
+                rotated(
+                    reg(rs),
+                    Some(format!("({} & 31)", reg(rb))),
+                    eval::mask(mb, me)
+                )
+            ),
+            Self::Addi { rd, ra, simm } if ra.0 == 0 => {
+                format!("{} = {} & {}", reg(rd), simm, WORD)
+            }
+            Self::Addi { rd, ra, simm } => format!("{} = {}", reg(rd), address(ra, simm)),
+            Self::Addis { rd, ra, simm } => {
+                let value = (simm as u16 as u32) << 16;
+                match ra.0 {
+                    0 => format!("{} = 0x{:08X}", reg(rd), value),
+                    _ => format!("{} = ({} + 0x{:08X}) & {}", reg(rd), reg(ra), value, WORD),
+                }
+            }
+            Self::Ori { ra, rs, uimm } => format!("{} = {} | 0x{:08X}", reg(ra), reg(rs), uimm),
+            Self::Oris { ra, rs, uimm } => {
+                let value = u32::from(uimm) << 16;
+                format!("{} = {} | 0x{:08X}", reg(ra), reg(rs), value)
+            }
+            Self::Load {
+                width,
+                update,
+                rd,
+                d,
+                ra,
+            } => update_form(update, ra, d, |ea| {
+                format!("{} = mem.read_{}({})", reg(rd), width.c_type(), ea)
+            }),
+            Self::Lha { update, rd, d, ra } => update_form(update, ra, d, |ea| {
+                format!(
+                    "{} = ((mem.read_u16({}) ^ 0x8000) - 0x8000) & {}",
+                    reg(rd),
+                    ea,
+                    WORD
+                )
+            }),
+            Self::Store {
+                width,
+                update,
+                rs,
+                d,
+                ra,
+            } => {
+                let value = match width {
+                    Width::Byte => format!("{} & 0xFF", reg(rs)),
+                    Width::Half => format!("{} & 0xFFFF", reg(rs)),
+                    Width::Word => reg(rs),
+                };
+                update_form(update, ra, d, |ea| {
+                    format!("mem.write_{}({}, {})", width.c_type(), ea, value)
+                })
+            }
+            Self::Cmp {
+                logical: true,
+                crf,
+                ra,
+                rb,
+            } => compare(crf.0, reg(ra), reg(rb)),
+            Self::Cmp { crf, ra, rb, .. } => compare(crf.0, signed(reg(ra)), signed(reg(rb))),
+            Self::Cmpi {
+                logical: false,
+                crf,
+                ra,
+                imm,
+            } => compare(crf.0, signed(reg(ra)), imm.to_string()),
+            Self::Cmpi { crf, ra, imm, .. } => compare(crf.0, reg(ra), (imm as u16).to_string()),
+            Self::CrOp { op, bt, ba, bb } => {
+                let (a, b) = (format!("cr[{}]", ba.0), format!("cr[{}]", bb.0));
+                let value = match op {
+                    CrLogic::And => format!("{} and {}", a, b),
+                    CrLogic::Or => format!("{} or {}", a, b),
+                    CrLogic::Xor => format!("{} != {}", a, b),
+                    CrLogic::Nand => format!("not ({} and {})", a, b),
+                    CrLogic::Nor => format!("not ({} or {})", a, b),
+                    CrLogic::Eqv => format!("{} == {}", a, b),
+                    CrLogic::Andc => format!("{} and not {}", a, b),
+                    CrLogic::Orc => format!("{} or not {}", a, b),
+                };
+                format!("cr[{}] = {}", bt.0, value)
+            }
+            // there's no goto to port a branch to
+            Self::B { .. } | Self::Bc { .. } => format!("# {}", self),
+            // canonicalize() leaves only real instructions
+            op => unreachable!("{} is a pseudo-op", op),
+        };
+        match self.record() {
+            Some(ra) => format!(
+                "{}; {}",
+                statement,
+                compare(0, signed(reg(ra)), "0".to_string())
+            ),
+            None => statement,
+        }
+    }
+}
+
+/// A load or store through `ea`, which with update is also written to rA.
+fn update_form(update: bool, ra: Register, d: i16, access: impl Fn(String) -> String) -> String {
+    match update {
+        false => access(address(ra, d)),
+        true => format!(
+            "ea = {}; {}; {} = ea",
+            address(ra, d),
+            access("ea".to_string()),
+            reg(ra)
+        ),
+    }
+}
+
+/// Sets LT, GT and EQ of `cr<field>` from comparing `a` with `b`. SO (a
+/// copy of XER[SO]) is left alone.
+fn compare(field: u8, a: String, b: String) -> String {
+    let bit = 4 * field;
+    format!(
+        "a, b = {}, {}; cr[{}], cr[{}], cr[{}] = a < b, a > b, a == b",
+        a,
+        b,
+        bit,
+        bit + 1,
+        bit + 2
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_opcode;
+
+    #[test]
+    fn test_highlevel_python() {
+        let python = |asm| {
+            parse_opcode(asm)
+                .expect("parse failed")
+                .1
+                .highlevel_python()
+        };
+        assert_eq!(
+            python("rlwinm r0,r7,16,0,15"),
+            "r[0] = (r[7] << 16 | r[7] >> (32 - 16)) & 0xFFFF0000"
+        );
+        assert_eq!(
+            python("rotlw r3,r4,r5"),
+            "r[3] = (r[4] << (r[5] & 31) | r[4] >> (32 - (r[5] & 31))) & 0xFFFFFFFF"
+        );
+        assert_eq!(python("clrlwi r3,r4,16"), "r[3] = r[4] & 0x0000FFFF");
+        assert_eq!(python("addi r1,r1,-16"), "r[1] = (r[1] - 16) & 0xFFFFFFFF");
+        assert_eq!(python("li r3,-1"), "r[3] = -1 & 0xFFFFFFFF");
+        assert_eq!(python("ori r3,r3,0x10"), "r[3] = r[3] | 0x00000010");
+        assert_eq!(
+            python("lhau r5,-2(r1)"),
+            "ea = (r[1] - 2) & 0xFFFFFFFF; \
+             r[5] = ((mem.read_u16(ea) ^ 0x8000) - 0x8000) & 0xFFFFFFFF; r[1] = ea"
+        );
+        assert_eq!(
+            python("cmpwi cr1,r3,-5"),
+            "a, b = ((r[3] ^ 0x80000000) - 0x80000000), -5; \
+             cr[4], cr[5], cr[6] = a < b, a > b, a == b"
+        );
+        assert_eq!(
+            python("rlwinm. r3,r4,0,24,31"),
+            "r[3] = r[4] & 0x000000FF; a, b = ((r[3] ^ 0x80000000) - 0x80000000), 0; \
+             cr[0], cr[1], cr[2] = a < b, a > b, a == b"
+        );
+    }
+}
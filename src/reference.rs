@@ -0,0 +1,160 @@
+//! Reference excerpts from the PowerPC manuals, one per instruction: the
+//! operation pseudocode, what else it changes and the notes that matter in
+//! practice. They're compiled in from `reference/ppc32.txt`.
+
+use core::fmt;
+
+use crate::parser::did_you_mean;
+use crate::Opcode;
+
+const REFERENCE: &str = include_str!("../reference/ppc32.txt");
+
+/// One instruction's entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    /// The real instruction, e.g. `rlwinm` or `cmp`
+    pub mnemonic: &'static str,
+    /// The manuals' name, e.g. "Rotate Left Word Immediate then AND with Mask"
+    pub name: &'static str,
+    /// Operands by their field names, e.g. `rlwinm rA,rS,SH,MB,ME`
+    pub syntax: &'static str,
+    /// Pseudo-ops that assemble to this instruction
+    pub simplified: Vec<&'static str>,
+    /// The manuals' pseudocode, a line per statement
+    pub operation: Vec<&'static str>,
+    /// Registers changed besides the destination, or "none"
+    pub affected: &'static str,
+    pub notes: Vec<&'static str>,
+}
+
+/// Every entry, in the order of the reference file.
+pub fn all() -> Vec<Reference> {
+    let mut entries: Vec<Reference> = Vec::new();
+    for line in REFERENCE.lines() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        if !line.starts_with(' ') {
+            let (mnemonic, name) = line.split_once(": ").expect("entry without a name");
+            entries.push(Reference {
+                mnemonic,
+                name,
+                syntax: "",
+                simplified: Vec::new(),
+                operation: Vec::new(),
+                affected: "",
+                notes: Vec::new(),
+            });
+            continue;
+        }
+        let entry = entries.last_mut().expect("indented line before any entry");
+        let line = line.trim();
+        match line.strip_prefix("| ") {
+            Some(statement) => entry.operation.push(statement),
+            None => match line.split_once(": ").expect("line without a key") {
+                ("syntax", syntax) => entry.syntax = syntax,
+                ("simplified", names) => entry.simplified = names.split(", ").collect(),
+                ("affected", affected) => entry.affected = affected,
+                ("note", note) => entry.notes.push(note),
+                (key, _) => panic!("unknown key `{}` in the reference", key),
+            },
+        }
+    }
+    entries
+}
+
+/// The entry for `mnemonic`, or for the instruction it's a simplified
+/// mnemonic of.
+pub fn lookup(mnemonic: &str) -> Result<Reference, String> {
+    let entries = all();
+    let names: Vec<_> = entries
+        .iter()
+        .flat_map(|e| Some(e.mnemonic).into_iter().chain(e.simplified.clone()))
+        .collect();
+    match entries
+        .iter()
+        .find(|e| e.mnemonic == mnemonic || e.simplified.contains(&mnemonic))
+    {
+        Some(entry) => Ok(entry.clone()),
+        None => {
+            let mut message = format!("no reference for `{}`", mnemonic);
+            let close: Vec<_> = did_you_mean(mnemonic, &names)
+                .iter()
+                .map(|m| format!("`{}`", m))
+                .collect();
+            if !close.is_empty() {
+                message += &format!("; did you mean {}?", close.join(" or "));
+            }
+            Err(message)
+        }
+    }
+}
+
+impl Opcode {
+    /// The reference entry for the instruction, of its real form for a
+    /// pseudo-op; a record form shares its entry.
+    pub fn reference(&self) -> Reference {
+        let mnemonic = self.canonicalize().mnemonic();
+        lookup(mnemonic.strip_suffix('.').unwrap_or(mnemonic))
+            .unwrap_or_else(|_| panic!("no reference entry for {}", self))
+    }
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} - {}", self.mnemonic, self.name)?;
+        writeln!(f, "    {}", self.syntax)?;
+        if !self.simplified.is_empty() {
+            writeln!(f, "    simplified: {}", self.simplified.join(", "))?;
+        }
+        writeln!(f)?;
+        for statement in &self.operation {
+            writeln!(f, "    {}", statement)?;
+        }
+        writeln!(f)?;
+        write!(f, "    affected: {}", self.affected)?;
+        for note in &self.notes {
+            write!(f, "\n    note: {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_opcode;
+
+    #[test]
+    fn test_reference() {
+        for entry in all() {
+            assert!(!entry.syntax.is_empty() && !entry.operation.is_empty());
+            assert!(
+                !entry.affected.is_empty(),
+                "{} has no affected:",
+                entry.mnemonic
+            );
+        }
+        let op = |asm| parse_opcode(asm).expect("parse failed").1;
+        for asm in &[
+            "srwi r3,r4,3",
+            "lhau r3,2(r4)",
+            "cmplwi cr7,r0,5",
+            "crnor 0,1,2",
+            "bl .+8",
+            "bdnz .-4",
+            "rlwinm. r3,r4,0,24,31",
+        ] {
+            assert_eq!(op(asm).reference().name, op(asm).manual_name());
+        }
+        assert_eq!(
+            lookup("rlwimi").unwrap().operation[3],
+            "rA <- (r & m) | (rA & ~m)"
+        );
+        assert_eq!(lookup("cmpw").unwrap().mnemonic, "cmp");
+        assert_eq!(
+            lookup("rlwimn").unwrap_err(),
+            "no reference for `rlwimn`; did you mean `rlwinm` or `rlwimi`?"
+        );
+    }
+}
@@ -0,0 +1,174 @@
+//! Places one payload at several candidate addresses (say, every code cave
+//! big enough for it). Labels move with the payload, so each `@ha`/`@l`
+//! reference to one, and each branch to one from outside the payload, is
+//! filled in again for every target.
+
+use core::fmt;
+use std::collections::BTreeMap;
+
+use crate::asm::{assemble, AsmError};
+use crate::parser::{Line, Program, Statement};
+use crate::{Opcode, Reloc};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RelocateError {
+    /// Neither a label of the payload nor a constant it defines.
+    UnknownSymbol { line: usize, symbol: String },
+    /// `@sda21` depends on the game's small data base, not the payload's.
+    SmallData { line: usize },
+    /// A branch target further away than the branch can reach, or not
+    /// word-aligned.
+    BranchRange {
+        line: usize,
+        symbol: String,
+        disp: i64,
+    },
+}
+
+impl fmt::Display for RelocateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownSymbol { line, symbol } => {
+                write!(f, "line {}: unknown symbol `{}`", line, symbol)
+            }
+            Self::SmallData { line } => {
+                write!(f, "line {}: @sda21 can't be resolved per target", line)
+            }
+            Self::BranchRange { line, symbol, disp } if disp % 4 != 0 => write!(
+                f,
+                "line {}: `{}` is {} bytes away, not a whole number of instructions",
+                line, symbol, disp
+            ),
+            Self::BranchRange { line, symbol, disp } => write!(
+                f,
+                "line {}: `{}` is {:#x} bytes away, out of the branch's reach",
+                line,
+                symbol,
+                disp.abs()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RelocateError {}
+
+/// Addresses of the labels of `program` placed at `base`. Address columns
+/// from a listing are ignored: the payload is being moved.
+pub fn labels(program: &Program, base: u32) -> BTreeMap<&str, u32> {
+    program
+        .layout(base, false)
+        .into_iter()
+        .filter_map(|(addr, line)| match &line.statement {
+            Statement::Label(name) => Some((name.as_str(), addr)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `op` at `addr` from `line` with its relocation, if any, resolved against
+/// `labels` or the program's constants.
+pub(crate) fn resolve(
+    program: &Program,
+    labels: &BTreeMap<&str, u32>,
+    line: &Line,
+    addr: u32,
+    op: &Opcode,
+) -> Result<Opcode, RelocateError> {
+    let reloc = match &line.reloc {
+        Some(reloc) => reloc,
+        None => return Ok(*op),
+    };
+    let value = match labels.get(reloc.symbol.as_str()) {
+        Some(&label) => i64::from(label),
+        None if reloc.symbol.is_empty() => 0,
+        None => program
+            .symbols
+            .get(&reloc.symbol)
+            .ok_or_else(|| RelocateError::UnknownSymbol {
+                line: line.number,
+                symbol: reloc.symbol.clone(),
+            })?,
+    };
+    if reloc.reloc == Reloc::Rel {
+        let disp = value + reloc.addend - i64::from(addr);
+        return op
+            .with_displacement(disp)
+            .ok_or_else(|| RelocateError::BranchRange {
+                line: line.number,
+                symbol: reloc.target(),
+                disp,
+            });
+    }
+    let imm = reloc
+        .reloc
+        .apply(value + reloc.addend)
+        .ok_or(RelocateError::SmallData { line: line.number })?;
+    Ok(op.with_immediate(imm))
+}
+
+/// `program` assembled at each of `targets`, its data included, with
+/// every relocation resolved against that placement.
+pub fn relocate(program: &Program, targets: &[u32]) -> Result<Vec<(u32, Vec<u8>)>, AsmError> {
+    targets
+        .iter()
+        .map(|&base| Ok((base, assemble(program, base)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse_opcode, parse_program};
+
+    #[test]
+    fn test_relocate() {
+        let program = parse_program(
+            "lis r3,table@ha\naddi r3,r3,table@l\nlwz r4,table+4@l(r3)\n\
+             table:\n.long 1, 2\n",
+        )
+        .unwrap();
+
+        let placed = relocate(&program, &[0x8000_3100, 0x8001_7ff8]).unwrap();
+        let ops = |bytes: &[u8]| -> Vec<_> {
+            bytes
+                .chunks_exact(4)
+                .map(|w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]))
+                .map(|w| crate::Opcode::decode(w).map_or(format!("{:#x}", w), |op| op.to_string()))
+                .collect()
+        };
+        assert_eq!(placed[0].0, 0x8000_3100);
+        assert_eq!(
+            ops(&placed[0].1),
+            vec![
+                "lis r3,0x8000",
+                "addi r3,r3,12556",
+                "lwz r4,12560(r3)",
+                "0x1",
+                "0x2"
+            ]
+        );
+        assert_eq!(
+            ops(&placed[1].1),
+            vec![
+                "lis r3,0x8002",
+                "addi r3,r3,-32764",
+                "lwz r4,-32760(r3)",
+                "0x1",
+                "0x2"
+            ]
+        );
+
+        let program = parse_program("lis r3,gState@ha\n").unwrap();
+        assert_eq!(
+            relocate(&program, &[0]),
+            Err(AsmError::Relocate(RelocateError::UnknownSymbol {
+                line: 1,
+                symbol: "gState".to_string()
+            }))
+        );
+        assert_eq!(
+            parse_opcode("li r3,-4").unwrap().1.with_immediate(8),
+            parse_opcode("li r3,8").unwrap().1
+        );
+    }
+}
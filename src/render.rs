@@ -0,0 +1,1380 @@
+use crate::compare::{origins, Origin};
+use crate::describe::kept;
+use crate::diff::DiffRow;
+use crate::encoding::EncodeError;
+use crate::eval::mask;
+use crate::parser::{Directive, Line, Program, Statement};
+use crate::symbols::SymbolTable;
+use crate::{mask_name, BitOrder, Opcode, Register, Reloc};
+use std::ops::Range;
+
+/// One row of a two-column listing: source on the left, explanation on the right.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub asm: String,
+    pub explanation: String,
+}
+
+/// `lis rX,sym@ha` followed by `addi rY,rX,sym@l` (or a load/store through
+/// `sym@l(rX)`), or `lis rX,sym@h` followed by `ori rY,rX,sym@l`, is one
+/// 32-bit address materialization: explains the second.
+fn materialized(prev: &Line, line: &Line) -> Option<String> {
+    let (high, low) = (prev.reloc.as_ref()?, line.reloc.as_ref()?);
+    let base = match prev.statement {
+        Statement::Instruction(Opcode::Lis { rd, .. }) => rd,
+        Statement::Instruction(Opcode::Addis { rd, ra, .. }) if ra.0 == 0 => rd,
+        _ => return None,
+    };
+    if low.reloc != Reloc::Lo || (&high.symbol, high.addend) != (&low.symbol, low.addend) {
+        return None;
+    }
+    let op = match line.statement {
+        Statement::Instruction(op) => op,
+        _ => return None,
+    };
+    // ori doesn't sign-extend its half, so it pairs with @h, not @ha
+    let (through, adjusted) = match op {
+        Opcode::Addi { ra, .. }
+        | Opcode::Load { ra, .. }
+        | Opcode::Lha { ra, .. }
+        | Opcode::Store { ra, .. } => (ra, Reloc::Ha),
+        Opcode::Ori { ra, rs, .. } => {
+            return match (rs == base, high.reloc) {
+                (true, Reloc::Hi) => Some(format!("{} = &{}", ra, low.target())),
+                _ => None,
+            }
+        }
+        _ => return None,
+    };
+    if through != base || high.reloc != adjusted {
+        return None;
+    }
+    op.highlevel_ea(|_| format!("&{}", low.target()))
+}
+
+/// `lis rX,hi` followed by `addi rY,rX,lo` or `ori rY,rX,lo` loads one
+/// 32-bit constant: explains the second as the whole value.
+fn constant(prev: &Line, line: &Line) -> Option<String> {
+    let (base, high) = match prev.statement {
+        Statement::Instruction(Opcode::Lis { rd, simm }) => (rd, simm),
+        Statement::Instruction(Opcode::Addis { rd, ra, simm }) if ra.0 == 0 => (rd, simm),
+        _ => return None,
+    };
+    match line.statement {
+        Statement::Instruction(Opcode::Addi { rd, ra, simm })
+            if ra == base && ra.0 != 0 && prev.reloc.is_none() && line.reloc.is_none() =>
+        {
+            let value = ((high as u16 as u32) << 16).wrapping_add(simm as i32 as u32);
+            Some(format!("{} = {:#x}", rd, value))
+        }
+        Statement::Instruction(Opcode::Ori { ra, rs, uimm })
+            if rs == base && prev.reloc.is_none() && line.reloc.is_none() =>
+        {
+            let value = ((high as u16 as u32) << 16) | u32::from(uimm);
+            Some(format!("{} = {:#x}", ra, value))
+        }
+        _ => None,
+    }
+}
+
+/// `rlwinm rT,rS,...` followed by an `rlwimi rD,rT,...` that inserts part
+/// of what it extracted copies one bit field from rS into rD: explains the
+/// second as the copy, if the inserted bits are one run of rS.
+fn field_copy(prev: &Line, line: &Line) -> Option<String> {
+    let (temp, extracted) = match prev.statement {
+        Statement::Instruction(op) if matches!(op.canonicalize(), Opcode::Rlwinm { .. }) => {
+            origins(&op)?
+        }
+        _ => return None,
+    };
+    let op = match line.statement {
+        Statement::Instruction(op) => op,
+        _ => return None,
+    };
+    let (ra, rs, sh, mb, me) = match op.canonicalize() {
+        Opcode::Rlwimi {
+            ra,
+            rs,
+            sh,
+            mb,
+            me,
+            rc: false,
+        } if rs == temp && ra != temp => (ra, rs, sh, mb, me),
+        _ => return None,
+    };
+    // a wrapped destination isn't one field
+    if mb > me {
+        return None;
+    }
+    let from = |i: u8| extracted[usize::from((i + sh) % 32)];
+    let (src, first) = match from(mb) {
+        Origin::Source { rs, rb: None, bit } => (rs, bit),
+        _ => return None,
+    };
+    let contiguous = (mb..=me).all(|i| {
+        from(i)
+            == Origin::Source {
+                rs: src,
+                rb: None,
+                bit: first + (i - mb),
+            }
+    });
+    if !contiguous || first + (me - mb) > 31 {
+        return None;
+    }
+    let via = match rs == src {
+        true => String::new(),
+        false => format!(", through {}", rs),
+    };
+    Some(format!(
+        "copy {} of {} into {} of {}{}",
+        field(first, first + (me - mb)),
+        src,
+        field(mb, me),
+        ra,
+        via
+    ))
+}
+
+/// `bits 4..11`, or `bit 4` for a field of one.
+fn field(first: u8, last: u8) -> String {
+    match first == last {
+        true => BitOrder::bit(first),
+        false => format!("bits {}", BitOrder::range(first, last)),
+    }
+}
+
+fn describe(directive: &Directive) -> String {
+    match directive {
+        Directive::Set { name, value } => format!("{} = {}", name, value),
+        Directive::Section(name) => format!("section {}", name),
+        Directive::Global(name) => format!("export {}", name),
+        Directive::Data { width, values } => {
+            let mask = u32::MAX >> (32 - 8 * width.bytes());
+            let values: Vec<_> = values
+                .iter()
+                .map(|&v| format!("{:#x}", v as u32 & mask))
+                .collect();
+            format!("{}[] = {{ {} }}", width.c_type(), values.join(", "))
+        }
+        Directive::Align(n) => format!("pad to a {}-byte boundary", 1u64 << n.min(&63)),
+        Directive::Space(n) => format!("{} zero bytes", n),
+    }
+}
+
+/// How instructions are explained.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Style {
+    /// Rotates as `(rs << sh) & MASK(mb..me)`, see [`Opcode::highlevel`]
+    Symbolic,
+    /// Rotates as C shifts and masks, see [`Opcode::highlevel_c`]
+    C,
+    /// Rust statements, see [`Opcode::highlevel_rust`]
+    Rust,
+    /// Python statements, see [`Opcode::highlevel_python`]
+    Python,
+}
+
+/// How much of each instruction [`listing`] explains.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Verbosity {
+    /// Just the explanation line
+    Terse,
+    /// Plus the bit diagram of rotates
+    Normal,
+    /// Plus the encoded fields and the manual's description
+    Verbose,
+}
+
+pub fn rows(program: &Program) -> Vec<Row> {
+    rows_styled(program, Style::Symbolic)
+}
+
+pub fn rows_styled(program: &Program, style: Style) -> Vec<Row> {
+    program
+        .lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| match &line.statement {
+            Statement::Instruction(op) if matches!(style, Style::Rust | Style::Python) => {
+                let (code, comment) = match style {
+                    Style::Rust => (op.highlevel_rust(), "//"),
+                    _ => (op.highlevel_python(), "#"),
+                };
+                Row {
+                    asm: line.source.trim().to_string(),
+                    explanation: match &line.reloc {
+                        // the field reads as 0 until linked
+                        Some(reloc) => format!("{} {} {}", code, comment, reloc),
+                        None => code,
+                    },
+                }
+            }
+            Statement::Instruction(op) => Row {
+                asm: line.source.trim().to_string(),
+                explanation: i
+                    .checked_sub(1)
+                    .and_then(|prev| {
+                        let prev = &program.lines[prev];
+                        materialized(prev, line)
+                            .or_else(|| constant(prev, line))
+                            .or_else(|| field_copy(prev, line))
+                    })
+                    .or_else(|| line.reloc.as_ref().map(|r| op.highlevel_reloc(r)))
+                    .unwrap_or_else(|| match style {
+                        Style::Symbolic => op.highlevel(),
+                        Style::C => op.highlevel_c(),
+                        Style::Rust | Style::Python => unreachable!(),
+                    }),
+            },
+            Statement::Directive(directive) => Row {
+                asm: line.source.trim().to_string(),
+                explanation: describe(directive),
+            },
+            Statement::Label(_) => Row {
+                asm: line.source.trim().to_string(),
+                explanation: String::new(),
+            },
+        })
+        .collect()
+}
+
+/// Names for the 32 bits of a rotate's source, bit 0 (the MSB) first.
+const BIT_NAMES: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyzABCDEF";
+
+/// 32 characters, one per bit from bit 0, in groups of 8.
+fn bit_string(bit: impl Fn(u32) -> char) -> String {
+    let mut out = String::new();
+    for i in 0..32 {
+        if i > 0 && i % 8 == 0 {
+            out.push(' ');
+        }
+        out.push(bit(i));
+    }
+    out
+}
+
+/// The bit strings of a rotate-and-mask, see [`mask_diagram`].
+struct Rotate {
+    ra: Register,
+    rs: Register,
+    /// The rotate amount: a number, or `rb` for rlwnm
+    amount: String,
+    /// The rotate for rlwinm and rlwimi; rlwnm's isn't known statically
+    sh: Option<u8>,
+    mb: u8,
+    me: u8,
+    insert: bool,
+    src: String,
+    rotl: String,
+    mask: String,
+    kept: String,
+}
+
+impl Rotate {
+    fn new(op: &Opcode) -> Option<Self> {
+        let (ra, rs, amount, sh, mb, me, insert) = match op.canonicalize() {
+            Opcode::Rlwinm {
+                ra, rs, sh, mb, me, ..
+            } => (ra, rs, sh.to_string(), Some(sh), mb, me, false),
+            Opcode::Rlwimi {
+                ra, rs, sh, mb, me, ..
+            } => (ra, rs, sh.to_string(), Some(sh), mb, me, true),
+            Opcode::Rlwnm {
+                ra, rs, rb, mb, me, ..
+            } => (ra, rs, rb.to_string(), None, mb, me, false),
+            _ => return None,
+        };
+        let mask = mask(mb, me);
+        let kept = |i: u32| mask & (0x8000_0000 >> i) != 0;
+        // the amount in rb isn't known statically
+        let rotated = |i: u32| match sh {
+            Some(sh) => BIT_NAMES[((i + u32::from(sh)) % 32) as usize] as char,
+            None => '?',
+        };
+        let result = |i| match (kept(i), insert) {
+            (true, _) => rotated(i),
+            (false, true) => '-',
+            (false, false) => '0',
+        };
+
+        Some(Self {
+            ra,
+            rs,
+            amount,
+            sh,
+            mb,
+            me,
+            insert,
+            src: bit_string(|i| BIT_NAMES[i as usize] as char),
+            rotl: bit_string(rotated),
+            mask: bit_string(|i| if kept(i) { '1' } else { '0' }),
+            kept: bit_string(result),
+        })
+    }
+
+    fn diagram(&self) -> String {
+        [
+            format!("src:  {}  {}", self.src, self.rs),
+            format!("rotl: {}  {} << {}", self.rotl, self.rs, self.amount),
+            format!("mask: {}  {}", self.mask, mask_name(self.mb, self.me)),
+            format!("kept: {}  {}{}", self.kept, self.ra, self.unchanged_note()),
+        ]
+        .join("\n")
+    }
+
+    fn unchanged_note(&self) -> &'static str {
+        if self.insert {
+            " (- is unchanged)"
+        } else {
+            ""
+        }
+    }
+}
+
+/// Bit diagram of a rotate-and-mask: each source bit is named by a letter,
+/// followed through the rotate and the mask into the result. `None` for
+/// anything but rlwinm, rlwimi, rlwnm and their pseudo-ops.
+///
+/// ```text
+/// src:  abcdefgh ijklmnop qrstuvwx yzABCDEF  r4
+/// rotl: yzABCDEF abcdefgh ijklmnop qrstuvwx  r4 << 24
+/// mask: 00000000 11111111 11111111 11111111  MASK(8..31)=0x00FFFFFF
+/// kept: 00000000 abcdefgh ijklmnop qrstuvwx  r3
+/// ```
+pub fn mask_diagram(op: &Opcode) -> Option<String> {
+    Rotate::new(op).map(|r| r.diagram())
+}
+
+/// [`mask_diagram`] with ANSI colors for a terminal: each bit is colored by
+/// the byte of the source register it came from, and cleared bits are grey.
+pub fn mask_diagram_colored(op: &Opcode) -> Option<String> {
+    let mut r = Rotate::new(op)?;
+    for row in [&mut r.src, &mut r.rotl, &mut r.mask, &mut r.kept] {
+        *row = paint(row);
+    }
+    Some(r.diagram())
+}
+
+/// Colors of the four source bytes, from bit 0: red, green, yellow, blue.
+const BYTE_COLORS: [u8; 4] = [31, 32, 33, 34];
+const GREY: u8 = 90;
+
+fn paint(bits: &str) -> String {
+    let mut out = String::new();
+    for c in bits.chars() {
+        let color = match c {
+            '0' | '-' => GREY,
+            c => match BIT_NAMES.iter().position(|&b| b as char == c) {
+                Some(i) => BYTE_COLORS[i / 8],
+                None => {
+                    out.push(c);
+                    continue;
+                }
+            },
+        };
+        out.push_str(&format!("\x1b[{}m{}\x1b[0m", color, c));
+    }
+    out
+}
+
+/// The same rotate-and-mask as [`mask_diagram`], as numbered stages for
+/// someone learning how these instructions work:
+///
+/// ```text
+/// 1. rotate r4 left by 24 bits
+///      abcdefgh ijklmnop qrstuvwx yzABCDEF  r4
+///      yzABCDEF abcdefgh ijklmnop qrstuvwx  rotl32(r4, 24)
+/// 2. build the mask of bits 8..31
+///      00000000 11111111 11111111 11111111  MASK(8..31)=0x00FFFFFF
+/// 3. AND the rotated value with the mask into r3
+///      00000000 abcdefgh ijklmnop qrstuvwx  r3
+/// ```
+pub fn explain_steps(op: &Opcode) -> Option<String> {
+    let r = Rotate::new(op)?;
+    let rotate = match op.canonicalize() {
+        Opcode::Rlwnm { .. } => format!("rotate {} left by the low 5 bits of {}", r.rs, r.amount),
+        _ => format!("rotate {} left by {} bits", r.rs, r.amount),
+    };
+    let combine = match r.insert {
+        true => format!(
+            "insert the masked bits into {}, keeping its other bits",
+            r.ra
+        ),
+        false => format!("AND the rotated value with the mask into {}", r.ra),
+    };
+
+    Some(
+        [
+            format!("1. {}", rotate),
+            format!("     {}  {}", r.src, r.rs),
+            format!("     {}  rotl32({}, {})", r.rotl, r.rs, r.amount),
+            format!("2. build the mask of {}", kept(r.mb, r.me)),
+            format!("     {}  {}", r.mask, mask_name(r.mb, r.me)),
+            format!("3. {}", combine),
+            format!("     {}  {}{}", r.kept, r.ra, r.unchanged_note()),
+        ]
+        .join("\n"),
+    )
+}
+
+/// The listing as each line followed by its explanation, with whatever
+/// `verbosity` asks for indented under instructions. Diagrams use ANSI
+/// colors if `color`; the manual text is wrapped to `width` columns.
+pub fn listing(
+    program: &Program,
+    style: Style,
+    verbosity: Verbosity,
+    color: bool,
+    width: usize,
+) -> String {
+    let mut out = String::new();
+    for (row, line) in rows_styled(program, style).iter().zip(&program.lines) {
+        out.push_str(&format!("{}\n{}\n", row.asm, row.explanation));
+        let op = match line.statement {
+            Statement::Instruction(op) if verbosity != Verbosity::Terse => op,
+            _ => continue,
+        };
+        let mut notes = Vec::new();
+        let diagram = match color {
+            true => mask_diagram_colored(&op),
+            false => mask_diagram(&op),
+        };
+        notes.extend(diagram.iter().flat_map(|d| d.lines().map(str::to_string)));
+        if verbosity == Verbosity::Verbose {
+            let real = op.canonicalize();
+            notes.push(match real.mnemonic() == op.mnemonic() {
+                true => format!("{}: {}", op.mnemonic(), op.manual_name()),
+                false => format!(
+                    "{} is {}: {}",
+                    op.mnemonic(),
+                    real.mnemonic(),
+                    op.manual_name()
+                ),
+            });
+            let fields: Vec<_> = fields(&op)
+                .into_iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect();
+            notes.push(match BitOrder::current() {
+                BitOrder::Msb0 => fields.join(" "),
+                BitOrder::Lsb0 => format!("{}  (IBM numbering)", fields.join(" ")),
+            });
+            notes.extend(wrap(&op.manual(), width.saturating_sub(4)));
+        }
+        for note in notes {
+            out.push_str(&format!("    {}\n", note));
+        }
+    }
+    out
+}
+
+/// The listing as one Rust function, each statement commented with the
+/// instruction it came from and labels kept as comments. See
+/// [`crate::rust`] for what `r`, `cr` and `mem` are.
+pub fn rust_fn(program: &Program, name: &str) -> String {
+    let open = format!(
+        "fn {}(r: &mut [u32; 32], cr: &mut [bool; 32], mem: &mut impl Memory) {{\n",
+        name
+    );
+    function(program, Style::Rust, open, "//", "}\n")
+}
+
+/// [`rust_fn`] in Python: a `def` for a cheat script. See
+/// [`crate::python`] for what `r`, `cr` and `mem` are.
+pub fn python_fn(program: &Program, name: &str) -> String {
+    let open = format!("def {}(r, cr, mem):\n", name);
+    function(program, Style::Python, open, "#", "")
+}
+
+/// The body of [`rust_fn`] and [`python_fn`], between `open` and `close`.
+fn function(program: &Program, style: Style, open: String, comment: &str, close: &str) -> String {
+    let mut out = open;
+    for (row, line) in rows_styled(program, style).iter().zip(&program.lines) {
+        match line.statement {
+            Statement::Instruction(_) => out.push_str(&format!(
+                "    {} {} {}\n",
+                row.explanation, comment, row.asm
+            )),
+            Statement::Label(_) => out.push_str(&format!("    {} {}\n", comment, row.asm)),
+            Statement::Directive(_) => {}
+        }
+    }
+    out.push_str(close);
+    out
+}
+
+/// Greedy word wrap. Words longer than `width` are hard-split.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for word in text.split_whitespace() {
+        let mut word = word;
+        while word.chars().count() > width {
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            let split = word
+                .char_indices()
+                .nth(width)
+                .map_or(word.len(), |(i, _)| i);
+            lines.push(word[..split].to_string());
+            word = &word[split..];
+        }
+
+        let needed = if current.is_empty() {
+            word.chars().count()
+        } else {
+            current.chars().count() + 1 + word.chars().count()
+        };
+        if needed > width && !current.is_empty() {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+
+    if !current.is_empty() || lines.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+/// Renders `rows` as two columns fitting in `width` terminal columns.
+pub fn side_by_side(rows: &[Row], width: usize) -> String {
+    const SEP: &str = " | ";
+
+    let widest = rows
+        .iter()
+        .map(|r| r.asm.chars().count())
+        .max()
+        .unwrap_or(0);
+    let left = widest.min(width.saturating_sub(SEP.len()) / 2).max(1);
+    let right = width.saturating_sub(left + SEP.len()).max(1);
+
+    let mut out = String::new();
+    for row in rows {
+        let asm = wrap(&row.asm, left);
+        let explanation = wrap(&row.explanation, right);
+        for i in 0..asm.len().max(explanation.len()) {
+            let l = asm.get(i).map_or("", String::as_str);
+            let r = explanation.get(i).map_or("", String::as_str);
+            let line = format!("{:<left$}{}{}", l, SEP, r, left = left);
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Renders a diff as two listings side by side with a change marker between
+/// them (see [`crate::diff::Change::marker`]), fitting in `width` terminal columns.
+pub fn split_view(rows: &[DiffRow], width: usize) -> String {
+    let column = (width.saturating_sub(3) / 2).max(1);
+    let asm = |line: Option<&Line>| line.map_or(String::new(), |l| l.source.trim().to_string());
+
+    let mut out = String::new();
+    for row in rows {
+        let left = wrap(&asm(row.left), column);
+        let right = wrap(&asm(row.right), column);
+        for i in 0..left.len().max(right.len()) {
+            let l = left.get(i).map_or("", String::as_str);
+            let r = right.get(i).map_or("", String::as_str);
+            let marker = if i == 0 { row.change.marker() } else { ' ' };
+            let line = format!("{:<column$} {} {}", l, marker, r, column = column);
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+    }
+    out
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Renders `rows` as a two-column HTML table; the browser does the wrapping.
+pub fn side_by_side_html(rows: &[Row]) -> String {
+    let mut out = String::from("<table class=\"ppcheat\">\n");
+    for row in rows {
+        out.push_str(&format!(
+            "  <tr><td><code>{}</code></td><td>{}</td></tr>\n",
+            escape_html(&row.asm),
+            escape_html(&row.explanation)
+        ));
+    }
+    out.push_str("</table>\n");
+    out
+}
+
+impl Rotate {
+    /// The word as two rows of 32 cells, the source above the result, with
+    /// the rotate between them and the mask shaded and marked by MB and ME.
+    fn tikz(&self) -> String {
+        let cells = |bits: &str| {
+            let bits: Vec<_> = bits
+                .chars()
+                .filter(|&c| c != ' ')
+                .map(String::from)
+                .collect();
+            bits.join(",")
+        };
+        let (mb, me) = (self.mb & 31, self.me & 31);
+        let shade = match mb <= me {
+            true => format!("\\fill[blue!15] ({},-1) rectangle ({},0);\n", mb, me + 1),
+            false => format!(
+                "\\fill[blue!15] (0,-1) rectangle ({},0);\n\\fill[blue!15] ({},-1) rectangle (32,0);\n",
+                me + 1,
+                mb
+            ),
+        };
+        let rotate = match self.sh {
+            Some(sh) => format!("rotl {} (SH={})", sh, sh),
+            None => format!("rotl {}", self.amount),
+        };
+
+        let mut out =
+            String::from("\\begin{tikzpicture}[x=0.42cm, y=0.6cm, font=\\ttfamily\\small]\n");
+        out.push_str(&shade);
+        out.push_str("\\draw (0,1) grid (32,2);\n\\draw (0,-1) grid (32,0);\n");
+        out.push_str(&format!(
+            "\\foreach \\c [count=\\i from 0] in {{{}}} \\node at (\\i+0.5,1.5) {{\\c}};\n",
+            cells(&self.src)
+        ));
+        out.push_str(&format!(
+            "\\foreach \\c [count=\\i from 0] in {{{}}} \\node at (\\i+0.5,-0.5) {{\\c}};\n",
+            cells(&self.kept)
+        ));
+        out.push_str(&format!(
+            "\\node[anchor=east] at (0,1.5) {{{}}};\n\\node[anchor=east] at (0,-0.5) {{{}}};\n",
+            self.rs, self.ra
+        ));
+        out.push_str(&format!(
+            "\\node at (16,0.5) {{$\\downarrow$ {}}};\n",
+            rotate
+        ));
+        // the fields as encoded, which are always IBM-numbered
+        let ibm = match BitOrder::current() {
+            BitOrder::Msb0 => "",
+            BitOrder::Lsb0 => " (IBM)",
+        };
+        out.push_str(&format!(
+            "\\draw[thick] ({mb},-1.4) -- ({mb},-1) node[pos=0,below] {{MB={mb}{ibm}}};\n\
+             \\draw[thick] ({end},-1.4) -- ({end},-1) node[pos=0,below] {{ME={me}{ibm}}};\n",
+            mb = mb,
+            me = me,
+            end = me + 1,
+            ibm = ibm
+        ));
+        out.push_str("\\end{tikzpicture}\n");
+        out
+    }
+}
+
+/// A TikZ picture of a rotate-and-mask, see [`mask_diagram`]. `None` for
+/// anything but rlwinm, rlwimi, rlwnm and their pseudo-ops.
+pub fn tikz_diagram(op: &Opcode) -> Option<String> {
+    Rotate::new(op).map(|r| r.tikz())
+}
+
+/// A TikZ picture for every rotate in a listing, each after a comment with
+/// its source line, for pasting into a LaTeX document.
+pub fn explain_tikz(program: &Program) -> String {
+    let mut pictures = Vec::new();
+    for line in &program.lines {
+        if let Statement::Instruction(op) = line.statement {
+            if let Some(picture) = tikz_diagram(&op) {
+                pictures.push(format!("% {}\n{}", line.source.trim(), picture));
+            }
+        }
+    }
+    pictures.join("\n")
+}
+
+/// The instruction's encoding as boxed fields, with each field's IBM bit
+/// range above it and its value in decimal and binary:
+///
+/// ```text
+///    0..5    6..10   11..15   16..20   21..25   26..30   31
+/// +--------+-------+--------+--------+--------+--------+----+
+/// |  OPCD  |  RS   |   RA   |   SH   |   MB   |   ME   | Rc |
+/// |   21   |   3   |   0    |   16   |   0    |   15   | 0  |
+/// | 010101 | 00011 | 00000  | 10000  | 00000  | 01111  | 0  |
+/// +--------+-------+--------+--------+--------+--------+----+
+/// = 0x5460801E
+/// ```
+pub fn encoding_diagram(op: &Opcode) -> Result<String, EncodeError> {
+    let mut first = 0;
+    let cells: Vec<_> = op
+        .fields()?
+        .iter()
+        .map(|f| {
+            let last = first + f.width - 1;
+            let bits = match f.width {
+                1 => first.to_string(),
+                _ => format!("{}..{}", first, last),
+            };
+            first = last + 1;
+            let value = f.value.to_string();
+            let binary = format!("{:0w$b}", f.value, w = usize::from(f.width));
+            let width = *[f.name.len(), value.len(), binary.len(), bits.len()]
+                .iter()
+                .max()
+                .unwrap();
+            (width, [bits, f.name.to_string(), value, binary])
+        })
+        .collect();
+
+    let border = || {
+        let dashes: Vec<_> = cells.iter().map(|(w, _)| "-".repeat(*w + 2)).collect();
+        format!("+{}+", dashes.join("+"))
+    };
+    let row = |i: usize, edge: &str| {
+        let texts: Vec<_> = cells
+            .iter()
+            .map(|(w, texts)| format!(" {:^w$} ", texts[i], w = w))
+            .collect();
+        format!("{}{}{}", edge, texts.join(edge), edge)
+            .trim_end()
+            .to_string()
+    };
+    Ok([
+        row(0, " "),
+        border(),
+        row(1, "|"),
+        row(2, "|"),
+        row(3, "|"),
+        border(),
+        format!("= 0x{:08X}", op.encode()?),
+    ]
+    .join("\n"))
+}
+
+/// [`encoding_diagram`] for every instruction in the listing, noting the
+/// real instruction a pseudo-op is encoded as, or why an instruction
+/// can't be encoded.
+pub fn explain_encoding(program: &Program) -> String {
+    let mut diagrams = Vec::new();
+    for line in &program.lines {
+        if let Statement::Instruction(op) = line.statement {
+            let source = line.source.trim();
+            diagrams.push(match encoding_diagram(&op) {
+                Ok(diagram) if op.canonicalize() != op => format!(
+                    "{}  (encoded as {})\n{}\n",
+                    source,
+                    op.canonicalize(),
+                    diagram
+                ),
+                Ok(diagram) => format!("{}\n{}\n", source, diagram),
+                Err(e) => format!("{}\n  can't encode: {}\n", source, e),
+            });
+        }
+    }
+    diagrams.join("\n")
+}
+
+/// Highlights, on hover, every bit in a diagram with the same `data-bit`
+/// and shows the boundaries of the masked field.
+const HOVER_SCRIPT: &str = "\
+document.querySelectorAll('.bits [data-bit]').forEach(function (bit) {
+  var diagram = bit.closest('.bits');
+  var same = diagram.querySelectorAll('[data-bit=\"' + bit.dataset.bit + '\"]');
+  bit.addEventListener('mouseenter', function () {
+    diagram.classList.add('hover');
+    same.forEach(function (b) { b.classList.add('hl'); });
+  });
+  bit.addEventListener('mouseleave', function () {
+    diagram.classList.remove('hover');
+    same.forEach(function (b) { b.classList.remove('hl'); });
+  });
+});
+";
+
+const HOVER_STYLE: &str = "\
+table.ppcheat td { vertical-align: top; padding: 0.2em 1em 0.2em 0; }
+.bits { font-family: monospace; margin-top: 0.3em; }
+.bits span { display: inline-block; width: 1ch; text-align: center; }
+.bits span.gap { width: 1ch; }
+.bits span.label { width: auto; margin-left: 1ch; color: #666; }
+.bits .cleared, .bits .unchanged { color: #aaa; }
+.bits.hover .mb { border-left: 2px solid #36c; }
+.bits.hover .me { border-right: 2px solid #36c; }
+.bits .hl { background: #fd6; }
+";
+
+/// One row of an HTML bit diagram: `bit(i)` gives the text, source bit and
+/// classes of bit `i`.
+fn html_bits(label: &str, bit: impl Fn(u32) -> (char, Option<u32>, Vec<&'static str>)) -> String {
+    let mut out = String::from("<div>");
+    for i in 0..32 {
+        if i > 0 && i % 8 == 0 {
+            out.push_str("<span class=\"gap\"></span>");
+        }
+        let (c, source, classes) = bit(i);
+        out.push_str("<span");
+        if let Some(source) = source {
+            out.push_str(&format!(" data-bit=\"{}\"", source));
+        }
+        if !classes.is_empty() {
+            out.push_str(&format!(" class=\"{}\"", classes.join(" ")));
+        }
+        out.push_str(&format!(">{}</span>", c));
+    }
+    out.push_str(&format!(
+        "<span class=\"label\">{}</span></div>",
+        escape_html(label)
+    ));
+    out
+}
+
+impl Rotate {
+    /// The source and destination rows of the diagram, with each destination
+    /// bit tied to the source bit it came from.
+    fn html(&self) -> String {
+        let mask = mask(self.mb, self.me);
+        let kept = |i: u32| mask & (0x8000_0000 >> i) != 0;
+        let source = |i: u32| self.sh.map(|sh| (i + u32::from(sh)) % 32);
+        let bounds = |i: u32, mb: u32, me: u32| {
+            let mut classes = Vec::new();
+            if i == mb {
+                classes.push("mb");
+            }
+            if i == me {
+                classes.push("me");
+            }
+            classes
+        };
+        let (mb, me) = (u32::from(self.mb & 31), u32::from(self.me & 31));
+
+        let src = html_bits(&self.rs.to_string(), |i| {
+            let field = |bit: u32| source(bit).unwrap_or(u32::MAX);
+            (
+                BIT_NAMES[i as usize] as char,
+                Some(i),
+                bounds(i, field(mb), field(me)),
+            )
+        });
+        let dest = html_bits(&self.ra.to_string(), |i| {
+            let mut classes = bounds(i, mb, me);
+            match (kept(i), source(i)) {
+                (true, Some(from)) => (BIT_NAMES[from as usize] as char, Some(from), classes),
+                (true, None) => ('?', None, classes),
+                (false, _) => {
+                    classes.push(if self.insert { "unchanged" } else { "cleared" });
+                    (if self.insert { '-' } else { '0' }, None, classes)
+                }
+            }
+        });
+        format!(
+            "<div class=\"bits\" title=\"{}\">{}{}</div>",
+            escape_html(&mask_name(self.mb, self.me)),
+            src,
+            dest
+        )
+    }
+}
+
+/// A self-contained HTML page of the listing: rotates get a bit diagram
+/// where hovering a destination bit highlights the source bit it came from
+/// and the boundaries of the mask.
+pub fn explain_html(program: &Program, style: Style) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>ppcheat</title>\n<style>\n{}</style>\n</head>\n<body>\n\
+         <table class=\"ppcheat\">\n",
+        HOVER_STYLE
+    );
+    for (row, line) in rows_styled(program, style).iter().zip(&program.lines) {
+        let diagram = match line.statement {
+            Statement::Instruction(op) => Rotate::new(&op).map(|r| r.html()),
+            _ => None,
+        };
+        out.push_str(&format!(
+            "  <tr><td><code>{}</code></td><td>{}{}</td></tr>\n",
+            escape_html(&row.asm),
+            escape_html(&row.explanation),
+            diagram.unwrap_or_default()
+        ));
+    }
+    out.push_str(&format!(
+        "</table>\n<script>\n{}</script>\n</body>\n</html>\n",
+        HOVER_SCRIPT
+    ));
+    out
+}
+
+fn escape_markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+/// The label naming a block inside a function rather than a function:
+/// `.L_80003110`, and Ghidra's `LAB_` and IDA's `loc_`.
+fn local_label(name: &str) -> bool {
+    name.starts_with('.') || name.starts_with("LAB_") || name.starts_with("loc_")
+}
+
+/// The lines of `program` that are the function `name`: from its label to
+/// the next label that isn't a local one, or in a listing with addresses,
+/// the lines `map` puts in the symbol `name`.
+pub fn function_range(program: &Program, name: &str, map: &SymbolTable) -> Option<Range<usize>> {
+    let lines = &program.lines;
+    fn label(line: &Line) -> Option<&str> {
+        match &line.statement {
+            Statement::Label(label) => Some(label),
+            _ => None,
+        }
+    }
+    if let Some(start) = lines.iter().position(|line| label(line) == Some(name)) {
+        let end = lines[start + 1..]
+            .iter()
+            .position(|line| label(line).is_some_and(|l| !local_label(l)))
+            .map_or(lines.len(), |i| start + 1 + i);
+        return Some(start..end);
+    }
+    let within = |line: &Line| {
+        line.address
+            .and_then(|addr| map.enclosing(addr))
+            .is_some_and(|(symbol, _)| symbol.name == name && !symbol.data)
+    };
+    let start = lines.iter().position(within)?;
+    let end = lines[start..]
+        .iter()
+        .position(|line| line.address.is_some() && !within(line))
+        .map_or(lines.len(), |i| start + i);
+    Some(start..end)
+}
+
+/// Markdown document for pasting into decompilation PR discussions: the
+/// listing in a fenced block followed by a per-line explanation table.
+pub fn export_markdown(symbol: &str, rows: &[Row]) -> String {
+    let mut out = format!("### `{}`\n\n```asm\n", symbol);
+    for row in rows {
+        out.push_str(&row.asm);
+        out.push('\n');
+    }
+    out.push_str("```\n\n| asm | explanation |\n| --- | --- |\n");
+    // an empty code span is two backticks, not nothing
+    let cell = |text: &str| match text {
+        "" => String::new(),
+        _ => format!("`{}`", escape_markdown_cell(text)),
+    };
+    for row in rows {
+        out.push_str(&format!(
+            "| {} | {} |\n",
+            cell(&row.asm),
+            cell(&row.explanation)
+        ));
+    }
+    out
+}
+
+/// The encoded fields of an instruction (of its real form, for a
+/// pseudo-op), as the PowerPC manuals name them.
+fn fields(op: &Opcode) -> Vec<(&'static str, String)> {
+    let reg = |r: Register| r.to_string();
+    match op.canonicalize() {
+        Opcode::Rlwinm {
+            ra, rs, sh, mb, me, ..
+        }
+        | Opcode::Rlwimi {
+            ra, rs, sh, mb, me, ..
+        } => vec![
+            ("rA", reg(ra)),
+            ("rS", reg(rs)),
+            ("SH", sh.to_string()),
+            ("MB", mb.to_string()),
+            ("ME", me.to_string()),
+        ],
+        Opcode::Rlwnm {
+            ra, rs, rb, mb, me, ..
+        } => vec![
+            ("rA", reg(ra)),
+            ("rS", reg(rs)),
+            ("rB", reg(rb)),
+            ("MB", mb.to_string()),
+            ("ME", me.to_string()),
+        ],
+        Opcode::Addi { rd, ra, simm } | Opcode::Addis { rd, ra, simm } => {
+            vec![("rD", reg(rd)), ("rA", reg(ra)), ("SIMM", simm.to_string())]
+        }
+        Opcode::Ori { ra, rs, uimm } | Opcode::Oris { ra, rs, uimm } => {
+            vec![
+                ("rA", reg(ra)),
+                ("rS", reg(rs)),
+                ("UIMM", format!("{:#x}", uimm)),
+            ]
+        }
+        Opcode::Load { rd, d, ra, .. } | Opcode::Lha { rd, d, ra, .. } => {
+            vec![("rD", reg(rd)), ("d", d.to_string()), ("rA", reg(ra))]
+        }
+        Opcode::Store { rs, d, ra, .. } => {
+            vec![("rS", reg(rs)), ("d", d.to_string()), ("rA", reg(ra))]
+        }
+        Opcode::Cmp { crf, ra, rb, .. } => {
+            vec![("crfD", crf.to_string()), ("rA", reg(ra)), ("rB", reg(rb))]
+        }
+        Opcode::Cmpi {
+            logical: false,
+            crf,
+            ra,
+            imm,
+        } => vec![
+            ("crfD", crf.to_string()),
+            ("rA", reg(ra)),
+            ("SIMM", imm.to_string()),
+        ],
+        Opcode::Cmpi { crf, ra, imm, .. } => vec![
+            ("crfD", crf.to_string()),
+            ("rA", reg(ra)),
+            ("UIMM", (imm as u16).to_string()),
+        ],
+        Opcode::CrOp { bt, ba, bb, .. } => vec![
+            ("crbD", format!("{:#}", bt)),
+            ("crbA", format!("{:#}", ba)),
+            ("crbB", format!("{:#}", bb)),
+        ],
+        _ => Vec::new(),
+    }
+}
+
+/// A whole listing as Markdown to paste into reviews or wiki pages: each
+/// instruction gets a heading, a table of its fields, its explanation in a
+/// fenced block and, for rotates, the bit diagram.
+pub fn explain_markdown(program: &Program, style: Style) -> String {
+    let mut out = String::new();
+    for (row, line) in rows_styled(program, style).iter().zip(&program.lines) {
+        let op = match line.statement {
+            Statement::Instruction(op) => op,
+            Statement::Label(_) => {
+                out.push_str(&format!("### `{}`\n\n", row.asm));
+                continue;
+            }
+            Statement::Directive(_) => {
+                out.push_str(&format!(
+                    "`{}`: {}\n\n",
+                    row.asm,
+                    escape_markdown_cell(&row.explanation)
+                ));
+                continue;
+            }
+        };
+
+        out.push_str(&format!("#### `{}`\n\n{}.\n\n", row.asm, op.describe()));
+        let fields = fields(&op);
+        if !fields.is_empty() {
+            let canonical = op.canonicalize();
+            if canonical != op {
+                out.push_str(&format!("Encoded as `{}`:\n\n", canonical));
+            }
+            out.push_str(match BitOrder::current() {
+                BitOrder::Msb0 => "| field | value |\n| --- | --- |\n",
+                BitOrder::Lsb0 => "| field | value (IBM numbering) |\n| --- | --- |\n",
+            });
+            for (name, value) in fields {
+                out.push_str(&format!("| {} | `{}` |\n", name, value));
+            }
+            out.push('\n');
+        }
+        out.push_str(&format!("```c\n{}\n```\n\n", row.explanation));
+        if let Some(diagram) = mask_diagram(&op) {
+            out.push_str(&format!("```text\n{}\n```\n\n", diagram));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_side_by_side_wraps() {
+        let rows = vec![Row {
+            asm: "srwi r3,r4,8".into(),
+            explanation: "r3 = r4 shifted right by eight bits".into(),
+        }];
+        let out = side_by_side(&rows, 32);
+
+        assert_eq!(
+            out,
+            "srwi r3,r4,8 | r3 = r4 shifted\n             | right by eight\n             | bits\n"
+        );
+        assert!(out.lines().all(|l| l.len() <= 32));
+    }
+
+    #[test]
+    fn test_mask_diagram() {
+        let op = crate::parser::parse_opcode("srwi r3,r4,8").unwrap().1;
+        assert_eq!(
+            mask_diagram(&op).unwrap(),
+            "\
+src:  abcdefgh ijklmnop qrstuvwx yzABCDEF  r4
+rotl: yzABCDEF abcdefgh ijklmnop qrstuvwx  r4 << 24
+mask: 00000000 11111111 11111111 11111111  MASK(8..31)=0x00FFFFFF
+kept: 00000000 abcdefgh ijklmnop qrstuvwx  r3"
+        );
+
+        let op = crate::parser::parse_opcode("rlwimi r3,r4,0,16,23")
+            .unwrap()
+            .1;
+        assert!(mask_diagram(&op)
+            .unwrap()
+            .ends_with("kept: -------- -------- qrstuvwx --------  r3 (- is unchanged)"));
+        let op = crate::parser::parse_opcode("li r3,0").unwrap().1;
+        assert_eq!(mask_diagram(&op), None);
+    }
+
+    #[test]
+    fn test_mask_diagram_colored() {
+        let op = crate::parser::parse_opcode("rlwinm r0,r7,16,0,15")
+            .unwrap()
+            .1;
+        let colored = mask_diagram_colored(&op).unwrap();
+        let kept = colored.lines().last().unwrap();
+        // the low halfword of r7 (yellow and blue) lands in the high halfword
+        assert!(kept.starts_with("kept: \x1b[33mq\x1b[0m"));
+        assert!(kept.contains("\x1b[34mF\x1b[0m \x1b[90m0\x1b[0m"));
+
+        let plain: String = colored
+            .split("\x1b[")
+            .map(|part| part.trim_start_matches(|c: char| c.is_ascii_digit()))
+            .map(|part| part.strip_prefix('m').unwrap_or(part))
+            .collect();
+        assert_eq!(plain, mask_diagram(&op).unwrap());
+    }
+
+    #[test]
+    fn test_listing() {
+        let program = crate::parser::parse_program("srwi r3,r4,8\nli r5,1\n").unwrap();
+        let listing = |verbosity| listing(&program, Style::C, verbosity, false, 60);
+        assert_eq!(
+            listing(Verbosity::Terse),
+            "srwi r3,r4,8\nr3 = r4 >> 8\nli r5,1\nr5 = 1\n"
+        );
+        assert_eq!(
+            listing(Verbosity::Verbose),
+            "srwi r3,r4,8\nr3 = r4 >> 8\n\
+             \x20   src:  abcdefgh ijklmnop qrstuvwx yzABCDEF  r4\n\
+             \x20   rotl: yzABCDEF abcdefgh ijklmnop qrstuvwx  r4 << 24\n\
+             \x20   mask: 00000000 11111111 11111111 11111111  MASK(8..31)=0x00FFFFFF\n\
+             \x20   kept: 00000000 abcdefgh ijklmnop qrstuvwx  r3\n\
+             \x20   srwi is rlwinm: Rotate Left Word Immediate then AND with Mask\n\
+             \x20   rA=r3 rS=r4 SH=24 MB=8 ME=31\n\
+             \x20   The contents of rS are rotated left SH bits. A mask is\n\
+             \x20   generated having 1-bits from bit MB through bit ME and\n\
+             \x20   0-bits elsewhere. The rotated data is ANDed with the\n\
+             \x20   generated mask and the result is placed into rA.\n\
+             li r5,1\nr5 = 1\n\
+             \x20   li is addi: Add Immediate\n\
+             \x20   rD=r5 rA=r0 SIMM=1\n\
+             \x20   The sum (rA|0) + SIMM is placed into rD.\n"
+        );
+    }
+
+    #[test]
+    fn test_explain_steps() {
+        let op = crate::parser::parse_opcode("srwi r3,r4,8").unwrap().1;
+        assert_eq!(
+            explain_steps(&op).unwrap(),
+            "\
+1. rotate r4 left by 24 bits
+     abcdefgh ijklmnop qrstuvwx yzABCDEF  r4
+     yzABCDEF abcdefgh ijklmnop qrstuvwx  rotl32(r4, 24)
+2. build the mask of bits 8..31
+     00000000 11111111 11111111 11111111  MASK(8..31)=0x00FFFFFF
+3. AND the rotated value with the mask into r3
+     00000000 abcdefgh ijklmnop qrstuvwx  r3"
+        );
+
+        let op = crate::parser::parse_opcode("rlwnm r3,r4,r5,24,7")
+            .unwrap()
+            .1;
+        let steps = explain_steps(&op).unwrap();
+        assert!(steps.starts_with("1. rotate r4 left by the low 5 bits of r5\n"));
+        assert!(steps.contains("2. build the mask of the top 8 and bottom 8 bits\n"));
+    }
+
+    #[test]
+    fn test_address_materialization() {
+        let src = "lis r3,gState@ha\naddi r3,r3,gState@l\nlis r4,gPad+4@ha\nlwz r5,gPad+4@l(r4)\n";
+        let rows = rows(&crate::parser::parse_program(src).unwrap());
+        let explanations: Vec<_> = rows.iter().map(|r| r.explanation.as_str()).collect();
+
+        assert_eq!(
+            explanations,
+            vec![
+                "r3 = gState@ha << 16",
+                "r3 = &gState",
+                "r4 = gPad+4@ha << 16",
+                "r5 = *(u32 *)(&gPad+4)",
+            ]
+        );
+
+        // @h pairs with ori; @ha would be off by 0x10000 for it
+        let src = "lis r3,gState@h\nori r3,r3,gState@l\nlis r4,gState@ha\nori r4,r4,gState@l\n";
+        let ored = super::rows(&crate::parser::parse_program(src).unwrap());
+        assert_eq!(ored[1].explanation, "r3 = &gState");
+        assert_ne!(ored[3].explanation, "r4 = &gState");
+    }
+
+    #[test]
+    fn test_explain_encoding() {
+        let program = crate::parser::parse_program("rlwinm r0,r3,16,0,15\nli r3,-1\n").unwrap();
+        assert_eq!(
+            explain_encoding(&program),
+            "rlwinm r0,r3,16,0,15
+   0..5    6..10   11..15   16..20   21..25   26..30   31
++--------+-------+--------+--------+--------+--------+----+
+|  OPCD  |  RS   |   RA   |   SH   |   MB   |   ME   | Rc |
+|   21   |   3   |   0    |   16   |   0    |   15   | 0  |
+| 010101 | 00011 | 00000  | 10000  | 00000  | 01111  | 0  |
++--------+-------+--------+--------+--------+--------+----+
+= 0x5460801E
+
+li r3,-1  (encoded as addi r3,r0,-1)
+   0..5    6..10   11..15        16..31
++--------+-------+--------+------------------+
+|  OPCD  |  RD   |   RA   |       SIMM       |
+|   14   |   3   |   0    |      65535       |
+| 001110 | 00011 | 00000  | 1111111111111111 |
++--------+-------+--------+------------------+
+= 0x3860FFFF
+"
+        );
+    }
+
+    #[test]
+    fn test_sequences() {
+        let src = "extrwi r0,r5,8,4\ninsrwi r6,r0,8,20\nlis r3,0x8000\naddi r3,r3,-4\n\
+                   rlwinm r0,r5,0,0,7\nrlwimi r6,r0,0,0,15\nlis r3,0x8040\nori r3,r3,0x10\n\
+                   lis r4,0x8000\nori r4,r4,0x8000\n";
+        let rows = rows(&crate::parser::parse_program(src).unwrap());
+        let explanations: Vec<_> = rows.iter().map(|r| r.explanation.as_str()).collect();
+
+        assert_eq!(
+            explanations[1],
+            "copy bits 4..11 of r5 into bits 20..27 of r6, through r0"
+        );
+        assert_eq!(explanations[3], "r3 = 0x7ffffffc");
+        // the rest of bits 0..15 are the zeros rlwinm cleared, not a copy
+        assert!(explanations[5].starts_with("r6 = (r6 & ~MASK(0..15)"));
+        assert_eq!(explanations[7], "r3 = 0x80400010");
+        // unlike addi's, ori's half isn't sign-extended
+        assert_eq!(explanations[9], "r4 = 0x80008000");
+    }
+
+    #[test]
+    fn test_explain_markdown() {
+        let program = crate::parser::parse_program("extrwi r3,r4,8,16\nli r0,1\n").unwrap();
+        let md = explain_markdown(&program, Style::C);
+
+        assert!(md.starts_with(
+            "#### `extrwi r3,r4,8,16`\n\n\
+             extract the 8-bit field starting at bit 16 of r4 into the low bits of r3.\n\n\
+             Encoded as `rlwinm r3,r4,24,24,31`:\n\n\
+             | field | value |\n| --- | --- |\n| rA | `r3` |\n"
+        ));
+        assert!(md.contains("```c\nr3 = (r4 >> 8) & 0xFF\n```\n\n```text\nsrc:  "));
+        assert!(md.ends_with("| SIMM | `1` |\n\n```c\nr0 = 1\n```\n\n"));
+    }
+
+    #[test]
+    fn test_explain_html() {
+        let program = crate::parser::parse_program("rlwinm r0,r7,16,0,15\nli r3,0\n").unwrap();
+        let html = explain_html(&program, Style::Symbolic);
+
+        assert!(html.starts_with("<!DOCTYPE html>\n"));
+        assert!(html.contains("<script>"));
+        // bit 0 of r0 comes from bit 16 of r7, and starts the field
+        assert!(html.contains("<div><span data-bit=\"16\" class=\"mb\">q</span>"));
+        assert!(html.contains("<div class=\"bits\" title=\"MASK(0..15)=0xFFFF0000\"><div><span data-bit=\"0\">a</span>"));
+        assert!(html.contains("<span class=\"cleared\">0</span><span class=\"label\">r0</span>"));
+        assert!(html.contains("<tr><td><code>li r3,0</code></td><td>r3 = 0</td></tr>"));
+    }
+
+    #[test]
+    fn test_explain_tikz() {
+        let program = crate::parser::parse_program("li r3,0\nrlwinm r0,r7,16,24,7\n").unwrap();
+        let tikz = explain_tikz(&program);
+
+        assert!(tikz.starts_with("% rlwinm r0,r7,16,24,7\n\\begin{tikzpicture}"));
+        assert!(tikz.contains("\\fill[blue!15] (0,-1) rectangle (8,0);\n"));
+        assert!(tikz.contains("\\fill[blue!15] (24,-1) rectangle (32,0);\n"));
+        assert!(tikz.contains("in {q,r,s,t,u,v,w,x,0,0,"));
+        assert!(tikz.contains("{$\\downarrow$ rotl 16 (SH=16)}"));
+        assert!(tikz.contains("node[pos=0,below] {MB=24}"));
+        assert!(tikz.ends_with("\\end{tikzpicture}\n"));
+    }
+
+    #[test]
+    fn test_rust_fn() {
+        let program = crate::parser::parse_program("loop:\nsrwi r3,r4,8\n").unwrap();
+        assert_eq!(
+            rust_fn(&program, "f"),
+            "fn f(r: &mut [u32; 32], cr: &mut [bool; 32], mem: &mut impl Memory) {\n    \
+             // loop:\n    r[3] = r[4].rotate_left(24) & 0x00FF_FFFF; // srwi r3,r4,8\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_python_fn() {
+        let program = crate::parser::parse_program("loop:\nsrwi r3,r4,8\n").unwrap();
+        assert_eq!(
+            python_fn(&program, "f"),
+            "def f(r, cr, mem):\n    # loop:\n    \
+             r[3] = (r[4] << 24 | r[4] >> (32 - 24)) & 0x00FFFFFF # srwi r3,r4,8\n"
+        );
+    }
+
+    #[test]
+    fn test_export_markdown() {
+        let rows = vec![Row {
+            asm: "or r3,r3,r4".into(),
+            explanation: "r3 = r3 | r4".into(),
+        }];
+        let md = export_markdown("fn_80045678", &rows);
+
+        assert!(md.starts_with("### `fn_80045678`\n\n```asm\nor r3,r3,r4\n```\n"));
+        assert!(md.ends_with("| `or r3,r3,r4` | `r3 = r3 \\| r4` |\n"));
+
+        let src = "fn_a:\nli r3,0\n.L_1:\naddi r3,r3,1\nb .L_1\nfn_b:\nli r4,1\nli r5,2\n";
+        let program = crate::parser::parse_program(src).unwrap();
+        let range = function_range(&program, "fn_a", &SymbolTable::default()).unwrap();
+        assert_eq!(range, 0..5);
+        let md = export_markdown("fn_a", &super::rows(&program)[range]);
+        assert!(md.contains("| `fn_a:` |  |\n| `li r3,0` | `r3 = 0` |\n| `.L_1:` |  |\n"));
+        assert!(!md.contains("fn_b") && !md.contains("r4"));
+        assert_eq!(
+            function_range(&program, "fn_b", &SymbolTable::default()),
+            Some(5..8)
+        );
+        assert_eq!(
+            function_range(&program, "fn_c", &SymbolTable::default()),
+            None
+        );
+
+        // a listing without labels, by its addresses
+        let src = "80003100: 38 60 00 00  li r3,0\n80003104: 38 63 00 01  addi r3,r3,1\n\
+                   80003108: 38 80 00 01  li r4,1\n8000310c: 38 a0 00 02  li r5,2\n";
+        let program = crate::parser::parse_program(src).unwrap();
+        let map = SymbolTable::parse(
+            "80003100 000008 80003100  4 fn_a\n80003108 000008 80003108  4 fn_b\n",
+        );
+        assert_eq!(function_range(&program, "fn_b", &map), Some(2..4));
+        assert_eq!(function_range(&program, "fn_a", &map), Some(0..2));
+    }
+}
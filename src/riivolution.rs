@@ -0,0 +1,151 @@
+//! Riivolution memory patches, for running a patch on a Wii from the SD
+//! card rather than through a code handler:
+//!
+//! ```text
+//! <patch id="infinite_lives">
+//!     <memory offset="0x80003100" value="38600001" original="7C0802A6" />
+//! </patch>
+//! ```
+//!
+//! A `memory` with an `original` is only applied when memory holds those
+//! bytes, so a patch built against one revision of a game leaves the others
+//! alone.
+
+use core::fmt;
+
+use crate::image::{Image, Verification};
+use crate::render::escape_html;
+
+/// One `<memory>`: `value` written at `offset`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Memory {
+    pub offset: u32,
+    pub value: Vec<u8>,
+    pub original: Option<Vec<u8>>,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+impl fmt::Display for Memory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "<memory offset=\"0x{:08X}\" value=\"{}\"",
+            self.offset,
+            hex(&self.value)
+        )?;
+        if let Some(original) = &self.original {
+            write!(f, " original=\"{}\"", hex(original))?;
+        }
+        write!(f, " />")
+    }
+}
+
+/// A `<patch>`, and the option turning it on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Patch {
+    /// The option's name, as the Riivolution menu shows it
+    pub name: String,
+    pub memory: Vec<Memory>,
+}
+
+impl Patch {
+    /// A `<memory>` per word of assembled `bytes` at `addr`, and one for
+    /// any bytes left over.
+    pub fn new(name: &str, bytes: &[u8], addr: u32) -> Self {
+        let memory = bytes
+            .chunks(4)
+            .zip((addr..).step_by(4))
+            .map(|(value, offset)| Memory {
+                offset,
+                value: value.to_vec(),
+                original: None,
+            })
+            .collect();
+        Patch {
+            name: name.to_string(),
+            memory,
+        }
+    }
+
+    /// Fills in each `original` from `image`, or gives the offset of the
+    /// first write to memory `image` doesn't load.
+    pub fn capture_originals(&mut self, image: &Image) -> Result<(), u32> {
+        for memory in &mut self.memory {
+            let size = memory.value.len() as u32;
+            let check = Verification::new(image, memory.offset, size).ok_or(memory.offset)?;
+            memory.original = Some(check.original);
+        }
+        Ok(())
+    }
+
+    /// The patch's `id`: its name in lower case, with `_` for anything but
+    /// letters and digits.
+    pub fn id(&self) -> String {
+        self.name
+            .chars()
+            .map(|c| match c.is_ascii_alphanumeric() {
+                true => c.to_ascii_lowercase(),
+                false => '_',
+            })
+            .collect()
+    }
+}
+
+/// A whole `<wiidisc>` document for `patches`, each an option of its own
+/// under a `ppcheat` section, for the game with ID `game` (its first three
+/// or four characters, so any region and revision) or for any game.
+pub fn document(game: Option<&str>, patches: &[Patch]) -> String {
+    let mut out = String::from("<wiidisc version=\"1\">\n");
+    if let Some(game) = game {
+        out += &format!("\t<id game=\"{}\" />\n", escape_html(game));
+    }
+    out += "\t<options>\n\t\t<section name=\"ppcheat\">\n";
+    for patch in patches {
+        out += &format!("\t\t\t<option name=\"{}\">\n", escape_html(&patch.name));
+        out += "\t\t\t\t<choice name=\"Enabled\">\n";
+        out += &format!("\t\t\t\t\t<patch id=\"{}\" />\n", patch.id());
+        out += "\t\t\t\t</choice>\n\t\t\t</option>\n";
+    }
+    out += "\t\t</section>\n\t</options>\n";
+    for patch in patches {
+        out += &format!("\t<patch id=\"{}\">\n", patch.id());
+        for memory in &patch.memory {
+            out += &format!("\t\t{}\n", memory);
+        }
+        out += "\t</patch>\n";
+    }
+    out + "</wiidisc>\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document() {
+        let mut patch = Patch::new(
+            "Infinite lives & more",
+            &[0x38, 0x60, 0x00, 0x01, 0x4E, 0x80, 0x00, 0x20],
+            0x8000_3100,
+        );
+        assert_eq!(patch.id(), "infinite_lives___more");
+        let image = Image::raw(
+            &[0x7C, 0x08, 0x02, 0xA6, 0x94, 0x21, 0xFF, 0xF0],
+            0x8000_3100,
+        );
+        patch.capture_originals(&image).unwrap();
+        assert_eq!(
+            patch.memory[1].to_string(),
+            "<memory offset=\"0x80003104\" value=\"4E800020\" original=\"9421FFF0\" />"
+        );
+        let xml = document(Some("RMC"), &[patch.clone()]);
+        assert!(xml.contains("<option name=\"Infinite lives &amp; more\">"));
+        assert!(xml.contains("<id game=\"RMC\" />"));
+
+        patch.memory[0].offset = 0x8000_3200;
+        assert_eq!(patch.capture_originals(&image), Err(0x8000_3200));
+    }
+}
@@ -0,0 +1,255 @@
+//! Round trips between [`Opcode::encode`] and [`Opcode::decode`]: every word
+//! that decodes has to encode back to itself, and every instruction that
+//! encodes has to decode to one with the same word. Field ranges small
+//! enough are covered exhaustively, the rest by seeded random words, so a
+//! failure always reproduces.
+
+use crate::vectors::Rng;
+use crate::{CrBit, CrField, CrLogic, Opcode, Register, Width};
+
+/// Primary opcodes ppcheat decodes.
+const PRIMARY: &[u32] = &[
+    10, 11, 14, 15, 16, 18, 19, 20, 21, 23, 24, 25, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42,
+    43, 44, 45,
+];
+
+/// Extended opcodes (bits 21..30) of the X- and XL-form instructions, for
+/// primary opcodes 31 and 19.
+const EXTENDED: &[(u32, &[u32])] = &[
+    (31, &[0, 32]),
+    (19, &[257, 449, 193, 225, 33, 289, 129, 417]),
+];
+
+/// `word` decodes to something that encodes back to exactly `word`.
+fn check_word(word: u32) -> bool {
+    match Opcode::decode(word) {
+        Ok(op) => {
+            assert_eq!(op.encode(), Ok(word), "{:08X} decoded to `{}`", word, op);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// `op` round-trips as `check_op` if it keeps the rules, and otherwise
+/// won't encode.
+fn check_checked(op: Opcode) {
+    if op.check().is_ok() {
+        return check_op(op);
+    }
+    assert!(op.encode().is_err(), "`{}` encoded", op);
+}
+
+/// `op` encodes, and what its word decodes to encodes the same.
+fn check_op(op: Opcode) {
+    let word = op
+        .encode()
+        .unwrap_or_else(|e| panic!("`{}` didn't encode: {}", op, e));
+    let decoded = Opcode::decode(word).unwrap_or_else(|e| panic!("`{}`: {}", op, e));
+    assert_eq!(
+        decoded.encode(),
+        Ok(word),
+        "`{}` came back as `{}`",
+        op,
+        decoded
+    );
+}
+
+/// A random word with primary opcode `opcd`, and half the time one of its
+/// extended opcodes.
+fn random_word(rng: &mut Rng, opcd: u32) -> u32 {
+    let mut word = (opcd << 26) | (rng.word() >> 6);
+    if let Some((_, xos)) = EXTENDED.iter().find(|(p, _)| *p == opcd) {
+        if rng.below(2) == 0 {
+            let xo = xos[rng.below(xos.len() as u64) as usize];
+            word = (word & !(0x3FF << 1)) | (xo << 1);
+        }
+    }
+    word
+}
+
+#[test]
+fn test_random_words() {
+    let mut rng = Rng(0x5eed_c0de);
+    for &opcd in PRIMARY {
+        let decoded = (0..20_000)
+            .filter(|_| check_word(random_word(&mut rng, opcd)))
+            .count();
+        assert!(decoded > 0, "no word with primary opcode {} decoded", opcd);
+    }
+}
+
+#[test]
+fn test_rotates() {
+    let mut rng = Rng(0x0707_a7e5);
+    for sh in 0..32 {
+        for mb in 0..32 {
+            for me in 0..32 {
+                let (ra, rs, rb) = (rng.reg(), rng.reg(), rng.reg());
+                let rc = rng.below(2) == 1;
+                check_op(Opcode::Rlwinm {
+                    ra,
+                    rs,
+                    sh,
+                    mb,
+                    me,
+                    rc,
+                });
+                check_op(Opcode::Rlwimi {
+                    ra,
+                    rs,
+                    sh,
+                    mb,
+                    me,
+                    rc,
+                });
+                if sh == 0 {
+                    check_op(Opcode::Rlwnm {
+                        ra,
+                        rs,
+                        rb,
+                        mb,
+                        me,
+                        rc,
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_pseudo_ops() {
+    let (ra, rs) = (Register(3), Register(4));
+    for n in 0..32 {
+        check_op(Opcode::Rotlwi { ra, rs, n });
+        check_op(Opcode::Rotrwi { ra, rs, n });
+        check_op(Opcode::Slwi { ra, rs, n });
+        check_op(Opcode::Srwi { ra, rs, n });
+        check_op(Opcode::Clrlwi { ra, rs, n });
+        check_op(Opcode::Clrrwi { ra, rs, n });
+        for b in n..32 {
+            check_op(Opcode::Clrlslwi { ra, rs, b, n });
+        }
+    }
+    for n in 1..=32 {
+        for b in 0..=32 - n {
+            check_op(Opcode::Extlwi { ra, rs, n, b });
+            check_op(Opcode::Extrwi { ra, rs, n, b });
+            check_op(Opcode::Inslwi { ra, rs, n, b });
+            check_op(Opcode::Insrwi { ra, rs, n, b });
+        }
+    }
+    check_op(Opcode::Rotlw {
+        ra,
+        rs,
+        rb: Register(5),
+    });
+}
+
+#[test]
+fn test_d_forms() {
+    let mut rng = Rng(0x00df_02a5);
+    for simm in (i16::MIN..=i16::MAX).step_by(7).chain([-1, 0, 1, i16::MAX]) {
+        let (rd, ra) = (rng.reg(), rng.reg());
+        check_op(Opcode::Addi { rd, ra, simm });
+        check_op(Opcode::Addis { rd, ra, simm });
+        check_op(Opcode::Li { rd, simm });
+        check_op(Opcode::Lis { rd, simm });
+        let uimm = simm as u16;
+        check_op(Opcode::Ori {
+            ra: rd,
+            rs: ra,
+            uimm,
+        });
+        check_op(Opcode::Oris {
+            ra: rd,
+            rs: ra,
+            uimm,
+        });
+        for &width in &[Width::Byte, Width::Half, Width::Word] {
+            for &update in &[false, true] {
+                let d = simm;
+                check_checked(Opcode::Load {
+                    width,
+                    update,
+                    rd,
+                    d,
+                    ra,
+                });
+                check_checked(Opcode::Store {
+                    width,
+                    update,
+                    rs: rd,
+                    d,
+                    ra,
+                });
+            }
+        }
+        check_checked(Opcode::Lha {
+            update: rng.below(2) == 1,
+            rd,
+            d: simm,
+            ra,
+        });
+        let crf = CrField(rng.below(8) as u8);
+        for &logical in &[false, true] {
+            check_op(Opcode::Cmpi {
+                logical,
+                crf,
+                ra,
+                imm: simm,
+            });
+            check_op(Opcode::Cmp {
+                logical,
+                crf,
+                ra,
+                rb: rd,
+            });
+        }
+    }
+}
+
+#[test]
+fn test_cr_ops_and_branches() {
+    let ops = [
+        CrLogic::And,
+        CrLogic::Or,
+        CrLogic::Xor,
+        CrLogic::Nand,
+        CrLogic::Nor,
+        CrLogic::Eqv,
+        CrLogic::Andc,
+        CrLogic::Orc,
+    ];
+    let mut rng = Rng(0x00b2_a2c4);
+    for bt in 0..32 {
+        for &op in &ops {
+            let (ba, bb) = (CrBit(rng.field()), CrBit(rng.field()));
+            check_op(Opcode::CrOp {
+                op,
+                bt: CrBit(bt),
+                ba,
+                bb,
+            });
+        }
+    }
+    for bo in 0..32 {
+        for bi in 0..32 {
+            let disp = rng.simm() & !3;
+            check_op(Opcode::Bc {
+                bo,
+                bi: CrBit(bi),
+                disp,
+            });
+        }
+    }
+    for disp in (-(1 << 25)..1 << 25)
+        .step_by(4 * 9973)
+        .chain([-4, 0, 4, (1 << 25) - 4])
+    {
+        for &link in &[false, true] {
+            check_op(Opcode::B { disp, link });
+        }
+    }
+}
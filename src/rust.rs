@@ -0,0 +1,256 @@
+//! Rust statements equivalent to each instruction, for porting routines:
+//! GPRs are `r: [u32; 32]`, condition register bits are `cr: [bool; 32]`
+//! (bit `4*crN + lt/gt/eq/so`, as in the ISA), and `mem` is whatever the
+//! caller uses for big-endian memory, with `read_u8/u16/u32(ea)` and
+//! `write_u8/u16/u32(ea, value)`.
+
+use crate::{eval, CrLogic, Opcode, Register, Width};
+
+/// A 32-bit constant the way rustfmt-friendly code writes masks.
+fn hex(v: u32) -> String {
+    format!("0x{:04X}_{:04X}", v >> 16, v & 0xFFFF)
+}
+
+fn reg(r: Register) -> String {
+    format!("r[{}]", r.0)
+}
+
+/// `(rA|0) + d`: rA=0 is the literal 0.
+fn address(ra: Register, d: i16) -> String {
+    match (ra.0, d) {
+        (0, d) => hex(d as i32 as u32),
+        (_, 0) => reg(ra),
+        (_, d) if d < 0 => format!("{}.wrapping_sub({})", reg(ra), -(d as i32)),
+        (_, d) => format!("{}.wrapping_add({})", reg(ra), d),
+    }
+}
+
+/// `value` rotated left by `by` and masked, leaving out no-op parts.
+fn rotated(value: String, by: String, mask: u32) -> String {
+    let value = match by.as_str() {
+        "0" => value,
+        _ => format!("{}.rotate_left({})", value, by),
+    };
+    match mask {
+        u32::MAX => value,
+        mask => format!("{} & {}", value, hex(mask)),
+    }
+}
+
+fn immediate(v: i32) -> String {
+    match v {
+        v if v < 0 => format!("{}i32 as u32", v),
+        v => v.to_string(),
+    }
+}
+
+impl Opcode {
+    /// One Rust statement (or block) doing what the instruction does, e.g.
+    /// `r[0] = r[7].rotate_left(16) & 0xFFFF_0000;`. See the module docs for
+    /// what `r`, `cr` and `mem` are. A record form goes on to compare its
+    /// result into cr0.
+    pub fn highlevel_rust(&self) -> String {
+        let statement = match self.canonicalize() {
+            Self::Rlwinm {
+                ra, rs, sh, mb, me, ..
+            } => format!(
+                "{} = {};",
+                reg(ra),
+                rotated(reg(rs), sh.to_string(), eval::mask(mb, me))
+            ),
+            Self::Rlwimi {
+                ra, rs, sh, mb, me, ..
+            } => {
+                let mask = eval::mask(mb, me);
+                format!(
+                    "{ra} = ({ra} & !{mask}) | ({value});",
+                    ra = reg(ra),
+                    mask = hex(mask),
+                    value = rotated(reg(rs), sh.to_string(), mask)
+                )
+            }
+            Self::Rlwnm {
+                ra, rs, rb, mb, me, ..
+            } => format!(
+                "{} = {};",
+                reg(ra),
+                rotated(reg(rs), format!("{} & 31", reg(rb)), eval::mask(mb, me))
+            ),
+            Self::Addi { rd, ra, simm } if ra.0 == 0 => {
+                format!("{} = {};", reg(rd), immediate(simm.into()))
+            }
+            Self::Addi { rd, ra, simm } if simm < 0 => format!(
+                "{} = {}.wrapping_sub({});",
+                reg(rd),
+                reg(ra),
+                -i32::from(simm)
+            ),
+            Self::Addi { rd, ra, simm } => {
+                format!("{} = {}.wrapping_add({});", reg(rd), reg(ra), simm)
+            }
+            Self::Addis { rd, ra, simm } => {
+                let value = hex((simm as u16 as u32) << 16);
+                match ra.0 {
+                    0 => format!("{} = {};", reg(rd), value),
+                    _ => format!("{} = {}.wrapping_add({});", reg(rd), reg(ra), value),
+                }
+            }
+            Self::Ori { ra, rs, uimm } => {
+                format!("{} = {} | {};", reg(ra), reg(rs), hex(uimm.into()))
+            }
+            Self::Oris { ra, rs, uimm } => {
+                format!(
+                    "{} = {} | {};",
+                    reg(ra),
+                    reg(rs),
+                    hex(u32::from(uimm) << 16)
+                )
+            }
+            Self::Load {
+                width,
+                update,
+                rd,
+                d,
+                ra,
+            } => {
+                let read = |ea: String| match width {
+                    Width::Word => format!("mem.read_u32({})", ea),
+                    _ => format!("mem.read_{}({}) as u32", width.c_type(), ea),
+                };
+                update_form(update, ra, d, |ea| format!("{} = {};", reg(rd), read(ea)))
+            }
+            Self::Lha { update, rd, d, ra } => update_form(update, ra, d, |ea| {
+                format!("{} = mem.read_u16({}) as i16 as u32;", reg(rd), ea)
+            }),
+            Self::Store {
+                width,
+                update,
+                rs,
+                d,
+                ra,
+            } => {
+                let value = match width {
+                    Width::Word => reg(rs),
+                    _ => format!("{} as {}", reg(rs), width.c_type()),
+                };
+                update_form(update, ra, d, |ea| {
+                    format!("mem.write_{}({}, {});", width.c_type(), ea, value)
+                })
+            }
+            Self::Cmp {
+                logical,
+                crf,
+                ra,
+                rb,
+            } => {
+                let (a, b) = match logical {
+                    true => (reg(ra), reg(rb)),
+                    false => (
+                        format!("({} as i32)", reg(ra)),
+                        format!("({} as i32)", reg(rb)),
+                    ),
+                };
+                compare(crf.0, format!("{}.cmp(&{})", a, b))
+            }
+            Self::Cmpi {
+                logical: false,
+                crf,
+                ra,
+                imm,
+            } => compare(crf.0, format!("({} as i32).cmp(&{})", reg(ra), imm)),
+            Self::Cmpi { crf, ra, imm, .. } => {
+                compare(crf.0, format!("{}.cmp(&{})", reg(ra), imm as u16))
+            }
+            Self::CrOp { op, bt, ba, bb } => {
+                let (a, b) = (format!("cr[{}]", ba.0), format!("cr[{}]", bb.0));
+                let value = match op {
+                    CrLogic::And => format!("{} & {}", a, b),
+                    CrLogic::Or => format!("{} | {}", a, b),
+                    CrLogic::Xor => format!("{} ^ {}", a, b),
+                    CrLogic::Nand => format!("!({} & {})", a, b),
+                    CrLogic::Nor => format!("!({} | {})", a, b),
+                    CrLogic::Eqv => format!("{} == {}", a, b),
+                    CrLogic::Andc => format!("{} & !{}", a, b),
+                    CrLogic::Orc => format!("{} | !{}", a, b),
+                };
+                format!("cr[{}] = {};", bt.0, value)
+            }
+            // there's no goto to port a branch to
+            Self::B { .. } | Self::Bc { .. } => format!("// {}", self),
+            // canonicalize() leaves only real instructions
+            op => unreachable!("{} is a pseudo-op", op),
+        };
+        match self.record() {
+            Some(ra) => format!(
+                "{} {}",
+                statement,
+                compare(0, format!("({} as i32).cmp(&0)", reg(ra)))
+            ),
+            None => statement,
+        }
+    }
+}
+
+/// A load or store through `ea`, which with update is also written to rA.
+fn update_form(update: bool, ra: Register, d: i16, access: impl Fn(String) -> String) -> String {
+    match update {
+        false => access(address(ra, d)),
+        true => format!(
+            "{{ let ea = {}; {} {} = ea; }}",
+            address(ra, d),
+            access("ea".to_string()),
+            reg(ra)
+        ),
+    }
+}
+
+/// Sets LT, GT and EQ of `cr<field>` from an `Ordering`. SO (a copy of
+/// XER[SO]) is left alone.
+fn compare(field: u8, ordering: String) -> String {
+    let bit = 4 * field;
+    format!(
+        "{{ let c = {}; cr[{}] = c.is_lt(); cr[{}] = c.is_gt(); cr[{}] = c.is_eq(); }}",
+        ordering,
+        bit,
+        bit + 1,
+        bit + 2
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_opcode;
+
+    #[test]
+    fn test_highlevel_rust() {
+        let rust = |asm| parse_opcode(asm).expect("parse failed").1.highlevel_rust();
+        assert_eq!(
+            rust("rlwinm r0,r7,16,0,15"),
+            "r[0] = r[7].rotate_left(16) & 0xFFFF_0000;"
+        );
+        assert_eq!(
+            rust("insrwi r3,r4,8,16"),
+            "r[3] = (r[3] & !0x0000_FF00) | (r[4].rotate_left(8) & 0x0000_FF00);"
+        );
+        assert_eq!(rust("li r3,-1"), "r[3] = -1i32 as u32;");
+        assert_eq!(rust("oris r3,r3,0x8040"), "r[3] = r[3] | 0x8040_0000;");
+        assert_eq!(
+            rust("lhau r5,-2(r1)"),
+            "{ let ea = r[1].wrapping_sub(2); r[5] = mem.read_u16(ea) as i16 as u32; r[1] = ea; }"
+        );
+        assert_eq!(
+            rust("stb r0,8(r3)"),
+            "mem.write_u8(r[3].wrapping_add(8), r[0] as u8);"
+        );
+        assert_eq!(
+            rust("cmpwi cr1,r3,-5"),
+            "{ let c = (r[3] as i32).cmp(&-5); cr[4] = c.is_lt(); cr[5] = c.is_gt(); cr[6] = c.is_eq(); }"
+        );
+        assert_eq!(rust("crandc eq,gt,lt"), "cr[2] = cr[1] & !cr[0];");
+        assert_eq!(
+            rust("rlwnm. r3,r4,r5,0,31"),
+            "r[3] = r[4].rotate_left(r[5] & 31); \
+             { let c = (r[3] as i32).cmp(&0); cr[0] = c.is_lt(); cr[1] = c.is_gt(); cr[2] = c.is_eq(); }"
+        );
+    }
+}
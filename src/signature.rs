@@ -0,0 +1,233 @@
+//! Signatures for the Gecko codes that find their own place: an F6 code
+//! searches memory for 16 bytes of a function and points `po` at them, so
+//! a code patching off `po` works wherever the game, or a REL it loaded,
+//! put the function. And the checksums F2 codes test before they hook.
+//!
+//! A signature has to be found once, and only at the function; one without
+//! branch displacements or addresses in it is likelier to still be found
+//! in another build of the game.
+
+use crate::gecko::{self, GeckoError, Line, ADDRESS_MASK};
+use crate::image::Image;
+use crate::port::signature_mask;
+
+/// How many words a signature is: 16 bytes, 2 lines of an F6 code.
+pub const WORDS: u32 = 4;
+/// How far from an address its function is looked for.
+const FUNCTION_LIMIT: u32 = 0x1000;
+const BLR: u32 = 0x4E80_0020;
+
+/// Where a signature could start, and how good it is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub addr: u32,
+    pub words: Vec<u32>,
+    /// How many times the words are in the image
+    pub matches: usize,
+    /// Whether none of the words has a branch displacement or an address
+    /// in it
+    pub stable: bool,
+}
+
+fn word(image: &Image, addr: u32) -> Option<u32> {
+    let b = image.read(addr, 4)?;
+    Some(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// The function `addr` is in, as a range: from just after the `blr` before
+/// it, through the `blr` ending it, as far as `image` loads and up to
+/// 0x1000 bytes either way.
+pub fn function(image: &Image, addr: u32) -> Option<(u32, u32)> {
+    let addr = addr & !3;
+    word(image, addr)?;
+    let mut start = addr;
+    while addr - start < FUNCTION_LIMIT {
+        match word(image, start.wrapping_sub(4)) {
+            Some(w) if w != BLR => start -= 4,
+            _ => break,
+        }
+    }
+    let mut end = addr;
+    while end - addr < FUNCTION_LIMIT {
+        match word(image, end) {
+            Some(w) => {
+                end += 4;
+                if w == BLR {
+                    break;
+                }
+            }
+            None => break,
+        }
+    }
+    Some((start, end))
+}
+
+/// What an F6 code searches to find anything `image` loads: its lowest
+/// to its highest address, rounded out to 64KB.
+pub fn search_range(image: &Image) -> (u32, u32) {
+    let start = image.sections.iter().map(|s| s.addr).min().unwrap_or(0);
+    let end = image
+        .sections
+        .iter()
+        .map(|s| s.addr.wrapping_add(s.data.len() as u32))
+        .max()
+        .unwrap_or(0);
+    (
+        start & 0xFFFF_0000,
+        end.saturating_add(0xFFFF) & 0xFFFF_0000,
+    )
+}
+
+/// How many times `words` are in `image`, word-aligned as F6 searches.
+fn matches(image: &Image, words: &[u32]) -> usize {
+    let mut count = 0;
+    for section in &image.sections {
+        let data: Vec<_> = section
+            .data
+            .chunks_exact(4)
+            .map(|w| u32::from_be_bytes([w[0], w[1], w[2], w[3]]))
+            .collect();
+        count += data.windows(words.len()).filter(|w| *w == words).count();
+    }
+    count
+}
+
+/// Each signature from the start of `addr`'s function up to `addr` itself,
+/// as a code off `po` can only patch after it, and in the function if it's
+/// long enough to have one.
+pub fn candidates(image: &Image, addr: u32) -> Vec<Candidate> {
+    let (start, end) = match function(image, addr) {
+        Some(range) => range,
+        None => return Vec::new(),
+    };
+    let last = (addr & !3).min(end.saturating_sub(4 * WORDS).max(start));
+    (start..=last)
+        .step_by(4)
+        .filter_map(|at| {
+            let words: Option<Vec<_>> = (0..WORDS).map(|i| word(image, at + 4 * i)).collect();
+            let words = words?;
+            Some(Candidate {
+                addr: at,
+                matches: matches(image, &words),
+                stable: words.iter().all(|&w| signature_mask(w) == !0),
+                words,
+            })
+        })
+        .collect()
+}
+
+/// The candidate found just once that's closest to the address it's for,
+/// preferring a stable one.
+pub fn best(candidates: &[Candidate]) -> Option<&Candidate> {
+    candidates
+        .iter()
+        .filter(|c| c.matches == 1)
+        .max_by_key(|c| (c.stable, c.addr))
+}
+
+/// The XOR of the half words of the `around` instructions before `addr`
+/// (after, for negative), as an F2 code tests them, if `image` loads them.
+pub fn checksum(image: &Image, addr: u32, around: i8) -> Option<u16> {
+    let n = u32::from(around.unsigned_abs());
+    let first = match around < 0 {
+        true => addr.wrapping_add(4),
+        false => addr.wrapping_sub(4 * n),
+    };
+    (0..n).try_fold(0, |sum, i| {
+        let w = word(image, first.wrapping_add(4 * i))?;
+        Some(sum ^ (w >> 16) as u16 ^ w as u16)
+    })
+}
+
+/// Codes off `po`, after an F6 `search` has pointed it at a signature
+/// `offset` bytes before where they patch: `patch` as 14 writes, or with
+/// `insert` a D2 code hooking there, then an E0 to end the search's
+/// conditional and put `ba` and `po` back.
+pub fn found(
+    search: Vec<Line>,
+    patch: &[u8],
+    offset: u32,
+    insert: bool,
+) -> Result<Vec<Line>, GeckoError> {
+    // made as 04 and C2 codes at the offset off ba, and moved off po
+    let at = gecko::BASE_ADDRESS.wrapping_add(offset);
+    let mut codes = match insert {
+        true => {
+            let mut lines = gecko::insert_asm(patch, at)?;
+            lines[0].0 |= 0x1000_0000;
+            lines
+        }
+        false => {
+            let mut lines = gecko::word_writes(patch, at)?;
+            for line in &mut lines {
+                line.0 = 0x1400_0000 | (line.0 & ADDRESS_MASK);
+            }
+            lines
+        }
+    };
+    let mut lines = search;
+    lines.append(&mut codes);
+    lines.push(Line(0xE000_0000, 0x8000_8000));
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature() {
+        // blr; then a function whose first words are also another's:
+        // stwu r1,-16(r1); mflr r0; stw r0,20(r1); bl; mr r31,r3;
+        // cmpwi r3,0; slwi r0,r3,2; blr
+        let words: [u32; 14] = [
+            BLR,
+            0x9421_FFF0,
+            0x7C08_02A6,
+            0x9001_0014,
+            0x4800_0101,
+            0x7C7F_1B78,
+            0x2C03_0000,
+            0x5460_103A,
+            BLR,
+            0x9421_FFF0,
+            0x7C08_02A6,
+            0x9001_0014,
+            0x4800_0101,
+            BLR,
+        ];
+        let bytes: Vec<_> = words.iter().flat_map(|w| w.to_be_bytes()).collect();
+        let image = Image::raw(&bytes, 0x8000_3100);
+        assert_eq!(
+            function(&image, 0x8000_3110),
+            Some((0x8000_3104, 0x8000_3124))
+        );
+        assert_eq!(search_range(&image), (0x8000_0000, 0x8001_0000));
+
+        let candidates = candidates(&image, 0x8000_3114);
+        let ranked: Vec<_> = candidates
+            .iter()
+            .map(|c| (c.addr, c.matches, c.stable))
+            .collect();
+        assert_eq!(
+            ranked,
+            [
+                (0x8000_3104, 2, false),
+                (0x8000_3108, 1, false),
+                (0x8000_310C, 1, false),
+                (0x8000_3110, 1, false),
+                (0x8000_3114, 1, true),
+            ]
+        );
+        assert_eq!(best(&candidates).unwrap().addr, 0x8000_3114);
+        assert_eq!(
+            checksum(&image, 0x8000_310C, 2),
+            Some(0x9421 ^ 0xFFF0 ^ 0x7C08 ^ 0x02A6)
+        );
+
+        let search = gecko::search(&best(&candidates).unwrap().words, 0x8000_0000, 0x8001_0000);
+        let lines = found(search.unwrap(), &[0x38, 0x60, 0x00, 0x05], 4, false).unwrap();
+        assert_eq!(lines[3], Line(0x1400_0004, 0x3860_0005));
+        assert_eq!(lines[4], Line(0xE000_0000, 0x8000_8000));
+    }
+}
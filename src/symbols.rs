@@ -0,0 +1,242 @@
+//! Symbol tables that name addresses in listings: an ELF's own, or one
+//! loaded from a text file in any of the forms GameCube tools write a line
+//! per symbol in:
+//!
+//! - Dolphin's and CodeWarrior's `.map`, `80003100 000040 80003100  4 main`:
+//!   starting offset, size, virtual address, alignment and name, under
+//!   `.text section layout` style headings
+//! - decomp-toolkit's `symbols.txt`, `main = .text:0x80003100; // type:function size:0x40`
+//! - a linker script's `main = 0x80003100;`
+//! - `nm`'s `80003100 T main`, or just `80003100 main`
+//!
+//! Lines in none of these forms, like headings and `UNUSED` entries, are
+//! skipped.
+
+use crate::{parse_hex_u32 as hex, Register};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub addr: u32,
+    /// How many bytes it covers, if the table says
+    pub size: Option<u32>,
+    pub name: String,
+    /// Data rather than code, so never a label in a listing
+    pub data: bool,
+}
+
+/// Symbols sorted by address.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    /// The symbols of a map or symbol list; see the module docs.
+    pub fn parse(text: &str) -> Self {
+        let mut table = SymbolTable::default();
+        let mut data = false;
+        for line in text.lines() {
+            if let Some(heading) = line.trim().strip_suffix("section layout") {
+                let heading = heading.trim();
+                data = !heading.starts_with(".text") && !heading.starts_with(".init");
+                continue;
+            }
+            if let Some(symbol) = assignment(line).or_else(|| columns(line, data)) {
+                table.insert(symbol);
+            }
+        }
+        table
+    }
+
+    pub fn insert(&mut self, symbol: Symbol) {
+        let at = self.symbols.partition_point(|s| s.addr <= symbol.addr);
+        self.symbols.insert(at, symbol);
+    }
+
+    pub fn extend(&mut self, other: SymbolTable) {
+        for symbol in other.symbols {
+            self.insert(symbol);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols.iter()
+    }
+
+    /// The address of the symbol called `name`.
+    pub fn get(&self, name: &str) -> Option<u32> {
+        self.symbols.iter().find(|s| s.name == name).map(|s| s.addr)
+    }
+
+    /// `addr` as `main` or `main+0x1c`, by the last symbol at or before it
+    /// that covers it. A symbol with a size covers just those bytes, and
+    /// one without reaches to the next symbol.
+    pub fn name(&self, addr: u32) -> Option<String> {
+        self.covering(addr, |_| true)
+    }
+
+    /// [`SymbolTable::name`] by code symbols only, for a branch target.
+    pub fn code_name(&self, addr: u32) -> Option<String> {
+        self.covering(addr, |s| !s.data)
+    }
+
+    /// The symbol covering `addr`, as for [`SymbolTable::name`], and how
+    /// far into it `addr` is.
+    pub fn enclosing(&self, addr: u32) -> Option<(&Symbol, u32)> {
+        self.enclosing_by(addr, |_| true)
+    }
+
+    fn covering(&self, addr: u32, wanted: impl Fn(&Symbol) -> bool) -> Option<String> {
+        let (symbol, offset) = self.enclosing_by(addr, wanted)?;
+        match offset {
+            0 => Some(symbol.name.clone()),
+            _ => Some(format!("{}+{:#x}", symbol.name, offset)),
+        }
+    }
+
+    fn enclosing_by(&self, addr: u32, wanted: impl Fn(&Symbol) -> bool) -> Option<(&Symbol, u32)> {
+        let at = self.symbols.partition_point(|s| s.addr <= addr);
+        let (_, symbol) = self.symbols[..at]
+            .iter()
+            .rev()
+            .enumerate()
+            .find(|&(i, s)| match s.size {
+                Some(size) if size > 0 => addr - s.addr < size && wanted(s),
+                _ => i == 0,
+            })?;
+        let offset = addr - symbol.addr;
+        match symbol.size {
+            _ if !wanted(symbol) => None,
+            Some(size) if size > 0 && offset >= size => None,
+            _ => Some((symbol, offset)),
+        }
+    }
+
+    /// The code symbols, as labels for a listing.
+    pub fn labels(&self) -> Vec<(u32, &str)> {
+        self.symbols
+            .iter()
+            .filter(|s| !s.data)
+            .map(|s| (s.addr, s.name.as_str()))
+            .collect()
+    }
+
+    /// What r13 or r2 points at for small data accesses: the EABI's
+    /// `_SDA_BASE_` and `_SDA2_BASE_`.
+    pub fn sda_base(&self, base: Register) -> Option<u32> {
+        match base.0 {
+            13 => self.get("_SDA_BASE_"),
+            2 => self.get("_SDA2_BASE_"),
+            _ => None,
+        }
+    }
+}
+
+/// `main = .text:0x80003100; // type:function size:0x40`, or without the
+/// section and comment.
+fn assignment(line: &str) -> Option<Symbol> {
+    let (name, rest) = line.split_once('=')?;
+    let (value, comment) = rest.split_once("//").unwrap_or((rest, ""));
+    let value = value.trim().trim_end_matches(';').trim();
+    let value = value.rsplit(':').next()?;
+    let name = name.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return None;
+    }
+    let attribute = |key: &str| {
+        comment
+            .split_whitespace()
+            .find_map(|word| word.strip_prefix(key))
+    };
+    Some(Symbol {
+        addr: hex(value)?,
+        size: attribute("size:").and_then(hex),
+        name: name.to_string(),
+        data: attribute("type:").is_some_and(|ty| ty == "object"),
+    })
+}
+
+/// A line of a map or `nm` listing: the address columns, then the name.
+fn columns(line: &str, data: bool) -> Option<Symbol> {
+    // map columns are six or eight digits; anything shorter is the
+    // alignment, an nm type letter, or the name
+    let is_column = |t: &str| t.len() >= 6 && t.len() <= 10 && hex(t).is_some();
+    let mut rest = line.trim_start();
+    let mut numbers = Vec::new();
+    while let Some(token) = rest.split_whitespace().next().filter(|t| is_column(t)) {
+        numbers.push(hex(token)?);
+        rest = rest[token.len()..].trim_start();
+    }
+    let token = rest.split_whitespace().next()?;
+    let (addr, size, data) = match numbers[..] {
+        [addr] => match token.len() {
+            1 if rest.len() > 1 => {
+                rest = rest[1..].trim_start();
+                (addr, None, !"TtWw".contains(token))
+            }
+            _ => (addr, None, data),
+        },
+        [_, size, addr, ..] if token.bytes().all(|b| b.is_ascii_digit()) => {
+            rest = rest[token.len()..].trim_start();
+            (addr, Some(size), data)
+        }
+        _ => return None,
+    };
+    // CodeWarrior puts the object file after a tab
+    let name = rest.split('\t').next()?.trim();
+    match name.is_empty() {
+        true => None,
+        false => Some(Symbol {
+            addr,
+            size,
+            name: name.to_string(),
+            data,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbol_table() {
+        let table = SymbolTable::parse(
+            ".text section layout\n\
+             \x20 Starting        Virtual\n\
+             \x20 00000000 000040 80003100  4 main \tmain.o\n\
+             \x20 UNUSED   000010 ........ unused \tmain.o\n\
+             .sdata section layout\n\
+             80400000 00000004 80400000 0 gameState\n\
+             OSReport = .text:0x800B3C20; // type:function size:0x80\n\
+             _SDA_BASE_ = 0x80408000;\n\
+             800b4000 T OSPanic\n",
+        );
+        assert_eq!(table.len(), 5);
+        assert_eq!(table.name(0x8000_3100).as_deref(), Some("main"));
+        assert_eq!(table.name(0x8000_311c).as_deref(), Some("main+0x1c"));
+        assert_eq!(table.name(0x8000_3140), None);
+        assert_eq!(table.name(0x800b_3c24).as_deref(), Some("OSReport+0x4"));
+        assert_eq!(table.name(0x800b_4010).as_deref(), Some("OSPanic+0x10"));
+        assert_eq!(table.name(0x8040_0000).as_deref(), Some("gameState"));
+        assert_eq!(table.code_name(0x8040_0000), None);
+        assert_eq!(table.sda_base(Register(13)), Some(0x8040_8000));
+        assert_eq!(
+            table.labels(),
+            [
+                (0x8000_3100, "main"),
+                (0x800b_3c20, "OSReport"),
+                (0x800b_4000, "OSPanic"),
+                (0x8040_8000, "_SDA_BASE_")
+            ]
+        );
+    }
+}
@@ -0,0 +1,360 @@
+//! Checking Gecko code lists as they're pasted from forums and chat, and
+//! writing them back tidied: a line of code per line, `XXXXXXXX YYYYYYYY`,
+//! in upper case, whatever words ran together or were split across lines.
+//!
+//! Each run of lines of code between names and notes is taken as a code of
+//! its own, so a count that runs past it, or a conditional left open at its
+//! end, is caught before it swallows the codes after it.
+
+use core::fmt;
+
+use crate::gecko::{extra_lines, Address, CodeType, Line};
+use crate::Width;
+
+/// Something wrong, or likely wrong, with a code list.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    /// A token on a line of code that isn't an 8-digit hex word
+    NotHex(String),
+    /// A line of code with an odd number of words, paired up with the next
+    /// line's
+    OddWords(usize),
+    /// A word with nothing to pair with at the end of a code
+    Unpaired(u32),
+    /// A code of type `code` needing `needs` lines, with only `left` left
+    /// in the code
+    Truncated {
+        code: u32,
+        needs: usize,
+        left: usize,
+    },
+    /// A C2 code of no lines
+    EmptyAsm,
+    /// A C2 code whose last line doesn't end in 00000000, the word the
+    /// codehandler puts its branch back in, with the count at which one
+    /// does
+    AsmCount { count: u32, likely: Option<u32> },
+    /// A write, test or hook at an address that isn't a multiple of its size
+    Misaligned { addr: Address, align: u32 },
+    /// `count` conditionals not ended by the end of the code
+    Unterminated(usize),
+}
+
+impl Issue {
+    /// Whether the list can't be right, rather than just likely wrong.
+    pub fn is_error(&self) -> bool {
+        !matches!(
+            self,
+            Self::OddWords(_) | Self::AsmCount { .. } | Self::Unterminated(_)
+        )
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotHex(token) => write!(f, "`{}` isn't an 8-digit hex word", token),
+            Self::OddWords(n) => write!(
+                f,
+                "{} word(s) on the line, so it's paired up with the next",
+                n
+            ),
+            Self::Unpaired(word) => write!(f, "{:08X} has no word to pair with", word),
+            Self::Truncated { code, needs, left } => write!(
+                f,
+                "a {:02X} code needs {} lines, and the code has {} left",
+                code, needs, left
+            ),
+            Self::EmptyAsm => write!(f, "a C2 code of no lines"),
+            Self::AsmCount { count, likely } => {
+                write!(
+                    f,
+                    "by its count of {}, the C2 code's last line doesn't end in 00000000",
+                    count
+                )?;
+                match likely {
+                    Some(likely) => write!(f, "; is its count {:X}?", likely),
+                    None => Ok(()),
+                }
+            }
+            Self::Misaligned { addr, align } => write!(
+                f,
+                "{} isn't a multiple of {}, the size of what's there",
+                addr, align
+            ),
+            Self::Unterminated(n) => write!(
+                f,
+                "{} conditional(s) not ended; end the code with E0000000 80008000",
+                n
+            ),
+        }
+    }
+}
+
+/// A code list tidied, and what's wrong with it by line number.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Tidied {
+    pub text: String,
+    pub issues: Vec<(usize, Issue)>,
+}
+
+/// A run of lines of code: each word, with the number of the line it's on.
+type Block = Vec<(usize, u32)>;
+
+/// Whether `text` is meant as a line of code: it starts with a hex number,
+/// or is all words the size of one that are mostly hex digits, as a word
+/// with a typo (`O` for `0`) is.
+fn is_code(text: &str) -> bool {
+    let first = text.split_whitespace().next().unwrap_or("");
+    let sized = |t: &str| t.len() == 8 || t.len() == 16;
+    crate::gecko::is_code(text)
+        || (text.split_whitespace().all(sized)
+            && first.chars().filter(|c| c.is_ascii_hexdigit()).count() >= 6)
+}
+
+/// The words of a line of code; a token of 16 digits is two words run
+/// together.
+fn words(text: &str) -> Result<Vec<u32>, Issue> {
+    let mut words = Vec::new();
+    for token in text.split_whitespace() {
+        let hex = token.chars().all(|c| c.is_ascii_hexdigit());
+        match token.len() {
+            8 | 16 if hex => words.extend(
+                (0..token.len())
+                    .step_by(8)
+                    .map(|i| u32::from_str_radix(&token[i..i + 8], 16).unwrap()),
+            ),
+            _ => return Err(Issue::NotHex(token.to_string())),
+        }
+    }
+    Ok(words)
+}
+
+/// Whether a code opens a conditional, and whether it ends one first.
+fn conditional(code: &CodeType) -> Option<bool> {
+    match *code {
+        CodeType::If { endif, .. }
+        | CodeType::RegisterIf { endif, .. }
+        | CodeType::CounterIf { endif, .. }
+        | CodeType::AddressRangeIf { endif, .. } => Some(endif),
+        CodeType::Search { .. } => Some(false),
+        _ => None,
+    }
+}
+
+/// The size of what `code` writes, tests or hooks, that its address has to
+/// be a multiple of.
+fn alignment(code: &CodeType) -> Option<(Address, u32)> {
+    match *code {
+        CodeType::Write { width, addr, .. }
+        | CodeType::SerialWrite { width, addr, .. }
+        | CodeType::If { width, addr, .. }
+            if width != Width::Byte =>
+        {
+            Some((addr, width.bytes()))
+        }
+        CodeType::InsertAsm { addr, .. }
+        | CodeType::Branch { addr, .. }
+        | CodeType::ChecksumAsm { addr, .. } => Some((addr, 4)),
+        _ => None,
+    }
+}
+
+/// Pairs `block`'s words into lines, warning of lines with an odd number
+/// of words.
+fn pair(block: &Block, issues: &mut Vec<(usize, Issue)>) -> Vec<(usize, Line)> {
+    let mut per_line: Vec<(usize, usize)> = Vec::new();
+    for &(number, _) in block {
+        match per_line.last_mut() {
+            Some((n, count)) if *n == number => *count += 1,
+            _ => per_line.push((number, 1)),
+        }
+    }
+    let odd = per_line.iter().filter(|(_, count)| count % 2 == 1);
+    issues.extend(odd.map(|&(number, count)| (number, Issue::OddWords(count))));
+
+    let pairs = block.chunks_exact(2);
+    if let [(number, word)] = *pairs.remainder() {
+        issues.push((number, Issue::Unpaired(word)));
+    }
+    pairs.map(|p| (p[0].0, Line(p[0].1, p[1].1))).collect()
+}
+
+/// Checks the codes of one block's `lines`.
+fn check(lines: &[(usize, Line)], issues: &mut Vec<(usize, Issue)>) {
+    let mut rest = lines;
+    let mut open = 0;
+    while let Some(&(number, first)) = rest.first() {
+        let needs = 1 + extra_lines(first);
+        let code = match rest.get(..needs) {
+            Some(code) => code,
+            None => {
+                let issue = Issue::Truncated {
+                    code: first.0 >> 24,
+                    needs,
+                    left: rest.len(),
+                };
+                issues.push((number, issue));
+                return;
+            }
+        };
+        let at = lines.len() - rest.len();
+        rest = &rest[needs..];
+
+        if (first.0 >> 24) & 0xEE == 0xC2 {
+            if first.1 == 0 {
+                issues.push((number, Issue::EmptyAsm));
+            } else if code[needs - 1].1 .1 != 0 {
+                let likely = lines[at + 1..]
+                    .iter()
+                    .position(|&(_, l)| l.1 == 0)
+                    .map(|i| i as u32 + 1);
+                let issue = Issue::AsmCount {
+                    count: first.1,
+                    likely,
+                };
+                issues.push((number, issue));
+            }
+        }
+
+        let parsed = CodeType::parse(&code.iter().map(|&(_, l)| l).collect::<Vec<_>>());
+        if let Some((addr, align)) = alignment(&parsed) {
+            if !addr.offset.is_multiple_of(align) {
+                issues.push((number, Issue::Misaligned { addr, align }));
+            }
+        }
+        match parsed {
+            CodeType::FullTerminator { .. } => open = 0,
+            CodeType::Endif { count, .. } => open -= open.min(usize::from(count)),
+            _ => {
+                if let Some(endif) = conditional(&parsed) {
+                    open = open - (endif && open > 0) as usize + 1;
+                }
+            }
+        }
+    }
+    if open > 0 {
+        issues.push((lines[lines.len() - 1].0, Issue::Unterminated(open)));
+    }
+}
+
+/// What's wrong with the code `lines`, by line numbered from 1.
+pub fn check_code(lines: &[Line]) -> Vec<(usize, Issue)> {
+    let numbered: Vec<_> = lines
+        .iter()
+        .copied()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l))
+        .collect();
+    let mut issues = Vec::new();
+    check(&numbered, &mut issues);
+    issues
+}
+
+/// Checks and writes out the lines of code in `block`, emptying it.
+fn end_block(block: &mut Block, out: &mut Tidied) {
+    let lines = pair(block, &mut out.issues);
+    check(&lines, &mut out.issues);
+    for (_, line) in &lines {
+        out.text += &format!("{}\n", line);
+    }
+    if block.len() % 2 == 1 {
+        out.text += &format!("{:08X}\n", block[block.len() - 1].1);
+    }
+    block.clear();
+}
+
+/// `text` tidied: each line of code one of `XXXXXXXX YYYYYYYY` in upper
+/// case, names and notes trimmed, and runs of blank lines made one.
+pub fn tidy(text: &str) -> Tidied {
+    let mut out = Tidied::default();
+    let mut block = Block::new();
+    let mut blank = false;
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            blank = true;
+            continue;
+        }
+        match is_code(line).then(|| words(line)) {
+            Some(Ok(words)) => {
+                if blank && block.is_empty() && !out.text.is_empty() {
+                    out.text.push('\n');
+                }
+                block.extend(words.into_iter().map(|w| (i + 1, w)));
+            }
+            code => {
+                end_block(&mut block, &mut out);
+                if let Some(Err(issue)) = code {
+                    out.issues.push((i + 1, issue));
+                }
+                if blank && !out.text.is_empty() {
+                    out.text.push('\n');
+                }
+                out.text += &format!("{}\n", line);
+            }
+        }
+        blank = false;
+    }
+    end_block(&mut block, &mut out);
+    out.issues.sort_by_key(|&(number, _)| number);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tidy() {
+        let tidied = tidy(
+            "Infinite lives [ppcheat]  \n\
+             04003100 38600001 04003104\n\
+             4e800020\n\
+             \n\
+             \n\
+             Moon jump\n\
+             0400310238600001\n\
+             C2003200 00000001\n\
+             38600001 60000000\n\
+             4e800020 00000000\n\
+             2800 3000 0000 0001\n\
+             28003000 00000001\n\
+             04OO3100 38600001\n",
+        );
+        assert_eq!(
+            tidied.text,
+            "Infinite lives [ppcheat]\n\
+             04003100 38600001\n\
+             04003104 4E800020\n\
+             \n\
+             Moon jump\n\
+             04003102 38600001\n\
+             C2003200 00000001\n\
+             38600001 60000000\n\
+             4E800020 00000000\n\
+             2800 3000 0000 0001\n\
+             28003000 00000001\n\
+             04OO3100 38600001\n"
+        );
+        let issues: Vec<_> = tidied
+            .issues
+            .iter()
+            .map(|(line, issue)| format!("{}: {}", line, issue))
+            .collect();
+        assert_eq!(
+            issues,
+            [
+                "2: 3 word(s) on the line, so it's paired up with the next",
+                "3: 1 word(s) on the line, so it's paired up with the next",
+                "7: ba+3102 isn't a multiple of 4, the size of what's there",
+                "8: by its count of 1, the C2 code's last line doesn't end in 00000000; is its count 2?",
+                "12: 1 conditional(s) not ended; end the code with E0000000 80008000",
+                "13: `04OO3100` isn't an 8-digit hex word",
+            ]
+        );
+        assert!(!tidied.issues[0].1.is_error());
+        assert!(tidied.issues[2].1.is_error());
+    }
+}
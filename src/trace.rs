@@ -0,0 +1,366 @@
+//! Replays execution traces (e.g. captured from Dolphin or hardware) through
+//! the evaluator and reports where ppcheat's semantics diverge.
+//!
+//! One record per line, `#` starts a comment:
+//!
+//! ```text
+//! 80003100: rlwinm r0,r7,16,0,15 | r7=12345678 | r0=56780000
+//! ```
+//!
+//! i.e. `PC: INSTRUCTION | REGS BEFORE | REGS AFTER`, register values in hex.
+//! Only the registers listed are set or checked; state carries over between
+//! records.
+//!
+//! Dolphin's interpreter trace log (`INTER PC: ... r00: ... INST DISASM`) can
+//! also be read and turned into an annotated narrative.
+
+use core::fmt;
+
+use crate::eval::{Cpu, EvalError};
+use crate::parser::parse_opcode;
+use crate::{parse_hex_u32, Opcode, Register};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    /// 1-based line number in the trace file
+    pub line: usize,
+    pub pc: u32,
+    pub op: Opcode,
+    pub before: Vec<(Register, u32)>,
+    pub after: Vec<(Register, u32)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+fn parse_regs(text: &str, line: usize) -> Result<Vec<(Register, u32)>, TraceError> {
+    let err = |message: String| TraceError { line, message };
+
+    text.split_whitespace()
+        .map(|item| {
+            let (name, value) = item
+                .split_once('=')
+                .ok_or_else(|| err(format!("expected REG=VALUE, found `{}`", item)))?;
+            let reg = name
+                .strip_prefix('r')
+                .and_then(|n| n.parse::<u8>().ok())
+                .filter(|&n| n < 32)
+                .map(Register)
+                .ok_or_else(|| err(format!("invalid register `{}`", name)))?;
+            let value =
+                parse_hex_u32(value).ok_or_else(|| err(format!("invalid value `{}`", value)))?;
+            Ok((reg, value))
+        })
+        .collect()
+}
+
+pub fn parse_trace(inp: &str) -> Result<Vec<Record>, TraceError> {
+    let mut records = Vec::new();
+
+    for (idx, raw) in inp.lines().enumerate() {
+        let line = idx + 1;
+        let text = raw.split('#').next().unwrap_or("").trim();
+        if text.is_empty() {
+            continue;
+        }
+        let err = |message: &str| TraceError {
+            line,
+            message: message.to_string(),
+        };
+
+        let mut fields = text.split('|');
+        let head = fields.next().unwrap_or("");
+        let (pc, asm) = head
+            .split_once(':')
+            .ok_or_else(|| err("expected `PC: INSTRUCTION`"))?;
+        let pc = parse_hex_u32(pc).ok_or_else(|| err("invalid PC"))?;
+        let op = match parse_opcode(asm.trim()) {
+            Ok((_, op)) => op,
+            Err(_) => return Err(err("unrecognized instruction")),
+        };
+        let before = parse_regs(fields.next().unwrap_or(""), line)?;
+        let after = parse_regs(fields.next().unwrap_or(""), line)?;
+        if fields.next().is_some() {
+            return Err(err("too many `|` fields"));
+        }
+
+        records.push(Record {
+            line,
+            pc,
+            op,
+            before,
+            after,
+        });
+    }
+
+    Ok(records)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Divergence {
+    /// A register ended up with a different value than the trace recorded.
+    Register {
+        reg: Register,
+        expected: u32,
+        actual: u32,
+    },
+    /// ppcheat refused to execute the instruction at all.
+    Eval(EvalError),
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Register {
+                reg,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{}: trace has {:08x}, ppcheat computed {:08x}",
+                reg, expected, actual
+            ),
+            Self::Eval(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Outcome of replaying one record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step<'a> {
+    pub record: &'a Record,
+    pub divergences: Vec<Divergence>,
+}
+
+/// Loads read memory the trace doesn't capture, so their destination
+/// registers can't be checked.
+fn unchecked(op: &Opcode) -> Option<Register> {
+    match *op {
+        Opcode::Load { rd, .. } | Opcode::Lha { rd, .. } => Some(rd),
+        _ => None,
+    }
+}
+
+pub fn replay(records: &[Record]) -> Vec<Step<'_>> {
+    let mut cpu = Cpu::new();
+
+    records
+        .iter()
+        .map(|record| {
+            for &(reg, value) in &record.before {
+                cpu.set_reg(reg, value);
+            }
+
+            let mut divergences = Vec::new();
+            match record.op.eval(&mut cpu) {
+                Ok(()) => {
+                    let skip = unchecked(&record.op);
+                    for &(reg, expected) in &record.after {
+                        let actual = cpu.reg(reg);
+                        if Some(reg) != skip && actual != expected {
+                            divergences.push(Divergence::Register {
+                                reg,
+                                expected,
+                                actual,
+                            });
+                        }
+                    }
+                }
+                Err(e) => divergences.push(Divergence::Eval(e)),
+            }
+
+            // keep following the trace rather than our own (possibly wrong) state
+            for &(reg, value) in &record.after {
+                cpu.set_reg(reg, value);
+            }
+
+            Step {
+                record,
+                divergences,
+            }
+        })
+        .collect()
+}
+
+/// One instruction from a Dolphin interpreter trace log, with the GPR dump
+/// taken just before it executed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    pub line: usize,
+    pub pc: u32,
+    pub word: u32,
+    pub asm: String,
+    pub gpr: [u32; 32],
+}
+
+fn hex_token(token: &str) -> Option<u32> {
+    if token.len() == 8 {
+        u32::from_str_radix(token, 16).ok()
+    } else {
+        None
+    }
+}
+
+fn parse_log_line(raw: &str, line: usize) -> Option<LogEntry> {
+    let rest = &raw[raw.find("PC: ")? + 4..];
+    let mut tokens = rest.split_whitespace().peekable();
+    let pc = hex_token(tokens.next()?)?;
+
+    let mut gpr = [0; 32];
+    let mut seen = 0;
+    while let Some(token) = tokens.next() {
+        if let Some(n) = token.strip_prefix('r').and_then(|t| t.strip_suffix(':')) {
+            let n: usize = n.parse().ok().filter(|&n| n < 32)?;
+            gpr[n] = hex_token(tokens.next()?)?;
+            seen += 1;
+        } else if token.starts_with('f') && token.ends_with(':') {
+            // paired-single dump: two values per register
+            tokens.next();
+            tokens.next();
+        } else if seen == 32 {
+            let word = hex_token(token)?;
+            let asm: Vec<_> = tokens.collect();
+            return Some(LogEntry {
+                line,
+                pc,
+                word,
+                asm: asm.join(" "),
+                gpr,
+            });
+        }
+    }
+    None
+}
+
+/// Lines that aren't interpreter trace lines are skipped.
+pub fn parse_dolphin_log(inp: &str) -> Vec<LogEntry> {
+    inp.lines()
+        .enumerate()
+        .filter_map(|(idx, raw)| parse_log_line(raw, idx + 1))
+        .collect()
+}
+
+/// Explains every logged instruction ppcheat understands, with the values it
+/// read and wrote. Written values come from the next entry's register dump
+/// when there is one, so the narrative follows what actually happened.
+pub fn annotate(entries: &[LogEntry]) -> String {
+    let mut out = String::new();
+
+    for (i, entry) in entries.iter().enumerate() {
+        let op = parse_opcode(&entry.asm).ok().map(|(_, op)| op);
+        let explanation = op.map(|op| op.highlevel()).unwrap_or_default();
+        out.push_str(&format!(
+            "{:08x}  {:<32} {}\n",
+            entry.pc, entry.asm, explanation
+        ));
+
+        let op = match op {
+            Some(op) => op,
+            None => continue,
+        };
+        let next = entries.get(i + 1).map(|e| e.gpr);
+        let mut cpu = Cpu::new();
+        cpu.gpr = entry.gpr;
+        let evaluated = op.eval(&mut cpu).is_ok();
+
+        let reads: Vec<_> = op
+            .reads()
+            .into_iter()
+            .map(|r| format!("{}={:08x}", r, entry.gpr[r.0 as usize]))
+            .collect();
+        let writes: Vec<_> = op
+            .writes()
+            .into_iter()
+            .filter_map(|r| {
+                let value = match next {
+                    Some(gpr) => gpr[r.0 as usize],
+                    None if evaluated => cpu.reg(r),
+                    None => return None,
+                };
+                Some(format!("{}={:08x}", r, value))
+            })
+            .collect();
+        out.push_str(&format!(
+            "{:10}{} -> {}\n",
+            "",
+            reads.join(" "),
+            writes.join(" ")
+        ));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay() {
+        let trace = "\
+80003100: rlwinm r0,r7,16,0,15 | r7=12345678 | r0=56780000
+80003104: addi r3,r0,1 | | r3=56780001  # wrong: rA=0 is literal 0
+";
+        let records = parse_trace(trace).unwrap();
+        let steps = replay(&records);
+
+        assert!(steps[0].divergences.is_empty());
+        assert_eq!(
+            steps[1].divergences,
+            vec![Divergence::Register {
+                reg: Register(3),
+                expected: 0x5678_0001,
+                actual: 1,
+            }]
+        );
+    }
+
+    fn log_line(pc: u32, gpr: &[u32; 32], word: u32, asm: &str) -> String {
+        let regs: String = gpr
+            .iter()
+            .enumerate()
+            .map(|(i, v)| format!("r{:02}: {:08x} ", i, v))
+            .collect();
+        format!(
+            "00:00:123 Interpreter.cpp:80 D[POWERPC]: INTER PC: {:08x} SRR0: 00000000 \
+             SRR1: 00000000 CRval: 0000000000000000 FPSCR: 00000000 MSR: 00002032 \
+             LR: 80003000 {} {:08x} {}",
+            pc, regs, word, asm
+        )
+    }
+
+    #[test]
+    fn test_annotate_dolphin_log() {
+        let mut gpr = [0; 32];
+        gpr[7] = 0x1234_5678;
+        let mut after = gpr;
+        after[0] = 0x5678_0000;
+
+        let log = format!(
+            "{}\nunrelated log noise\n{}\n",
+            log_line(0x8000_3100, &gpr, 0x54e0_803e, "rlwinm\tr0, r7, 16, 0, 15"),
+            log_line(0x8000_3104, &after, 0x7c08_02a6, "mflr\tr0"),
+        );
+        let entries = parse_dolphin_log(&log);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].line, 3);
+
+        let out = annotate(&entries);
+        let lines: Vec<_> = out.lines().collect();
+        assert!(lines[0].starts_with("80003100  rlwinm r0, r7, 16, 0, 15"));
+        assert!(lines[0].ends_with("r0 = (r7 << 16) & MASK(0..15)=0xFFFF0000"));
+        assert_eq!(lines[1].trim(), "r7=12345678 -> r0=56780000");
+        assert!(lines[2].starts_with("80003104  mflr r0"));
+    }
+}
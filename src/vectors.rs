@@ -0,0 +1,370 @@
+//! Reference-semantics test vectors generated from the evaluator, for
+//! emulator authors who want to check their implementation against ppcheat.
+//!
+//! XER isn't modelled, so there's none in a vector: the SO bit a compare
+//! or record form copies into its CR field is always clear.
+
+use crate::eval::Cpu;
+use crate::{CrBit, CrField, CrLogic, Opcode, Register, Width};
+
+/// Register and memory values relevant to one vector.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct State {
+    pub gpr: Vec<(Register, u32)>,
+    pub mem: Vec<(u32, u8)>,
+    /// The whole CR, if the instruction reads or writes it
+    pub cr: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vector {
+    pub op: Opcode,
+    /// `op` encoded
+    pub word: u32,
+    pub inputs: State,
+    pub outputs: State,
+}
+
+/// xorshift64*, so the corpus is reproducible from a seed.
+pub(crate) struct Rng(pub(crate) u64);
+
+impl Rng {
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    pub(crate) fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    pub(crate) fn word(&mut self) -> u32 {
+        (self.next() >> 32) as u32
+    }
+
+    pub(crate) fn reg(&mut self) -> Register {
+        Register(self.below(32) as u8)
+    }
+
+    pub(crate) fn field(&mut self) -> u8 {
+        self.below(32) as u8
+    }
+
+    pub(crate) fn simm(&mut self) -> i16 {
+        self.next() as i16
+    }
+}
+
+const LOADS: &[(Width, bool)] = &[
+    (Width::Byte, false),
+    (Width::Byte, true),
+    (Width::Half, false),
+    (Width::Half, true),
+    (Width::Word, false),
+    (Width::Word, true),
+];
+
+const CR_OPS: &[CrLogic] = &[
+    CrLogic::And,
+    CrLogic::Or,
+    CrLogic::Xor,
+    CrLogic::Nand,
+    CrLogic::Nor,
+    CrLogic::Eqv,
+    CrLogic::Andc,
+    CrLogic::Orc,
+];
+
+/// One random instance of every base (non-pseudo) instruction form that
+/// writes GPRs, memory or CR. Branches are left out.
+fn random_ops(rng: &mut Rng) -> Vec<Opcode> {
+    let mut ops = vec![
+        Opcode::Rlwinm {
+            ra: rng.reg(),
+            rs: rng.reg(),
+            sh: rng.field(),
+            mb: rng.field(),
+            me: rng.field(),
+            rc: false,
+        },
+        Opcode::Rlwimi {
+            ra: rng.reg(),
+            rs: rng.reg(),
+            sh: rng.field(),
+            mb: rng.field(),
+            me: rng.field(),
+            rc: false,
+        },
+        Opcode::Rlwnm {
+            ra: rng.reg(),
+            rs: rng.reg(),
+            rb: rng.reg(),
+            mb: rng.field(),
+            me: rng.field(),
+            rc: false,
+        },
+        Opcode::Addi {
+            rd: rng.reg(),
+            ra: rng.reg(),
+            simm: rng.simm(),
+        },
+        Opcode::Addis {
+            rd: rng.reg(),
+            ra: rng.reg(),
+            simm: rng.simm(),
+        },
+        Opcode::Ori {
+            ra: rng.reg(),
+            rs: rng.reg(),
+            uimm: rng.simm() as u16,
+        },
+        Opcode::Oris {
+            ra: rng.reg(),
+            rs: rng.reg(),
+            uimm: rng.simm() as u16,
+        },
+    ];
+
+    // update forms need rA != 0 (and rA != rD for loads) to be valid
+    let base = |rng: &mut Rng, update: bool, rx: Register| loop {
+        let ra = rng.reg();
+        if !update || (ra.0 != 0 && ra != rx) {
+            return ra;
+        }
+    };
+
+    for &(width, update) in LOADS {
+        let (rd, rs) = (rng.reg(), rng.reg());
+        ops.push(Opcode::Load {
+            width,
+            update,
+            rd,
+            d: rng.simm(),
+            ra: base(rng, update, rd),
+        });
+        ops.push(Opcode::Store {
+            width,
+            update,
+            rs,
+            d: rng.simm(),
+            ra: base(rng, update, Register(0)),
+        });
+    }
+    for &update in &[false, true] {
+        let rd = rng.reg();
+        ops.push(Opcode::Lha {
+            update,
+            rd,
+            d: rng.simm(),
+            ra: base(rng, update, rd),
+        });
+    }
+    let record: Vec<_> = ops.iter().filter_map(|op| op.with_record(true)).collect();
+    ops.extend(record);
+    for &logical in &[false, true] {
+        let crf = CrField(rng.below(8) as u8);
+        ops.push(Opcode::Cmp {
+            logical,
+            crf,
+            ra: rng.reg(),
+            rb: rng.reg(),
+        });
+        ops.push(Opcode::Cmpi {
+            logical,
+            crf,
+            ra: rng.reg(),
+            imm: rng.simm(),
+        });
+    }
+    for &op in CR_OPS {
+        let mut bit = || CrBit(rng.field());
+        ops.push(Opcode::CrOp {
+            op,
+            bt: bit(),
+            ba: bit(),
+            bb: bit(),
+        });
+    }
+
+    ops
+}
+
+fn uses_cr(op: &Opcode) -> bool {
+    matches!(
+        op,
+        Opcode::Cmp { .. } | Opcode::Cmpi { .. } | Opcode::CrOp { .. }
+    ) || op.record().is_some()
+}
+
+fn access(op: &Opcode, cpu: &Cpu) -> Option<(u32, Width)> {
+    match *op {
+        Opcode::Load { width, d, ra, .. } | Opcode::Store { width, d, ra, .. } => {
+            Some((cpu.reg_or_zero(ra).wrapping_add(d as i32 as u32), width))
+        }
+        Opcode::Lha { d, ra, .. } => Some((
+            cpu.reg_or_zero(ra).wrapping_add(d as i32 as u32),
+            Width::Half,
+        )),
+        _ => None,
+    }
+}
+
+fn vector(op: Opcode, rng: &mut Rng) -> Vector {
+    let mut cpu = Cpu::new();
+    let mut inputs = State::default();
+
+    for r in op.reads() {
+        if !inputs.gpr.iter().any(|&(seen, _)| seen == r) {
+            let value = rng.word();
+            cpu.set_reg(r, value);
+            inputs.gpr.push((r, value));
+        }
+    }
+
+    // all of CR, so a vector also checks the fields it leaves alone
+    if uses_cr(&op) {
+        cpu.cr = rng.word();
+        inputs.cr = Some(cpu.cr);
+    }
+
+    let is_load = matches!(op, Opcode::Load { .. } | Opcode::Lha { .. });
+    if let Some((ea, width)) = access(&op, &cpu) {
+        if is_load {
+            cpu.mem.write(ea, width, rng.word());
+            inputs.mem = cpu.mem.bytes().collect();
+        }
+    }
+
+    op.eval(&mut cpu).expect("generated an invalid form");
+
+    let gpr = op.writes().into_iter().map(|r| (r, cpu.reg(r))).collect();
+    let mem = if is_load {
+        vec![]
+    } else {
+        cpu.mem.bytes().collect()
+    };
+
+    let cr = inputs.cr.map(|_| cpu.cr);
+    Vector {
+        op,
+        word: op.encode().expect("generated an unencodable form"),
+        inputs,
+        outputs: State { gpr, mem, cr },
+    }
+}
+
+/// `rounds` random instances of every base instruction form, reproducible from `seed`.
+pub fn generate(seed: u64, rounds: usize) -> Vec<Vector> {
+    let mut rng = Rng(seed | 1);
+    let mut vectors = Vec::new();
+    for _ in 0..rounds {
+        for op in random_ops(&mut rng) {
+            vectors.push(vector(op, &mut rng));
+        }
+    }
+    vectors
+}
+
+fn state_json(state: &State) -> String {
+    let gpr: Vec<_> = state
+        .gpr
+        .iter()
+        .map(|(r, v)| format!("\"{}\": {}", r, v))
+        .collect();
+    let mem: Vec<_> = state
+        .mem
+        .iter()
+        .map(|(addr, b)| format!("\"{:#010x}\": {}", addr, b))
+        .collect();
+    let cr = match state.cr {
+        Some(cr) => format!(", \"cr\": \"{:#010x}\"", cr),
+        None => String::new(),
+    };
+    format!(
+        "{{\"gpr\": {{{}}}, \"mem\": {{{}}}{}}}",
+        gpr.join(", "),
+        mem.join(", "),
+        cr
+    )
+}
+
+/// Serializes `vectors` as a JSON array, one vector per line.
+pub fn to_json(vectors: &[Vector]) -> String {
+    let items: Vec<_> = vectors
+        .iter()
+        .map(|v| {
+            format!(
+                "  {{\"asm\": \"{}\", \"word\": \"{:#010x}\", \"inputs\": {}, \"outputs\": {}}}",
+                v.op,
+                v.word,
+                state_json(&v.inputs),
+                state_json(&v.outputs)
+            )
+        })
+        .collect();
+    format!("[\n{}\n]\n", items.join(",\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vectors_replay() {
+        let vectors = generate(0x5eed, 4);
+        assert_eq!(vectors, generate(0x5eed, 4));
+
+        for v in &vectors {
+            let mut cpu = Cpu::new();
+            for &(r, value) in &v.inputs.gpr {
+                cpu.set_reg(r, value);
+            }
+            for &(addr, byte) in &v.inputs.mem {
+                cpu.mem.write(addr, Width::Byte, byte as u32);
+            }
+            if let Some(cr) = v.inputs.cr {
+                cpu.cr = cr;
+            }
+            v.op.eval(&mut cpu).unwrap();
+            for &(r, value) in &v.outputs.gpr {
+                assert_eq!(cpu.reg(r), value, "{}", v.op);
+            }
+            assert_eq!(v.outputs.cr.map(|_| cpu.cr), v.outputs.cr, "{}", v.op);
+            assert_eq!(Opcode::decode(v.word).unwrap(), v.op);
+        }
+        assert!(vectors.iter().any(|v| v.op.mnemonic() == "rlwinm."));
+        assert!(vectors.iter().any(|v| v.op.mnemonic() == "cmplwi"));
+        assert!(vectors.iter().any(|v| v.op.mnemonic() == "crnand"));
+    }
+
+    #[test]
+    fn test_to_json() {
+        let v = Vector {
+            op: Opcode::Cmpi {
+                logical: false,
+                crf: CrField(0),
+                ra: Register(3),
+                imm: 0,
+            },
+            word: 0x2C03_0000,
+            inputs: State {
+                gpr: vec![(Register(3), 1)],
+                mem: vec![],
+                cr: Some(0x2000_0001),
+            },
+            outputs: State {
+                gpr: vec![],
+                mem: vec![],
+                cr: Some(0x4000_0001),
+            },
+        };
+        assert_eq!(
+            to_json(&[v]),
+            "[\n  {\"asm\": \"cmpwi cr0,r3,0\", \"word\": \"0x2c030000\", \
+             \"inputs\": {\"gpr\": {\"r3\": 1}, \"mem\": {}, \"cr\": \"0x20000001\"}, \
+             \"outputs\": {\"gpr\": {}, \"mem\": {}, \"cr\": \"0x40000001\"}}\n]\n"
+        );
+    }
+}